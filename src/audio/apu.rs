@@ -0,0 +1,781 @@
+use crate::emu::save_state::{StateReader, StateWriter};
+
+// Mixer channel names, in the order `channel_output` numbers them
+pub const CHANNEL_NAMES: [&str; 5] = ["Pulse 1", "Pulse 2", "Triangle", "Noise", "DMC"];
+
+pub const STATUS: u16 = 0x4015;
+pub const FRAME_COUNTER: u16 = 0x4017;
+
+const LENGTH_TABLE: [u8; 32] = [
+  10, 254, 20, 2, 40, 4, 80, 6, 160, 8, 60, 10, 14, 12, 26, 14,
+  12, 16, 24, 18, 48, 20, 96, 22, 192, 24, 72, 26, 16, 28, 32, 30
+];
+
+const DUTY_SEQUENCES: [[u8; 8]; 4] = [
+  [0, 1, 0, 0, 0, 0, 0, 0],
+  [0, 1, 1, 0, 0, 0, 0, 0],
+  [0, 1, 1, 1, 1, 0, 0, 0],
+  [1, 0, 0, 1, 1, 1, 1, 1]
+];
+
+const TRIANGLE_SEQUENCE: [u8; 32] = [
+  15, 14, 13, 12, 11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 0,
+  0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15
+];
+
+// NTSC noise and DMC timer periods in CPU cycles
+const NOISE_PERIODS: [u16; 16] = [4, 8, 16, 32, 64, 96, 128, 160, 202, 254, 380, 508, 762, 1016, 2034, 4068];
+const DMC_PERIODS: [u16; 16] = [428, 380, 340, 320, 286, 254, 226, 214, 190, 160, 142, 128, 106, 84, 72, 54];
+
+// Frame counter steps in CPU cycles. Both modes clock envelopes on every step and lengths and
+// sweeps on the second and last, the 4 step mode raises its IRQ on the last.
+const FRAME_STEPS: [u32; 3] = [7457, 14913, 22371];
+const FOUR_STEP_LAST: u32 = 29829;
+const FIVE_STEP_LAST: u32 = 37281;
+
+const FIVE_STEP_BIT: u8 = 0x80;
+const IRQ_INHIBIT_BIT: u8 = 0x40;
+
+// Largest pulse period, sweeps that would go past it mute the channel
+const MAX_PULSE_PERIOD: u16 = 0x7FF;
+const DMC_SAMPLE_BEGIN: u16 = 0xC000;
+
+// Volume unit shared by the pulses and the noise
+#[derive(Default)]
+struct Envelope {
+  start: bool,
+  divider: u8,
+  decay: u8,
+  // Shares its bit with the length counter halt
+  looping: bool,
+  constant: bool,
+  volume: u8
+}
+
+impl Envelope {
+  fn write(&mut self, value: u8) {
+    self.looping = value & 0x20 != 0;
+    self.constant = value & 0x10 != 0;
+    self.volume = value & 0x0F;
+  }
+
+  fn clock(&mut self) {
+    if self.start {
+      self.start = false;
+      self.decay = 15;
+      self.divider = self.volume;
+    } else if self.divider == 0 {
+      self.divider = self.volume;
+      if self.decay > 0 {
+        self.decay -= 1;
+      } else if self.looping {
+        self.decay = 15;
+      }
+    } else {
+      self.divider -= 1;
+    }
+  }
+
+  fn output(&self) -> u8 {
+    if self.constant { self.volume } else { self.decay }
+  }
+
+  fn save_state(&self, state: &mut StateWriter) {
+    for value in &[self.start, self.looping, self.constant] {
+      state.bool(*value);
+    }
+    for value in &[self.divider, self.decay, self.volume] {
+      state.u8(*value);
+    }
+  }
+
+  fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+    for value in [&mut self.start, &mut self.looping, &mut self.constant] {
+      *value = state.bool()?;
+    }
+    for value in [&mut self.divider, &mut self.decay, &mut self.volume] {
+      *value = state.u8()?;
+    }
+    Ok(())
+  }
+}
+
+#[derive(Default)]
+struct Pulse {
+  // Pulse 1 negates its sweep in ones' complement, one lower than pulse 2
+  ones_complement: bool,
+  envelope: Envelope,
+  duty: u8,
+  step: usize,
+  period: u16,
+  timer: u16,
+  enabled: bool,
+  length: u8,
+  sweep_enabled: bool,
+  sweep_period: u8,
+  sweep_negate: bool,
+  sweep_shift: u8,
+  sweep_reload: bool,
+  sweep_divider: u8
+}
+
+impl Pulse {
+  fn write(&mut self, register: u16, value: u8) {
+    match register {
+      0 => {
+        self.duty = value >> 6;
+        self.envelope.write(value);
+      }
+      1 => {
+        self.sweep_enabled = value & 0x80 != 0;
+        self.sweep_period = (value >> 4) & 0x07;
+        self.sweep_negate = value & 0x08 != 0;
+        self.sweep_shift = value & 0x07;
+        self.sweep_reload = true;
+      }
+      2 => self.period = (self.period & 0x0700) | value as u16,
+      _ => {
+        self.period = (self.period & 0x00FF) | ((value as u16 & 0x07) << 8);
+        if self.enabled {
+          self.length = LENGTH_TABLE[(value >> 3) as usize];
+        }
+        self.step = 0;
+        self.envelope.start = true;
+      }
+    }
+  }
+
+  fn set_enabled(&mut self, enabled: bool) {
+    self.enabled = enabled;
+    if !enabled {
+      self.length = 0;
+    }
+  }
+
+  // Runs every other CPU cycle
+  fn clock_timer(&mut self) {
+    if self.timer == 0 {
+      self.timer = self.period;
+      self.step = (self.step + 1) % 8;
+    } else {
+      self.timer -= 1;
+    }
+  }
+
+  fn target_period(&self) -> u16 {
+    let change = self.period >> self.sweep_shift;
+    if !self.sweep_negate {
+      return self.period + change;
+    }
+    self.period.saturating_sub(change + self.ones_complement as u16)
+  }
+
+  // Sweeps mute the channel even while they are disabled
+  fn muted(&self) -> bool {
+    self.period < 8 || self.target_period() > MAX_PULSE_PERIOD
+  }
+
+  fn clock_half_frame(&mut self) {
+    if self.sweep_divider == 0 && self.sweep_enabled && self.sweep_shift > 0 && !self.muted() {
+      self.period = self.target_period();
+    }
+    if self.sweep_divider == 0 || self.sweep_reload {
+      self.sweep_divider = self.sweep_period;
+      self.sweep_reload = false;
+    } else {
+      self.sweep_divider -= 1;
+    }
+
+    if !self.envelope.looping && self.length > 0 {
+      self.length -= 1;
+    }
+  }
+
+  fn output(&self) -> u8 {
+    if self.length == 0 || self.muted() || DUTY_SEQUENCES[self.duty as usize][self.step] == 0 {
+      return 0;
+    }
+    self.envelope.output()
+  }
+
+  fn save_state(&self, state: &mut StateWriter) {
+    self.envelope.save_state(state);
+    for value in &[self.duty, self.step as u8, self.length, self.sweep_period, self.sweep_shift, self.sweep_divider] {
+      state.u8(*value);
+    }
+    state.u16(self.period);
+    state.u16(self.timer);
+    for value in &[self.enabled, self.sweep_enabled, self.sweep_negate, self.sweep_reload] {
+      state.bool(*value);
+    }
+  }
+
+  fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+    self.envelope.load_state(state)?;
+    self.duty = state.u8()? & 0x03;
+    self.step = (state.u8()? & 0x07) as usize;
+    for value in [&mut self.length, &mut self.sweep_period, &mut self.sweep_shift, &mut self.sweep_divider] {
+      *value = state.u8()?;
+    }
+    self.period = state.u16()?;
+    self.timer = state.u16()?;
+    for value in [&mut self.enabled, &mut self.sweep_enabled, &mut self.sweep_negate, &mut self.sweep_reload] {
+      *value = state.bool()?;
+    }
+    Ok(())
+  }
+}
+
+#[derive(Default)]
+struct Triangle {
+  // Halts the length counter and keeps reloading the linear counter
+  control: bool,
+  linear_period: u8,
+  linear: u8,
+  linear_reload: bool,
+  period: u16,
+  timer: u16,
+  step: usize,
+  enabled: bool,
+  length: u8
+}
+
+impl Triangle {
+  fn write(&mut self, register: u16, value: u8) {
+    match register {
+      0 => {
+        self.control = value & 0x80 != 0;
+        self.linear_period = value & 0x7F;
+      }
+      2 => self.period = (self.period & 0x0700) | value as u16,
+      3 => {
+        self.period = (self.period & 0x00FF) | ((value as u16 & 0x07) << 8);
+        if self.enabled {
+          self.length = LENGTH_TABLE[(value >> 3) as usize];
+        }
+        self.linear_reload = true;
+      }
+      _ => {}
+    }
+  }
+
+  fn set_enabled(&mut self, enabled: bool) {
+    self.enabled = enabled;
+    if !enabled {
+      self.length = 0;
+    }
+  }
+
+  // Runs every CPU cycle, the sequence stops where it is while either counter is 0
+  fn clock_timer(&mut self) {
+    if self.timer == 0 {
+      self.timer = self.period;
+      if self.length > 0 && self.linear > 0 {
+        self.step = (self.step + 1) % TRIANGLE_SEQUENCE.len();
+      }
+    } else {
+      self.timer -= 1;
+    }
+  }
+
+  fn clock_quarter_frame(&mut self) {
+    if self.linear_reload {
+      self.linear = self.linear_period;
+    } else if self.linear > 0 {
+      self.linear -= 1;
+    }
+    if !self.control {
+      self.linear_reload = false;
+    }
+  }
+
+  fn clock_half_frame(&mut self) {
+    if !self.control && self.length > 0 {
+      self.length -= 1;
+    }
+  }
+
+  fn output(&self) -> u8 {
+    TRIANGLE_SEQUENCE[self.step]
+  }
+
+  fn save_state(&self, state: &mut StateWriter) {
+    for value in &[self.control, self.linear_reload, self.enabled] {
+      state.bool(*value);
+    }
+    for value in &[self.linear_period, self.linear, self.step as u8, self.length] {
+      state.u8(*value);
+    }
+    state.u16(self.period);
+    state.u16(self.timer);
+  }
+
+  fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+    for value in [&mut self.control, &mut self.linear_reload, &mut self.enabled] {
+      *value = state.bool()?;
+    }
+    self.linear_period = state.u8()?;
+    self.linear = state.u8()?;
+    self.step = state.u8()? as usize % TRIANGLE_SEQUENCE.len();
+    self.length = state.u8()?;
+    self.period = state.u16()?;
+    self.timer = state.u16()?;
+    Ok(())
+  }
+}
+
+struct Noise {
+  envelope: Envelope,
+  // Short mode taps bit 6 instead of bit 1 for a 93 step metallic loop
+  short_mode: bool,
+  period: u16,
+  timer: u16,
+  shift: u16,
+  enabled: bool,
+  length: u8
+}
+
+impl Noise {
+  fn new() -> Noise {
+    Noise { envelope: Envelope::default(), short_mode: false, period: NOISE_PERIODS[0], timer: 0, shift: 1, enabled: false, length: 0 }
+  }
+
+  fn write(&mut self, register: u16, value: u8) {
+    match register {
+      0 => self.envelope.write(value),
+      2 => {
+        self.short_mode = value & 0x80 != 0;
+        self.period = NOISE_PERIODS[(value & 0x0F) as usize];
+      }
+      3 => {
+        if self.enabled {
+          self.length = LENGTH_TABLE[(value >> 3) as usize];
+        }
+        self.envelope.start = true;
+      }
+      _ => {}
+    }
+  }
+
+  fn set_enabled(&mut self, enabled: bool) {
+    self.enabled = enabled;
+    if !enabled {
+      self.length = 0;
+    }
+  }
+
+  fn clock_timer(&mut self) {
+    if self.timer == 0 {
+      self.timer = self.period - 1;
+      let tap = if self.short_mode { 6 } else { 1 };
+      let feedback = (self.shift ^ (self.shift >> tap)) & 0x01;
+      self.shift = (self.shift >> 1) | (feedback << 14);
+    } else {
+      self.timer -= 1;
+    }
+  }
+
+  fn clock_half_frame(&mut self) {
+    if !self.envelope.looping && self.length > 0 {
+      self.length -= 1;
+    }
+  }
+
+  fn output(&self) -> u8 {
+    if self.length == 0 || self.shift & 0x01 != 0 {
+      return 0;
+    }
+    self.envelope.output()
+  }
+
+  fn save_state(&self, state: &mut StateWriter) {
+    self.envelope.save_state(state);
+    state.bool(self.short_mode);
+    state.bool(self.enabled);
+    for value in &[self.period, self.timer, self.shift] {
+      state.u16(*value);
+    }
+    state.u8(self.length);
+  }
+
+  fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+    self.envelope.load_state(state)?;
+    self.short_mode = state.bool()?;
+    self.enabled = state.bool()?;
+    self.period = state.u16()?.max(1);
+    self.timer = state.u16()?;
+    self.shift = state.u16()?;
+    self.length = state.u8()?;
+    Ok(())
+  }
+}
+
+// Delta modulation channel, plays 1 bit deltas fetched from CPU memory onto a 7 bit level
+struct Dmc {
+  irq_enabled: bool,
+  looping: bool,
+  period: u16,
+  timer: u16,
+  level: u8,
+  sample_address: u16,
+  sample_length: u16,
+  address: u16,
+  bytes_remaining: u16,
+  // Next byte for the shifter, filled by the bus through `dmc_fetch`
+  buffer: Option<u8>,
+  shift: u8,
+  bits_remaining: u8,
+  silence: bool,
+  irq: bool
+}
+
+impl Dmc {
+  fn new() -> Dmc {
+    Dmc {
+      irq_enabled: false,
+      looping: false,
+      period: DMC_PERIODS[0],
+      timer: 0,
+      level: 0,
+      sample_address: DMC_SAMPLE_BEGIN,
+      sample_length: 1,
+      address: DMC_SAMPLE_BEGIN,
+      bytes_remaining: 0,
+      buffer: None,
+      shift: 0,
+      bits_remaining: 8,
+      silence: true,
+      irq: false
+    }
+  }
+
+  fn write(&mut self, register: u16, value: u8) {
+    match register {
+      0 => {
+        self.irq_enabled = value & 0x80 != 0;
+        if !self.irq_enabled {
+          self.irq = false;
+        }
+        self.looping = value & 0x40 != 0;
+        self.period = DMC_PERIODS[(value & 0x0F) as usize];
+      }
+      1 => self.level = value & 0x7F,
+      2 => self.sample_address = DMC_SAMPLE_BEGIN | (value as u16) << 6,
+      _ => self.sample_length = (value as u16) << 4 | 1
+    }
+  }
+
+  fn set_enabled(&mut self, enabled: bool) {
+    self.irq = false;
+    if !enabled {
+      self.bytes_remaining = 0;
+    } else if self.bytes_remaining == 0 {
+      self.restart();
+    }
+  }
+
+  fn restart(&mut self) {
+    self.address = self.sample_address;
+    self.bytes_remaining = self.sample_length;
+  }
+
+  fn clock_timer(&mut self) {
+    if self.timer > 0 {
+      self.timer -= 1;
+      return;
+    }
+    self.timer = self.period - 1;
+
+    if !self.silence {
+      if self.shift & 0x01 != 0 {
+        if self.level <= 125 {
+          self.level += 2;
+        }
+      } else if self.level >= 2 {
+        self.level -= 2;
+      }
+    }
+    self.shift >>= 1;
+    self.bits_remaining -= 1;
+    if self.bits_remaining == 0 {
+      self.bits_remaining = 8;
+      match self.buffer.take() {
+        Some(byte) => {
+          self.silence = false;
+          self.shift = byte;
+        }
+        None => self.silence = true
+      }
+    }
+  }
+
+  fn fetch(&mut self, value: u8) {
+    self.buffer = Some(value);
+    // Samples wrap from $FFFF round to $8000
+    self.address = if self.address == 0xFFFF { 0x8000 } else { self.address + 1 };
+    self.bytes_remaining -= 1;
+    if self.bytes_remaining == 0 {
+      if self.looping {
+        self.restart();
+      } else if self.irq_enabled {
+        self.irq = true;
+      }
+    }
+  }
+
+  fn save_state(&self, state: &mut StateWriter) {
+    for value in &[self.irq_enabled, self.looping, self.buffer.is_some(), self.silence, self.irq] {
+      state.bool(*value);
+    }
+    for value in &[self.period, self.timer, self.sample_address, self.sample_length, self.address, self.bytes_remaining] {
+      state.u16(*value);
+    }
+    for value in &[self.level, self.buffer.unwrap_or(0), self.shift, self.bits_remaining] {
+      state.u8(*value);
+    }
+  }
+
+  fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+    self.irq_enabled = state.bool()?;
+    self.looping = state.bool()?;
+    let buffered = state.bool()?;
+    self.silence = state.bool()?;
+    self.irq = state.bool()?;
+    self.period = state.u16()?.max(1);
+    for value in [&mut self.timer, &mut self.sample_address, &mut self.sample_length, &mut self.address, &mut self.bytes_remaining] {
+      *value = state.u16()?;
+    }
+    self.level = state.u8()? & 0x7F;
+    let buffer = state.u8()?;
+    self.buffer = if buffered { Some(buffer) } else { None };
+    self.shift = state.u8()?;
+    self.bits_remaining = state.u8()?.clamp(1, 8);
+    Ok(())
+  }
+}
+
+// The 2A03's sound generator with NTSC timing: two pulses, a triangle, noise and the DMC at
+// $4000-$4013, enables and status on $4015 and the frame counter on $4017. Clocked once per CPU
+// cycle. The DMC asks for sample bytes through `dmc_fetch_address` rather than reading memory
+// itself, so the bus decides what is there and how long the CPU is held up.
+pub struct Apu {
+  pulses: [Pulse; 2],
+  triangle: Triangle,
+  noise: Noise,
+  dmc: Dmc,
+  odd_cycle: bool,
+  five_step: bool,
+  irq_inhibit: bool,
+  frame_irq: bool,
+  // CPU cycles since the frame counter started its sequence
+  frame_cycle: u32
+}
+
+impl Apu {
+  pub fn new() -> Apu {
+    Apu {
+      pulses: [Pulse { ones_complement: true, ..Pulse::default() }, Pulse::default()],
+      triangle: Triangle::default(),
+      noise: Noise::new(),
+      dmc: Dmc::new(),
+      odd_cycle: false,
+      five_step: false,
+      irq_inhibit: false,
+      frame_irq: false,
+      frame_cycle: 0
+    }
+  }
+
+  // Reset silences every channel and restarts the frame counter in the mode it was in
+  pub fn reset(&mut self) {
+    self.write(STATUS, 0x00);
+    self.frame_irq = false;
+    self.frame_cycle = 0;
+  }
+
+  pub fn write(&mut self, addr: u16, value: u8) {
+    match addr {
+      0x4000 ..= 0x4003 => self.pulses[0].write(addr & 0x03, value),
+      0x4004 ..= 0x4007 => self.pulses[1].write(addr & 0x03, value),
+      0x4008 ..= 0x400B => self.triangle.write(addr & 0x03, value),
+      0x400C ..= 0x400F => self.noise.write(addr & 0x03, value),
+      0x4010 ..= 0x4013 => self.dmc.write(addr & 0x03, value),
+      STATUS => {
+        self.pulses[0].set_enabled(value & 0x01 != 0);
+        self.pulses[1].set_enabled(value & 0x02 != 0);
+        self.triangle.set_enabled(value & 0x04 != 0);
+        self.noise.set_enabled(value & 0x08 != 0);
+        self.dmc.set_enabled(value & 0x10 != 0);
+      }
+      FRAME_COUNTER => {
+        self.five_step = value & FIVE_STEP_BIT != 0;
+        self.irq_inhibit = value & IRQ_INHIBIT_BIT != 0;
+        if self.irq_inhibit {
+          self.frame_irq = false;
+        }
+        self.frame_cycle = 0;
+        // The 5 step mode clocks everything straight away
+        if self.five_step {
+          self.clock_quarter_frame();
+          self.clock_half_frame();
+        }
+      }
+      _ => {}
+    }
+  }
+
+  // $4015: which length counters are running, whether the DMC has bytes left and the two
+  // IRQ flags. Reading acknowledges the frame IRQ.
+  pub fn read_status(&mut self) -> u8 {
+    let status = self.peek_status();
+    self.frame_irq = false;
+    status
+  }
+
+  pub fn peek_status(&self) -> u8 {
+    let mut status = 0;
+    for (bit, length) in [self.pulses[0].length, self.pulses[1].length, self.triangle.length, self.noise.length].iter().enumerate() {
+      if *length > 0 {
+        status |= 1 << bit;
+      }
+    }
+    if self.dmc.bytes_remaining > 0 {
+      status |= 0x10;
+    }
+    if self.frame_irq {
+      status |= 0x40;
+    }
+    if self.dmc.irq {
+      status |= 0x80;
+    }
+    status
+  }
+
+  // IRQ line, held until the flags are acknowledged
+  pub fn irq(&self) -> bool {
+    self.frame_irq || self.dmc.irq
+  }
+
+  pub fn clock(&mut self) {
+    self.odd_cycle = !self.odd_cycle;
+    if self.odd_cycle {
+      for pulse in &mut self.pulses {
+        pulse.clock_timer();
+      }
+    }
+    self.triangle.clock_timer();
+    self.noise.clock_timer();
+    self.dmc.clock_timer();
+
+    self.frame_cycle += 1;
+    let last = if self.five_step { FIVE_STEP_LAST } else { FOUR_STEP_LAST };
+    if self.frame_cycle == last {
+      self.clock_quarter_frame();
+      self.clock_half_frame();
+      if !self.five_step && !self.irq_inhibit {
+        self.frame_irq = true;
+      }
+      self.frame_cycle = 0;
+    } else if FRAME_STEPS.contains(&self.frame_cycle) {
+      self.clock_quarter_frame();
+      if self.frame_cycle == FRAME_STEPS[1] {
+        self.clock_half_frame();
+      }
+    }
+  }
+
+  // Address of the next sample byte when the DMC wants one, hand it over with `dmc_fetch`
+  pub fn dmc_fetch_address(&self) -> Option<u16> {
+    if self.dmc.buffer.is_none() && self.dmc.bytes_remaining > 0 {
+      Some(self.dmc.address)
+    } else {
+      None
+    }
+  }
+
+  pub fn dmc_fetch(&mut self, value: u8) {
+    self.dmc.fetch(value);
+  }
+
+  fn clock_quarter_frame(&mut self) {
+    for pulse in &mut self.pulses {
+      pulse.envelope.clock();
+    }
+    self.noise.envelope.clock();
+    self.triangle.clock_quarter_frame();
+  }
+
+  fn clock_half_frame(&mut self) {
+    for pulse in &mut self.pulses {
+      pulse.clock_half_frame();
+    }
+    self.triangle.clock_half_frame();
+    self.noise.clock_half_frame();
+  }
+
+  // One channel's share of the APU's nonlinear mix, on the same scale as the expansion chips.
+  // The pulses and the other three go through separate DACs, each channel gets the part of
+  // its DAC's output in proportion to what it puts in, so the shares add up to the real mix.
+  pub fn channel_output(&self, channel: usize) -> f32 {
+    match channel {
+      0 | 1 => {
+        let sum = self.pulses[0].output() as f32 + self.pulses[1].output() as f32;
+        if sum == 0.0 {
+          return 0.0;
+        }
+        let mixed = 95.88 / (8128.0 / sum + 100.0);
+        mixed * self.pulses[channel].output() as f32 / sum
+      }
+      _ => {
+        let inputs = [
+          self.triangle.output() as f32 / 8227.0,
+          self.noise.output() as f32 / 12241.0,
+          self.dmc.level as f32 / 22638.0
+        ];
+        let sum: f32 = inputs.iter().sum();
+        if sum == 0.0 {
+          return 0.0;
+        }
+        let mixed = 159.79 / (1.0 / sum + 100.0);
+        mixed * inputs[channel - 2] / sum
+      }
+    }
+  }
+
+  pub fn output(&self) -> f32 {
+    (0..CHANNEL_NAMES.len()).map(|channel| self.channel_output(channel)).sum()
+  }
+
+  pub fn save_state(&self, state: &mut StateWriter) {
+    for pulse in &self.pulses {
+      pulse.save_state(state);
+    }
+    self.triangle.save_state(state);
+    self.noise.save_state(state);
+    self.dmc.save_state(state);
+    for value in &[self.odd_cycle, self.five_step, self.irq_inhibit, self.frame_irq] {
+      state.bool(*value);
+    }
+    state.u32(self.frame_cycle);
+  }
+
+  pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+    for pulse in &mut self.pulses {
+      pulse.load_state(state)?;
+    }
+    self.triangle.load_state(state)?;
+    self.noise.load_state(state)?;
+    self.dmc.load_state(state)?;
+    for value in [&mut self.odd_cycle, &mut self.five_step, &mut self.irq_inhibit, &mut self.frame_irq] {
+      *value = state.bool()?;
+    }
+    self.frame_cycle = state.u32()?;
+    Ok(())
+  }
+}
+
+impl Default for Apu {
+  fn default() -> Apu {
+    Apu::new()
+  }
+}
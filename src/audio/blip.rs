@@ -0,0 +1,128 @@
+use std::f64::consts::PI;
+
+// Sub-sample positions the kernel is tabulated at
+const PHASES: usize = 32;
+const HALF_WIDTH: usize = 8;
+const KERNEL_WIDTH: usize = HALF_WIDTH * 2;
+// Fraction of the output Nyquist frequency that passes, the rest is the transition band
+const CUTOFF: f64 = 0.9;
+
+// Band limited resampler in the style of blip_buf. Amplitude changes are added as deltas at
+// clock times and come out as windowed sinc steps at the output rate, so a waveform clocked at
+// the CPU rate can be downsampled without aliasing. Output lags the input by HALF_WIDTH samples.
+pub struct BlipBuffer {
+  clock_rate: f64,
+  sample_rate: f64,
+  // Output samples per clock
+  factor: f64,
+  // Output position of clock 0 of the current frame, the whole part is ready to read
+  offset: f64,
+  // One impulse per phase plus the next whole sample, each summing to 1
+  kernel: Vec<[f32; KERNEL_WIDTH]>,
+  // Deltas waiting to be integrated, index 0 is the next sample read
+  deltas: Vec<f32>,
+  integrator: f32
+}
+
+impl BlipBuffer {
+  pub fn new(clock_rate: f64, sample_rate: f64) -> BlipBuffer {
+    BlipBuffer {
+      clock_rate,
+      sample_rate,
+      factor: sample_rate / clock_rate,
+      offset: 0.0,
+      kernel: (0..=PHASES).map(impulse).collect(),
+      deltas: vec![0.0; KERNEL_WIDTH],
+      integrator: 0.0
+    }
+  }
+
+  pub fn clock_rate(&self) -> f64 {
+    self.clock_rate
+  }
+
+  pub fn sample_rate(&self) -> f64 {
+    self.sample_rate
+  }
+
+  // Takes effect from the start of the current frame, change it between frames
+  pub fn set_rates(&mut self, clock_rate: f64, sample_rate: f64) {
+    self.clock_rate = clock_rate;
+    self.sample_rate = sample_rate;
+    self.factor = sample_rate / clock_rate;
+  }
+
+  // Drops everything buffered and carries on from `level` with no step to get there
+  pub fn reset(&mut self, level: f32) {
+    self.offset = 0.0;
+    self.deltas.clear();
+    self.deltas.resize(KERNEL_WIDTH, 0.0);
+    self.integrator = level;
+  }
+
+  // Changes the output level by `delta` at `clock` cycles into the current frame, 1.0 is full scale
+  pub fn add_delta(&mut self, clock: u32, delta: f32) {
+    let position = self.offset + clock as f64 * self.factor;
+    let index = position as usize;
+    let phase = ((position - index as f64) * PHASES as f64).round() as usize;
+
+    let end = index + KERNEL_WIDTH;
+    if self.deltas.len() < end {
+      self.deltas.resize(end, 0.0);
+    }
+    for (target, weight) in self.deltas[index..end].iter_mut().zip(&self.kernel[phase]) {
+      *target += weight * delta;
+    }
+  }
+
+  // Ends a frame `clocks` long, the next frame's clock 0 follows straight on
+  pub fn end_frame(&mut self, clocks: u32) {
+    self.offset += clocks as f64 * self.factor;
+    let end = self.offset as usize + KERNEL_WIDTH;
+    if self.deltas.len() < end {
+      self.deltas.resize(end, 0.0);
+    }
+  }
+
+  pub fn samples_available(&self) -> usize {
+    self.offset as usize
+  }
+
+  // Clocks the current frame has to run for `samples` samples to be ready
+  pub fn clocks_needed(&self, samples: usize) -> u32 {
+    let needed = (samples as f64 - self.offset) / self.factor;
+    needed.max(0.0).ceil() as u32
+  }
+
+  // Takes up to `count` samples as 16 bit PCM
+  pub fn read_samples(&mut self, count: usize) -> Vec<i16> {
+    let count = count.min(self.samples_available());
+    let mut samples = Vec::with_capacity(count);
+    for delta in self.deltas.drain(..count) {
+      self.integrator += delta;
+      samples.push((self.integrator * i16::MAX as f32).clamp(i16::MIN as f32, i16::MAX as f32) as i16);
+    }
+    self.deltas.resize(self.deltas.len().max(KERNEL_WIDTH), 0.0);
+    self.offset -= count as f64;
+    samples
+  }
+}
+
+// Hann windowed sinc for a step `phase` / PHASES of a sample late, normalized so a step of 1
+// settles at exactly 1
+fn impulse(phase: usize) -> [f32; KERNEL_WIDTH] {
+  let mut taps = [0.0; KERNEL_WIDTH];
+  for (tap, value) in taps.iter_mut().enumerate() {
+    let x = tap as f64 - (HALF_WIDTH - 1) as f64 - phase as f64 / PHASES as f64;
+    let sinc = if x == 0.0 { 1.0 } else { (PI * CUTOFF * x).sin() / (PI * CUTOFF * x) };
+    let window = if x.abs() < HALF_WIDTH as f64 { 0.5 + 0.5 * (PI * x / HALF_WIDTH as f64).cos() } else { 0.0 };
+    *value = sinc * window;
+  }
+
+  let sum: f64 = taps.iter().sum();
+  let mut kernel = [0.0; KERNEL_WIDTH];
+  for (weight, tap) in kernel.iter_mut().zip(&taps) {
+    *weight = (tap / sum) as f32;
+  }
+  kernel
+}
@@ -0,0 +1,60 @@
+use crate::audio::mmc5::Mmc5;
+use crate::audio::namco163::Namco163;
+use crate::audio::sunsoft5b::Sunsoft5b;
+use crate::audio::vrc6::Vrc6;
+use crate::audio::vrc7::Vrc7;
+
+// One APU pulse channel at full volume on the APU mixer's 0.0-1.0 scale. Chip levels are given
+// relative to it, rounded from hardware measurements collected on the NESdev wiki.
+pub const APU_PULSE: f32 = 95.88 / (8128.0 / 15.0 + 100.0);
+
+// Loudest output of each chip in APU pulses, the channels mentioned are at full volume
+pub const VRC6_PULSE_LEVEL: f32 = 1.0;
+pub const VRC7_CHANNEL_LEVEL: f32 = 1.6;
+pub const SUNSOFT_5B_CHANNEL_LEVEL: f32 = 1.9;
+pub const NAMCO_163_LEVEL: f32 = 3.0;
+pub const MMC5_PULSE_LEVEL: f32 = 1.0;
+pub const MMC5_PCM_LEVEL: f32 = 3.8;
+pub const FDS_LEVEL: f32 = 2.4;
+
+// Sound chip on the cartridge. Chips see every CPU write and pick out their own registers, run
+// once per CPU cycle, and their output is added to the APU's.
+pub trait ExpansionAudio {
+  // Returns whether the chip decodes the address
+  fn write(&mut self, addr: u16, value: u8) -> bool;
+
+  // Value of a readable register, None for addresses the chip does not decode
+  fn read(&mut self, _addr: u16) -> Option<u8> {
+    None
+  }
+
+  fn clock(&mut self);
+
+  // Short name the mixer puts in front of the channel names
+  fn name(&self) -> &'static str;
+
+  // Channel names in the order `channel_output` numbers them
+  fn channel_names(&self) -> &'static [&'static str];
+
+  // One channel's share of `output`
+  fn channel_output(&self, channel: usize) -> f32;
+
+  // Current level on the APU mixer's scale
+  fn output(&self) -> f32 {
+    (0..self.channel_names().len()).map(|channel| self.channel_output(channel)).sum()
+  }
+}
+
+// Sound chip wired to an iNES mapper, the FDS is not a cartridge so it only comes from NSF files
+pub fn for_mapper(mapper: u8) -> Option<Box<dyn ExpansionAudio>> {
+  match mapper {
+    5 => Some(Box::new(Mmc5::default())),
+    19 => Some(Box::new(Namco163::default())),
+    24 => Some(Box::new(Vrc6::new(false))),
+    // VRC6b has address lines A0 and A1 swapped
+    26 => Some(Box::new(Vrc6::new(true))),
+    69 => Some(Box::new(Sunsoft5b::default())),
+    85 => Some(Box::new(Vrc7::default())),
+    _ => None
+  }
+}
@@ -0,0 +1,252 @@
+use crate::audio::expansion::{ExpansionAudio, APU_PULSE, FDS_LEVEL};
+
+// Largest sample times the largest gain that reaches the output
+const LEVEL: f32 = APU_PULSE * FDS_LEVEL / (63.0 * 32.0);
+
+const WAVE_LENGTH: usize = 64;
+const MAX_GAIN: u8 = 32;
+
+// Master volume of 2/2, 2/3, 2/4 and 2/5
+const MASTER_VOLUMES: [f32; 4] = [1.0, 2.0 / 3.0, 2.0 / 4.0, 2.0 / 5.0];
+// Counter change for each modulation table entry, 4 resets the counter to 0
+const MODULATION_STEPS: [i8; 8] = [0, 1, 2, 4, 0, -4, -2, -1];
+const MODULATION_RESET: u8 = 4;
+
+// RC filter on the output, about 2 kHz against the CPU clock
+const FILTER_COEFFICIENT: f32 = 0.007;
+
+const ENVELOPE_DISABLE_BIT: u8 = 0x80;
+const ENVELOPE_INCREASE_BIT: u8 = 0x40;
+const HALT_BIT: u8 = 0x80;
+const ENVELOPE_HALT_BIT: u8 = 0x40;
+const WAVE_WRITE_BIT: u8 = 0x80;
+
+#[derive(Default)]
+struct Envelope {
+  disabled: bool,
+  increase: bool,
+  speed: u8,
+  gain: u8,
+  timer: u32
+}
+
+impl Envelope {
+  fn write(&mut self, value: u8) {
+    self.disabled = value & ENVELOPE_DISABLE_BIT != 0;
+    self.increase = value & ENVELOPE_INCREASE_BIT != 0;
+    self.speed = value & 0x3F;
+    self.timer = 0;
+    // Without the envelope the speed is the gain
+    if self.disabled {
+      self.gain = self.speed;
+    }
+  }
+
+  fn clock(&mut self, master_speed: u8) {
+    if self.disabled || master_speed == 0 {
+      return;
+    }
+    self.timer += 1;
+    if self.timer < 8 * (self.speed as u32 + 1) * master_speed as u32 {
+      return;
+    }
+    self.timer = 0;
+
+    if self.increase && self.gain < MAX_GAIN {
+      self.gain += 1;
+    } else if !self.increase && self.gain > 0 {
+      self.gain -= 1;
+    }
+  }
+}
+
+// Famicom Disk System, a 64 step wavetable with a frequency modulator at $4040-$408A
+pub struct Fds {
+  wave: [u8; WAVE_LENGTH],
+  wave_write: bool,
+  wave_frequency: u16,
+  wave_halted: bool,
+  wave_accumulator: u32,
+  envelopes_halted: bool,
+  master_volume: usize,
+  envelope_speed: u8,
+  volume: Envelope,
+  // Gain is only picked up at the start of each wave cycle
+  volume_latch: u8,
+  modulator: Envelope,
+  modulation_table: [u8; WAVE_LENGTH],
+  modulation_position: usize,
+  modulation_frequency: u16,
+  modulation_halted: bool,
+  modulation_accumulator: u32,
+  // 7 bit signed
+  modulation_counter: i8,
+  filtered: f32
+}
+
+impl Default for Fds {
+  fn default() -> Self {
+    Fds {
+      wave: [0; WAVE_LENGTH],
+      wave_write: false,
+      wave_frequency: 0,
+      wave_halted: true,
+      wave_accumulator: 0,
+      envelopes_halted: false,
+      master_volume: 0,
+      envelope_speed: 0xE8,
+      volume: Envelope::default(),
+      volume_latch: 0,
+      modulator: Envelope::default(),
+      modulation_table: [0; WAVE_LENGTH],
+      modulation_position: 0,
+      modulation_frequency: 0,
+      modulation_halted: true,
+      modulation_accumulator: 0,
+      modulation_counter: 0,
+      filtered: 0.0
+    }
+  }
+}
+
+impl Fds {
+  // Wave frequency bent by the modulator, following the hardware's rounding
+  fn pitch(&self) -> u32 {
+    if self.modulation_halted {
+      return self.wave_frequency as u32;
+    }
+
+    let counter = self.modulation_counter as i32;
+    let mut temp = counter * self.modulator.gain as i32;
+    let remainder = temp & 0x0F;
+    temp >>= 4;
+    if remainder > 0 && temp & 0x80 == 0 {
+      temp += if counter < 0 { -1 } else { 2 };
+    }
+
+    if temp >= 192 {
+      temp -= 256;
+    } else if temp < -64 {
+      temp += 256;
+    }
+
+    temp *= self.wave_frequency as i32;
+    let remainder = temp & 0x3F;
+    temp >>= 6;
+    if remainder >= 32 {
+      temp += 1;
+    }
+    (self.wave_frequency as i32 + temp).max(0) as u32
+  }
+
+  fn clock_modulator(&mut self) {
+    if self.modulation_halted {
+      return;
+    }
+    self.modulation_accumulator += self.modulation_frequency as u32;
+    if self.modulation_accumulator < 0x10000 {
+      return;
+    }
+    self.modulation_accumulator -= 0x10000;
+
+    let entry = self.modulation_table[self.modulation_position];
+    self.modulation_position = (self.modulation_position + 1) % WAVE_LENGTH;
+    let counter = if entry == MODULATION_RESET { 0 } else { self.modulation_counter + MODULATION_STEPS[entry as usize] };
+    self.set_modulation_counter(counter);
+  }
+
+  fn set_modulation_counter(&mut self, counter: i8) {
+    self.modulation_counter = ((counter as i16 + 64) & 0x7F) as i8 - 64;
+  }
+}
+
+impl ExpansionAudio for Fds {
+  fn write(&mut self, addr: u16, value: u8) -> bool {
+    match addr {
+      0x4040 ..= 0x407F => {
+        if self.wave_write {
+          self.wave[(addr - 0x4040) as usize] = value & 0x3F;
+        }
+      }
+      0x4080 => self.volume.write(value),
+      0x4082 => self.wave_frequency = (self.wave_frequency & 0x0F00) | value as u16,
+      0x4083 => {
+        self.wave_frequency = (self.wave_frequency & 0x00FF) | ((value as u16 & 0x0F) << 8);
+        self.wave_halted = value & HALT_BIT != 0;
+        self.envelopes_halted = value & ENVELOPE_HALT_BIT != 0;
+        if self.wave_halted {
+          self.wave_accumulator = 0;
+        }
+      }
+      0x4084 => self.modulator.write(value),
+      0x4085 => self.set_modulation_counter((value << 1) as i8 >> 1),
+      0x4086 => self.modulation_frequency = (self.modulation_frequency & 0x0F00) | value as u16,
+      0x4087 => {
+        self.modulation_frequency = (self.modulation_frequency & 0x00FF) | ((value as u16 & 0x0F) << 8);
+        self.modulation_halted = value & HALT_BIT != 0;
+        if self.modulation_halted {
+          self.modulation_accumulator = 0;
+        }
+      }
+      0x4088 => {
+        // Entries go in pairs and only while the modulator is halted
+        if self.modulation_halted {
+          self.modulation_table[self.modulation_position] = value & 0x07;
+          self.modulation_table[self.modulation_position + 1] = value & 0x07;
+          self.modulation_position = (self.modulation_position + 2) % WAVE_LENGTH;
+        }
+      }
+      0x4089 => {
+        self.wave_write = value & WAVE_WRITE_BIT != 0;
+        self.master_volume = (value & 0x03) as usize;
+      }
+      0x408A => self.envelope_speed = value,
+      _ => return false
+    }
+    return true;
+  }
+
+  fn read(&mut self, addr: u16) -> Option<u8> {
+    // Bit 6 reads back as open bus, which is the high byte of the address
+    match addr {
+      0x4040 ..= 0x407F => Some(self.wave[(addr - 0x4040) as usize] | 0x40),
+      0x4090 => Some(self.volume.gain | 0x40),
+      0x4092 => Some(self.modulator.gain | 0x40),
+      _ => None
+    }
+  }
+
+  fn clock(&mut self) {
+    if !self.wave_halted && !self.envelopes_halted {
+      self.volume.clock(self.envelope_speed);
+      self.modulator.clock(self.envelope_speed);
+    }
+
+    self.clock_modulator();
+
+    // Wave RAM writes freeze the wave
+    if !self.wave_halted && !self.wave_write {
+      let position = self.wave_accumulator >> 16;
+      self.wave_accumulator = (self.wave_accumulator + self.pitch()) & 0x3F_FFFF;
+      if self.wave_accumulator >> 16 < position {
+        self.volume_latch = self.volume.gain.min(MAX_GAIN);
+      }
+    }
+
+    let sample = if self.wave_write { 0 } else { self.wave[(self.wave_accumulator >> 16) as usize] };
+    let level = (sample as u32 * self.volume_latch as u32) as f32 * MASTER_VOLUMES[self.master_volume];
+    self.filtered += (level - self.filtered) * FILTER_COEFFICIENT;
+  }
+
+  fn name(&self) -> &'static str {
+    "FDS"
+  }
+
+  fn channel_names(&self) -> &'static [&'static str] {
+    &["Wave"]
+  }
+
+  fn channel_output(&self, _channel: usize) -> f32 {
+    self.filtered * LEVEL
+  }
+}
@@ -0,0 +1,87 @@
+use crate::audio::apu::Apu;
+use crate::audio::expansion::ExpansionAudio;
+
+pub use crate::audio::apu::CHANNEL_NAMES as APU_CHANNELS;
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct MixerChannel {
+  pub name: String,
+  pub muted: bool,
+  pub solo: bool,
+  pub gain: f32
+}
+
+impl MixerChannel {
+  fn new(name: String) -> MixerChannel {
+    MixerChannel { name, muted: false, solo: false, gain: 1.0 }
+  }
+}
+
+// Per channel mute, solo and gain. The APU's five channels come first, then each expansion
+// chip's, named like "VRC6 Saw", in the order the chips are listed.
+pub struct Mixer {
+  pub channels: Vec<MixerChannel>
+}
+
+impl Mixer {
+  pub fn new(chips: &[Box<dyn ExpansionAudio>]) -> Mixer {
+    let mut channels: Vec<MixerChannel> = APU_CHANNELS.iter().map(|name| MixerChannel::new(name.to_string())).collect();
+    for chip in chips {
+      for name in chip.channel_names() {
+        channels.push(MixerChannel::new(format!("{} {}", chip.name(), name)));
+      }
+    }
+    Mixer { channels }
+  }
+
+  // Finds a channel by name ignoring case and anything but letters and digits, so "vrc6-saw"
+  // matches "VRC6 Saw"
+  pub fn channel(&mut self, name: &str) -> Result<&mut MixerChannel, String> {
+    let key = channel_key(name);
+    self.channels.iter_mut().find(|channel| channel_key(&channel.name) == key)
+      .ok_or_else(|| format!("NO MIXER CHANNEL NAMED {}", name.to_uppercase()))
+  }
+
+  // Gain a channel is mixed at, 0.0 when it is muted or another channel is soloed
+  pub fn volume(&self, index: usize) -> f32 {
+    let soloing = self.channels.iter().any(|channel| channel.solo);
+    channel_volume(&self.channels[index], soloing)
+  }
+
+  // Mixed output of the APU and the chips, which must be the ones the mixer was made for
+  pub fn mix(&self, apu: &Apu, chips: &[Box<dyn ExpansionAudio>]) -> f32 {
+    let soloing = self.channels.iter().any(|channel| channel.solo);
+    let mut sum = 0.0;
+    for (index, channel) in self.channels[..APU_CHANNELS.len()].iter().enumerate() {
+      sum += apu.channel_output(index) * channel_volume(channel, soloing);
+    }
+    let mut channels = self.channels[APU_CHANNELS.len()..].iter();
+    for chip in chips {
+      for index in 0..chip.channel_names().len() {
+        sum += chip.channel_output(index) * channel_volume(channels.next().unwrap(), soloing);
+      }
+    }
+    sum
+  }
+
+  // Every channel's level at its gain, ignoring mute and solo, for rendering stems
+  pub fn levels(&self, apu: &Apu, chips: &[Box<dyn ExpansionAudio>], levels: &mut Vec<f32>) {
+    levels.clear();
+    for index in 0..APU_CHANNELS.len() {
+      levels.push(apu.channel_output(index) * self.channels[index].gain);
+    }
+    for chip in chips {
+      for index in 0..chip.channel_names().len() {
+        levels.push(chip.channel_output(index) * self.channels[levels.len()].gain);
+      }
+    }
+  }
+}
+
+fn channel_volume(channel: &MixerChannel, soloing: bool) -> f32 {
+  if channel.muted || (soloing && !channel.solo) { 0.0 } else { channel.gain }
+}
+
+fn channel_key(name: &str) -> String {
+  name.chars().filter(|c| c.is_ascii_alphanumeric()).map(|c| c.to_ascii_lowercase()).collect()
+}
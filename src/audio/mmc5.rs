@@ -0,0 +1,185 @@
+use crate::audio::expansion::{ExpansionAudio, APU_PULSE, MMC5_PCM_LEVEL, MMC5_PULSE_LEVEL};
+
+const PULSE_LEVEL: f32 = APU_PULSE * MMC5_PULSE_LEVEL / 15.0;
+const PCM_LEVEL: f32 = APU_PULSE * MMC5_PCM_LEVEL / 255.0;
+
+const DUTY_SEQUENCES: [[u8; 8]; 4] = [
+  [0, 1, 0, 0, 0, 0, 0, 0],
+  [0, 1, 1, 0, 0, 0, 0, 0],
+  [0, 1, 1, 1, 1, 0, 0, 0],
+  [1, 0, 0, 1, 1, 1, 1, 1]
+];
+
+const LENGTH_TABLE: [u8; 32] = [
+  10, 254, 20, 2, 40, 4, 80, 6, 160, 8, 60, 10, 14, 12, 26, 14,
+  12, 16, 24, 18, 48, 20, 96, 22, 192, 24, 72, 26, 16, 28, 32, 30
+];
+
+// Envelopes and length counters run at a fixed 240 Hz instead of the APU frame counter
+const FRAME_PERIOD: u16 = 7457;
+
+const PCM_READ_MODE_BIT: u8 = 0x01;
+
+// APU pulse without the sweep unit
+#[derive(Default)]
+struct Pulse {
+  duty: u8,
+  step: usize,
+  period: u16,
+  timer: u16,
+  enabled: bool,
+  length: u8,
+  // Also loops the envelope
+  halt: bool,
+  constant: bool,
+  volume: u8,
+  envelope_start: bool,
+  envelope_divider: u8,
+  decay: u8
+}
+
+impl Pulse {
+  fn write(&mut self, register: u16, value: u8) {
+    match register {
+      0 => {
+        self.duty = value >> 6;
+        self.halt = value & 0x20 != 0;
+        self.constant = value & 0x10 != 0;
+        self.volume = value & 0x0F;
+      }
+      2 => self.period = (self.period & 0x0700) | value as u16,
+      3 => {
+        self.period = (self.period & 0x00FF) | ((value as u16 & 0x07) << 8);
+        if self.enabled {
+          self.length = LENGTH_TABLE[(value >> 3) as usize];
+        }
+        self.step = 0;
+        self.envelope_start = true;
+      }
+      _ => {}
+    }
+  }
+
+  fn set_enabled(&mut self, enabled: bool) {
+    self.enabled = enabled;
+    if !enabled {
+      self.length = 0;
+    }
+  }
+
+  // Runs every other CPU cycle like the APU pulses
+  fn clock_timer(&mut self) {
+    if self.timer == 0 {
+      self.timer = self.period;
+      self.step = (self.step + 1) % 8;
+    } else {
+      self.timer -= 1;
+    }
+  }
+
+  fn clock_frame(&mut self) {
+    if self.envelope_start {
+      self.envelope_start = false;
+      self.decay = 15;
+      self.envelope_divider = self.volume;
+    } else if self.envelope_divider == 0 {
+      self.envelope_divider = self.volume;
+      if self.decay > 0 {
+        self.decay -= 1;
+      } else if self.halt {
+        self.decay = 15;
+      }
+    } else {
+      self.envelope_divider -= 1;
+    }
+
+    if !self.halt && self.length > 0 {
+      self.length -= 1;
+    }
+  }
+
+  fn output(&self) -> u8 {
+    if self.length == 0 {
+      return 0;
+    }
+    let volume = if self.constant { self.volume } else { self.decay };
+    DUTY_SEQUENCES[self.duty as usize][self.step] * volume
+  }
+}
+
+// Nintendo MMC5, two pulses and an 8 bit PCM channel at $5000-$5015. PCM read mode, where the
+// samples come from CPU reads of $8000-$BFFF, and the PCM IRQ are not emulated.
+#[derive(Default)]
+pub struct Mmc5 {
+  pulses: [Pulse; 2],
+  pcm_read_mode: bool,
+  pcm: u8,
+  odd_cycle: bool,
+  frame_timer: u16
+}
+
+impl ExpansionAudio for Mmc5 {
+  fn write(&mut self, addr: u16, value: u8) -> bool {
+    match addr {
+      0x5000 ..= 0x5003 => self.pulses[0].write(addr & 0x03, value),
+      0x5004 ..= 0x5007 => self.pulses[1].write(addr & 0x03, value),
+      0x5010 => self.pcm_read_mode = value & PCM_READ_MODE_BIT != 0,
+      0x5011 => {
+        // Zero is ignored in write mode
+        if !self.pcm_read_mode && value != 0 {
+          self.pcm = value;
+        }
+      }
+      0x5015 => {
+        self.pulses[0].set_enabled(value & 0x01 != 0);
+        self.pulses[1].set_enabled(value & 0x02 != 0);
+      }
+      _ => return false
+    }
+    return true;
+  }
+
+  fn read(&mut self, addr: u16) -> Option<u8> {
+    match addr {
+      0x5010 => Some(0),
+      0x5015 => {
+        let first = (self.pulses[0].length > 0) as u8;
+        let second = (self.pulses[1].length > 0) as u8;
+        Some(first | (second << 1))
+      }
+      _ => None
+    }
+  }
+
+  fn clock(&mut self) {
+    self.odd_cycle = !self.odd_cycle;
+    if self.odd_cycle {
+      for pulse in &mut self.pulses {
+        pulse.clock_timer();
+      }
+    }
+
+    self.frame_timer += 1;
+    if self.frame_timer == FRAME_PERIOD {
+      self.frame_timer = 0;
+      for pulse in &mut self.pulses {
+        pulse.clock_frame();
+      }
+    }
+  }
+
+  fn name(&self) -> &'static str {
+    "MMC5"
+  }
+
+  fn channel_names(&self) -> &'static [&'static str] {
+    &["Pulse 1", "Pulse 2", "PCM"]
+  }
+
+  fn channel_output(&self, channel: usize) -> f32 {
+    match channel {
+      0 | 1 => self.pulses[channel].output() as f32 * PULSE_LEVEL,
+      _ => self.pcm as f32 * PCM_LEVEL
+    }
+  }
+}
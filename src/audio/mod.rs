@@ -0,0 +1,13 @@
+pub mod apu;
+pub mod blip;
+pub mod expansion;
+pub mod fds;
+pub mod mixer;
+pub mod mmc5;
+pub mod namco163;
+pub mod output;
+pub mod ring_buffer;
+pub mod sunsoft5b;
+pub mod vrc6;
+pub mod vrc7;
+pub mod wav;
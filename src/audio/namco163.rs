@@ -0,0 +1,134 @@
+use crate::audio::expansion::{ExpansionAudio, APU_PULSE, NAMCO_163_LEVEL};
+
+// A single channel swings between -8 and 7 times volume 15
+const LEVEL: f32 = APU_PULSE * NAMCO_163_LEVEL / 120.0;
+
+const RAM_SIZE: usize = 128;
+const CHANNEL_REGISTERS: usize = 0x40;
+// One channel is updated every 15 CPU cycles, so more channels play at a lower rate
+const UPDATE_PERIOD: u8 = 15;
+
+const AUTO_INCREMENT_BIT: u8 = 0x80;
+const SOUND_DISABLE_BIT: u8 = 0x40;
+
+// Namco 163, up to 8 wavetable channels whose registers and 4 bit samples share 128 bytes of RAM
+pub struct Namco163 {
+  ram: [u8; RAM_SIZE],
+  address: u8,
+  auto_increment: bool,
+  disabled: bool,
+  timer: u8,
+  // Position in the round robin over the enabled channels
+  current: usize,
+  outputs: [i16; 8]
+}
+
+impl Default for Namco163 {
+  fn default() -> Self {
+    Namco163 {
+      ram: [0; RAM_SIZE],
+      address: 0,
+      auto_increment: false,
+      disabled: false,
+      timer: 0,
+      current: 0,
+      outputs: [0; 8]
+    }
+  }
+}
+
+impl Namco163 {
+  // Enabled channels count down from channel 7
+  fn channel_count(&self) -> usize {
+    ((self.ram[0x7F] >> 4) & 0x07) as usize + 1
+  }
+
+  fn step_address(&mut self) {
+    if self.auto_increment {
+      self.address = (self.address + 1) & 0x7F;
+    }
+  }
+
+  fn update_channel(&mut self, channel: usize) {
+    let base = CHANNEL_REGISTERS + channel * 8;
+    let ram = &mut self.ram;
+
+    let frequency = ram[base] as u32 | (ram[base + 2] as u32) << 8 | (ram[base + 4] as u32 & 0x03) << 16;
+    let phase = ram[base + 1] as u32 | (ram[base + 3] as u32) << 8 | (ram[base + 5] as u32) << 16;
+    let length = 256 - (ram[base + 4] & 0xFC) as u32;
+
+    let phase = (phase + frequency) % (length << 16);
+    ram[base + 1] = phase as u8;
+    ram[base + 3] = (phase >> 8) as u8;
+    ram[base + 5] = (phase >> 16) as u8;
+
+    // Samples are packed two to a byte, low nibble first
+    let sample_index = ((phase >> 16) + ram[base + 6] as u32) & 0xFF;
+    let byte = ram[(sample_index >> 1) as usize];
+    let sample = if sample_index & 0x01 != 0 { byte >> 4 } else { byte & 0x0F };
+
+    let volume = (ram[base + 7] & 0x0F) as i16;
+    self.outputs[channel] = (sample as i16 - 8) * volume;
+  }
+}
+
+impl ExpansionAudio for Namco163 {
+  fn write(&mut self, addr: u16, value: u8) -> bool {
+    match addr & 0xF800 {
+      0x4800 => {
+        self.ram[self.address as usize] = value;
+        self.step_address();
+      }
+      0xF800 => {
+        self.address = value & 0x7F;
+        self.auto_increment = value & AUTO_INCREMENT_BIT != 0;
+      }
+      // Shared with the PRG bank select at $E000
+      0xE000 => {
+        self.disabled = value & SOUND_DISABLE_BIT != 0;
+        return false;
+      }
+      _ => return false
+    }
+    return true;
+  }
+
+  fn read(&mut self, addr: u16) -> Option<u8> {
+    if addr & 0xF800 != 0x4800 {
+      return None;
+    }
+    let value = self.ram[self.address as usize];
+    self.step_address();
+    Some(value)
+  }
+
+  fn clock(&mut self) {
+    self.timer += 1;
+    if self.timer < UPDATE_PERIOD {
+      return;
+    }
+    self.timer = 0;
+
+    let count = self.channel_count();
+    self.current = (self.current + 1) % count;
+    self.update_channel(7 - self.current);
+  }
+
+  fn name(&self) -> &'static str {
+    "N163"
+  }
+
+  fn channel_names(&self) -> &'static [&'static str] {
+    &["Channel 1", "Channel 2", "Channel 3", "Channel 4", "Channel 5", "Channel 6", "Channel 7", "Channel 8"]
+  }
+
+  // The chip plays one channel at a time, averaging them stands in for the fast switching.
+  // Enabled channels count down from channel 8.
+  fn channel_output(&self, channel: usize) -> f32 {
+    let count = self.channel_count();
+    if self.disabled || channel < 8 - count {
+      return 0.0;
+    }
+    self.outputs[channel] as f32 / count as f32 * LEVEL
+  }
+}
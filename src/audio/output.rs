@@ -0,0 +1,245 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::audio::blip::BlipBuffer;
+use crate::audio::ring_buffer::{ring_buffer, RingConsumer, RingProducer};
+use crate::audio::wav::WavWriter;
+
+// Largest change to the resampling ratio rate control makes, small enough not to be heard
+pub const DEFAULT_MAX_RATE_ADJUSTMENT: f64 = 0.005;
+// Seconds of audio the ring buffer holds when it is half full
+pub const DEFAULT_LATENCY: f64 = 0.05;
+
+// Where resampled audio ends up, a host audio device or a stand-in for one
+pub trait AudioSink {
+  fn sample_rate(&self) -> u32;
+  // Called once when output starts. From then on the device pulls samples out of `ring` on its
+  // own thread as it plays them, the way a host audio callback does.
+  fn start(&mut self, ring: RingConsumer) -> Result<(), String>;
+  // Called once when output stops
+  fn finish(&mut self) -> Result<(), String> {
+    Ok(())
+  }
+}
+
+// How often a headless device wakes up to take what it has played since
+const DEVICE_PERIOD: Duration = Duration::from_millis(5);
+
+// Thread standing in for a device playing `speed` times its nominal rate in real time, handing
+// what it takes out of the ring to `play`
+struct Playback {
+  sample_rate: u32,
+  speed: f64,
+  underruns: Arc<AtomicU64>,
+  stop: Arc<AtomicBool>,
+  thread: Option<JoinHandle<Result<(), String>>>
+}
+
+impl Playback {
+  fn new(sample_rate: u32) -> Playback {
+    Playback { sample_rate, speed: 1.0, underruns: Arc::new(AtomicU64::new(0)), stop: Arc::new(AtomicBool::new(false)), thread: None }
+  }
+
+  fn start<F>(&mut self, mut ring: RingConsumer, mut play: F) -> Result<(), String>
+  where F: FnMut(&[i16]) -> Result<(), String> + Send + 'static {
+    if self.thread.is_some() {
+      return Err("AUDIO DEVICE IS ALREADY PLAYING".to_string());
+    }
+
+    let rate = self.sample_rate as f64 * self.speed;
+    let underruns = self.underruns.clone();
+    let stop = self.stop.clone();
+    self.thread = Some(thread::spawn(move || {
+      let started = Instant::now();
+      let mut played = 0;
+      let mut samples = Vec::new();
+      while !stop.load(Ordering::Acquire) {
+        thread::sleep(DEVICE_PERIOD);
+        // Silence where the buffer ran dry
+        let due = (started.elapsed().as_secs_f64() * rate) as u64;
+        samples.clear();
+        samples.resize((due - played) as usize, 0);
+        if ring.pop(&mut samples) < samples.len() {
+          underruns.fetch_add(1, Ordering::Relaxed);
+        }
+        played = due;
+        play(&samples)?;
+      }
+      Ok(())
+    }));
+    Ok(())
+  }
+
+  // Stops the thread and returns the error that stopped it early, if any
+  fn stop(&mut self) -> Result<(), String> {
+    self.stop.store(true, Ordering::Release);
+    match self.thread.take() {
+      Some(thread) => thread.join().map_err(|_| "AUDIO DEVICE THREAD PANICKED".to_string())?,
+      None => Ok(())
+    }
+  }
+}
+
+impl Drop for Playback {
+  fn drop(&mut self) {
+    let _ = self.stop();
+  }
+}
+
+// Throws samples away at the rate a device would play them
+pub struct NullSink {
+  playback: Playback,
+  played: Arc<AtomicU64>
+}
+
+impl NullSink {
+  pub fn new(sample_rate: u32) -> NullSink {
+    NullSink { playback: Playback::new(sample_rate), played: Arc::new(AtomicU64::new(0)) }
+  }
+
+  // Device clock relative to its nominal rate, real devices drift a little. Takes effect on start.
+  pub fn set_speed(&mut self, speed: f64) {
+    self.playback.speed = speed;
+  }
+
+  // Samples the device has played so far
+  pub fn played(&self) -> u64 {
+    self.played.load(Ordering::Relaxed)
+  }
+
+  // Pulls that found too few samples in the buffer
+  pub fn underruns(&self) -> u64 {
+    self.playback.underruns.load(Ordering::Relaxed)
+  }
+}
+
+impl AudioSink for NullSink {
+  fn sample_rate(&self) -> u32 {
+    self.playback.sample_rate
+  }
+
+  fn start(&mut self, ring: RingConsumer) -> Result<(), String> {
+    let played = self.played.clone();
+    self.playback.start(ring, move |samples| {
+      played.fetch_add(samples.len() as u64, Ordering::Relaxed);
+      Ok(())
+    })
+  }
+
+  fn finish(&mut self) -> Result<(), String> {
+    self.playback.stop()
+  }
+}
+
+// Writes what a device would have played to a mono WAV, underruns included as silence
+pub struct WavSink {
+  playback: Playback,
+  writer: Arc<Mutex<Option<WavWriter<BufWriter<File>>>>>
+}
+
+impl WavSink {
+  pub fn create(path: &str, sample_rate: u32) -> Result<WavSink, String> {
+    let file = File::create(path).map_err(|error| format!("UNABLE TO CREATE {}: {}", path, error))?;
+    let writer = WavWriter::new(BufWriter::new(file), sample_rate, 1).map_err(|error| format!("UNABLE TO WRITE {}: {}", path, error))?;
+    Ok(WavSink { playback: Playback::new(sample_rate), writer: Arc::new(Mutex::new(Some(writer))) })
+  }
+
+  pub fn set_speed(&mut self, speed: f64) {
+    self.playback.speed = speed;
+  }
+
+  pub fn underruns(&self) -> u64 {
+    self.playback.underruns.load(Ordering::Relaxed)
+  }
+}
+
+impl AudioSink for WavSink {
+  fn sample_rate(&self) -> u32 {
+    self.playback.sample_rate
+  }
+
+  fn start(&mut self, ring: RingConsumer) -> Result<(), String> {
+    let writer = self.writer.clone();
+    self.playback.start(ring, move |samples| {
+      match writer.lock().map_err(|_| "WAV SINK IS POISONED".to_string())?.as_mut() {
+        Some(writer) => writer.write_samples(samples).map_err(|error| format!("UNABLE TO WRITE AUDIO: {}", error)),
+        None => Err("WAV SINK IS FINISHED".to_string())
+      }
+    })
+  }
+
+  fn finish(&mut self) -> Result<(), String> {
+    self.playback.stop()?;
+    let writer = self.writer.lock().map_err(|_| "WAV SINK IS POISONED".to_string())?.take();
+    match writer {
+      Some(writer) => writer.finish().map_err(|error| format!("UNABLE TO WRITE AUDIO: {}", error)),
+      None => Ok(())
+    }
+  }
+}
+
+// Resampling ratio that steers the buffer back towards half full, above 1 makes more samples
+pub fn rate_ratio(fill: f64, max_adjustment: f64) -> f64 {
+  1.0 + max_adjustment * (1.0 - 2.0 * fill.clamp(0.0, 1.0))
+}
+
+// Resamples emulator output into a ring buffer the sink's device pulls from on its own thread.
+// The emulator runs on its own clock and the device on another, so with rate control on the
+// ratio is nudged every frame to steer the buffer towards half full instead of drifting into
+// underruns or overflows.
+pub struct AudioOutput {
+  pub blip: BlipBuffer,
+  pub ring: RingProducer,
+  pub sink: Box<dyn AudioSink>,
+  pub rate_control: bool,
+  pub max_rate_adjustment: f64
+}
+
+impl AudioOutput {
+  // `latency` is the seconds of audio kept queued, the buffer starts that full of silence
+  pub fn new(mut sink: Box<dyn AudioSink>, clock_rate: f64, latency: f64) -> Result<AudioOutput, String> {
+    let sample_rate = sink.sample_rate() as f64;
+    let (mut ring, consumer) = ring_buffer((latency * sample_rate * 2.0) as usize);
+    ring.push(&vec![0; ring.capacity() / 2]);
+    sink.start(consumer)?;
+
+    Ok(AudioOutput {
+      blip: BlipBuffer::new(clock_rate, sample_rate),
+      ring,
+      sink,
+      rate_control: true,
+      max_rate_adjustment: DEFAULT_MAX_RATE_ADJUSTMENT
+    })
+  }
+
+  pub fn add_delta(&mut self, clock: u32, delta: f32) {
+    self.blip.add_delta(clock, delta);
+  }
+
+  // Ends a frame `clocks` long and queues what it made for the device
+  pub fn end_frame(&mut self, clocks: u32) -> Result<(), String> {
+    if self.ring.is_abandoned() {
+      return Err("AUDIO DEVICE STOPPED PLAYING".to_string());
+    }
+
+    // What the device left of the last frames, before this one is added on top
+    let fill = self.ring.fill();
+    self.blip.end_frame(clocks);
+    let samples = self.blip.read_samples(self.blip.samples_available());
+    self.ring.push(&samples);
+
+    if self.rate_control {
+      let sample_rate = self.sink.sample_rate() as f64 * rate_ratio(fill, self.max_rate_adjustment);
+      self.blip.set_rates(self.blip.clock_rate(), sample_rate);
+    }
+    Ok(())
+  }
+
+  pub fn finish(&mut self) -> Result<(), String> {
+    self.sink.finish()
+  }
+}
@@ -0,0 +1,109 @@
+use std::sync::atomic::{AtomicI16, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+// Fixed size FIFO of samples between the emulator and an audio device, one thread writing and
+// another reading without locks. Samples that do not fit are dropped and reads past the end
+// come back short, rate control keeps both rare.
+struct RingBuffer {
+  samples: Vec<AtomicI16>,
+  // Samples ever read and written, wrapping, so their difference is the fill level
+  read: AtomicUsize,
+  write: AtomicUsize
+}
+
+impl RingBuffer {
+  fn capacity(&self) -> usize {
+    self.samples.len()
+  }
+
+  fn len(&self) -> usize {
+    self.write.load(Ordering::Acquire).wrapping_sub(self.read.load(Ordering::Acquire))
+  }
+}
+
+// Makes a ring buffer and returns its two ends, the producer for the emulator and the consumer
+// for the device
+pub fn ring_buffer(capacity: usize) -> (RingProducer, RingConsumer) {
+  let ring = Arc::new(RingBuffer {
+    samples: (0..capacity.max(1)).map(|_| AtomicI16::new(0)).collect(),
+    read: AtomicUsize::new(0),
+    write: AtomicUsize::new(0)
+  });
+  (RingProducer { ring: ring.clone() }, RingConsumer { ring })
+}
+
+pub struct RingProducer {
+  ring: Arc<RingBuffer>
+}
+
+impl RingProducer {
+  pub fn capacity(&self) -> usize {
+    self.ring.capacity()
+  }
+
+  pub fn len(&self) -> usize {
+    self.ring.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  // How full the buffer is from 0.0 to 1.0, as of the consumer's last read
+  pub fn fill(&self) -> f64 {
+    self.len() as f64 / self.capacity() as f64
+  }
+
+  // True once the consumer is gone, nothing pushed will be played
+  pub fn is_abandoned(&self) -> bool {
+    Arc::strong_count(&self.ring) == 1
+  }
+
+  // Appends as many samples as fit and returns how many that was
+  pub fn push(&mut self, samples: &[i16]) -> usize {
+    let ring = &self.ring;
+    // Only this end moves `write`, the consumer can only make more room meanwhile
+    let write = ring.write.load(Ordering::Relaxed);
+    let count = samples.len().min(ring.capacity() - ring.len());
+    for (offset, sample) in samples[..count].iter().enumerate() {
+      ring.samples[write.wrapping_add(offset) % ring.capacity()].store(*sample, Ordering::Relaxed);
+    }
+    ring.write.store(write.wrapping_add(count), Ordering::Release);
+    count
+  }
+}
+
+pub struct RingConsumer {
+  ring: Arc<RingBuffer>
+}
+
+impl RingConsumer {
+  pub fn capacity(&self) -> usize {
+    self.ring.capacity()
+  }
+
+  pub fn len(&self) -> usize {
+    self.ring.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  pub fn fill(&self) -> f64 {
+    self.len() as f64 / self.capacity() as f64
+  }
+
+  // Fills `out` from the oldest samples and returns how many were there
+  pub fn pop(&mut self, out: &mut [i16]) -> usize {
+    let ring = &self.ring;
+    // Only this end moves `read`, the producer can only add more meanwhile
+    let read = ring.read.load(Ordering::Relaxed);
+    let count = out.len().min(ring.len());
+    for (offset, sample) in out[..count].iter_mut().enumerate() {
+      *sample = ring.samples[read.wrapping_add(offset) % ring.capacity()].load(Ordering::Relaxed);
+    }
+    ring.read.store(read.wrapping_add(count), Ordering::Release);
+    count
+  }
+}
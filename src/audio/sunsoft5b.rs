@@ -0,0 +1,199 @@
+use crate::audio::expansion::{ExpansionAudio, APU_PULSE, SUNSOFT_5B_CHANNEL_LEVEL};
+
+const LEVEL: f32 = APU_PULSE * SUNSOFT_5B_CHANNEL_LEVEL;
+
+// Tone, noise and envelope counters step once every 16 CPU cycles
+const PRESCALER: u8 = 16;
+
+const ENVELOPE_CONTINUE_BIT: u8 = 0b1000;
+const ENVELOPE_ATTACK_BIT: u8 = 0b0100;
+const ENVELOPE_ALTERNATE_BIT: u8 = 0b0010;
+const ENVELOPE_HOLD_BIT: u8 = 0b0001;
+
+const VOLUME_ENVELOPE_BIT: u8 = 0x10;
+
+#[derive(Default)]
+struct Tone {
+  period: u16,
+  counter: u16,
+  high: bool
+}
+
+// Sunsoft 5B, a YM2149F (AY-3-8910 compatible) core behind $C000 register select and $E000 data
+pub struct Sunsoft5b {
+  register: u8,
+  tones: [Tone; 3],
+  noise_period: u8,
+  noise_counter: u16,
+  noise_shift: u32,
+  // Active low tone and noise disables for each channel
+  mixer: u8,
+  volumes: [u8; 3],
+  envelope_period: u16,
+  envelope_counter: u16,
+  envelope_shape: u8,
+  envelope_step: u8,
+  envelope_attack: bool,
+  envelope_holding: bool,
+  prescaler: u8,
+  // Output of a 5 bit volume, 1.5 dB apart
+  levels: [f32; 32]
+}
+
+impl Default for Sunsoft5b {
+  fn default() -> Self {
+    let mut levels = [0.0; 32];
+    for (volume, level) in levels.iter_mut().enumerate().skip(1) {
+      *level = 10f32.powf((volume as f32 - 31.0) * 1.5 / 20.0);
+    }
+
+    Sunsoft5b {
+      register: 0,
+      tones: [Tone::default(), Tone::default(), Tone::default()],
+      noise_period: 0,
+      noise_counter: 0,
+      noise_shift: 1,
+      mixer: 0xFF,
+      volumes: [0; 3],
+      envelope_period: 0,
+      envelope_counter: 0,
+      envelope_shape: 0,
+      envelope_step: 0,
+      envelope_attack: false,
+      envelope_holding: true,
+      prescaler: 0,
+      levels
+    }
+  }
+}
+
+impl Sunsoft5b {
+  fn write_register(&mut self, value: u8) {
+    match self.register {
+      0 | 2 | 4 => {
+        let tone = &mut self.tones[self.register as usize / 2];
+        tone.period = (tone.period & 0x0F00) | value as u16;
+      }
+      1 | 3 | 5 => {
+        let tone = &mut self.tones[self.register as usize / 2];
+        tone.period = (tone.period & 0x00FF) | ((value as u16 & 0x0F) << 8);
+      }
+      6 => self.noise_period = value & 0x1F,
+      7 => self.mixer = value,
+      8 ..= 10 => self.volumes[self.register as usize - 8] = value & 0x1F,
+      11 => self.envelope_period = (self.envelope_period & 0xFF00) | value as u16,
+      12 => self.envelope_period = (self.envelope_period & 0x00FF) | ((value as u16) << 8),
+      13 => {
+        self.envelope_shape = value & 0x0F;
+        self.envelope_attack = value & ENVELOPE_ATTACK_BIT != 0;
+        self.envelope_step = 0;
+        self.envelope_counter = 0;
+        self.envelope_holding = false;
+      }
+      _ => {}
+    }
+  }
+
+  fn envelope_level(&self) -> u8 {
+    if self.envelope_attack { self.envelope_step } else { 31 - self.envelope_step }
+  }
+
+  fn clock_envelope(&mut self) {
+    if self.envelope_holding {
+      return;
+    }
+    self.envelope_step += 1;
+    if self.envelope_step < 32 {
+      return;
+    }
+
+    let shape = self.envelope_shape;
+    if shape & ENVELOPE_CONTINUE_BIT == 0 {
+      // One ramp then silence
+      self.envelope_attack = false;
+      self.envelope_holding = true;
+      self.envelope_step = 31;
+    } else if shape & ENVELOPE_HOLD_BIT != 0 {
+      if shape & ENVELOPE_ALTERNATE_BIT != 0 {
+        self.envelope_attack = !self.envelope_attack;
+      }
+      self.envelope_holding = true;
+      self.envelope_step = 31;
+    } else {
+      if shape & ENVELOPE_ALTERNATE_BIT != 0 {
+        self.envelope_attack = !self.envelope_attack;
+      }
+      self.envelope_step = 0;
+    }
+  }
+
+  fn step(&mut self) {
+    for tone in &mut self.tones {
+      tone.counter += 1;
+      if tone.counter >= tone.period.max(1) {
+        tone.counter = 0;
+        tone.high = !tone.high;
+      }
+    }
+
+    // Noise runs at half the tone rate through a 17 bit LFSR
+    self.noise_counter += 1;
+    if self.noise_counter >= (self.noise_period.max(1) as u16) * 2 {
+      self.noise_counter = 0;
+      let bit = (self.noise_shift ^ (self.noise_shift >> 3)) & 0x01;
+      self.noise_shift = (self.noise_shift >> 1) | (bit << 16);
+    }
+
+    self.envelope_counter += 1;
+    if self.envelope_counter >= self.envelope_period.max(1) {
+      self.envelope_counter = 0;
+      self.clock_envelope();
+    }
+  }
+}
+
+impl ExpansionAudio for Sunsoft5b {
+  fn write(&mut self, addr: u16, value: u8) -> bool {
+    match addr & 0xE000 {
+      0xC000 => self.register = value & 0x0F,
+      0xE000 => self.write_register(value),
+      _ => return false
+    }
+    return true;
+  }
+
+  fn clock(&mut self) {
+    self.prescaler += 1;
+    if self.prescaler == PRESCALER {
+      self.prescaler = 0;
+      self.step();
+    }
+  }
+
+  fn name(&self) -> &'static str {
+    "5B"
+  }
+
+  fn channel_names(&self) -> &'static [&'static str] {
+    &["Channel A", "Channel B", "Channel C"]
+  }
+
+  fn channel_output(&self, channel: usize) -> f32 {
+    // With both tone and noise off the channel outputs its volume constantly
+    let tone_on = self.tones[channel].high || self.mixer & (1 << channel) != 0;
+    let noise_on = self.noise_shift & 0x01 != 0 || self.mixer & (8 << channel) != 0;
+    if !(tone_on && noise_on) {
+      return 0.0;
+    }
+
+    let volume = self.volumes[channel];
+    let level = if volume & VOLUME_ENVELOPE_BIT != 0 {
+      self.envelope_level()
+    } else if volume & 0x0F == 0 {
+      0
+    } else {
+      (volume & 0x0F) * 2 + 1
+    };
+    self.levels[level as usize] * LEVEL
+  }
+}
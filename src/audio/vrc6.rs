@@ -0,0 +1,171 @@
+use crate::audio::expansion::{ExpansionAudio, APU_PULSE, VRC6_PULSE_LEVEL};
+
+// Output step of a pulse at volume 1, the saw shares the same DAC
+const LEVEL: f32 = APU_PULSE * VRC6_PULSE_LEVEL / 15.0;
+
+const HALT_BIT: u8 = 0x01;
+const SHIFT_4_BIT: u8 = 0x02;
+const SHIFT_8_BIT: u8 = 0x04;
+const ENABLE_BIT: u8 = 0x80;
+
+#[derive(Default)]
+struct Pulse {
+  volume: u8,
+  duty: u8,
+  // Ignores the duty cycle and outputs the volume all the time
+  constant: bool,
+  period: u16,
+  enabled: bool,
+  timer: u16,
+  // Counts down from 15, the output is high while it is at or below the duty
+  step: u8
+}
+
+impl Pulse {
+  fn write(&mut self, register: u16, value: u8) {
+    match register {
+      0 => {
+        self.volume = value & 0x0F;
+        self.duty = (value >> 4) & 0x07;
+        self.constant = value & 0x80 != 0;
+      }
+      1 => self.period = (self.period & 0x0F00) | value as u16,
+      _ => {
+        self.period = (self.period & 0x00FF) | ((value as u16 & 0x0F) << 8);
+        self.enabled = value & ENABLE_BIT != 0;
+        if !self.enabled {
+          self.step = 15;
+        }
+      }
+    }
+  }
+
+  fn clock(&mut self, shift: u8) {
+    if !self.enabled {
+      return;
+    }
+    if self.timer == 0 {
+      self.timer = self.period >> shift;
+      self.step = (self.step + 15) % 16;
+    } else {
+      self.timer -= 1;
+    }
+  }
+
+  fn output(&self) -> u8 {
+    if self.enabled && (self.constant || self.step <= self.duty) { self.volume } else { 0 }
+  }
+}
+
+#[derive(Default)]
+struct Saw {
+  rate: u8,
+  period: u16,
+  enabled: bool,
+  timer: u16,
+  step: u8,
+  accumulator: u8
+}
+
+impl Saw {
+  fn write(&mut self, register: u16, value: u8) {
+    match register {
+      0 => self.rate = value & 0x3F,
+      1 => self.period = (self.period & 0x0F00) | value as u16,
+      _ => {
+        self.period = (self.period & 0x00FF) | ((value as u16 & 0x0F) << 8);
+        self.enabled = value & ENABLE_BIT != 0;
+        if !self.enabled {
+          self.step = 0;
+          self.accumulator = 0;
+        }
+      }
+    }
+  }
+
+  // The rate is added on every second step and the accumulator clears after the seventh add.
+  // Rates above 42 overflow it, which games use for a distorted sound.
+  fn clock(&mut self, shift: u8) {
+    if !self.enabled {
+      return;
+    }
+    if self.timer > 0 {
+      self.timer -= 1;
+      return;
+    }
+
+    self.timer = self.period >> shift;
+    self.step += 1;
+    if self.step == 14 {
+      self.step = 0;
+      self.accumulator = 0;
+    } else if self.step & 0x01 == 0 {
+      self.accumulator = self.accumulator.wrapping_add(self.rate);
+    }
+  }
+
+  fn output(&self) -> u8 {
+    self.accumulator >> 3
+  }
+}
+
+// Konami VRC6, two pulses and a sawtooth at $9000-$B002
+pub struct Vrc6 {
+  swapped: bool,
+  pulses: [Pulse; 2],
+  saw: Saw,
+  halted: bool,
+  shift: u8
+}
+
+impl Vrc6 {
+  pub fn new(swapped: bool) -> Vrc6 {
+    Vrc6 { swapped, pulses: [Pulse::default(), Pulse::default()], saw: Saw::default(), halted: false, shift: 0 }
+  }
+}
+
+impl ExpansionAudio for Vrc6 {
+  fn write(&mut self, addr: u16, value: u8) -> bool {
+    let addr = if self.swapped { (addr & !0x03) | ((addr & 0x01) << 1) | ((addr >> 1) & 0x01) } else { addr };
+    let register = addr & 0x03;
+
+    match addr & 0xF003 {
+      0x9003 => {
+        // Frequency control, the shifts speed every channel up for 16 or 256 times the pitch
+        self.halted = value & HALT_BIT != 0;
+        self.shift = if value & SHIFT_8_BIT != 0 { 8 } else if value & SHIFT_4_BIT != 0 { 4 } else { 0 };
+      }
+      0x9000 ..= 0x9002 => self.pulses[0].write(register, value),
+      0xA000 ..= 0xA002 => self.pulses[1].write(register, value),
+      0xB000 ..= 0xB002 => self.saw.write(register, value),
+      _ => return false
+    }
+    return true;
+  }
+
+  fn clock(&mut self) {
+    if self.halted {
+      return;
+    }
+    for pulse in &mut self.pulses {
+      pulse.clock(self.shift);
+    }
+    self.saw.clock(self.shift);
+  }
+
+  fn name(&self) -> &'static str {
+    "VRC6"
+  }
+
+  fn channel_names(&self) -> &'static [&'static str] {
+    &["Pulse 1", "Pulse 2", "Saw"]
+  }
+
+  fn channel_output(&self, channel: usize) -> f32 {
+    let level = match channel {
+      0 | 1 => self.pulses[channel].output(),
+      _ => self.saw.output()
+    };
+    level as f32 * LEVEL
+  }
+}
@@ -0,0 +1,353 @@
+use std::f64::consts::PI;
+
+use crate::audio::expansion::{ExpansionAudio, APU_PULSE, VRC7_CHANNEL_LEVEL};
+
+const LEVEL: f32 = APU_PULSE * VRC7_CHANNEL_LEVEL;
+
+const CHANNEL_COUNT: usize = 6;
+// The OPLL core makes one sample every 72 clocks of its 3.58 MHz crystal, twice the CPU clock
+const CYCLES_PER_SAMPLE: u8 = 36;
+const SAMPLE_RATE: f64 = 3_579_545.0 / 72.0;
+
+// Built in instruments 1-15, dumped from a VRC7 die
+const PATCHES: [[u8; 8]; 15] = [
+  [0x03, 0x21, 0x05, 0x06, 0xE8, 0x81, 0x42, 0x27],
+  [0x13, 0x41, 0x14, 0x0D, 0xD8, 0xF6, 0x23, 0x12],
+  [0x11, 0x11, 0x08, 0x08, 0xFA, 0xB2, 0x20, 0x12],
+  [0x31, 0x61, 0x0C, 0x07, 0xA8, 0x64, 0x61, 0x27],
+  [0x32, 0x21, 0x1E, 0x06, 0xE1, 0x76, 0x01, 0x28],
+  [0x02, 0x01, 0x06, 0x00, 0xA3, 0xE2, 0xF4, 0xF4],
+  [0x21, 0x61, 0x1D, 0x07, 0x82, 0x81, 0x11, 0x07],
+  [0x23, 0x21, 0x22, 0x17, 0xA2, 0x72, 0x01, 0x17],
+  [0x35, 0x11, 0x25, 0x00, 0x40, 0x73, 0x72, 0x01],
+  [0xB5, 0x01, 0x0F, 0x0F, 0xA8, 0xA5, 0x51, 0x02],
+  [0x17, 0xC1, 0x24, 0x07, 0xF8, 0xF8, 0x22, 0x12],
+  [0x71, 0x23, 0x11, 0x06, 0x65, 0x74, 0x18, 0x16],
+  [0x01, 0x02, 0xD3, 0x05, 0xC9, 0x95, 0x03, 0x02],
+  [0x61, 0x63, 0x0C, 0x00, 0x94, 0xC0, 0x33, 0xF6],
+  [0x21, 0x72, 0x0D, 0x00, 0xC1, 0xD5, 0x56, 0x06]
+];
+
+const MULTIPLIERS: [f64; 16] = [0.5, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 10.0, 10.0, 12.0, 12.0, 15.0, 15.0];
+
+// Key scale level in dB at block 7 by the top 4 bits of the frequency, 3 dB less per block down
+const KEY_SCALE_LEVELS: [f64; 16] = [
+  0.0, 9.0, 12.0, 13.875, 15.0, 16.125, 16.875, 17.625,
+  18.0, 18.75, 19.125, 19.5, 19.875, 20.25, 20.625, 21.0
+];
+
+// Envelope attenuation is counted in 0.375 dB steps down to silence at 48 dB
+const ENVELOPE_STEP_DB: f64 = 0.375;
+const ENVELOPE_SILENT: f64 = 127.0;
+
+const TREMOLO_RATE: f64 = 3.7;
+const TREMOLO_DB: f64 = 4.8;
+const VIBRATO_RATE: f64 = 6.4;
+const VIBRATO_DEPTH: f64 = 0.004;
+
+// Full scale modulator output moves the carrier by two cycles
+const MODULATION_DEPTH: f64 = 2.0;
+
+// Patch flag bits for either operator
+const TREMOLO_BIT: u8 = 0x80;
+const VIBRATO_BIT: u8 = 0x40;
+const SUSTAINED_BIT: u8 = 0x20;
+const KEY_SCALE_RATE_BIT: u8 = 0x10;
+
+const KEY_ON_BIT: u8 = 0x10;
+const SUSTAIN_BIT: u8 = 0x20;
+const SILENCE_BIT: u8 = 0x40;
+
+#[derive(Clone, Copy, PartialEq)]
+enum EnvelopeState {
+  Attack,
+  Decay,
+  Sustain,
+  Release
+}
+
+#[derive(Clone, Copy)]
+struct Operator {
+  phase: f64,
+  state: EnvelopeState,
+  attenuation: f64
+}
+
+impl Default for Operator {
+  fn default() -> Self {
+    Operator { phase: 0.0, state: EnvelopeState::Release, attenuation: ENVELOPE_SILENT }
+  }
+}
+
+// One operator's settings unpacked from a patch
+struct OperatorPatch {
+  flags: u8,
+  key_scale_level: u8,
+  rectified: bool,
+  attack: u8,
+  decay: u8,
+  sustain_level: u8,
+  release: u8
+}
+
+impl OperatorPatch {
+  fn new(patch: &[u8; 8], carrier: bool) -> OperatorPatch {
+    let index = carrier as usize;
+    OperatorPatch {
+      flags: patch[index],
+      key_scale_level: patch[2 + index] >> 6,
+      rectified: patch[3] & if carrier { 0x10 } else { 0x08 } != 0,
+      attack: patch[4 + index] >> 4,
+      decay: patch[4 + index] & 0x0F,
+      sustain_level: patch[6 + index] >> 4,
+      release: patch[6 + index] & 0x0F
+    }
+  }
+}
+
+#[derive(Clone, Copy, Default)]
+struct Channel {
+  frequency: u16,
+  block: u8,
+  key_on: bool,
+  sustain: bool,
+  instrument: u8,
+  volume: u8,
+  modulator: Operator,
+  carrier: Operator,
+  // Last two modulator outputs for feedback
+  feedback: [f64; 2]
+}
+
+impl Channel {
+  fn set_key(&mut self, key_on: bool) {
+    if key_on && !self.key_on {
+      for operator in [&mut self.modulator, &mut self.carrier].iter_mut() {
+        operator.phase = 0.0;
+        operator.state = EnvelopeState::Attack;
+      }
+    } else if !key_on && self.key_on {
+      self.modulator.state = EnvelopeState::Release;
+      self.carrier.state = EnvelopeState::Release;
+    }
+    self.key_on = key_on;
+  }
+
+  fn key_code(&self) -> u8 {
+    (self.block << 1) | (self.frequency >> 8) as u8
+  }
+}
+
+// Konami VRC7, a cut down YM2413 (OPLL) with six two operator FM channels behind $9010 address and
+// $9030 data. Synthesis is done in floating point rather than with the chip's log-sin tables.
+pub struct Vrc7 {
+  address: u8,
+  custom: [u8; 8],
+  channels: [Channel; CHANNEL_COUNT],
+  silenced: bool,
+  cycles: u8,
+  tremolo_phase: f64,
+  vibrato_phase: f64,
+  // Latest sample of each channel
+  samples: [f32; CHANNEL_COUNT]
+}
+
+impl Default for Vrc7 {
+  fn default() -> Self {
+    Vrc7 {
+      address: 0,
+      custom: [0; 8],
+      channels: [Channel::default(); CHANNEL_COUNT],
+      silenced: false,
+      cycles: 0,
+      tremolo_phase: 0.0,
+      vibrato_phase: 0.0,
+      samples: [0.0; CHANNEL_COUNT]
+    }
+  }
+}
+
+impl Vrc7 {
+  fn write_register(&mut self, value: u8) {
+    let channel = (self.address & 0x0F) as usize;
+    match self.address {
+      0x00 ..= 0x07 => self.custom[self.address as usize] = value,
+      0x10 ..= 0x15 => {
+        let channel = &mut self.channels[channel];
+        channel.frequency = (channel.frequency & 0x100) | value as u16;
+      }
+      0x20 ..= 0x25 => {
+        let channel = &mut self.channels[channel];
+        channel.frequency = (channel.frequency & 0xFF) | ((value as u16 & 0x01) << 8);
+        channel.block = (value >> 1) & 0x07;
+        channel.sustain = value & SUSTAIN_BIT != 0;
+        channel.set_key(value & KEY_ON_BIT != 0);
+      }
+      0x30 ..= 0x35 => {
+        let channel = &mut self.channels[channel];
+        channel.instrument = value >> 4;
+        channel.volume = value & 0x0F;
+      }
+      _ => {}
+    }
+  }
+
+  fn patch(&self, instrument: u8) -> [u8; 8] {
+    if instrument == 0 { self.custom } else { PATCHES[instrument as usize - 1] }
+  }
+
+  fn render(&mut self) {
+    self.tremolo_phase = (self.tremolo_phase + TREMOLO_RATE / SAMPLE_RATE).fract();
+    self.vibrato_phase = (self.vibrato_phase + VIBRATO_RATE / SAMPLE_RATE).fract();
+    let tremolo_db = TREMOLO_DB * (1.0 - (2.0 * PI * self.tremolo_phase).cos()) / 2.0;
+    let vibrato = 1.0 + VIBRATO_DEPTH * (2.0 * PI * self.vibrato_phase).sin();
+
+    for index in 0..CHANNEL_COUNT {
+      let patch = self.patch(self.channels[index].instrument);
+      let modulator_patch = OperatorPatch::new(&patch, false);
+      let carrier_patch = OperatorPatch::new(&patch, true);
+      let channel = &mut self.channels[index];
+
+      let key_scale_db = (KEY_SCALE_LEVELS[(channel.frequency >> 5) as usize] - 3.0 * (7 - channel.block) as f64).max(0.0);
+      let base_frequency = channel.frequency as f64 * (1 << channel.block) as f64 / (1 << 19) as f64;
+      let key_code = channel.key_code();
+      let (key_on, sustain) = (channel.key_on, channel.sustain);
+
+      // Modulator, with its total level and self feedback
+      let feedback = patch[3] & 0x07;
+      let feedback_phase = if feedback == 0 {
+        0.0
+      } else {
+        (channel.feedback[0] + channel.feedback[1]) / 2.0 * (1 << (feedback - 1)) as f64 / 32.0
+      };
+      let total_level_db = (patch[2] & 0x3F) as f64 * 0.75;
+      let modulator = operator_output(
+        &mut channel.modulator, &modulator_patch, base_frequency, feedback_phase,
+        total_level_db, key_scale_db, tremolo_db, vibrato, key_code, key_on, sustain
+      );
+      channel.feedback = [channel.feedback[1], modulator];
+
+      let volume_db = channel.volume as f64 * 3.0;
+      let carrier = operator_output(
+        &mut channel.carrier, &carrier_patch, base_frequency, modulator * MODULATION_DEPTH,
+        volume_db, key_scale_db, tremolo_db, vibrato, key_code, key_on, sustain
+      );
+      self.samples[index] = if self.silenced { 0.0 } else { carrier as f32 };
+    }
+  }
+}
+
+// Envelope change in attenuation steps per sample for an effective rate of 0-63
+fn envelope_rate(rate: u8, key_code: u8, key_scale_rate: bool) -> f64 {
+  if rate == 0 {
+    return 0.0;
+  }
+  let key_scale = if key_scale_rate { key_code } else { key_code >> 2 };
+  let effective = (rate * 4 + key_scale).min(63) as i32;
+  (4 + (effective & 0x03)) as f64 / 4.0 * 2f64.powi((effective >> 2) - 13)
+}
+
+// Advances one operator by a sample and returns its output from -1 to 1
+#[allow(clippy::too_many_arguments)]
+fn operator_output(
+  operator: &mut Operator, patch: &OperatorPatch, base_frequency: f64, phase_offset: f64,
+  level_db: f64, key_scale_db: f64, tremolo_db: f64, vibrato: f64, key_code: u8, key_on: bool, sustain: bool
+) -> f64 {
+  let key_scale_rate = patch.flags & KEY_SCALE_RATE_BIT != 0;
+  let sustained = patch.flags & SUSTAINED_BIT != 0;
+  let sustain_level = patch.sustain_level as f64 * 8.0;
+
+  match operator.state {
+    EnvelopeState::Attack => {
+      if patch.attack == 15 {
+        operator.attenuation = 0.0;
+      } else {
+        // Attack is exponential, fast at first and slowing as it nears full level
+        let rate = envelope_rate(patch.attack, key_code, key_scale_rate);
+        operator.attenuation -= rate * (operator.attenuation / 8.0 + 1.0);
+      }
+      if operator.attenuation <= 0.0 {
+        operator.attenuation = 0.0;
+        operator.state = EnvelopeState::Decay;
+      }
+    }
+    EnvelopeState::Decay => {
+      operator.attenuation += envelope_rate(patch.decay, key_code, key_scale_rate);
+      if operator.attenuation >= sustain_level {
+        operator.attenuation = sustain_level;
+        operator.state = EnvelopeState::Sustain;
+      }
+    }
+    EnvelopeState::Sustain => {
+      // Percussive patches keep fading at the release rate while the key is held
+      if !sustained {
+        operator.attenuation += envelope_rate(patch.release, key_code, key_scale_rate);
+      }
+    }
+    EnvelopeState::Release => {
+      let release = if sustain { 5 } else if sustained { patch.release } else { 7 };
+      if !key_on {
+        operator.attenuation += envelope_rate(release, key_code, key_scale_rate);
+      }
+    }
+  }
+  operator.attenuation = operator.attenuation.min(ENVELOPE_SILENT);
+  if operator.attenuation >= ENVELOPE_SILENT {
+    return 0.0;
+  }
+
+  let frequency = base_frequency * MULTIPLIERS[(patch.flags & 0x0F) as usize];
+  let frequency = if patch.flags & VIBRATO_BIT != 0 { frequency * vibrato } else { frequency };
+  operator.phase = (operator.phase + frequency).fract();
+
+  let mut attenuation_db = operator.attenuation * ENVELOPE_STEP_DB + level_db;
+  if patch.key_scale_level != 0 {
+    attenuation_db += key_scale_db * (1 << patch.key_scale_level) as f64 / 4.0;
+  }
+  if patch.flags & TREMOLO_BIT != 0 {
+    attenuation_db += tremolo_db;
+  }
+
+  let wave = (2.0 * PI * (operator.phase + phase_offset)).sin();
+  let wave = if patch.rectified && wave < 0.0 { 0.0 } else { wave };
+  wave * 10f64.powf(-attenuation_db / 20.0)
+}
+
+impl ExpansionAudio for Vrc7 {
+  fn write(&mut self, addr: u16, value: u8) -> bool {
+    match addr & 0xF030 {
+      0x9010 => self.address = value,
+      0x9030 => self.write_register(value),
+      _ => {
+        // Shared with the mapper's control register at $E000
+        if addr & 0xF000 == 0xE000 {
+          self.silenced = value & SILENCE_BIT != 0;
+        }
+        return false;
+      }
+    }
+    return true;
+  }
+
+  fn clock(&mut self) {
+    self.cycles += 1;
+    if self.cycles < CYCLES_PER_SAMPLE {
+      return;
+    }
+    self.cycles = 0;
+
+    self.render();
+  }
+
+  fn name(&self) -> &'static str {
+    "VRC7"
+  }
+
+  fn channel_names(&self) -> &'static [&'static str] {
+    &["FM 1", "FM 2", "FM 3", "FM 4", "FM 5", "FM 6"]
+  }
+
+  fn channel_output(&self, channel: usize) -> f32 {
+    self.samples[channel] * LEVEL
+  }
+}
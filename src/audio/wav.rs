@@ -0,0 +1,59 @@
+use std::io::{self, Seek, SeekFrom, Write};
+
+const HEADER_SIZE: u32 = 44;
+const BITS_PER_SAMPLE: u16 = 16;
+
+// 16 bit PCM WAV, sizes are filled in by `finish`
+pub struct WavWriter<W: Write + Seek> {
+  out: W,
+  channels: u16,
+  samples: u32
+}
+
+impl<W: Write + Seek> WavWriter<W> {
+  pub fn new(mut out: W, sample_rate: u32, channels: u16) -> io::Result<WavWriter<W>> {
+    let block_align = channels * BITS_PER_SAMPLE / 8;
+
+    let mut header = Vec::with_capacity(HEADER_SIZE as usize);
+    header.extend_from_slice(b"RIFF");
+    header.extend_from_slice(&0u32.to_le_bytes());
+    header.extend_from_slice(b"WAVEfmt ");
+    header.extend_from_slice(&16u32.to_le_bytes());
+    header.extend_from_slice(&1u16.to_le_bytes());
+    header.extend_from_slice(&channels.to_le_bytes());
+    header.extend_from_slice(&sample_rate.to_le_bytes());
+    header.extend_from_slice(&(sample_rate * block_align as u32).to_le_bytes());
+    header.extend_from_slice(&block_align.to_le_bytes());
+    header.extend_from_slice(&BITS_PER_SAMPLE.to_le_bytes());
+    header.extend_from_slice(b"data");
+    header.extend_from_slice(&0u32.to_le_bytes());
+
+    out.write_all(&header)?;
+    Ok(WavWriter { out, channels, samples: 0 })
+  }
+
+  // Interleaved when there is more than one channel
+  pub fn write_samples(&mut self, samples: &[i16]) -> io::Result<()> {
+    let mut bytes = Vec::with_capacity(samples.len() * 2);
+    for sample in samples {
+      bytes.extend_from_slice(&sample.to_le_bytes());
+    }
+    self.samples += samples.len() as u32;
+    self.out.write_all(&bytes)
+  }
+
+  // Sample frames written so far, one per channel group
+  pub fn length(&self) -> u32 {
+    self.samples / self.channels as u32
+  }
+
+  pub fn finish(mut self) -> io::Result<()> {
+    let data_size = self.samples * 2;
+    self.out.seek(SeekFrom::Start(4))?;
+    self.out.write_all(&(HEADER_SIZE - 8 + data_size).to_le_bytes())?;
+    self.out.seek(SeekFrom::Start(40))?;
+    self.out.write_all(&data_size.to_le_bytes())?;
+    self.out.seek(SeekFrom::End(0))?;
+    self.out.flush()
+  }
+}
@@ -0,0 +1,40 @@
+// Standard base64 (RFC 4648) with padding, as FM2 headers store binary values
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const PADDING: u8 = b'=';
+
+pub fn encode(data: &[u8]) -> String {
+  let mut text = String::with_capacity(data.len().div_ceil(3) * 4);
+  for chunk in data.chunks(3) {
+    let bits = (chunk[0] as u32) << 16 | (*chunk.get(1).unwrap_or(&0) as u32) << 8 | *chunk.get(2).unwrap_or(&0) as u32;
+    for index in 0..4 {
+      if index <= chunk.len() {
+        text.push(ALPHABET[(bits >> (18 - 6 * index) & 0x3F) as usize] as char);
+      } else {
+        text.push(PADDING as char);
+      }
+    }
+  }
+  text
+}
+
+pub fn decode(text: &str) -> Result<Vec<u8>, String> {
+  let text = text.trim_end_matches(PADDING as char).as_bytes();
+  let mut data = Vec::with_capacity(text.len() * 3 / 4);
+  let mut bits: u32 = 0;
+  let mut count = 0;
+  for character in text {
+    let value = ALPHABET.iter().position(|letter| letter == character).ok_or_else(|| "BAD BASE64 DATA".to_string())?;
+    bits = bits << 6 | value as u32;
+    count += 6;
+    if count >= 8 {
+      count -= 8;
+      data.push((bits >> count) as u8);
+    }
+  }
+  // A lone character cannot make a byte
+  if text.len() % 4 == 1 {
+    return Err("BAD BASE64 DATA".to_string());
+  }
+  Ok(data)
+}
@@ -0,0 +1,400 @@
+use crate::audio::apu::{self, Apu};
+use crate::audio::expansion::{self, ExpansionAudio};
+use crate::audio::mixer::Mixer;
+use crate::emu::ppu::PPU;
+use crate::emu::cartridge::Cartridge;
+use crate::emu::cpu_bus::CpuBus;
+use crate::emu::input::{self, ExpansionDevice, InputDevice};
+use crate::emu::joypad::Joypad;
+use crate::emu::keyboard::FamilyBasicKeyboard;
+use crate::emu::multitap::{FourScore, HoriAdapter};
+use crate::emu::power_pad::PowerPad;
+use crate::emu::save_state::{StateReader, StateWriter};
+use crate::emu::vaus::Vaus;
+use crate::emu::zapper::Zapper;
+
+// RAM Addresses
+const RAM_BEGIN: u16 = 0x0000;
+const RAM_END: u16 = 0x1FFF;
+
+// PPU Register Addresses
+const PPU_REGISTER_BEGIN: u16 = 0x2000;
+const PPU_REGISTER_END: u16 = 0x3FFF;
+
+const PPU_CONTROL: u16 = 0x2000;
+const PPU_MASK: u16 = 0x2001;
+const PPU_SCROLL: u16 = 0x2005;
+const PPU_STATUS: u16 = 0x2002;
+const PPU_MAP_ADDR: u16 = 0x2006;
+const PPU_MAP_DATA: u16 = 0x2007;
+const PPU_OAM_ADDR: u16 = 0x2003;
+const PPU_OAM_DATA: u16 = 0x2004;
+
+// Writing a page number copies that page of CPU memory into OAM
+const OAM_DMA: u16 = 0x4014;
+// CPU cycles the copy halts the CPU for, one more when it starts on an odd cycle
+const OAM_DMA_CYCLES: u16 = 513;
+// CPU cycles a DMC sample fetch steals
+const DMC_FETCH_CYCLES: u16 = 4;
+
+const APU_REGISTER_BEGIN: u16 = 0x4000;
+const APU_REGISTER_END: u16 = 0x4013;
+
+// Controller ports
+const JOYPAD_1: u16 = 0x4016;
+const JOYPAD_2: u16 = 0x4017;
+// Upper bits of $4016/$4017 reads come from open bus, which holds the $40 address byte
+const OPEN_BUS: u8 = 0x40;
+// Famicom second controller microphone level, on $4016 rather than $4017
+const MICROPHONE_BIT: u8 = 0x04;
+
+const PRG_RAM_BEGIN: u16 = 0x6000;
+const PRG_RAM_END: u16 = 0x7FFF;
+
+const PRG_ROM_BEGIN: u16 = 0x8000;
+const PRG_ROM_END: u16 = 0xFFFF;
+
+pub struct Bus {
+  pub ram: Vec<u8>,
+  pub ppu: PPU,
+  pub cartridge: Cartridge,
+  // Controller ports, standard controllers unless something else is plugged in
+  pub ports: [Box<dyn InputDevice>; 2],
+  pub expansion_port: Option<Box<dyn ExpansionDevice>>,
+  // Someone is blowing into the Famicom second controller's microphone
+  pub microphone: bool,
+  pub apu: Apu,
+  // Sound chips on the cartridge, NSF files can have several
+  pub expansion_audio: Vec<Box<dyn ExpansionAudio>>,
+  pub mixer: Mixer,
+  // CPU cycles OAM DMA and DMC fetches still have to take, collected by the CPU after each instruction
+  dma_cycles: u16
+}
+
+impl Bus {
+  pub fn new(cartridge: Cartridge) -> Bus {
+    let mut ppu = PPU::new(cartridge.chr_rom.clone(), cartridge.mirroring);
+    ppu.chr_ram = cartridge.chr_ram;
+
+    let expansion_audio: Vec<Box<dyn ExpansionAudio>> = expansion::for_mapper(cartridge.mapper).into_iter().collect();
+    let mixer = Mixer::new(&expansion_audio);
+
+    let mut bus = Bus {
+      ram: Vec::with_capacity(0x800),
+      ppu,
+      apu: Apu::new(),
+      expansion_audio,
+      mixer,
+      cartridge,
+      ports: [Box::new(Joypad::default()), Box::new(Joypad::default())],
+      expansion_port: None,
+      microphone: false,
+      dma_cycles: 0
+    };
+    bus.ram.resize(0x800, 0x00);
+    bus.plug_default_devices(bus.cartridge.expansion_device);
+    return bus;
+  }
+
+  // Power cycles everything on the bus but the input devices and the mixer settings. The PPU
+  // frame counter keeps going so frame numbers stay meaningful across it.
+  pub fn power(&mut self) {
+    for byte in self.ram.iter_mut() {
+      *byte = 0x00;
+    }
+    self.cartridge.power();
+
+    let frame = self.ppu.frame;
+    self.ppu = PPU::new(self.cartridge.chr_rom.clone(), self.cartridge.mirroring);
+    self.ppu.chr_ram = self.cartridge.chr_ram;
+    self.ppu.frame = frame;
+
+    self.apu = Apu::new();
+    self.expansion_audio = expansion::for_mapper(self.cartridge.mapper).into_iter().collect();
+  }
+
+  // RAM, the cartridge, the PPU, the APU and the latches of what is plugged in. Each device's state is
+  // kept apart so one that saves nothing cannot throw the rest off.
+  pub fn save_state(&self, state: &mut StateWriter) {
+    state.bytes(&self.ram);
+    state.u16(self.dma_cycles);
+    self.cartridge.save_state(state);
+    self.ppu.save_state(state);
+    self.apu.save_state(state);
+    for port in &self.ports {
+      let mut device = StateWriter::new();
+      port.save_state(&mut device);
+      state.bytes(&device.bytes);
+    }
+    let mut device = StateWriter::new();
+    if let Some(expansion) = &self.expansion_port {
+      expansion.save_state(&mut device);
+    }
+    state.bytes(&device.bytes);
+  }
+
+  pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+    state.bytes_into(&mut self.ram)?;
+    self.dma_cycles = state.u16()?;
+    self.cartridge.load_state(state)?;
+    self.ppu.load_state(state)?;
+    self.apu.load_state(state)?;
+    for port in &mut self.ports {
+      let mut device = StateReader::new(state.bytes()?);
+      port.load_state(&mut device)?;
+      if !device.is_finished() {
+        return Err("SAVE STATE DOES NOT MATCH THE DEVICES PLUGGED IN".to_string());
+      }
+    }
+    let mut device = StateReader::new(state.bytes()?);
+    if let Some(expansion) = &mut self.expansion_port {
+      expansion.load_state(&mut device)?;
+    }
+    if !device.is_finished() {
+      return Err("SAVE STATE DOES NOT MATCH THE DEVICES PLUGGED IN".to_string());
+    }
+    Ok(())
+  }
+
+  pub fn read(&mut self, addr: u16) -> u8 {
+    match addr {
+      // Main RAM read
+      RAM_BEGIN ..= RAM_END => {
+        return self.ram[usize::from(addr & 0x7FF)];
+      }
+      0x2000 | 0x2001 | 0x2003 | 0x2005 | 0x2006 | 0x4014 => {
+        // Write only registers
+        return 0;
+      }
+      PPU_STATUS => {
+        return self.ppu.read_status();
+      }
+      PPU_OAM_DATA => {
+        return self.ppu.read_oam_data();
+      }
+      PPU_MAP_DATA => {
+        return self.ppu.read();
+      }
+      apu::STATUS => {
+        return self.apu.read_status();
+      }
+      JOYPAD_1 => {
+        let mut value = OPEN_BUS | self.ports[0].read() | self.read_expansion_port(0);
+        if self.microphone {
+          value |= MICROPHONE_BIT;
+        }
+        return value;
+      }
+      JOYPAD_2 => {
+        return OPEN_BUS | self.ports[1].read() | self.read_expansion_port(1);
+      }
+      0x2008 ..= PPU_REGISTER_END => {
+        // Mirror down address to real PPU space
+        return self.read(addr & 0x2007);
+      }
+      PRG_RAM_BEGIN ..= PRG_RAM_END => {
+        return self.cartridge.prg_ram[(addr - PRG_RAM_BEGIN) as usize];
+      }
+      PRG_ROM_BEGIN ..= PRG_ROM_END => {
+        return self.cartridge.prg_rom[self.prg_rom_index(addr)];
+      }
+      _ => {
+        for chip in &mut self.expansion_audio {
+          if let Some(value) = chip.read(addr) {
+            return value;
+          }
+        }
+        // Open bus, nothing answers here
+        return 0;
+      }
+    }
+  }
+
+  pub fn write(&mut self, addr: u16, value: u8) {
+    // Sound chip registers can sit on top of mapper registers, so every chip sees every write
+    for chip in &mut self.expansion_audio {
+      chip.write(addr, value);
+    }
+
+    match addr {
+      RAM_BEGIN ..= RAM_END => {
+        self.ram[usize::from(addr & 0x7FF)] = value;
+      }
+      PPU_CONTROL | PPU_MASK | PPU_OAM_ADDR | PPU_OAM_DATA | PPU_SCROLL | PPU_MAP_ADDR | PPU_MAP_DATA => {
+        self.ppu.write(addr, value);
+      }
+      OAM_DMA => {
+        let page = (value as u16) << 8;
+        for offset in 0..0x100 {
+          let data = self.read(page | offset);
+          self.ppu.write(PPU_OAM_DATA, data);
+        }
+        self.dma_cycles += OAM_DMA_CYCLES;
+      }
+      APU_REGISTER_BEGIN ..= APU_REGISTER_END | apu::STATUS | apu::FRAME_COUNTER => {
+        self.apu.write(addr, value);
+      }
+      JOYPAD_1 => {
+        // Strobe is wired to both ports and the expansion port
+        for port in &mut self.ports {
+          port.write(value);
+        }
+        if let Some(device) = &mut self.expansion_port {
+          device.write(value);
+        }
+      }
+      0x2008 ..= PPU_REGISTER_END => {
+        // Mirror down address to real PPU space
+        return self.write(addr & 0x2007, value);
+      }
+      PRG_RAM_BEGIN ..= PRG_RAM_END => {
+        self.cartridge.prg_ram[(addr - PRG_RAM_BEGIN) as usize] = value;
+      }
+      PRG_ROM_BEGIN ..= PRG_ROM_END => {
+        if let Some(mirroring) = self.cartridge.write_register(addr, value) {
+          self.ppu.set_mirroring(mirroring);
+        }
+      }
+      _ => {}
+    }
+  }
+
+  pub fn peek(&self, addr: u16) -> u8 {
+    match addr {
+      RAM_BEGIN ..= RAM_END => {
+        return self.ram[usize::from(addr & 0x7FF)];
+      }
+      PRG_RAM_BEGIN ..= PRG_RAM_END => {
+        return self.cartridge.prg_ram[(addr - PRG_RAM_BEGIN) as usize];
+      }
+      PRG_ROM_BEGIN ..= PRG_ROM_END => {
+        return self.cartridge.prg_rom[self.prg_rom_index(addr)];
+      }
+      _ => {
+        // Registers with read side effects are not observable without them
+        return 0;
+      }
+    }
+  }
+
+  pub fn tick(&mut self, cycles: u8) {
+    // The PPU runs three dots for every CPU cycle
+    self.ppu.tick(cycles as usize * 3);
+
+    for _cycle in 0..cycles {
+      self.apu.clock();
+      if let Some(addr) = self.apu.dmc_fetch_address() {
+        let value = self.peek(addr);
+        self.apu.dmc_fetch(value);
+        self.dma_cycles += DMC_FETCH_CYCLES;
+      }
+    }
+
+    for chip in &mut self.expansion_audio {
+      for _cycle in 0..cycles {
+        chip.clock();
+      }
+    }
+  }
+
+  // Device in a port, counted from 0, if it is a `T`
+  pub fn device<T: InputDevice + 'static>(&mut self, port: usize) -> Option<&mut T> {
+    self.ports[port].as_any_mut().downcast_mut::<T>()
+  }
+
+  pub fn expansion_device<T: ExpansionDevice + 'static>(&mut self) -> Option<&mut T> {
+    self.expansion_port.as_mut().and_then(|device| device.as_any_mut().downcast_mut::<T>())
+  }
+
+  // Plugs a Four Score into both ports
+  pub fn plug_four_score(&mut self) {
+    self.ports = [Box::new(FourScore::new(0)), Box::new(FourScore::new(1))];
+  }
+
+  // Plugs in what an NES 2.0 header asks for. Devices this emulator lacks, and headers that
+  // do not say, leave standard controllers in both ports and the expansion port empty.
+  pub fn plug_default_devices(&mut self, device: u8) {
+    self.ports = [Box::new(Joypad::default()), Box::new(Joypad::default())];
+    self.expansion_port = None;
+    match device {
+      input::DEFAULT_DEVICE_FOUR_SCORE => self.plug_four_score(),
+      input::DEFAULT_DEVICE_FOUR_PLAYERS_ADAPTER => self.expansion_port = Some(Box::new(HoriAdapter::new(false))),
+      input::DEFAULT_DEVICE_ZAPPER => self.ports[1] = Box::new(Zapper::default()),
+      input::DEFAULT_DEVICE_TWO_ZAPPERS => self.ports = [Box::new(Zapper::default()), Box::new(Zapper::default())],
+      input::DEFAULT_DEVICE_POWER_PAD_A | input::DEFAULT_DEVICE_POWER_PAD_B => self.ports[1] = Box::new(PowerPad::default()),
+      input::DEFAULT_DEVICE_FAMILY_TRAINER_A | input::DEFAULT_DEVICE_FAMILY_TRAINER_B => {
+        self.expansion_port = Some(Box::new(PowerPad::default()));
+      }
+      input::DEFAULT_DEVICE_VAUS_NES => self.ports[1] = Box::new(Vaus::default()),
+      input::DEFAULT_DEVICE_VAUS_FAMICOM => self.expansion_port = Some(Box::new(Vaus::default())),
+      input::DEFAULT_DEVICE_FAMILY_BASIC_KEYBOARD => self.expansion_port = Some(Box::new(FamilyBasicKeyboard::new())),
+      _ => {}
+    }
+  }
+
+  // Standard controller of a player counted from 0. Players 3 and 4 are the second controller
+  // on each port with a Four Score, or on the expansion port with a Hori adapter: the second
+  // one in each report in four player mode, the plain expansion controllers in two player mode.
+  pub fn controller(&mut self, player: usize) -> Option<&mut Joypad> {
+    let hori_four_player = self.expansion_device::<HoriAdapter>().map(|adapter| adapter.four_player);
+    match (hori_four_player, player) {
+      (Some(true), 2 ..= 3) => self.expansion_device::<HoriAdapter>()?.controllers.get_mut(player),
+      (Some(false), 2 ..= 3) => self.expansion_device::<HoriAdapter>()?.controllers.get_mut(player - 2),
+      _ => self.ports[player % 2].controller(player / 2)
+    }
+  }
+
+  fn read_expansion_port(&mut self, port: usize) -> u8 {
+    match &mut self.expansion_port {
+      Some(device) => device.read(port),
+      None => 0
+    }
+  }
+
+  // The APU and the expansion chips through the mixer
+  pub fn audio_output(&self) -> f32 {
+    self.mixer.mix(&self.apu, &self.expansion_audio)
+  }
+
+  fn prg_rom_index(&self, addr: u16) -> usize {
+    return self.cartridge.prg_rom_index(addr);
+  }
+}
+
+impl CpuBus for Bus {
+  fn read(&mut self, addr: u16) -> u8 {
+    Bus::read(self, addr)
+  }
+
+  fn write(&mut self, addr: u16, value: u8) {
+    Bus::write(self, addr, value)
+  }
+
+  fn peek(&self, addr: u16) -> u8 {
+    Bus::peek(self, addr)
+  }
+
+  fn tick(&mut self, cycles: u8) {
+    Bus::tick(self, cycles)
+  }
+
+  fn poll_nmi(&mut self) -> bool {
+    self.ppu.poll_nmi()
+  }
+
+  fn irq_pending(&self) -> bool {
+    self.apu.irq()
+  }
+
+  fn take_dma_cycles(&mut self) -> u16 {
+    std::mem::take(&mut self.dma_cycles)
+  }
+
+  fn ppu_position(&self) -> Option<(u16, u16)> {
+    Some((self.ppu.scanline, self.ppu.dot))
+  }
+
+  fn frame(&self) -> u64 {
+    self.ppu.frame
+  }
+}
\ No newline at end of file
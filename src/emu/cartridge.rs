@@ -1,25 +1,87 @@
+use crate::emu::md5::md5;
+use crate::emu::save_state::{StateReader, StateWriter};
+
 const NES_TAG: [u8; 4] = [0x4E, 0x45, 0x53, 0x1A];
 const HEADER_LENGTH: usize = 16;
 const PRG_ROM_PAGE_SIZE: usize = 16384;
 const CHR_ROM_PAGE_SIZE: usize = 8192;
+const PRG_RAM_SIZE: usize = 8192;
+const CHR_RAM_SIZE: usize = 8192;
+const PRG_ROM_BEGIN: u16 = 0x8000;
+const PRG_BANK_8K: usize = 0x2000;
+const PRG_BANK_16K: usize = 0x4000;
+const PRG_BANK_32K: usize = 0x8000;
+
+const MAPPER_MMC1: u8 = 1;
+const MAPPER_MMC3: u8 = 4;
+const MAPPER_AXROM: u8 = 7;
+
+// MMC1 loads its registers one bit per write, 5 writes per register
+const MMC1_RESET_BIT: u8 = 0x80;
+const MMC1_SHIFT_WRITES: u8 = 5;
+const MMC1_CONTROL_END: u16 = 0x9FFF;
+const MMC1_PRG_BANK_BEGIN: u16 = 0xE000;
+// Control bits 2-3 pick the PRG mode, it powers up with the last bank fixed at $C000
+const MMC1_PRG_MODE_SHIFT: u8 = 2;
+const MMC1_PRG_MODE_FIX_LAST: u8 = 0x0C;
+const MMC1_PRG_BANK_MASK: u8 = 0x0F;
+
+// MMC3 registers are picked by the address range and whether the address is even or odd
+const MMC3_REGISTER_MASK: u16 = 0xE001;
+const MMC3_BANK_SELECT: u16 = 0x8000;
+const MMC3_BANK_DATA: u16 = 0x8001;
+const MMC3_MIRRORING: u16 = 0xA000;
+// Bank select bits 0-2 pick R0-R7, R6 and R7 are the 8 KiB PRG banks
+const MMC3_REGISTER_BITS: u8 = 0x07;
+const MMC3_PRG_R6: u8 = 6;
+const MMC3_PRG_R7: u8 = 7;
+// Swaps R6 at $8000 with the second to last bank at $C000
+const MMC3_PRG_MODE_BIT: u8 = 0x40;
 
-#[derive(Clone, Copy, PartialEq)]
+const AXROM_PAGE_BIT: u8 = 0x10;
+const AXROM_BANK_MASK: u8 = 0x07;
+
+// NES 2.0 byte 15 holds the default expansion device in its low 6 bits
+const EXPANSION_DEVICE_MASK: u8 = 0x3F;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub enum Mirroring {
   Vertical,
   Horizontal,
+  // Every nametable shows the first or the second KiB of VRAM
+  SingleScreenA,
+  SingleScreenB,
+  // Cartridge supplies another 2 KiB so all four nametables are distinct
   FourScreen
 }
 
 pub struct Cartridge {
   pub prg_rom: Vec<u8>,
+  // Pattern tables, writable when `chr_ram` is set
   pub chr_rom: Vec<u8>,
+  pub chr_ram: bool,
+  // Work RAM at $6000-$7FFF
+  pub prg_ram: Vec<u8>,
   pub mapper: u8,
-  pub mirroring: Mirroring
+  pub mirroring: Mirroring,
+  // NES 2.0 default expansion device from byte 15, 0 when the header does not say
+  pub expansion_device: u8,
+
+  // Mirroring from the header, which mappers start up with
+  header_mirroring: Mirroring,
+  mmc1_shift: u8,
+  mmc1_writes: u8,
+  mmc1_control: u8,
+  mmc1_prg_bank: u8,
+  mmc3_bank_select: u8,
+  // R6 and R7
+  mmc3_prg_banks: [u8; 2],
+  axrom_bank: u8
 }
 
 impl Cartridge {
-  pub fn new(bytes: &Vec<u8>) -> Result<Cartridge, String> {
-    if &bytes[0..4] != NES_TAG {
+  pub fn new(bytes: &[u8]) -> Result<Cartridge, String> {
+    if bytes[0..4] != NES_TAG {
       return Err("FILE IS NOT AN iNES ROM".to_string());
     }
 
@@ -29,11 +91,18 @@ impl Cartridge {
     // iNES version info is in bits 2 & 3 of byte 7
     let ines_version = (bytes[7] >> 2) & 0x03;
 
-    if ines_version == 2 {
-      return Err("iNES VERSION 2 IS NOT SUPPORTED".to_string());
-    } else if ines_version != 0 {
+    if ines_version != 0 && ines_version != 2 {
       return Err("UNSUPPORTED iNES VERSION DETECTED".to_string());
     }
+    let nes2 = ines_version == 2;
+
+    // NES 2.0 keeps mapper bits 8-11 in byte 8 and ROM size MSBs in byte 9
+    if nes2 && bytes[8] & 0x0F != 0 {
+      return Err("MAPPERS ABOVE 255 ARE NOT SUPPORTED".to_string());
+    }
+    if nes2 && (bytes[9] & 0x0F == 0x0F || bytes[9] >> 4 == 0x0F) {
+      return Err("EXPONENT ROM SIZES ARE NOT SUPPORTED".to_string());
+    }
 
     // Four screen info is bit 3 of byte 6
     let four_screen = bytes[6] & 0x08 != 0;
@@ -47,27 +116,245 @@ impl Cartridge {
       (false, false) => Mirroring::Horizontal
     };
 
-    let prg_rom_length = bytes[4] as usize * PRG_ROM_PAGE_SIZE;
-    let chr_rom_length = bytes[5] as usize * CHR_ROM_PAGE_SIZE;
+    let (prg_pages, chr_pages) = if nes2 {
+      (bytes[4] as usize | (bytes[9] as usize & 0x0F) << 8, bytes[5] as usize | (bytes[9] as usize >> 4) << 8)
+    } else {
+      (bytes[4] as usize, bytes[5] as usize)
+    };
+    if prg_pages == 0 {
+      return Err("CARTRIDGE HAS NO PRG ROM".to_string());
+    }
+    let prg_rom_length = prg_pages * PRG_ROM_PAGE_SIZE;
+    let chr_rom_length = chr_pages * CHR_ROM_PAGE_SIZE;
 
     // If byte 6 bit 2 is true there is a 512 byte block between the HEADER and PRG_ROM
-    let trainer_length = if bytes[6] & 0x04 == 1 { 512 as usize } else { 0 as usize };
+    let trainer_length: usize = if bytes[6] & 0x04 != 0 { 512 } else { 0 };
 
     let prg_rom_start = HEADER_LENGTH + trainer_length;
     let chr_rom_start = prg_rom_start + prg_rom_length;
 
     let prg_rom = bytes[prg_rom_start..(prg_rom_start + prg_rom_length)].to_vec();
-    let chr_rom = bytes[chr_rom_start..(chr_rom_start + chr_rom_length)].to_vec();
+
+    // No CHR ROM banks means the cartridge has CHR RAM instead
+    let chr_ram = chr_rom_length == 0;
+    let chr_rom = if chr_ram {
+      vec![0; chr_ram_size(bytes, nes2)]
+    } else {
+      bytes[chr_rom_start..(chr_rom_start + chr_rom_length)].to_vec()
+    };
+
+    let expansion_device = if nes2 { bytes[15] & EXPANSION_DEVICE_MASK } else { 0 };
 
     Ok(Cartridge {
       prg_rom,
       chr_rom,
+      chr_ram,
+      prg_ram: vec![0; PRG_RAM_SIZE],
       mapper,
-      mirroring
+      mirroring,
+      expansion_device,
+      header_mirroring: mirroring,
+      mmc1_shift: 0,
+      mmc1_writes: 0,
+      mmc1_control: MMC1_PRG_MODE_FIX_LAST,
+      mmc1_prg_bank: 0,
+      mmc3_bank_select: 0,
+      mmc3_prg_banks: [0, 1],
+      axrom_bank: 0
+    })
+  }
+
+  // Puts the mapper back the way it powers up, work RAM is left alone
+  pub fn power(&mut self) {
+    self.mirroring = self.header_mirroring;
+    self.mmc1_shift = 0;
+    self.mmc1_writes = 0;
+    self.mmc1_control = MMC1_PRG_MODE_FIX_LAST;
+    self.mmc1_prg_bank = 0;
+    self.mmc3_bank_select = 0;
+    self.mmc3_prg_banks = [0, 1];
+    self.axrom_bank = 0;
+  }
+
+  // MD5 of the PRG and CHR ROM without the header, what FCEUX knows a game by
+  pub fn checksum(&self) -> [u8; 16] {
+    let mut rom = self.prg_rom.clone();
+    if !self.chr_ram {
+      rom.extend_from_slice(&self.chr_rom);
+    }
+    md5(&rom)
+  }
+
+  // Work RAM and mapper registers, CHR RAM is saved with the PPU
+  pub fn save_state(&self, state: &mut StateWriter) {
+    state.bytes(&self.prg_ram);
+    state.u8(mirroring_code(self.mirroring));
+    let registers = [
+      self.mmc1_shift, self.mmc1_writes, self.mmc1_control, self.mmc1_prg_bank,
+      self.mmc3_bank_select, self.mmc3_prg_banks[0], self.mmc3_prg_banks[1], self.axrom_bank
+    ];
+    for value in &registers {
+      state.u8(*value);
+    }
+  }
+
+  pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+    state.bytes_into(&mut self.prg_ram)?;
+    self.mirroring = mirroring_from_code(state.u8()?)?;
+    let [mmc3_r6, mmc3_r7] = &mut self.mmc3_prg_banks;
+    let registers = [
+      &mut self.mmc1_shift, &mut self.mmc1_writes, &mut self.mmc1_control, &mut self.mmc1_prg_bank,
+      &mut self.mmc3_bank_select, mmc3_r6, mmc3_r7, &mut self.axrom_bank
+    ];
+    for value in registers {
+      *value = state.u8()?;
+    }
+    Ok(())
+  }
+
+  // Mapper register write to $8000-$FFFF, returns the new mirroring when the write changes it.
+  // PRG banks and mirroring are decoded, CHR bank switching is not emulated.
+  pub fn write_register(&mut self, addr: u16, value: u8) -> Option<Mirroring> {
+    let mirroring = match self.mapper {
+      MAPPER_MMC1 => self.write_mmc1(addr, value)?,
+      MAPPER_MMC3 => self.write_mmc3(addr, value)?,
+      MAPPER_AXROM => {
+        self.axrom_bank = value & AXROM_BANK_MASK;
+        if value & AXROM_PAGE_BIT == 0 { Mirroring::SingleScreenA } else { Mirroring::SingleScreenB }
+      }
+      _ => return None
+    };
+
+    // Four screen VRAM is wired on the board and cannot be switched off
+    if self.mirroring == Mirroring::FourScreen {
+      return None;
+    }
+    self.mirroring = mirroring;
+    Some(mirroring)
+  }
+
+  // Offset into `prg_rom` the CPU sees at `addr` in $8000-$FFFF with the banks selected now
+  pub fn prg_rom_index(&self, addr: u16) -> usize {
+    let offset = (addr - PRG_ROM_BEGIN) as usize;
+    let index = match self.mapper {
+      MAPPER_MMC1 => {
+        let bank = (self.mmc1_prg_bank & MMC1_PRG_BANK_MASK) as usize;
+        let last = self.prg_rom.len() / PRG_BANK_16K - 1;
+        match ((self.mmc1_control >> MMC1_PRG_MODE_SHIFT) & 0b11, offset < PRG_BANK_16K) {
+          // 32 KiB mode ignores the low bit of the bank number
+          (0, _) | (1, _) => (bank & !1) * PRG_BANK_16K + offset,
+          // First bank fixed at $8000
+          (2, true) => offset,
+          (2, false) => bank * PRG_BANK_16K + offset - PRG_BANK_16K,
+          // Last bank fixed at $C000
+          (_, true) => bank * PRG_BANK_16K + offset,
+          (_, false) => last * PRG_BANK_16K + offset - PRG_BANK_16K
+        }
+      }
+      MAPPER_MMC3 => {
+        let last = self.prg_rom.len() / PRG_BANK_8K - 1;
+        let swapped = self.mmc3_bank_select & MMC3_PRG_MODE_BIT != 0;
+        let bank = match (offset / PRG_BANK_8K, swapped) {
+          (0, false) | (2, true) => self.mmc3_prg_banks[0] as usize,
+          (1, _) => self.mmc3_prg_banks[1] as usize,
+          (0, true) | (2, false) => last - 1,
+          _ => last
+        };
+        bank * PRG_BANK_8K + offset % PRG_BANK_8K
+      }
+      MAPPER_AXROM => self.axrom_bank as usize * PRG_BANK_32K + offset,
+      _ => offset
+    };
+
+    // Bank numbers past the end wrap around like the unconnected address lines do, which also
+    // mirrors 16 KiB NROM into both halves
+    index % self.prg_rom.len()
+  }
+
+  fn write_mmc1(&mut self, addr: u16, value: u8) -> Option<Mirroring> {
+    if value & MMC1_RESET_BIT != 0 {
+      self.mmc1_shift = 0;
+      self.mmc1_writes = 0;
+      self.mmc1_control |= MMC1_PRG_MODE_FIX_LAST;
+      return None;
+    }
+
+    self.mmc1_shift |= (value & 0x01) << self.mmc1_writes;
+    self.mmc1_writes += 1;
+    if self.mmc1_writes < MMC1_SHIFT_WRITES {
+      return None;
+    }
+
+    let data = self.mmc1_shift;
+    self.mmc1_shift = 0;
+    self.mmc1_writes = 0;
+
+    // The register is picked by the address of the fifth write, control is $8000-$9FFF and the
+    // PRG bank $E000-$FFFF
+    if addr >= MMC1_PRG_BANK_BEGIN {
+      self.mmc1_prg_bank = data;
+      return None;
+    }
+    if addr > MMC1_CONTROL_END {
+      return None;
+    }
+    self.mmc1_control = data;
+    Some(match data & 0b11 {
+      0 => Mirroring::SingleScreenA,
+      1 => Mirroring::SingleScreenB,
+      2 => Mirroring::Vertical,
+      _ => Mirroring::Horizontal
     })
   }
 
+  fn write_mmc3(&mut self, addr: u16, value: u8) -> Option<Mirroring> {
+    match addr & MMC3_REGISTER_MASK {
+      MMC3_BANK_SELECT => self.mmc3_bank_select = value,
+      MMC3_BANK_DATA => {
+        let register = self.mmc3_bank_select & MMC3_REGISTER_BITS;
+        if register == MMC3_PRG_R6 || register == MMC3_PRG_R7 {
+          self.mmc3_prg_banks[(register - MMC3_PRG_R6) as usize] = value;
+        }
+      }
+      MMC3_MIRRORING => return Some(if value & 0x01 == 0 { Mirroring::Vertical } else { Mirroring::Horizontal }),
+      _ => {}
+    }
+    None
+  }
+
   pub fn load(path: &str) -> Result<Cartridge, String> {
     return Cartridge::new(&std::fs::read(path).unwrap());
   }
-}
\ No newline at end of file
+}
+
+// Mirroring as save states store it
+pub fn mirroring_code(mirroring: Mirroring) -> u8 {
+  match mirroring {
+    Mirroring::Vertical => 0,
+    Mirroring::Horizontal => 1,
+    Mirroring::SingleScreenA => 2,
+    Mirroring::SingleScreenB => 3,
+    Mirroring::FourScreen => 4
+  }
+}
+
+pub fn mirroring_from_code(code: u8) -> Result<Mirroring, String> {
+  match code {
+    0 => Ok(Mirroring::Vertical),
+    1 => Ok(Mirroring::Horizontal),
+    2 => Ok(Mirroring::SingleScreenA),
+    3 => Ok(Mirroring::SingleScreenB),
+    4 => Ok(Mirroring::FourScreen),
+    _ => Err(format!("BAD MIRRORING {} IN SAVE STATE", code))
+  }
+}
+
+// NES 2.0 gives CHR RAM sizes as 64 << shift in byte 11, volatile in the low nibble and
+// battery backed in the high one. iNES 1.0 cartridges get 8 KiB.
+fn chr_ram_size(bytes: &[u8], nes2: bool) -> usize {
+  let shift = (bytes[11] & 0x0F).max(bytes[11] >> 4);
+  if nes2 && shift != 0 {
+    return 64 << shift;
+  }
+  return CHR_RAM_SIZE;
+}
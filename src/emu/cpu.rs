@@ -1,8 +1,11 @@
-use std::fs::{File, OpenOptions};
-use std::io::Write;
-
-use crate::emu::bus::Bus;
+use crate::emu::cpu_bus::CpuBus;
 use crate::emu::cpu_opcodes::{Opcode, Instruction, AddressingMode};
+use crate::emu::save_state::{StateReader, StateWriter};
+use crate::emu::trace::{Tracer, TraceState};
+
+// CPU clock rates in Hz, the master clock divided by 12 on NTSC and 16 on PAL
+pub const NTSC_CPU_CLOCK: f64 = 236_250_000.0 / 11.0 / 12.0;
+pub const PAL_CPU_CLOCK: f64 = 26_601_712.5 / 16.0;
 
 pub enum InterruptType {
   IRQ,
@@ -10,8 +13,18 @@ pub enum InterruptType {
   BRK_
 }
 
+// Chip variant the core emulates
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CpuVariant {
+  // NES CPU, decimal mode flag is stored but ignored by ADC/SBC
+  Ricoh2A03,
+  // Generic NMOS 6502 with working decimal mode
+  Nmos6502
+}
+
 // 6502 CPU
 pub struct CPU {
+  pub variant: CpuVariant,
   // Registers
   pub sp: u8, // Stack Pointer, Grows downward
   pub r_a: u8, // Accumulator
@@ -22,6 +35,8 @@ pub struct CPU {
   // Cycle Counts
   pub cycles: u32,
   pub skip_cycles: u8,
+  // Cycles the CPU is halted for by DMA once the current instruction is done
+  pub stall_cycles: u16,
   // Status flags
   pub f_c: bool,
   pub f_z: bool,
@@ -35,17 +50,13 @@ pub struct CPU {
   pub location: u16,
   pub relative_location: u16,
 
-  trace_file_name: Option<String>,
-  trace_file: Option<File>
+  pub tracer: Option<Tracer>
 }
 
 impl CPU {
-  pub fn new(trace_file_name: Option<String>) -> CPU {
-    let mut trace_file = None;
-    if trace_file_name.is_some() {
-      trace_file = Some(OpenOptions::new().write(true).append(true).create(true).open(trace_file_name.clone().unwrap().as_str()).unwrap());
-    }
+  pub fn new(tracer: Option<Tracer>) -> CPU {
     CPU {
+      variant: CpuVariant::Ricoh2A03,
       sp: 0x00,
       r_a: 0x00,
       r_x: 0x00,
@@ -54,6 +65,7 @@ impl CPU {
       pc: 0x0000,
       cycles: 0,
       skip_cycles: 0,
+      stall_cycles: 0,
       f_c: false,
       f_z: false,
       f_i: false,
@@ -64,22 +76,21 @@ impl CPU {
       f_u: false,
       location: 0x0000,
       relative_location: 0x0000,
-      trace_file_name: trace_file_name,
-      trace_file
+      tracer
     }
   }
 
-  pub fn reset(&mut self, bus: &mut Bus) {
+  pub fn reset<B: CpuBus>(&mut self, bus: &mut B) {
     self.r_a = 0x00;
     self.r_x = 0x00;
     self.r_y = 0x00;
     self.sp = 0xFD;
-    self.r_status = 0x00 | ((self.f_u as u8) << 5);
+    self.r_status = (self.f_u as u8) << 5;
 
     self.location = 0xFFFC;
 
     let lo = bus.read(self.location);
-    let hi = bus.read(self.location + 1);
+    let hi = bus.read(self.location.wrapping_add(1));
 
     self.pc = ((hi as u16) << 8) | (lo as u16);
 
@@ -100,126 +111,205 @@ impl CPU {
     self.skip_cycles = 7;
   }
 
-  pub fn interrupt(&mut self, bus: &mut Bus) {
-    if self.f_i == true {
+  pub fn interrupt<B: CpuBus>(&mut self, bus: &mut B) {
+    if self.f_i {
       return;
     }
 
     bus.write(0x0100 + (self.sp as u16), (self.pc >> 8) as u8);
-    self.sp -= 1;
+    self.sp = self.sp.wrapping_sub(1);
     bus.write(0x0100 + (self.sp as u16), (self.pc & 0x00FF) as u8);
-    self.sp -= 1;
+    self.sp = self.sp.wrapping_sub(1);
 
+    // The pushed status keeps the I flag from before the interrupt so RTI restores it
     self.f_b = false;
     self.f_u = true;
-    self.f_i = true;
 
     self.update_status_register();
     bus.write(0x0100 + (self.sp as u16), self.r_status);
-    self.sp -= 1;
+    self.sp = self.sp.wrapping_sub(1);
+    self.f_i = true;
 
     self.location = 0xFFFE;
 
     let lo = bus.read(self.location);
-    let hi = bus.read(self.location + 1);
+    let hi = bus.read(self.location.wrapping_add(1));
 
     self.pc = ((hi as u16) << 8) | (lo as u16);
 
     self.skip_cycles = 7;
   }
 
-  pub fn non_maskable_interrupt(&mut self, bus: &mut Bus) {
+  pub fn non_maskable_interrupt<B: CpuBus>(&mut self, bus: &mut B) {
     bus.write(0x0100 + (self.sp as u16), (self.pc >> 8) as u8);
-    self.sp -= 1;
+    self.sp = self.sp.wrapping_sub(1);
     bus.write(0x0100 + (self.sp as u16), (self.pc & 0x00FF) as u8);
-    self.sp -= 1;
+    self.sp = self.sp.wrapping_sub(1);
 
+    // The pushed status keeps the I flag from before the interrupt so RTI restores it
     self.f_b = false;
     self.f_u = true;
-    self.f_i = true;
 
     self.update_status_register();
     bus.write(0x0100 + (self.sp as u16), self.r_status);
-    self.sp -= 1;
+    self.sp = self.sp.wrapping_sub(1);
+    self.f_i = true;
 
     self.location = 0xFFFA;
 
     let lo = bus.read(self.location);
-    let hi = bus.read(self.location + 1);
+    let hi = bus.read(self.location.wrapping_add(1));
 
     self.pc = ((hi as u16) << 8) | (lo as u16);
 
     self.skip_cycles = 7;
   }
 
-  pub fn step(&mut self, bus: &mut Bus) {
+  // Registers, flags and cycle counts, the variant and tracer are settings rather than state
+  pub fn save_state(&self, state: &mut StateWriter) {
+    for value in &[self.sp, self.r_a, self.r_x, self.r_y, self.r_status, self.skip_cycles] {
+      state.u8(*value);
+    }
+    state.u16(self.pc);
+    state.u32(self.cycles);
+    state.u16(self.stall_cycles);
+    for flag in &[self.f_c, self.f_z, self.f_i, self.f_d, self.f_v, self.f_n, self.f_b, self.f_u] {
+      state.bool(*flag);
+    }
+    state.u16(self.location);
+    state.u16(self.relative_location);
+  }
+
+  pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+    for value in [&mut self.sp, &mut self.r_a, &mut self.r_x, &mut self.r_y, &mut self.r_status, &mut self.skip_cycles] {
+      *value = state.u8()?;
+    }
+    self.pc = state.u16()?;
+    self.cycles = state.u32()?;
+    self.stall_cycles = state.u16()?;
+    for flag in [&mut self.f_c, &mut self.f_z, &mut self.f_i, &mut self.f_d, &mut self.f_v, &mut self.f_n, &mut self.f_b, &mut self.f_u] {
+      *flag = state.bool()?;
+    }
+    self.location = state.u16()?;
+    self.relative_location = state.u16()?;
+    Ok(())
+  }
+
+  pub fn step<B: CpuBus>(&mut self, bus: &mut B) {
+    self.try_step(bus).unwrap_or_else(|error| panic!("{}", error));
+  }
+
+  // Like `step` but an opcode the core does not run is an error rather than a panic. The
+  // program counter stays on the opcode.
+  pub fn try_step<B: CpuBus>(&mut self, bus: &mut B) -> Result<(), String> {
     self.update_status_register();
-    if self.skip_cycles > 0 {
+    if self.skip_cycles > 0 || self.stall_cycles > 0 {
+      if self.skip_cycles > 0 {
+        self.skip_cycles -= 1;
+      } else {
+        self.stall_cycles -= 1;
+      }
+      self.cycles += 1;
+      bus.tick(1);
+      return Ok(());
+    }
+
+    // Interrupts are only taken between instructions
+    if bus.poll_nmi() {
+      self.non_maskable_interrupt(bus);
       self.skip_cycles -= 1;
       self.cycles += 1;
-      return;
+      bus.tick(1);
+      return Ok(());
+    }
+    if !self.f_i && bus.irq_pending() {
+      self.interrupt(bus);
+      self.skip_cycles -= 1;
+      self.cycles += 1;
+      bus.tick(1);
+      return Ok(());
     }
 
     // Get instruction from next program counter target
     let op_byte = bus.read(self.pc);
     let instruction = Instruction::from_u8(op_byte);
 
-    if instruction.opcode == Opcode::UnknownOperation {
-      panic!("UNKNOWN CPU OPERATION {}", op_byte);
+    if self.tracer.is_some() {
+      self.trace(bus);
     }
 
-    if self.trace_file.is_some() {
-      self.trace(bus);
+    if instruction.opcode == Opcode::UnknownOperation {
+      return Err(format!("UNKNOWN CPU OPERATION {:02x} AT {:04x}", op_byte, self.pc));
     }
 
-    self.pc += 1;
+    self.pc = self.pc.wrapping_add(1);
 
     // Execute instruction
     let wait_cycles = self.execute_instruction(&instruction, bus);
     self.skip_cycles = wait_cycles - 1;
+
+    self.stall_cycles = bus.take_dma_cycles();
+    if self.stall_cycles > 0 && (self.cycles + wait_cycles as u32) % 2 == 1 {
+      self.stall_cycles += 1;
+    }
+
     self.cycles += 1;
+    bus.tick(1);
+    Ok(())
   }
   
-  pub fn read(&self, addr: u16, bus: &mut Bus) -> u8 {
+  // Finish the instruction in flight and then run the next one to completion
+  pub fn step_instruction<B: CpuBus>(&mut self, bus: &mut B) {
+    self.try_step_instruction(bus).unwrap_or_else(|error| panic!("{}", error));
+  }
+
+  // Like `step_instruction` with unknown opcodes as errors, see `try_step`
+  pub fn try_step_instruction<B: CpuBus>(&mut self, bus: &mut B) -> Result<(), String> {
+    while self.skip_cycles > 0 || self.stall_cycles > 0 {
+      self.try_step(bus)?;
+    }
+    self.try_step(bus)?;
+    while self.skip_cycles > 0 || self.stall_cycles > 0 {
+      self.try_step(bus)?;
+    }
+    Ok(())
+  }
+
+  pub fn read<B: CpuBus>(&self, addr: u16, bus: &mut B) -> u8 {
     bus.read(addr)
   }
   
-  pub fn write(&mut self, addr: u16, value: u8, bus: &mut Bus) {
+  pub fn write<B: CpuBus>(&mut self, addr: u16, value: u8, bus: &mut B) {
     bus.write(addr, value);
   }
 
-  fn execute_instruction(&mut self, instruction: &Instruction, bus: &mut Bus) -> u8 {
-    let address_mode_cycles = self.load_address_mode(&instruction.addr_mode, bus);
+  fn execute_instruction<B: CpuBus>(&mut self, instruction: &Instruction, bus: &mut B) -> u8 {
+    // JSR fetches its target around the return address pushes, so it does its own addressing
+    let address_mode_cycles = if instruction.opcode == Opcode::JSR { 0 } else { self.load_address_mode(instruction, bus) };
 
     let mut op_data = self.r_a;
 
-    if instruction.addr_mode != AddressingMode::Implied && instruction.addr_mode != AddressingMode::Accumulator {
+    // Only fetch the operand when the instruction uses it, reads can have side effects
+    if Self::reads_operand(instruction) {
       op_data = bus.read(self.location);
     }
 
+    // Read-modify-write instructions write the unmodified value back while they work on it
+    if Self::modifies_memory(instruction) {
+      bus.write(self.location, op_data);
+    }
+
     match instruction.opcode {
       Opcode::ADC => {
-        let sum = self.r_a as u16 + op_data as u16 + self.f_c as u16;
-        self.f_c = sum > 255;
-        self.f_z = (sum & 0x00FF) == 0;
-        self.f_v = (!((self.r_a as u16) ^ op_data as u16) & ((self.r_a as u16) ^ sum) & 0x0080) != 0;
-        self.f_n = sum & 0x80 != 0;
-        self.r_a = (sum & 0x00FF) as u8;
+        self.add_with_carry(op_data);
         return address_mode_cycles + instruction.cycles;
       },
       Opcode::SBC => {
-        let inverted = (op_data as u16) ^ 0x00FF;
-        let difference = self.r_a as u16 + inverted as u16 + self.f_c as u16;
-
-        self.f_c = difference & 0xFF00 != 0;
-        self.f_z = (difference & 0x00FF) == 0;
-        self.f_v = ((difference ^ self.r_a as u16) & (difference ^ inverted) & 0x0080) != 0;
-        self.f_n = difference & 0x0080 != 0;
-        self.r_a = (difference & 0x00FF) as u8;
+        self.subtract_with_carry(op_data);
         return address_mode_cycles + instruction.cycles;
       },
       Opcode::AND => {
-        self.r_a = self.r_a & op_data;
+        self.r_a &= op_data;
         self.f_z = self.r_a == 0x00;
         self.f_n = (self.r_a & 0x80) != 0;
         return address_mode_cycles + instruction.cycles;
@@ -239,25 +329,16 @@ impl CPU {
         return address_mode_cycles + instruction.cycles;
       },
       Opcode::BCC => {
-        if self.f_c == false {
-          self.location = self.pc + self.relative_location;
-          self.pc = self.location;
-        }
-        return address_mode_cycles + instruction.cycles + if self.f_c {0} else {1};
+        let taken = !self.f_c;
+        return address_mode_cycles + instruction.cycles + self.branch(taken, bus);
       },
       Opcode::BCS => {
-        if self.f_c == true {
-          self.location = self.pc + self.relative_location;
-          self.pc = self.location;
-        }
-        return address_mode_cycles + instruction.cycles + if self.f_c {1} else {0};
+        let taken = self.f_c;
+        return address_mode_cycles + instruction.cycles + self.branch(taken, bus);
       },
       Opcode::BEQ => {
-        if self.f_z == true {
-          self.location = self.pc + self.relative_location;
-          self.pc = self.location;
-        }
-        return address_mode_cycles + instruction.cycles + if self.f_z {1} else {0};
+        let taken = self.f_z;
+        return address_mode_cycles + instruction.cycles + self.branch(taken, bus);
       },
       Opcode::BIT => {
         let bit = self.r_a & op_data;
@@ -267,56 +348,43 @@ impl CPU {
         return address_mode_cycles + instruction.cycles;
       },
       Opcode::BMI => {
-        if self.f_n == true {
-          self.location = self.pc + self.relative_location;
-          self.pc = self.location;
-        }
-        return address_mode_cycles + instruction.cycles + if self.f_n {1} else {0};
+        let taken = self.f_n;
+        return address_mode_cycles + instruction.cycles + self.branch(taken, bus);
       },
       Opcode::BNE => {
-        if self.f_z == false {
-          self.location = self.pc + self.relative_location;
-          self.pc = self.location;
-        }
-        return address_mode_cycles + instruction.cycles + if self.f_z {0} else {1};
+        let taken = !self.f_z;
+        return address_mode_cycles + instruction.cycles + self.branch(taken, bus);
       },
       Opcode::BPL => {
-        if self.f_n == false {
-          self.location = self.pc + self.relative_location;
-          self.pc = self.location;
-        }
-        return address_mode_cycles + instruction.cycles + if self.f_n {0} else {1};
+        let taken = !self.f_n;
+        return address_mode_cycles + instruction.cycles + self.branch(taken, bus);
       },
       Opcode::BRK => {
-        self.f_i = true;
-        bus.write(0x0100 + self.sp as u16, ((self.pc >> 8) as u8) & 0x00FF);
-        self.sp -= 1;
+        // BRK skips a padding byte, so the return address is one past it
+        self.pc = self.pc.wrapping_add(1);
+        bus.write(0x0100 + self.sp as u16, (self.pc >> 8) as u8);
+        self.sp = self.sp.wrapping_sub(1);
         bus.write(0x0100 + self.sp as u16, (self.pc & 0x00FF) as u8);
-        self.sp -= 1;
+        self.sp = self.sp.wrapping_sub(1);
 
         self.f_b = true;
         self.update_status_register();
         bus.write(0x0100 + self.sp as u16, self.r_status);
-        self.sp -= 1;
+        self.sp = self.sp.wrapping_sub(1);
         self.f_b = false;
+        self.f_i = true;
         self.update_status_register();
 
-        self.pc = bus.read(0xFFFE) as u16 | bus.read(0xFFFF) as u16;
+        self.pc = bus.read(0xFFFE) as u16 | ((bus.read(0xFFFF) as u16) << 8);
         return address_mode_cycles + instruction.cycles;
       },
       Opcode::BVC => {
-        if self.f_v == false {
-          self.location = self.pc + self.relative_location;
-          self.pc = self.location;
-        }
-        return address_mode_cycles + instruction.cycles + if self.f_v {0} else {1};
+        let taken = !self.f_v;
+        return address_mode_cycles + instruction.cycles + self.branch(taken, bus);
       },
       Opcode::BVS => {
-        if self.f_v == true {
-          self.location = self.pc + self.relative_location;
-          self.pc = self.location;
-        }
-        return address_mode_cycles + instruction.cycles + if self.f_v {1} else {0};
+        let taken = self.f_v;
+        return address_mode_cycles + instruction.cycles + self.branch(taken, bus);
       },
       Opcode::CLC => {
         self.f_c = false;
@@ -335,66 +403,57 @@ impl CPU {
         return address_mode_cycles + instruction.cycles;
       },
       Opcode::CMP => {
-        let difference = self.r_a as u16 - op_data as u16;
-        self.f_c = self.r_a >= op_data;
-        self.f_z = (difference & 0x00FF) == 0;
-        self.f_n = (difference & 0x0080) != 0;
+        self.compare(self.r_a, op_data);
         return address_mode_cycles + instruction.cycles;
       },
       Opcode::CPX => {
-        let difference = self.r_x as u16 - op_data as u16;
-        self.f_c = self.r_x >= op_data;
-        self.f_z = (difference & 0x00FF) == 0x0000;
-        self.f_n = (difference & 0x0080) != 0;
+        self.compare(self.r_x, op_data);
         return address_mode_cycles + instruction.cycles;
       },
       Opcode::CPY => {
-        let difference = self.r_y as u16 - op_data as u16;
-        self.f_c = self.r_y >= op_data;
-        self.f_z = (difference & 0x00FF) == 0x0000;
-        self.f_n = (difference & 0x0080) != 0;
+        self.compare(self.r_y, op_data);
         return address_mode_cycles + instruction.cycles;
       },
       Opcode::DEC => {
-        let difference = op_data - 1;
+        let difference = op_data.wrapping_sub(1);
         bus.write(self.location, difference);
-        self.f_z = (difference & 0x00FF) == 0;
+        self.f_z = difference == 0;
         self.f_n = (difference & 0x80) != 0;
         return address_mode_cycles + instruction.cycles;
       },
       Opcode::DEX => {
-        self.r_x -= 1;
+        self.r_x = self.r_x.wrapping_sub(1);
         self.f_z = self.r_x == 0;
         self.f_n = (self.r_x & 0x80) != 0;
         return address_mode_cycles + instruction.cycles;
       },
       Opcode::DEY => {
-        self.r_y -= 1;
+        self.r_y = self.r_y.wrapping_sub(1);
         self.f_z = self.r_y == 0;
         self.f_n = (self.r_y & 0x80) != 0;
         return address_mode_cycles + instruction.cycles;
       },
       Opcode::EOR => {
-        self.r_a = self.r_a ^ op_data;
+        self.r_a ^= op_data;
         self.f_z = self.r_a == 0;
         self.f_n = (self.r_a & 0x80) != 0;
         return address_mode_cycles + instruction.cycles;
       },
       Opcode::INC => {
-        let value = op_data + 1;
-        bus.write(self.location, value & 0x00FF);
-        self.f_z = (value & 0x00FF) == 0;
+        let value = op_data.wrapping_add(1);
+        bus.write(self.location, value);
+        self.f_z = value == 0;
         self.f_n = (value & 0x80) != 0;
         return address_mode_cycles + instruction.cycles;
       },
       Opcode::INX => {
-        self.r_x += 1;
+        self.r_x = self.r_x.wrapping_add(1);
         self.f_z = self.r_x == 0;
         self.f_n = (self.r_x & 0x80) != 0;
         return address_mode_cycles + instruction.cycles;
       },
       Opcode::INY => {
-        self.r_y += 1;
+        self.r_y = self.r_y.wrapping_add(1);
         self.f_z = self.r_y == 0;
         self.f_n = (self.r_y & 0x80) != 0;
         return address_mode_cycles + instruction.cycles;
@@ -404,13 +463,18 @@ impl CPU {
         return address_mode_cycles + instruction.cycles;
       },
       Opcode::JSR => {
-        self.pc -= 1;
+        let lo = bus.read(self.pc) as u16;
+        self.pc = self.pc.wrapping_add(1);
+        bus.read(0x0100 + self.sp as u16);
 
+        // The return address is the last byte of the JSR, the high byte of the target is still to come
         bus.write(0x0100 + self.sp as u16, ((self.pc >> 8) & 0x00FF) as u8);
-        self.sp -= 1;
+        self.sp = self.sp.wrapping_sub(1);
         bus.write(0x0100 + self.sp as u16, (self.pc & 0x00FF) as u8);
-        self.sp -= 1;
+        self.sp = self.sp.wrapping_sub(1);
 
+        let hi = bus.read(self.pc) as u16;
+        self.location = (hi << 8) | lo;
         self.pc = self.location;
 
         return address_mode_cycles + instruction.cycles;
@@ -434,7 +498,6 @@ impl CPU {
         return address_mode_cycles + instruction.cycles;
       },
       Opcode::LSR => {
-        println!("LSR {}", op_data);
         self.f_c = (op_data & 0x0001) != 0;
         let shifted = op_data >> 1;
         self.f_z = shifted == 0;
@@ -458,26 +521,29 @@ impl CPU {
       },
       Opcode::PHA => {
         bus.write(0x0100 + self.sp as u16, self.r_a);
-        self.sp -= 1;
+        self.sp = self.sp.wrapping_sub(1);
         return address_mode_cycles + instruction.cycles;
       },
       Opcode::PHP => {
-        let stored_status = self.r_status;
+        // Status is always pushed with the break and unused bits set
+        let stored_status = self.r_status | 0x30;
         bus.write(0x0100 + self.sp as u16, stored_status);
-        self.sp -= 1;
+        self.sp = self.sp.wrapping_sub(1);
         self.f_b = false;
         self.f_u = true;
         return address_mode_cycles + instruction.cycles;
       },
       Opcode::PLA => {
-        self.sp += 1;
+        bus.read(0x0100 + self.sp as u16);
+        self.sp = self.sp.wrapping_add(1);
         self.r_a = bus.read(self.sp as u16 + 0x0100);
         self.f_z = self.r_a == 0;
         self.f_n = (self.r_a & 0x80) != 0;
         return address_mode_cycles + instruction.cycles;
       },
       Opcode::PLP => {
-        self.sp += 1;
+        bus.read(0x0100 + self.sp as u16);
+        self.sp = self.sp.wrapping_add(1);
         self.set_status_register(bus.read(0x100 + self.sp as u16));
         self.f_u = true;
         self.f_b = false;
@@ -511,7 +577,8 @@ impl CPU {
         return address_mode_cycles + instruction.cycles;
       },
       Opcode::RTI => {
-        self.sp += 1;
+        bus.read(0x0100 + self.sp as u16);
+        self.sp = self.sp.wrapping_add(1);
         self.set_status_register(bus.read(0x0100 + self.sp as u16));
 
         self.f_b = false;
@@ -519,20 +586,23 @@ impl CPU {
 
         self.update_status_register();
 
-        self.sp += 1;
+        self.sp = self.sp.wrapping_add(1);
         self.pc = bus.read(self.sp as u16 + 0x100) as u16;
-        self.sp += 1;
+        self.sp = self.sp.wrapping_add(1);
         self.pc |= (bus.read(self.sp as u16 + 0x100) as u16) << 8;
 
         return address_mode_cycles + instruction.cycles;
       },
       Opcode::RTS => {
-        self.sp += 1;
+        bus.read(0x0100 + self.sp as u16);
+        self.sp = self.sp.wrapping_add(1);
         self.pc = bus.read(self.sp as u16 + 0x100) as u16;
-        self.sp += 1;
+        self.sp = self.sp.wrapping_add(1);
         self.pc |= (bus.read(self.sp as u16 + 0x100) as u16) << 8;
 
-        self.pc += 1;
+        // The pulled address is the last byte of the JSR, which gets read again before moving past it
+        bus.read(self.pc);
+        self.pc = self.pc.wrapping_add(1);
         return address_mode_cycles + instruction.cycles;
       },
       Opcode::SEC => {
@@ -593,43 +663,297 @@ impl CPU {
         self.f_n = (self.r_a & 0x80) != 0;
         return address_mode_cycles + instruction.cycles;
       },
+      Opcode::LAX => {
+        self.r_a = op_data;
+        self.r_x = op_data;
+        self.f_z = self.r_a == 0x00;
+        self.f_n = (self.r_a & 0x80) != 0;
+        return address_mode_cycles + instruction.cycles;
+      },
+      Opcode::SAX => {
+        bus.write(self.location, self.r_a & self.r_x);
+        return address_mode_cycles + instruction.cycles;
+      },
+      Opcode::DCP => {
+        let value = op_data.wrapping_sub(1);
+        bus.write(self.location, value);
+        self.compare(self.r_a, value);
+        return address_mode_cycles + instruction.cycles;
+      },
+      Opcode::ISC => {
+        let value = op_data.wrapping_add(1);
+        bus.write(self.location, value);
+        self.subtract_with_carry(value);
+        return address_mode_cycles + instruction.cycles;
+      },
+      Opcode::SLO => {
+        let value = op_data << 1;
+        self.f_c = (op_data & 0x80) != 0;
+        bus.write(self.location, value);
+        self.r_a |= value;
+        self.f_z = self.r_a == 0x00;
+        self.f_n = (self.r_a & 0x80) != 0;
+        return address_mode_cycles + instruction.cycles;
+      },
+      Opcode::RLA => {
+        let value = (op_data << 1) | self.f_c as u8;
+        self.f_c = (op_data & 0x80) != 0;
+        bus.write(self.location, value);
+        self.r_a &= value;
+        self.f_z = self.r_a == 0x00;
+        self.f_n = (self.r_a & 0x80) != 0;
+        return address_mode_cycles + instruction.cycles;
+      },
+      Opcode::SRE => {
+        let value = op_data >> 1;
+        self.f_c = (op_data & 0x01) != 0;
+        bus.write(self.location, value);
+        self.r_a ^= value;
+        self.f_z = self.r_a == 0x00;
+        self.f_n = (self.r_a & 0x80) != 0;
+        return address_mode_cycles + instruction.cycles;
+      },
+      Opcode::RRA => {
+        let value = ((self.f_c as u8) << 7) | (op_data >> 1);
+        self.f_c = (op_data & 0x01) != 0;
+        bus.write(self.location, value);
+        self.add_with_carry(value);
+        return address_mode_cycles + instruction.cycles;
+      },
+      Opcode::ANC => {
+        self.r_a &= op_data;
+        self.f_z = self.r_a == 0x00;
+        self.f_n = (self.r_a & 0x80) != 0;
+        self.f_c = self.f_n;
+        return address_mode_cycles + instruction.cycles;
+      },
+      Opcode::ALR => {
+        let value = self.r_a & op_data;
+        self.f_c = (value & 0x01) != 0;
+        self.r_a = value >> 1;
+        self.f_z = self.r_a == 0x00;
+        self.f_n = false;
+        return address_mode_cycles + instruction.cycles;
+      },
+      Opcode::ARR => {
+        self.arr(op_data);
+        return address_mode_cycles + instruction.cycles;
+      },
+      Opcode::SBX => {
+        let value = self.r_a & self.r_x;
+        self.f_c = value >= op_data;
+        self.r_x = value.wrapping_sub(op_data);
+        self.f_z = self.r_x == 0x00;
+        self.f_n = (self.r_x & 0x80) != 0;
+        return address_mode_cycles + instruction.cycles;
+      },
+      Opcode::LAS => {
+        let value = op_data & self.sp;
+        self.r_a = value;
+        self.r_x = value;
+        self.sp = value;
+        self.f_z = value == 0x00;
+        self.f_n = (value & 0x80) != 0;
+        return address_mode_cycles + instruction.cycles;
+      },
       Opcode::UnknownOperation => {
         return address_mode_cycles + instruction.cycles;
       }
     }
   }
 
-  fn load_address_mode(&mut self, addr_mode: &AddressingMode, bus: &mut Bus) -> u8 {
-    match addr_mode {
-      AddressingMode::Implied => {
-        return 0
+  fn reads_operand(instruction: &Instruction) -> bool {
+    match instruction.addr_mode {
+      AddressingMode::Implied | AddressingMode::Accumulator | AddressingMode::Relative => false,
+      _ => !matches!(instruction.opcode, Opcode::STA | Opcode::STX | Opcode::STY | Opcode::SAX | Opcode::JMP | Opcode::JSR)
+    }
+  }
+
+  fn modifies_memory(instruction: &Instruction) -> bool {
+    match instruction.addr_mode {
+      AddressingMode::Implied | AddressingMode::Accumulator => false,
+      _ => matches!(instruction.opcode,
+        Opcode::ASL | Opcode::LSR | Opcode::ROL | Opcode::ROR | Opcode::INC | Opcode::DEC |
+        Opcode::SLO | Opcode::RLA | Opcode::SRE | Opcode::RRA | Opcode::DCP | Opcode::ISC)
+    }
+  }
+
+  // Stores and read-modify-write instructions take the same time whether or not indexing crosses a page
+  fn writes_memory(instruction: &Instruction) -> bool {
+    Self::modifies_memory(instruction) || matches!(instruction.opcode, Opcode::STA | Opcode::STX | Opcode::STY | Opcode::SAX)
+  }
+
+  // Taken branches spend a cycle reading the next opcode, and one more when the target is on another page
+  fn branch<B: CpuBus>(&mut self, taken: bool, bus: &mut B) -> u8 {
+    if !taken {
+      return 0;
+    }
+
+    bus.read(self.pc);
+    self.location = self.pc.wrapping_add(self.relative_location);
+    let page_crossed = (self.location & 0xFF00) != (self.pc & 0xFF00);
+    if page_crossed {
+      bus.read((self.pc & 0xFF00) | (self.location & 0x00FF));
+    }
+    self.pc = self.location;
+
+    if page_crossed { 2 } else { 1 }
+  }
+
+  fn compare(&mut self, register: u8, op_data: u8) {
+    let difference = register.wrapping_sub(op_data);
+    self.f_c = register >= op_data;
+    self.f_z = difference == 0;
+    self.f_n = (difference & 0x80) != 0;
+  }
+
+  fn add_with_carry(&mut self, op_data: u8) {
+    if self.decimal_enabled() {
+      self.adc_decimal(op_data);
+      return;
+    }
+
+    let sum = self.r_a as u16 + op_data as u16 + self.f_c as u16;
+    self.f_c = sum > 255;
+    self.f_z = (sum & 0x00FF) == 0;
+    self.f_v = (!((self.r_a as u16) ^ op_data as u16) & ((self.r_a as u16) ^ sum) & 0x0080) != 0;
+    self.f_n = sum & 0x80 != 0;
+    self.r_a = (sum & 0x00FF) as u8;
+  }
+
+  fn subtract_with_carry(&mut self, op_data: u8) {
+    if self.decimal_enabled() {
+      self.sbc_decimal(op_data);
+      return;
+    }
+
+    let inverted = (op_data as u16) ^ 0x00FF;
+    let difference = self.r_a as u16 + inverted + self.f_c as u16;
+
+    self.f_c = difference & 0xFF00 != 0;
+    self.f_z = (difference & 0x00FF) == 0;
+    self.f_v = ((difference ^ self.r_a as u16) & (difference ^ inverted) & 0x0080) != 0;
+    self.f_n = difference & 0x0080 != 0;
+    self.r_a = (difference & 0x00FF) as u8;
+  }
+
+  // AND then ROR A, with C and V taken from bits 6 and 5 of the result. In decimal mode the
+  // rotated value gets a BCD style fix up of each nibble and C comes from the high one.
+  fn arr(&mut self, op_data: u8) {
+    let value = self.r_a & op_data;
+    let mut result = ((self.f_c as u8) << 7) | (value >> 1);
+
+    if !self.decimal_enabled() {
+      self.f_z = result == 0x00;
+      self.f_n = (result & 0x80) != 0;
+      self.f_c = (result & 0x40) != 0;
+      self.f_v = ((result >> 6) ^ (result >> 5)) & 0x01 != 0;
+      self.r_a = result;
+      return;
+    }
+
+    self.f_n = self.f_c;
+    self.f_z = result == 0x00;
+    self.f_v = ((value ^ result) & 0x40) != 0;
+    if (value & 0x0F) + (value & 0x01) > 0x05 {
+      result = (result & 0xF0) | (result.wrapping_add(0x06) & 0x0F);
+    }
+    self.f_c = (value as u16 & 0xF0) + (value as u16 & 0x10) > 0x50;
+    if self.f_c {
+      result = result.wrapping_add(0x60);
+    }
+    self.r_a = result;
+  }
+
+  fn decimal_enabled(&self) -> bool {
+    self.f_d && self.variant == CpuVariant::Nmos6502
+  }
+
+  // NMOS BCD addition, N and V come from the intermediate result and Z from the binary sum
+  fn adc_decimal(&mut self, op_data: u8) {
+    let a = self.r_a as u16;
+    let m = op_data as u16;
+    let c = self.f_c as u16;
+
+    let mut lo = (a & 0x0F) + (m & 0x0F) + c;
+    if lo >= 0x0A {
+      lo = ((lo + 0x06) & 0x0F) + 0x10;
+    }
+
+    let mut sum = (a & 0xF0) + (m & 0xF0) + lo;
+
+    self.f_z = ((a + m + c) & 0x00FF) == 0;
+    self.f_n = (sum & 0x0080) != 0;
+    self.f_v = (!(a ^ m) & (a ^ sum) & 0x0080) != 0;
+
+    if sum >= 0xA0 {
+      sum += 0x60;
+    }
+
+    self.f_c = sum >= 0x100;
+    self.r_a = (sum & 0x00FF) as u8;
+  }
+
+  // NMOS BCD subtraction, all flags come from the equivalent binary subtraction
+  fn sbc_decimal(&mut self, op_data: u8) {
+    let a = self.r_a as i16;
+    let m = op_data as i16;
+    let c = self.f_c as i16;
+
+    let binary = a - m + c - 1;
+    self.f_c = binary >= 0;
+    self.f_z = (binary & 0x00FF) == 0;
+    self.f_n = (binary & 0x0080) != 0;
+    self.f_v = ((a ^ binary) & (a ^ m) & 0x0080) != 0;
+
+    let mut lo = (a & 0x0F) - (m & 0x0F) + c - 1;
+    if lo < 0 {
+      lo = ((lo - 0x06) & 0x0F) - 0x10;
+    }
+
+    let mut difference = (a & 0xF0) - (m & 0xF0) + lo;
+    if difference < 0 {
+      difference -= 0x60;
+    }
+
+    self.r_a = (difference & 0x00FF) as u8;
+  }
+
+  fn load_address_mode<B: CpuBus>(&mut self, instruction: &Instruction, bus: &mut B) -> u8 {
+    match instruction.addr_mode {
+      AddressingMode::Implied | AddressingMode::Accumulator => {
+        // One byte instructions still read the byte after the opcode
+        bus.read(self.pc);
+        return 0;
       },
       AddressingMode::Immediate => {
         self.location = self.pc;
-        self.pc += 1;
+        self.pc = self.pc.wrapping_add(1);
         return 0;
       },
       AddressingMode::ZeroPage => {
         let msb = bus.read(self.pc);
-        self.pc += 1;
+        self.pc = self.pc.wrapping_add(1);
         self.location = msb as u16 & 0x00FF;
         return 0;
       },
       AddressingMode::ZeroPageX => {
         let msb = bus.read(self.pc);
-        self.pc += 1;
+        self.pc = self.pc.wrapping_add(1);
+        bus.read(msb as u16);
         self.location = (msb as u16 + self.r_x as u16) & 0x00FF;
         return 0;
       },
       AddressingMode::ZeroPageY => {
         let msb = bus.read(self.pc);
-        self.pc += 1;
+        self.pc = self.pc.wrapping_add(1);
+        bus.read(msb as u16);
         self.location = (msb as u16 + self.r_y as u16) & 0x00FF;
         return 0;
       },
       AddressingMode::Relative => {
         self.relative_location = bus.read(self.pc) as u16;
-        self.pc += 1;
+        self.pc = self.pc.wrapping_add(1);
         if (self.relative_location & 0x80) != 0x0000 {
           self.relative_location |= 0xFF00;
         }
@@ -637,56 +961,47 @@ impl CPU {
       },
       AddressingMode::Absolute => {
         let lo = bus.read(self.pc) as u16;
-        self.pc += 1;
+        self.pc = self.pc.wrapping_add(1);
         let hi = bus.read(self.pc) as u16;
-        self.pc += 1;
+        self.pc = self.pc.wrapping_add(1);
         self.location = (hi << 8) | lo;
         return 0;
       },
       AddressingMode::AbsoluteX => {
         let lo = bus.read(self.pc) as u16;
-        self.pc += 1;
+        self.pc = self.pc.wrapping_add(1);
         let hi = bus.read(self.pc) as u16;
-        self.pc += 1;
+        self.pc = self.pc.wrapping_add(1);
 
-        self.location = (hi << 8) | lo;
-        self.location += self.r_x as u16;
-
-        return 0;
+        return self.index_location((hi << 8) | lo, self.r_x, instruction, bus);
       },
       AddressingMode::AbsoluteY => {
         let lo = bus.read(self.pc) as u16;
-        self.pc += 1;
+        self.pc = self.pc.wrapping_add(1);
         let hi = bus.read(self.pc) as u16;
-        self.pc += 1;
+        self.pc = self.pc.wrapping_add(1);
 
-        self.location = (hi << 8) | lo;
-        self.location += self.r_y as u16;
-
-        return 0;
+        return self.index_location((hi << 8) | lo, self.r_y, instruction, bus);
       },
       AddressingMode::Indirect => {
         let ptr_lo = bus.read(self.pc) as u16;
-        self.pc += 1;
+        self.pc = self.pc.wrapping_add(1);
         let ptr_hi = bus.read(self.pc) as u16;
-        self.pc += 1;
+        self.pc = self.pc.wrapping_add(1);
 
         let ptr = (ptr_hi << 8) | ptr_lo;
 
-        // Page boundary hardware bug simulation
-        if ptr_lo == 0x00FF {
-          self.location = ((bus.read(ptr & 0xFF00) as u16) << 8) | bus.read(ptr) as u16;
-        } else {
-          let lo = bus.read(ptr);
-          let hi = bus.read(ptr + 1);
-          self.location = ((hi as u16) << 8) | lo as u16;
-        }
+        // Page boundary hardware bug, the high byte comes from the start of the same page
+        let lo = bus.read(ptr) as u16;
+        let hi = bus.read((ptr & 0xFF00) | (ptr.wrapping_add(1) & 0x00FF)) as u16;
+        self.location = (hi << 8) | lo;
 
         return 0;
       },
       AddressingMode::IndirectX => {
         let address = bus.read(self.pc) as u16;
-        self.pc += 1;
+        self.pc = self.pc.wrapping_add(1);
+        bus.read(address);
 
         let lo = bus.read((address + (self.r_x as u16)) & 0x00FF) as u16;
         let hi = bus.read((address + (self.r_x as u16) + 1) & 0x00FF) as u16;
@@ -696,49 +1011,45 @@ impl CPU {
       },
       AddressingMode::IndirectY => {
         let address = bus.read(self.pc) as u16;
-        self.pc += 1;
+        self.pc = self.pc.wrapping_add(1);
 
         let lo = bus.read(address & 0x00FF) as u16;
         let hi = bus.read((address + 0x0001) & 0x00FF) as u16;
 
-        self.location = (hi << 8) | lo;
-        self.location += self.r_y as u16;
-
-        if (self.location & 0xFF00) != (hi << 8) {
-          return 1;
-        } else {
-          return 0;
-        }
+        return self.index_location((hi << 8) | lo, self.r_y, instruction, bus);
       }
-      _ => { return 0; }
     }
   }
 
-  fn trace(&self, bus: &mut Bus) {
-    let opcode = bus.read(self.pc);
-    let instruction = Instruction::from_u8(opcode);
+  // Adds an index register to a base address. The low byte is added first, so the CPU reads
+  // from the unfixed address whenever the high byte needs a carry, and always before a write.
+  fn index_location<B: CpuBus>(&mut self, base: u16, index: u8, instruction: &Instruction, bus: &mut B) -> u8 {
+    self.location = base.wrapping_add(index as u16);
+    let page_crossed = (self.location & 0xFF00) != (base & 0xFF00);
+    let writes = Self::writes_memory(instruction);
 
-    let mut instruction_bytes = vec![opcode];
+    if page_crossed || writes {
+      bus.read((base & 0xFF00) | (self.location & 0x00FF));
+    }
 
-    match instruction.addr_mode {
-      AddressingMode::Immediate | AddressingMode::ZeroPage | AddressingMode::ZeroPageX | AddressingMode::ZeroPageY
-        | AddressingMode::IndirectX | AddressingMode::IndirectY | AddressingMode::Relative => {
-        instruction_bytes.push(bus.read(self.pc + 1));
-      }
-      AddressingMode::Absolute | AddressingMode::AbsoluteX | AddressingMode::AbsoluteY | AddressingMode::Indirect => {
-        instruction_bytes.push(bus.read(self.pc + 1));
-        instruction_bytes.push(bus.read(self.pc + 2));
-      }
-      _ => {}
-    };
+    if page_crossed && !writes { 1 } else { 0 }
+  }
 
-    let byte_str = instruction_bytes.iter().map(|byte| format!("{:02x}", byte)).collect::<Vec<String>>().join(" ");
-    let instruction_string = format!("{:04x}  {:8} {: >4}", self.pc, byte_str, instruction.opcode);
+  fn trace<B: CpuBus>(&mut self, bus: &B) {
+    let tracer = self.tracer.as_ref().unwrap();
+    if !tracer.wants(self.pc, bus.frame()) {
+      return;
+    }
 
-    let trace_string = format!("{:47} A:{:02x} X:{:02x} Y:{:02x} P:{:02x} SP:{:02x} CYC:{}\n", 
-      instruction_string, self.r_a, self.r_x, self.r_y, self.r_status, self.sp, self.cycles).to_ascii_uppercase();
+    let registers = (self.r_a, self.r_x, self.r_y, self.r_status, self.sp);
+    let state = TraceState::capture(bus, self.pc, registers, self.cycles);
+    self.tracer.as_mut().unwrap().log(&state);
+  }
 
-    self.trace_file.as_ref().unwrap().write_all(trace_string.as_bytes());
+  // Current processor status packed from the individual flags
+  pub fn status(&mut self) -> u8 {
+    self.update_status_register();
+    self.r_status
   }
 
   fn update_status_register(&mut self) {
@@ -777,7 +1088,7 @@ impl CPU {
     }
   }
 
-  fn set_status_register(&mut self, status: u8) {
+  pub fn set_status_register(&mut self, status: u8) {
     self.f_n = (status & (0x01 << 7)) != 0;
     self.f_v = (status & (0x01 << 6)) != 0;
     self.f_u = (status & (0x01 << 5)) != 0;
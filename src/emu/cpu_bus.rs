@@ -0,0 +1,133 @@
+// Memory interface seen by the 6502 core
+pub trait CpuBus {
+  // Read a byte, triggering any side effects of the access
+  fn read(&mut self, addr: u16) -> u8;
+
+  fn write(&mut self, addr: u16, value: u8);
+
+  // Read a byte without side effects, used for tracing and debugging
+  fn peek(&self, addr: u16) -> u8;
+
+  // Advance the rest of the system by the given number of CPU cycles
+  fn tick(&mut self, cycles: u8);
+
+  // Current PPU scanline and dot, for buses that have a PPU
+  fn ppu_position(&self) -> Option<(u16, u16)> {
+    None
+  }
+
+  // Number of completed video frames
+  fn frame(&self) -> u64 {
+    0
+  }
+
+  // True once for each NMI edge raised since the last poll
+  fn poll_nmi(&mut self) -> bool {
+    false
+  }
+
+  // IRQ line level, taken between instructions while the I flag is clear
+  fn irq_pending(&self) -> bool {
+    false
+  }
+
+  // CPU cycles DMAs started since the last instruction halt the CPU for
+  fn take_dma_cycles(&mut self) -> u16 {
+    0
+  }
+}
+
+// Flat 64 KiB RAM with no memory mapped devices
+pub struct FlatBus {
+  pub ram: Vec<u8>,
+  pub cycles: u64
+}
+
+impl Default for FlatBus {
+  fn default() -> Self {
+    FlatBus { ram: vec![0; 0x10000], cycles: 0 }
+  }
+}
+
+impl FlatBus {
+  pub fn new() -> FlatBus {
+    FlatBus::default()
+  }
+
+  // Copy a program into RAM at the given address
+  pub fn load(&mut self, addr: u16, bytes: &[u8]) {
+    let start = addr as usize;
+    let end = (start + bytes.len()).min(self.ram.len());
+    self.ram[start..end].copy_from_slice(&bytes[..(end - start)]);
+  }
+}
+
+impl CpuBus for FlatBus {
+  fn read(&mut self, addr: u16) -> u8 {
+    self.ram[addr as usize]
+  }
+
+  fn write(&mut self, addr: u16, value: u8) {
+    self.ram[addr as usize] = value;
+  }
+
+  fn peek(&self, addr: u16) -> u8 {
+    self.ram[addr as usize]
+  }
+
+  fn tick(&mut self, cycles: u8) {
+    self.cycles += cycles as u64;
+  }
+}
+
+// Single bus access as seen from the CPU pins
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BusAccess {
+  Read { addr: u16, value: u8 },
+  Write { addr: u16, value: u8 }
+}
+
+// Flat 64 KiB RAM that logs every read and write in order
+pub struct RecordingBus {
+  pub ram: Vec<u8>,
+  pub accesses: Vec<BusAccess>,
+  pub cycles: u64
+}
+
+impl Default for RecordingBus {
+  fn default() -> Self {
+    RecordingBus { ram: vec![0; 0x10000], accesses: Vec::new(), cycles: 0 }
+  }
+}
+
+impl RecordingBus {
+  pub fn new() -> RecordingBus {
+    RecordingBus::default()
+  }
+
+  pub fn clear_log(&mut self) {
+    self.accesses.clear();
+    self.cycles = 0;
+  }
+}
+
+impl CpuBus for RecordingBus {
+  fn read(&mut self, addr: u16) -> u8 {
+    let value = self.ram[addr as usize];
+    self.accesses.push(BusAccess::Read { addr, value });
+    value
+  }
+
+  fn write(&mut self, addr: u16, value: u8) {
+    self.ram[addr as usize] = value;
+    self.accesses.push(BusAccess::Write { addr, value });
+  }
+
+  fn peek(&self, addr: u16) -> u8 {
+    self.ram[addr as usize]
+  }
+
+  fn tick(&mut self, cycles: u8) {
+    self.cycles += cycles as u64;
+  }
+}
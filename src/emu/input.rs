@@ -0,0 +1,100 @@
+use std::any::Any;
+
+use crate::emu::joypad::Joypad;
+use crate::emu::keyboard::FamilyBasicKeyboard;
+use crate::emu::multitap::HoriAdapter;
+use crate::emu::power_pad::PowerPad;
+use crate::emu::save_state::{StateReader, StateWriter};
+use crate::emu::vaus::Vaus;
+use crate::emu::zapper::Zapper;
+use crate::graphics::frame::Frame;
+
+// NES 2.0 default expansion devices this emulator can plug in, from header byte 15
+pub const DEFAULT_DEVICE_UNSPECIFIED: u8 = 0x00;
+pub const DEFAULT_DEVICE_CONTROLLERS: u8 = 0x01;
+pub const DEFAULT_DEVICE_FOUR_SCORE: u8 = 0x02;
+pub const DEFAULT_DEVICE_FOUR_PLAYERS_ADAPTER: u8 = 0x03;
+pub const DEFAULT_DEVICE_ZAPPER: u8 = 0x08;
+pub const DEFAULT_DEVICE_TWO_ZAPPERS: u8 = 0x09;
+pub const DEFAULT_DEVICE_POWER_PAD_A: u8 = 0x0B;
+pub const DEFAULT_DEVICE_POWER_PAD_B: u8 = 0x0C;
+pub const DEFAULT_DEVICE_FAMILY_TRAINER_A: u8 = 0x0D;
+pub const DEFAULT_DEVICE_FAMILY_TRAINER_B: u8 = 0x0E;
+pub const DEFAULT_DEVICE_VAUS_NES: u8 = 0x0F;
+pub const DEFAULT_DEVICE_VAUS_FAMICOM: u8 = 0x10;
+pub const DEFAULT_DEVICE_FAMILY_BASIC_KEYBOARD: u8 = 0x23;
+
+// Something plugged into one of the two controller ports
+pub trait InputDevice {
+  // $4016 writes reach both ports, bit 0 is the strobe
+  fn write(&mut self, value: u8);
+
+  // Low bits of a $4016 or $4017 read, the bus fills the rest with open bus
+  fn read(&mut self) -> u8;
+
+  // Called as the PPU starts each scanline with the picture drawn so far, for devices that
+  // watch the screen
+  fn scanline(&mut self, _frame: &Frame, _scanline: u16) {}
+
+  // Standard controllers the device carries, counted from 0, for setting buttons
+  fn controller(&mut self, _index: usize) -> Option<&mut Joypad> {
+    None
+  }
+
+  // Latches and shift registers for save states. What the host drives, like buttons held, is
+  // not state, and devices that save nothing keep theirs when a state is loaded.
+  fn save_state(&self, _state: &mut StateWriter) {}
+
+  fn load_state(&mut self, _state: &mut StateReader) -> Result<(), String> {
+    Ok(())
+  }
+
+  // Lets the host get at the concrete device to drive it
+  fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+// Something on the Famicom expansion port, which sees every $4016 write and drives bits 1-4 of
+// both $4016 and $4017 reads alongside the controller ports
+pub trait ExpansionDevice {
+  fn write(&mut self, value: u8);
+
+  // Bits to add to a read of port 0 ($4016) or port 1 ($4017)
+  fn read(&mut self, port: usize) -> u8;
+
+  fn scanline(&mut self, _frame: &Frame, _scanline: u16) {}
+
+  fn controller(&mut self, _index: usize) -> Option<&mut Joypad> {
+    None
+  }
+
+  fn save_state(&self, _state: &mut StateWriter) {}
+
+  fn load_state(&mut self, _state: &mut StateReader) -> Result<(), String> {
+    Ok(())
+  }
+
+  fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+// Controller port device by name, for picking one on the command line
+pub fn port_device(name: &str) -> Result<Box<dyn InputDevice>, String> {
+  match name {
+    "joypad" => Ok(Box::new(Joypad::default())),
+    "zapper" => Ok(Box::new(Zapper::default())),
+    "vaus" => Ok(Box::new(Vaus::default())),
+    "power-pad" => Ok(Box::new(PowerPad::default())),
+    _ => Err(format!("UNKNOWN CONTROLLER PORT DEVICE {}", name.to_uppercase()))
+  }
+}
+
+// Expansion port device by name, "none" leaves the port empty
+pub fn expansion_device(name: &str) -> Result<Option<Box<dyn ExpansionDevice>>, String> {
+  match name {
+    "none" => Ok(None),
+    "hori" => Ok(Some(Box::new(HoriAdapter::new(true)))),
+    "vaus" => Ok(Some(Box::new(Vaus::default()))),
+    "family-trainer" => Ok(Some(Box::new(PowerPad::default()))),
+    "keyboard" => Ok(Some(Box::new(FamilyBasicKeyboard::new()))),
+    _ => Err(format!("UNKNOWN EXPANSION PORT DEVICE {}", name.to_uppercase()))
+  }
+}
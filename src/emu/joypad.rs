@@ -0,0 +1,60 @@
+// Standard controller buttons in the order they are shifted out
+pub const BUTTON_A: u8 = 0b0000_0001;
+pub const BUTTON_B: u8 = 0b0000_0010;
+pub const BUTTON_SELECT: u8 = 0b0000_0100;
+pub const BUTTON_START: u8 = 0b0000_1000;
+pub const BUTTON_UP: u8 = 0b0001_0000;
+pub const BUTTON_DOWN: u8 = 0b0010_0000;
+pub const BUTTON_LEFT: u8 = 0b0100_0000;
+pub const BUTTON_RIGHT: u8 = 0b1000_0000;
+
+use std::any::Any;
+
+use crate::emu::input::InputDevice;
+use crate::emu::save_state::{StateReader, StateWriter};
+
+#[derive(Default)]
+pub struct Joypad {
+  pub buttons: u8,
+  strobe: bool,
+  shift_reg: u8
+}
+
+impl InputDevice for Joypad {
+  fn write(&mut self, value: u8) {
+    self.strobe = value & 0x01 != 0;
+    if self.strobe {
+      self.shift_reg = self.buttons;
+    }
+  }
+
+  fn read(&mut self) -> u8 {
+    if self.strobe {
+      return self.buttons & 0x01;
+    }
+
+    let bit = self.shift_reg & 0x01;
+    // Official controllers report pressed once all eight buttons are read
+    self.shift_reg = (self.shift_reg >> 1) | 0x80;
+    return bit;
+  }
+
+  fn controller(&mut self, index: usize) -> Option<&mut Joypad> {
+    if index == 0 { Some(self) } else { None }
+  }
+
+  fn save_state(&self, state: &mut StateWriter) {
+    state.bool(self.strobe);
+    state.u8(self.shift_reg);
+  }
+
+  fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+    self.strobe = state.bool()?;
+    self.shift_reg = state.u8()?;
+    Ok(())
+  }
+
+  fn as_any_mut(&mut self) -> &mut dyn Any {
+    self
+  }
+}
@@ -0,0 +1,220 @@
+use std::any::Any;
+
+use crate::emu::input::ExpansionDevice;
+use crate::emu::save_state::{StateReader, StateWriter};
+use crate::graphics::frame::Frame;
+
+// $4016 write bits
+const RESET_BIT: u8 = 0x01;
+const COLUMN_BIT: u8 = 0x02;
+const ENABLE_BIT: u8 = 0x04;
+// The tape output shares a line with the row reset
+const TAPE_OUT_BIT: u8 = 0x01;
+// Tape input is on $4016 D1
+const TAPE_IN_BIT: u8 = 0x02;
+
+// Key states are read on $4017 D1-D4, 0 for a pressed key
+const KEY_BITS: u8 = 0x1E;
+// A tenth row with no keys wired to it lets software tell the keyboard is there
+const ROW_COUNT: usize = 10;
+
+// Family BASIC keyboard matrix as [row][column], each giving the keys read on D1 to D4
+const KEYS: [[[&str; 4]; 2]; 9] = [
+  [["F8", "RETURN", "[", "]"], ["KANA", "RSHIFT", "YEN", "STOP"]],
+  [["F7", "@", ":", ";"], ["_", "/", "-", "^"]],
+  [["F6", "O", "L", "K"], [".", ",", "P", "0"]],
+  [["F5", "I", "U", "J"], ["M", "N", "9", "8"]],
+  [["F4", "Y", "G", "H"], ["B", "V", "7", "6"]],
+  [["F3", "T", "R", "D"], ["F", "C", "5", "4"]],
+  [["F2", "W", "S", "A"], ["X", "Z", "E", "3"]],
+  [["F1", "ESC", "Q", "CTR"], ["LSHIFT", "GRPH", "1", "2"]],
+  [["CLR", "UP", "RIGHT", "LEFT"], ["DOWN", "SPACE", "DEL", "INS"]]
+];
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TapeMode {
+  Stopped,
+  Playing,
+  Recording
+}
+
+// Famicom Data Recorder, a cassette deck wired through the keyboard. The tape is kept as one
+// level, 0 or 1, per scanline, which is plenty for the tones Family BASIC saves with.
+pub struct DataRecorder {
+  pub tape: Vec<u8>,
+  pub mode: TapeMode,
+  // Next level to play, or where recording carries on from
+  pub position: usize,
+  output: u8
+}
+
+impl DataRecorder {
+  pub fn new() -> DataRecorder {
+    DataRecorder { tape: Vec::new(), mode: TapeMode::Stopped, position: 0, output: 0 }
+  }
+
+  pub fn play(&mut self) {
+    self.mode = TapeMode::Playing;
+  }
+
+  // Records over the tape from the current position on
+  pub fn record(&mut self) {
+    self.tape.truncate(self.position);
+    self.mode = TapeMode::Recording;
+  }
+
+  pub fn stop(&mut self) {
+    self.mode = TapeMode::Stopped;
+  }
+
+  pub fn rewind(&mut self) {
+    self.position = 0;
+  }
+
+  fn input(&self) -> u8 {
+    match self.mode {
+      TapeMode::Playing => self.tape.get(self.position).copied().unwrap_or(0),
+      _ => 0
+    }
+  }
+
+  // The tape is saved too since recording writes it
+  fn save_state(&self, state: &mut StateWriter) {
+    state.bytes(&self.tape);
+    state.u8(match self.mode {
+      TapeMode::Stopped => 0,
+      TapeMode::Playing => 1,
+      TapeMode::Recording => 2
+    });
+    state.u64(self.position as u64);
+    state.u8(self.output);
+  }
+
+  fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+    self.tape = state.bytes()?.to_vec();
+    self.mode = match state.u8()? {
+      0 => TapeMode::Stopped,
+      1 => TapeMode::Playing,
+      2 => TapeMode::Recording,
+      code => return Err(format!("BAD TAPE MODE {} IN SAVE STATE", code))
+    };
+    self.position = (state.u64()? as usize).min(self.tape.len());
+    self.output = state.u8()?;
+    Ok(())
+  }
+
+  fn advance(&mut self) {
+    match self.mode {
+      TapeMode::Playing if self.position < self.tape.len() => self.position += 1,
+      TapeMode::Playing => self.mode = TapeMode::Stopped,
+      TapeMode::Recording => {
+        self.tape.push(self.output);
+        self.position += 1;
+      }
+      TapeMode::Stopped => {}
+    }
+  }
+}
+
+impl Default for DataRecorder {
+  fn default() -> DataRecorder {
+    DataRecorder::new()
+  }
+}
+
+// Family BASIC keyboard on the Famicom expansion port, with a data recorder plugged into it.
+// Software resets to the first row, then reads both columns of each row in turn, moving to the
+// next row each time the column select goes from 1 back to 0.
+pub struct FamilyBasicKeyboard {
+  // Pressed keys as bits per [row][column], D1 in bit 0
+  pub pressed: [[u8; 2]; 9],
+  pub recorder: DataRecorder,
+  row: usize,
+  column: usize,
+  enabled: bool
+}
+
+impl FamilyBasicKeyboard {
+  pub fn new() -> FamilyBasicKeyboard {
+    FamilyBasicKeyboard { pressed: [[0; 2]; 9], recorder: DataRecorder::new(), row: 0, column: 0, enabled: false }
+  }
+
+  // Presses or releases a key by the name on its keycap, like "A", "RETURN" or "LSHIFT"
+  pub fn set_key(&mut self, name: &str, pressed: bool) -> Result<(), String> {
+    let name = name.to_uppercase();
+    for (row, columns) in KEYS.iter().enumerate() {
+      for (column, keys) in columns.iter().enumerate() {
+        if let Some(bit) = keys.iter().position(|key| *key == name) {
+          if pressed {
+            self.pressed[row][column] |= 1 << bit;
+          } else {
+            self.pressed[row][column] &= !(1 << bit);
+          }
+          return Ok(());
+        }
+      }
+    }
+    Err(format!("NO KEY NAMED {}", name))
+  }
+
+  pub fn release_all(&mut self) {
+    self.pressed = [[0; 2]; 9];
+  }
+}
+
+impl Default for FamilyBasicKeyboard {
+  fn default() -> FamilyBasicKeyboard {
+    FamilyBasicKeyboard::new()
+  }
+}
+
+impl ExpansionDevice for FamilyBasicKeyboard {
+  fn write(&mut self, value: u8) {
+    self.recorder.output = value & TAPE_OUT_BIT;
+
+    let column = ((value & COLUMN_BIT) >> 1) as usize;
+    self.enabled = value & ENABLE_BIT != 0;
+    if self.enabled {
+      if self.column == 1 && column == 0 {
+        self.row = (self.row + 1) % ROW_COUNT;
+      }
+      if value & RESET_BIT != 0 {
+        self.row = 0;
+      }
+    }
+    self.column = column;
+  }
+
+  fn read(&mut self, port: usize) -> u8 {
+    if port == 0 {
+      return if self.recorder.input() != 0 { TAPE_IN_BIT } else { 0 };
+    }
+    if !self.enabled {
+      return 0;
+    }
+    let pressed = self.pressed.get(self.row).map(|columns| columns[self.column]).unwrap_or(0);
+    !(pressed << 1) & KEY_BITS
+  }
+
+  fn scanline(&mut self, _frame: &Frame, _scanline: u16) {
+    self.recorder.advance();
+  }
+
+  fn save_state(&self, state: &mut StateWriter) {
+    state.u8(self.row as u8);
+    state.u8(self.column as u8);
+    state.bool(self.enabled);
+    self.recorder.save_state(state);
+  }
+
+  fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+    self.row = (state.u8()? as usize).min(ROW_COUNT - 1);
+    self.column = (state.u8()? & 0x01) as usize;
+    self.enabled = state.bool()?;
+    self.recorder.load_state(state)
+  }
+
+  fn as_any_mut(&mut self) -> &mut dyn Any {
+    self
+  }
+}
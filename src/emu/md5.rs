@@ -0,0 +1,62 @@
+// MD5 digest (RFC 1321), which FCEUX identifies ROMs by in FM2 movies
+
+// Left rotation of each step, four per round
+const SHIFTS: [u32; 16] = [7, 12, 17, 22, 5, 9, 14, 20, 4, 11, 16, 23, 6, 10, 15, 21];
+
+// Per step constants, the integer part of abs(sin(i + 1)) * 2^32
+const SINES: [u32; 64] = [
+  0xD76AA478, 0xE8C7B756, 0x242070DB, 0xC1BDCEEE, 0xF57C0FAF, 0x4787C62A, 0xA8304613, 0xFD469501,
+  0x698098D8, 0x8B44F7AF, 0xFFFF5BB1, 0x895CD7BE, 0x6B901122, 0xFD987193, 0xA679438E, 0x49B40821,
+  0xF61E2562, 0xC040B340, 0x265E5A51, 0xE9B6C7AA, 0xD62F105D, 0x02441453, 0xD8A1E681, 0xE7D3FBC8,
+  0x21E1CDE6, 0xC33707D6, 0xF4D50D87, 0x455A14ED, 0xA9E3E905, 0xFCEFA3F8, 0x676F02D9, 0x8D2A4C8A,
+  0xFFFA3942, 0x8771F681, 0x6D9D6122, 0xFDE5380C, 0xA4BEEA44, 0x4BDECFA9, 0xF6BB4B60, 0xBEBFBC70,
+  0x289B7EC6, 0xEAA127FA, 0xD4EF3085, 0x04881D05, 0xD9D4D039, 0xE6DB99E5, 0x1FA27CF8, 0xC4AC5665,
+  0xF4292244, 0x432AFF97, 0xAB9423A7, 0xFC93A039, 0x655B59C3, 0x8F0CCC92, 0xFFEFF47D, 0x85845DD1,
+  0x6FA87E4F, 0xFE2CE6E0, 0xA3014314, 0x4E0811A1, 0xF7537E82, 0xBD3AF235, 0x2AD7D2BB, 0xEB86D391
+];
+
+const BLOCK_SIZE: usize = 64;
+
+pub fn md5(data: &[u8]) -> [u8; 16] {
+  // Padded with a 1 bit, zeros and the length in bits to a whole number of blocks
+  let mut message = data.to_vec();
+  message.push(0x80);
+  while message.len() % BLOCK_SIZE != BLOCK_SIZE - 8 {
+    message.push(0x00);
+  }
+  message.extend_from_slice(&((data.len() as u64).wrapping_mul(8)).to_le_bytes());
+
+  let mut state: [u32; 4] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476];
+  for block in message.chunks(BLOCK_SIZE) {
+    let mut words = [0u32; 16];
+    for (word, bytes) in words.iter_mut().zip(block.chunks(4)) {
+      *word = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    }
+
+    let [mut a, mut b, mut c, mut d] = state;
+    for step in 0..64 {
+      let (mix, index) = match step / 16 {
+        0 => ((b & c) | (!b & d), step),
+        1 => ((d & b) | (!d & c), (5 * step + 1) % 16),
+        2 => (b ^ c ^ d, (3 * step + 5) % 16),
+        _ => (c ^ (b | !d), (7 * step) % 16)
+      };
+      let rotated = a.wrapping_add(mix).wrapping_add(SINES[step]).wrapping_add(words[index])
+        .rotate_left(SHIFTS[step / 16 * 4 + step % 4]);
+      a = d;
+      d = c;
+      c = b;
+      b = b.wrapping_add(rotated);
+    }
+
+    for (value, add) in state.iter_mut().zip([a, b, c, d].iter()) {
+      *value = value.wrapping_add(*add);
+    }
+  }
+
+  let mut digest = [0; 16];
+  for (bytes, value) in digest.chunks_mut(4).zip(state.iter()) {
+    bytes.copy_from_slice(&value.to_le_bytes());
+  }
+  digest
+}
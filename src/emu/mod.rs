@@ -1,6 +1,23 @@
+pub mod base64;
 pub mod bus;
 pub mod cartridge;
+pub mod cpu_bus;
 pub mod cpu_opcodes;
 pub mod cpu;
+pub mod input;
+pub mod joypad;
+pub mod keyboard;
+pub mod md5;
+pub mod movie;
+pub mod multitap;
+pub mod nsf;
+pub mod nsf_player;
+pub mod power_pad;
 pub mod ppu;
-pub mod nes;
\ No newline at end of file
+pub mod recorder;
+pub mod save_state;
+pub mod test_rom;
+pub mod trace;
+pub mod vaus;
+pub mod zapper;
+pub mod nes;
@@ -0,0 +1,401 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+use crate::emu::base64;
+use crate::emu::bus::Bus;
+use crate::emu::cartridge::Cartridge;
+use crate::emu::joypad::Joypad;
+use crate::emu::multitap::FourScore;
+use crate::emu::zapper::Zapper;
+
+// Button characters of an FM2 input log field, in the order they appear
+const FM2_BUTTONS: [(char, u8); 8] = [
+  ('R', crate::emu::joypad::BUTTON_RIGHT),
+  ('L', crate::emu::joypad::BUTTON_LEFT),
+  ('D', crate::emu::joypad::BUTTON_DOWN),
+  ('U', crate::emu::joypad::BUTTON_UP),
+  ('T', crate::emu::joypad::BUTTON_START),
+  ('S', crate::emu::joypad::BUTTON_SELECT),
+  ('B', crate::emu::joypad::BUTTON_B),
+  ('A', crate::emu::joypad::BUTTON_A)
+];
+
+// Commands field bits, run at the start of the frame they are logged on
+pub const COMMAND_RESET: u8 = 0x01;
+pub const COMMAND_POWER: u8 = 0x02;
+
+const FM2_VERSION: &str = "3";
+// FCEUX input device numbers for the port0 and port1 header keys
+const FM2_PORT_NONE: u8 = 0;
+const FM2_PORT_GAMEPAD: u8 = 1;
+const FM2_PORT_ZAPPER: u8 = 2;
+// Zapper trigger in the mouse button field
+const FM2_ZAPPER_TRIGGER: u8 = 0x01;
+// A Zapper pointed away from the screen is logged below the picture
+const OFF_SCREEN_Y: u8 = 255;
+// Binary header values, FCEUX writes base64 and also reads hex
+const FM2_BASE64_PREFIX: &str = "base64:";
+const FM2_HEX_PREFIX: &str = "0x";
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PortDevice {
+  None,
+  Gamepad,
+  Zapper
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ZapperInput {
+  pub x: u8,
+  pub y: u8,
+  pub trigger: bool
+}
+
+impl Default for ZapperInput {
+  fn default() -> ZapperInput {
+    ZapperInput { x: 0, y: OFF_SCREEN_Y, trigger: false }
+  }
+}
+
+// Input for one frame, the header decides which of it the ports see
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct MovieFrame {
+  pub commands: u8,
+  // Players 1-4, 3 and 4 only with a Four Score
+  pub buttons: [u8; 4],
+  // Ports with a Zapper in them
+  pub zappers: [ZapperInput; 2]
+}
+
+impl MovieFrame {
+  // Input as the devices on the bus hold it now
+  pub fn capture(bus: &mut Bus, commands: u8) -> MovieFrame {
+    let mut frame = MovieFrame { commands, ..MovieFrame::default() };
+    for (player, buttons) in frame.buttons.iter_mut().enumerate() {
+      *buttons = bus.controller(player).map(|joypad| joypad.buttons).unwrap_or(0);
+    }
+    for (port, input) in frame.zappers.iter_mut().enumerate() {
+      if let Some(zapper) = bus.device::<Zapper>(port) {
+        if let Some((x, y)) = zapper.cursor {
+          input.x = x.min(u8::MAX as usize) as u8;
+          input.y = y.min(OFF_SCREEN_Y as usize) as u8;
+        }
+        input.trigger = zapper.trigger;
+      }
+    }
+    frame
+  }
+
+  // Sets the devices on the bus to this frame's input
+  pub fn apply(&self, bus: &mut Bus) {
+    for (player, buttons) in self.buttons.iter().enumerate() {
+      if let Some(joypad) = bus.controller(player) {
+        joypad.buttons = *buttons;
+      }
+    }
+    for (port, input) in self.zappers.iter().enumerate() {
+      if let Some(zapper) = bus.device::<Zapper>(port) {
+        zapper.cursor = Some((input.x as usize, input.y as usize));
+        zapper.trigger = input.trigger;
+      }
+    }
+  }
+}
+
+// FCEUX FM2 movie, per frame input from power on or from a save state. The save state is this
+// emulator's own, ones FCEUX wrote do not load.
+pub struct Movie {
+  // Header lines this emulator does not use, like romFilename or comment, kept in order so a
+  // movie saves back the way it was loaded
+  pub header: Vec<(String, String)>,
+  pub rerecord_count: u32,
+  pub four_score: bool,
+  pub ports: [PortDevice; 2],
+  // State the movie starts from instead of power on
+  pub savestate: Option<Vec<u8>>,
+  pub frames: Vec<MovieFrame>
+}
+
+impl Movie {
+  pub fn new() -> Movie {
+    Movie {
+      header: Vec::new(),
+      rerecord_count: 0,
+      four_score: false,
+      ports: [PortDevice::Gamepad; 2],
+      savestate: None,
+      frames: Vec::new()
+    }
+  }
+
+  // Empty movie for the cartridge and devices plugged into the bus. Devices FM2 has no input
+  // log for are an error rather than a movie that would not play back.
+  pub fn for_bus(bus: &mut Bus) -> Result<Movie, String> {
+    if bus.expansion_port.is_some() {
+      return Err("FM2 MOVIES CANNOT LOG EXPANSION PORT DEVICES".to_string());
+    }
+
+    let mut movie = Movie::new();
+    movie.four_score = bus.device::<FourScore>(0).is_some() && bus.device::<FourScore>(1).is_some();
+    for port in 0..2 {
+      movie.ports[port] = if bus.device::<Zapper>(port).is_some() {
+        PortDevice::Zapper
+      } else if bus.device::<Joypad>(port).is_some() || movie.four_score {
+        PortDevice::Gamepad
+      } else {
+        return Err(format!("FM2 MOVIES CANNOT LOG THE DEVICE IN PORT {}", port + 1));
+      };
+    }
+
+    movie.set_header("emuVersion", &emu_version());
+    movie.set_header("romChecksum", &rom_checksum(&bus.cartridge));
+    movie.set_header("guid", &new_guid());
+    Ok(movie)
+  }
+
+  pub fn parse_fm2(text: &str) -> Result<Movie, String> {
+    let mut movie = Movie::new();
+
+    for (number, line) in text.lines().enumerate() {
+      if line.starts_with('|') {
+        let frame = movie.parse_input(line, number)?;
+        movie.frames.push(frame);
+        continue;
+      }
+      if line.trim().is_empty() {
+        continue;
+      }
+
+      let mut parts = line.splitn(2, ' ');
+      let key = parts.next().unwrap_or("");
+      let value = parts.next().unwrap_or("").trim();
+      match key {
+        "version" if value != FM2_VERSION => return Err(format!("UNSUPPORTED FM2 VERSION {}", value)),
+        "version" => {}
+        "binary" if value != "0" => return Err("BINARY FM2 MOVIES ARE NOT SUPPORTED".to_string()),
+        "savestate" => movie.savestate = Some(parse_binary(key, value)?),
+        "rerecordCount" => movie.rerecord_count = parse_number(key, value)?,
+        "fourscore" => movie.four_score = value == "1",
+        "port0" => movie.ports[0] = parse_port(value)?,
+        "port1" => movie.ports[1] = parse_port(value)?,
+        "port2" if value != "0" => return Err(format!("UNSUPPORTED FM2 EXPANSION PORT DEVICE {}", value)),
+        "port2" => {}
+        _ => movie.header.push((key.to_string(), value.to_string()))
+      }
+    }
+
+    Ok(movie)
+  }
+
+  pub fn load(path: &str) -> Result<Movie, String> {
+    let text = std::fs::read_to_string(path).map_err(|error| format!("UNABLE TO READ MOVIE {}: {}", path, error))?;
+    Movie::parse_fm2(&text)
+  }
+
+  pub fn to_fm2(&self) -> String {
+    let mut text = format!("version {}\n", FM2_VERSION);
+    for (key, value) in &self.header {
+      text.push_str(&format!("{} {}\n", key, value));
+    }
+    text.push_str(&format!("rerecordCount {}\n", self.rerecord_count));
+    text.push_str(&format!("fourscore {}\n", self.four_score as u8));
+    for (port, device) in self.ports.iter().enumerate() {
+      let number = match device {
+        PortDevice::None => FM2_PORT_NONE,
+        PortDevice::Gamepad => FM2_PORT_GAMEPAD,
+        PortDevice::Zapper => FM2_PORT_ZAPPER
+      };
+      text.push_str(&format!("port{} {}\n", port, number));
+    }
+    text.push_str("port2 0\n");
+    if let Some(state) = &self.savestate {
+      text.push_str(&format!("savestate {}{}\n", FM2_BASE64_PREFIX, base64::encode(state)));
+    }
+
+    for frame in &self.frames {
+      text.push_str(&format!("|{}|", frame.commands));
+      if self.four_score {
+        for buttons in &frame.buttons {
+          text.push_str(&format_buttons(*buttons));
+          text.push('|');
+        }
+      } else {
+        for (port, device) in self.ports.iter().enumerate() {
+          match device {
+            PortDevice::None => {}
+            PortDevice::Gamepad => text.push_str(&format_buttons(frame.buttons[port])),
+            PortDevice::Zapper => {
+              let zapper = &frame.zappers[port];
+              let buttons = if zapper.trigger { FM2_ZAPPER_TRIGGER } else { 0 };
+              text.push_str(&format!("{:3} {:3} {} 0 0", zapper.x, zapper.y, buttons));
+            }
+          }
+          text.push('|');
+        }
+      }
+      text.push_str("|\n");
+    }
+    text
+  }
+
+  pub fn save(&self, path: &str) -> Result<(), String> {
+    std::fs::write(path, self.to_fm2()).map_err(|error| format!("UNABLE TO WRITE MOVIE {}: {}", path, error))
+  }
+
+  // Replaces a header line, or adds it when the movie has none
+  pub fn set_header(&mut self, key: &str, value: &str) {
+    match self.header.iter_mut().find(|(existing, _)| existing == key) {
+      Some(line) => line.1 = value.to_string(),
+      None => self.header.push((key.to_string(), value.to_string()))
+    }
+  }
+
+  // False when the movie names a ROM checksum and the cartridge has another
+  pub fn matches_rom(&self, cartridge: &Cartridge) -> bool {
+    match self.header.iter().find(|(key, _)| key == "romChecksum") {
+      Some((_, checksum)) => *checksum == rom_checksum(cartridge),
+      None => true
+    }
+  }
+
+  pub fn input(&self, frame: u64) -> Option<&MovieFrame> {
+    self.frames.get(frame as usize)
+  }
+
+  // Plugs in the devices the movie was recorded with
+  pub fn plug_devices(&self, bus: &mut Bus) {
+    if self.four_score {
+      bus.plug_four_score();
+      return;
+    }
+    for (port, device) in self.ports.iter().enumerate() {
+      bus.ports[port] = match device {
+        PortDevice::Zapper => Box::new(Zapper::default()),
+        _ => Box::new(Joypad::default())
+      };
+    }
+  }
+
+  fn parse_input(&self, line: &str, number: usize) -> Result<MovieFrame, String> {
+    let fields: Vec<&str> = line.split('|').collect();
+    let field = |index: usize| fields.get(index).copied().ok_or_else(|| format!("BAD FM2 INPUT LINE {}", number + 1));
+
+    let mut frame = MovieFrame::default();
+    let commands = field(1)?.trim();
+    if !commands.is_empty() {
+      frame.commands = parse_number("commands", commands)?;
+    }
+
+    if self.four_score {
+      for player in 0..4 {
+        frame.buttons[player] = parse_buttons(field(player + 2)?)?;
+      }
+      return Ok(frame);
+    }
+
+    for (port, device) in self.ports.iter().enumerate() {
+      match device {
+        PortDevice::None => {}
+        PortDevice::Gamepad => frame.buttons[port] = parse_buttons(field(port + 2)?)?,
+        PortDevice::Zapper => frame.zappers[port] = parse_zapper(field(port + 2)?)?
+      }
+    }
+    Ok(frame)
+  }
+}
+
+impl Default for Movie {
+  fn default() -> Movie {
+    Movie::new()
+  }
+}
+
+fn parse_number<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, String> {
+  value.parse::<T>().map_err(|_| format!("BAD FM2 {} \"{}\"", key.to_uppercase(), value))
+}
+
+fn parse_binary(key: &str, value: &str) -> Result<Vec<u8>, String> {
+  if let Some(text) = value.strip_prefix(FM2_BASE64_PREFIX) {
+    return base64::decode(text);
+  }
+  let text = value.strip_prefix(FM2_HEX_PREFIX).ok_or_else(|| format!("BAD FM2 {} \"{}\"", key.to_uppercase(), value))?;
+  if text.len() % 2 != 0 {
+    return Err(format!("BAD FM2 {} \"{}\"", key.to_uppercase(), value));
+  }
+  (0..text.len()).step_by(2).map(|index| parse_hex_byte(key, &text[index..(index + 2)])).collect()
+}
+
+fn parse_hex_byte(key: &str, digits: &str) -> Result<u8, String> {
+  u8::from_str_radix(digits, 16).map_err(|_| format!("BAD FM2 {} \"{}\"", key.to_uppercase(), digits))
+}
+
+// FCEUX's numbering, 2.2.3 is 20203
+fn emu_version() -> String {
+  let part = |text: &str| text.parse::<u32>().unwrap_or(0);
+  let version = part(env!("CARGO_PKG_VERSION_MAJOR")) * 10000
+    + part(env!("CARGO_PKG_VERSION_MINOR")) * 100
+    + part(env!("CARGO_PKG_VERSION_PATCH"));
+  version.to_string()
+}
+
+pub fn rom_checksum(cartridge: &Cartridge) -> String {
+  format!("{}{}", FM2_BASE64_PREFIX, base64::encode(&cartridge.checksum()))
+}
+
+// Random id that tells movies apart, written like 452DE2C3-EF43-2FA9-77AC-0677FC51543B
+fn new_guid() -> String {
+  let random = || RandomState::new().build_hasher().finish();
+  let (high, low) = (random(), random());
+  format!(
+    "{:08X}-{:04X}-{:04X}-{:04X}-{:012X}",
+    high >> 32,
+    (high >> 16) & 0xFFFF,
+    high & 0xFFFF,
+    low >> 48,
+    low & 0xFFFF_FFFF_FFFF
+  )
+}
+
+fn parse_port(value: &str) -> Result<PortDevice, String> {
+  match parse_number::<u8>("port", value)? {
+    FM2_PORT_NONE => Ok(PortDevice::None),
+    FM2_PORT_GAMEPAD => Ok(PortDevice::Gamepad),
+    FM2_PORT_ZAPPER => Ok(PortDevice::Zapper),
+    _ => Err(format!("UNSUPPORTED FM2 PORT DEVICE {}", value))
+  }
+}
+
+fn parse_buttons(field: &str) -> Result<u8, String> {
+  if field.is_empty() {
+    return Ok(0);
+  }
+
+  if field.len() != FM2_BUTTONS.len() {
+    return Err(format!("BAD FM2 CONTROLLER FIELD \"{}\"", field));
+  }
+
+  let mut buttons = 0;
+  for (character, (_, bit)) in field.chars().zip(FM2_BUTTONS.iter()) {
+    if character != '.' && character != ' ' {
+      buttons |= bit;
+    }
+  }
+  Ok(buttons)
+}
+
+fn format_buttons(buttons: u8) -> String {
+  FM2_BUTTONS.iter().map(|(character, bit)| if buttons & bit != 0 { *character } else { '.' }).collect()
+}
+
+// X, Y and mouse buttons, then fields FCEUX keeps for itself
+fn parse_zapper(field: &str) -> Result<ZapperInput, String> {
+  let numbers: Vec<&str> = field.split_whitespace().collect();
+  if numbers.len() < 3 {
+    return Err(format!("BAD FM2 ZAPPER FIELD \"{}\"", field));
+  }
+  Ok(ZapperInput {
+    x: parse_number("zapper x", numbers[0])?,
+    y: parse_number("zapper y", numbers[1])?,
+    trigger: parse_number::<u8>("zapper buttons", numbers[2])? & FM2_ZAPPER_TRIGGER != 0
+  })
+}
@@ -0,0 +1,165 @@
+use std::any::Any;
+
+use crate::emu::input::{ExpansionDevice, InputDevice};
+use crate::emu::joypad::Joypad;
+use crate::emu::save_state::{StateReader, StateWriter};
+
+// Third byte of a report tells the ports apart, read 20 on $4016 and read 19 on $4017 are 1
+const FOUR_SCORE_SIGNATURES: [u8; 2] = [0x08, 0x04];
+// The Hori adapter swaps them
+const HORI_SIGNATURES: [u8; 2] = [0x04, 0x08];
+
+// Expansion port controllers answer on D1
+const EXPANSION_DATA_SHIFT: u8 = 1;
+
+// 24 bit serial report of a four player adapter, two controllers and a signature byte. Reads
+// past the end return 1 like an official controller.
+#[derive(Default)]
+struct Report {
+  shift: u32
+}
+
+impl Report {
+  fn latch(&mut self, first: u8, second: u8, signature: u8) {
+    self.shift = first as u32 | (second as u32) << 8 | (signature as u32) << 16;
+  }
+
+  // Two player mode passes the first controller straight through
+  fn latch_single(&mut self, first: u8) {
+    self.shift = first as u32 | 0xFF_FF00;
+  }
+
+  fn next(&mut self) -> u8 {
+    let bit = (self.shift & 0x01) as u8;
+    self.shift = (self.shift >> 1) | 0x80_0000;
+    bit
+  }
+}
+
+// One half of an NES Four Score or NES Satellite, the one in port 0 carries players 1 and 3
+// and the one in port 1 players 2 and 4. With the switch in two player mode only players 1 and
+// 2 are read and there is no signature.
+pub struct FourScore {
+  pub controllers: [Joypad; 2],
+  pub four_player: bool,
+  port: usize,
+  strobe: bool,
+  report: Report
+}
+
+impl FourScore {
+  pub fn new(port: usize) -> FourScore {
+    FourScore { controllers: [Joypad::default(), Joypad::default()], four_player: true, port, strobe: false, report: Report::default() }
+  }
+
+  fn latch(&mut self) {
+    let (first, second) = (self.controllers[0].buttons, self.controllers[1].buttons);
+    if self.four_player {
+      self.report.latch(first, second, FOUR_SCORE_SIGNATURES[self.port]);
+    } else {
+      self.report.latch_single(first);
+    }
+  }
+}
+
+impl InputDevice for FourScore {
+  fn write(&mut self, value: u8) {
+    self.strobe = value & 0x01 != 0;
+    if self.strobe {
+      self.latch();
+    }
+  }
+
+  fn read(&mut self) -> u8 {
+    if self.strobe {
+      self.latch();
+    }
+    self.report.next()
+  }
+
+  fn controller(&mut self, index: usize) -> Option<&mut Joypad> {
+    self.controllers.get_mut(index)
+  }
+
+  fn save_state(&self, state: &mut StateWriter) {
+    state.bool(self.strobe);
+    state.u32(self.report.shift);
+  }
+
+  fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+    self.strobe = state.bool()?;
+    self.report.shift = state.u32()?;
+    Ok(())
+  }
+
+  fn as_any_mut(&mut self) -> &mut dyn Any {
+    self
+  }
+}
+
+// Hori 4 Players Adapter on the Famicom expansion port, reporting on D1. In four player mode
+// it works like a Four Score with $4016 carrying players 1 and 3 and $4017 players 2 and 4. In
+// two player mode players 1 and 2 are plain expansion port controllers, which is also how most
+// Famicom games that take a third and fourth player read them.
+pub struct HoriAdapter {
+  pub controllers: [Joypad; 4],
+  pub four_player: bool,
+  strobe: bool,
+  reports: [Report; 2]
+}
+
+impl HoriAdapter {
+  pub fn new(four_player: bool) -> HoriAdapter {
+    HoriAdapter { controllers: Default::default(), four_player, strobe: false, reports: Default::default() }
+  }
+
+  fn latch(&mut self) {
+    for (port, report) in self.reports.iter_mut().enumerate() {
+      let first = self.controllers[port].buttons;
+      if self.four_player {
+        report.latch(first, self.controllers[port + 2].buttons, HORI_SIGNATURES[port]);
+      } else {
+        report.latch_single(first);
+      }
+    }
+  }
+}
+
+impl ExpansionDevice for HoriAdapter {
+  fn write(&mut self, value: u8) {
+    self.strobe = value & 0x01 != 0;
+    if self.strobe {
+      self.latch();
+    }
+  }
+
+  fn read(&mut self, port: usize) -> u8 {
+    if self.strobe {
+      self.latch();
+    }
+    self.reports[port].next() << EXPANSION_DATA_SHIFT
+  }
+
+  fn controller(&mut self, index: usize) -> Option<&mut Joypad> {
+    self.controllers.get_mut(index)
+  }
+
+  fn save_state(&self, state: &mut StateWriter) {
+    state.bool(self.strobe);
+    for report in &self.reports {
+      state.u32(report.shift);
+    }
+  }
+
+  fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+    self.strobe = state.bool()?;
+    for report in &mut self.reports {
+      report.shift = state.u32()?;
+    }
+    Ok(())
+  }
+
+  fn as_any_mut(&mut self) -> &mut dyn Any {
+    self
+  }
+}
@@ -1,13 +1,388 @@
-use crate::emu::cpu::CPU;
+use crate::audio::blip::BlipBuffer;
+use crate::audio::output::{AudioOutput, AudioSink, DEFAULT_LATENCY};
+use crate::emu::bus::Bus;
+use crate::emu::cartridge::Cartridge;
+use crate::emu::cpu::{CPU, NTSC_CPU_CLOCK};
+use crate::emu::movie::{Movie, MovieFrame, COMMAND_POWER, COMMAND_RESET};
+use crate::emu::recorder::{Recorder, SAMPLE_RATE};
+use crate::emu::save_state::{StateReader, StateWriter, STATE_TAG, STATE_VERSION};
+use crate::graphics::frame::{Frame, WIDTH};
+use crate::graphics::index_frame::IndexFrame;
+use crate::graphics::palette::Palette;
+
+// Conditions that end a headless run early
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ExitCondition {
+  // Program counter about to execute this address
+  PcReached(u16),
+  // CPU address space byte holds this value
+  MemoryValue(u16, u8),
+  // Completed frame hashes to this value
+  FrameHash(u64),
+  // CPU stopped on an opcode it does not run, at this address. Always on, never passed in.
+  UnknownOpcode(u16, u8)
+}
+
+pub struct RunResult {
+  pub frames: u64,
+  pub condition: Option<ExitCondition>
+}
 
 pub struct NES {
-  pub cpu: CPU
+  pub cpu: CPU,
+  pub bus: Bus,
+  pub frame: Frame,
+  // Same picture as `frame` before the palette lookup
+  pub index_frame: IndexFrame,
+  pub palette: Palette,
+  // Samples produced during the current frame, handed to the recorder when it ends
+  pub audio_samples: Vec<i16>,
+  pub recorder: Option<Recorder>,
+  // Resamples to SAMPLE_RATE for `audio_samples`
+  pub audio: BlipBuffer,
+  // Live output with its own rate controlled resampler
+  pub audio_output: Option<AudioOutput>,
+  // Movie input is appended to at the start of every frame
+  pub movie_recording: Option<Movie>,
+  // Movie commands to run at the start of the next frame
+  commands: u8,
+  audio_level: f32,
+  // CPU cycle count when the current frame started
+  frame_start_cycle: u32,
+  // PPU scanline the controller ports were last told about
+  scanline: u16,
+  // PPU frame and number of its pixels copied into `frame` so far
+  drawn_frame: u64,
+  drawn_pixels: usize
 }
 
 impl NES {
-  pub fn new() -> NES {
+  pub fn new(cartridge: Cartridge) -> NES {
     NES {
-      cpu: CPU::new(None)
+      cpu: CPU::new(None),
+      bus: Bus::new(cartridge),
+      frame: Frame::default(),
+      index_frame: IndexFrame::default(),
+      palette: Palette::default(),
+      audio_samples: Vec::new(),
+      recorder: None,
+      audio: BlipBuffer::new(NTSC_CPU_CLOCK, SAMPLE_RATE as f64),
+      audio_output: None,
+      movie_recording: None,
+      commands: 0,
+      audio_level: 0.0,
+      frame_start_cycle: 0,
+      scanline: 0,
+      drawn_frame: 0,
+      drawn_pixels: 0
+    }
+  }
+
+  pub fn reset(&mut self) {
+    self.bus.apu.reset();
+    self.cpu.reset(&mut self.bus);
+    self.frame_start_cycle = self.cpu.cycles;
+    self.audio_level = self.bus.audio_output();
+    self.audio.reset(self.audio_level);
+  }
+
+  // Power cycles the console. Cartridge RAM and whatever is plugged into the ports stay as
+  // they are.
+  pub fn power(&mut self) {
+    let variant = self.cpu.variant;
+    self.cpu = CPU::new(self.cpu.tracer.take());
+    self.cpu.variant = variant;
+    self.bus.power();
+    self.scanline = 0;
+    self.reset();
+  }
+
+  pub fn frame_count(&self) -> u64 {
+    self.bus.ppu.frame
+  }
+
+  // RGB for a palette index under the current PPUMASK grayscale and emphasis bits
+  pub fn color(&self, index: u8) -> (u8, u8, u8) {
+    self.palette.rgb(index, self.bus.ppu.grayscale(), self.bus.ppu.emphasis())
+  }
+
+  // Writes a pixel to both the index and RGB frames
+  pub fn put_pixel(&mut self, x: usize, y: usize, index: u8) {
+    let grayscale = self.bus.ppu.grayscale();
+    let emphasis = self.bus.ppu.emphasis();
+    self.index_frame.set_pixel(x, y, index, grayscale, emphasis);
+    self.frame.set_pixel(x, y, self.palette.rgb(index, grayscale, emphasis));
+  }
+
+  pub fn step_instruction(&mut self) {
+    self.try_step_instruction().unwrap_or_else(|error| panic!("{}", error));
+  }
+
+  // Like `step_instruction` but an unknown opcode is an error, with the CPU left on it
+  pub fn try_step_instruction(&mut self) -> Result<(), String> {
+    self.cpu.try_step_instruction(&mut self.bus)?;
+    self.draw_pixels();
+
+    let scanline = self.bus.ppu.scanline;
+    if scanline != self.scanline {
+      self.scanline = scanline;
+      for port in &mut self.bus.ports {
+        port.scanline(&self.frame, scanline);
+      }
+      if let Some(device) = &mut self.bus.expansion_port {
+        device.scanline(&self.frame, scanline);
+      }
+    }
+
+    let level = self.bus.audio_output();
+    if level != self.audio_level {
+      let clock = self.cpu.cycles.wrapping_sub(self.frame_start_cycle);
+      self.audio.add_delta(clock, level - self.audio_level);
+      if let Some(output) = &mut self.audio_output {
+        output.add_delta(clock, level - self.audio_level);
+      }
+      self.audio_level = level;
+    }
+    Ok(())
+  }
+
+  // Copies the dots the PPU has drawn since the last call into the frames
+  fn draw_pixels(&mut self) {
+    if self.bus.ppu.frame != self.drawn_frame {
+      self.drawn_frame = self.bus.ppu.frame;
+      self.drawn_pixels = 0;
+    }
+
+    let drawn = self.bus.ppu.pixels_drawn();
+    for position in self.drawn_pixels..drawn {
+      let index = self.bus.ppu.pixels[position];
+      self.put_pixel(position % WIDTH, position / WIDTH, index);
+    }
+    self.drawn_pixels = self.drawn_pixels.max(drawn);
+  }
+
+  pub fn run_frame(&mut self) {
+    self.begin_frame(None, 0);
+    let frame = self.frame_count();
+    while self.frame_count() == frame {
+      self.step_instruction();
+    }
+    self.end_frame();
+  }
+
+  // Snapshot of the console to carry on from later, taken between frames like `run` leaves it.
+  // Expansion sound chips are not covered, so cartridges with one cannot be saved.
+  pub fn save_state(&self) -> Result<Vec<u8>, String> {
+    if !self.bus.expansion_audio.is_empty() {
+      return Err("SAVE STATES DO NOT COVER EXPANSION SOUND CHIPS".to_string());
     }
+
+    let mut state = StateWriter::new();
+    state.bytes(&STATE_TAG);
+    state.u8(STATE_VERSION);
+    state.bytes(&self.bus.cartridge.checksum());
+    self.cpu.save_state(&mut state);
+    self.bus.save_state(&mut state);
+    state.f32(self.audio_level);
+    state.u32(self.frame_start_cycle);
+    state.u16(self.scanline);
+    state.u64(self.drawn_frame);
+    state.u64(self.drawn_pixels as u64);
+    Ok(state.bytes)
   }
-}
\ No newline at end of file
+
+  // Picks up from a state `save_state` made with the same ROM. A state that turns out to be
+  // damaged part way through leaves the console half loaded, power cycle it after an error.
+  pub fn load_state(&mut self, bytes: &[u8]) -> Result<(), String> {
+    let mut state = StateReader::new(bytes);
+    if state.bytes() != Ok(&STATE_TAG[..]) {
+      return Err("NOT A SAVE STATE FROM THIS EMULATOR".to_string());
+    }
+    let version = state.u8()?;
+    if version != STATE_VERSION {
+      return Err(format!("UNSUPPORTED SAVE STATE VERSION {}", version));
+    }
+    if state.bytes()? != self.bus.cartridge.checksum() {
+      return Err("SAVE STATE IS FOR A DIFFERENT ROM".to_string());
+    }
+    if !self.bus.expansion_audio.is_empty() {
+      return Err("SAVE STATES DO NOT COVER EXPANSION SOUND CHIPS".to_string());
+    }
+
+    self.cpu.load_state(&mut state)?;
+    self.bus.load_state(&mut state)?;
+    self.audio_level = state.f32()?;
+    self.frame_start_cycle = state.u32()?;
+    self.scanline = state.u16()?;
+    self.drawn_frame = state.u64()?;
+    self.drawn_pixels = state.u64()? as usize;
+    if !state.is_finished() {
+      return Err("SAVE STATE HAS DATA LEFT OVER".to_string());
+    }
+
+    self.commands = 0;
+    self.audio.reset(self.audio_level);
+    Ok(())
+  }
+
+  // Power cycles and starts recording input into an FM2 movie for the devices plugged in
+  pub fn start_movie_recording(&mut self) -> Result<(), String> {
+    let movie = Movie::for_bus(&mut self.bus)?;
+    self.power();
+    self.commands = 0;
+    self.movie_recording = Some(movie);
+    Ok(())
+  }
+
+  // Starts recording an FM2 movie from the console as it is now, which the movie keeps as a
+  // save state to start from
+  pub fn start_movie_recording_from_state(&mut self) -> Result<(), String> {
+    let mut movie = Movie::for_bus(&mut self.bus)?;
+    movie.savestate = Some(self.save_state()?);
+    self.commands = 0;
+    self.movie_recording = Some(movie);
+    Ok(())
+  }
+
+  // Plugs in the devices a movie was recorded with and puts the console where it starts, at
+  // power on or at its save state. Then `run` plays it.
+  pub fn start_movie_playback(&mut self, movie: &Movie) -> Result<(), String> {
+    movie.plug_devices(&mut self.bus);
+    match &movie.savestate {
+      Some(state) => self.load_state(state),
+      None => {
+        self.power();
+        self.commands = 0;
+        Ok(())
+      }
+    }
+  }
+
+  pub fn stop_movie_recording(&mut self) -> Option<Movie> {
+    self.movie_recording.take()
+  }
+
+  // Resets or power cycles, with COMMAND_RESET or COMMAND_POWER, at the start of the next
+  // frame so a movie being recorded can log it
+  pub fn queue_command(&mut self, commands: u8) {
+    self.commands |= commands;
+  }
+
+  // Starts writing every completed frame, see `Recorder::start` for the formats
+  pub fn start_recording(&mut self, video_path: Option<&str>, audio_path: Option<&str>) -> Result<(), String> {
+    self.stop_recording()?;
+    self.recorder = Some(Recorder::start(video_path, audio_path, &self.palette)?);
+    Ok(())
+  }
+
+  pub fn stop_recording(&mut self) -> Result<(), String> {
+    match self.recorder.take() {
+      Some(recorder) => recorder.stop(),
+      None => Ok(())
+    }
+  }
+
+  // Starts `sink` pulling audio queued at the end of every frame, with rate control on
+  pub fn start_audio_output(&mut self, sink: Box<dyn AudioSink>) -> Result<(), String> {
+    self.stop_audio_output()?;
+    self.audio_output = Some(AudioOutput::new(sink, NTSC_CPU_CLOCK, DEFAULT_LATENCY)?);
+    Ok(())
+  }
+
+  pub fn stop_audio_output(&mut self) -> Result<(), String> {
+    match self.audio_output.take() {
+      Some(mut output) => output.finish(),
+      None => Ok(())
+    }
+  }
+
+  fn end_frame(&mut self) {
+    let clocks = self.cpu.cycles.wrapping_sub(self.frame_start_cycle);
+    self.frame_start_cycle = self.cpu.cycles;
+    self.audio.end_frame(clocks);
+    self.audio_samples = self.audio.read_samples(self.audio.samples_available());
+
+    if let Some(output) = &mut self.audio_output {
+      if let Err(error) = output.end_frame(clocks) {
+        eprintln!("{}, AUDIO OUTPUT STOPPED", error);
+        self.audio_output = None;
+      }
+    }
+
+    if let Some(recorder) = &mut self.recorder {
+      if let Err(error) = recorder.record_frame(&self.frame, &self.audio_samples) {
+        eprintln!("{}, RECORDING STOPPED", error);
+        self.recorder = None;
+      }
+    }
+    self.audio_samples.clear();
+  }
+
+  // Run up to `max_frames` frames, feeding movie input at the start of each frame, and stop
+  // as soon as one of the conditions holds or the CPU hits an opcode it does not run
+  pub fn run(&mut self, max_frames: u64, conditions: &[ExitCondition], movie: Option<&Movie>) -> RunResult {
+    let first_frame = self.frame_count();
+    let mut frame = first_frame;
+    self.begin_frame(movie, 0);
+
+    loop {
+      if let Some(condition) = self.instruction_condition(conditions) {
+        return RunResult { frames: frame - first_frame, condition: Some(condition) };
+      }
+
+      if self.try_step_instruction().is_err() {
+        let condition = ExitCondition::UnknownOpcode(self.cpu.pc, self.bus.peek(self.cpu.pc));
+        return RunResult { frames: frame - first_frame, condition: Some(condition) };
+      }
+
+      if self.frame_count() != frame {
+        self.end_frame();
+        frame = self.frame_count();
+        let frames = frame - first_frame;
+
+        if let Some(condition) = self.frame_condition(conditions) {
+          return RunResult { frames, condition: Some(condition) };
+        }
+
+        if frames >= max_frames {
+          return RunResult { frames, condition: None };
+        }
+
+        self.begin_frame(movie, frames);
+      }
+    }
+  }
+
+  // Runs queued and movie commands and sets the movie's input for a frame, then logs the
+  // frame to the movie being recorded
+  fn begin_frame(&mut self, movie: Option<&Movie>, frame: u64) {
+    let mut commands = std::mem::take(&mut self.commands);
+    if let Some(input) = movie.and_then(|movie| movie.input(frame)) {
+      commands |= input.commands;
+      input.apply(&mut self.bus);
+    }
+
+    if commands & COMMAND_POWER != 0 {
+      self.power();
+    } else if commands & COMMAND_RESET != 0 {
+      self.reset();
+    }
+
+    if let Some(recording) = &mut self.movie_recording {
+      recording.frames.push(MovieFrame::capture(&mut self.bus, commands));
+    }
+  }
+
+  fn instruction_condition(&self, conditions: &[ExitCondition]) -> Option<ExitCondition> {
+    conditions.iter().copied().find(|condition| match *condition {
+      ExitCondition::PcReached(addr) => self.cpu.pc == addr,
+      ExitCondition::MemoryValue(addr, value) => self.bus.peek(addr) == value,
+      ExitCondition::FrameHash(_) | ExitCondition::UnknownOpcode(..) => false
+    })
+  }
+
+  fn frame_condition(&self, conditions: &[ExitCondition]) -> Option<ExitCondition> {
+    let hash = self.frame.hash();
+    conditions.iter().copied().find(|condition| *condition == ExitCondition::FrameHash(hash))
+  }
+}
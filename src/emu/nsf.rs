@@ -0,0 +1,212 @@
+use std::fs;
+
+const NSF_TAG: [u8; 5] = [0x4E, 0x45, 0x53, 0x4D, 0x1A];
+const NSFE_TAG: [u8; 4] = [0x4E, 0x53, 0x46, 0x45];
+const NSF_HEADER_LENGTH: usize = 0x80;
+
+// Expansion sound chip bits
+pub const EXPANSION_VRC6: u8 = 0x01;
+pub const EXPANSION_VRC7: u8 = 0x02;
+pub const EXPANSION_FDS: u8 = 0x04;
+pub const EXPANSION_MMC5: u8 = 0x08;
+pub const EXPANSION_N163: u8 = 0x10;
+pub const EXPANSION_5B: u8 = 0x20;
+
+const PAL_BIT: u8 = 0x01;
+const DUAL_REGION_BIT: u8 = 0x02;
+
+// Play rates in microseconds the format is built around, used when a file gives none
+const DEFAULT_NTSC_SPEED: u16 = 16639;
+const DEFAULT_PAL_SPEED: u16 = 19997;
+
+// NSF or NSFe music rip, the tune's code and data plus how to call it
+pub struct Nsf {
+  pub title: String,
+  pub artist: String,
+  pub copyright: String,
+  pub total_songs: u8,
+  // Counted from 0
+  pub starting_song: u8,
+  pub load_addr: u16,
+  pub init_addr: u16,
+  pub play_addr: u16,
+  // Microseconds between PLAY calls
+  pub ntsc_speed: u16,
+  pub pal_speed: u16,
+  pub region: u8,
+  pub expansion: u8,
+  // Initial 4 KiB banks for $8000-$FFFF, all zero means the tune is not bankswitched
+  pub bankswitch: [u8; 8],
+  pub data: Vec<u8>,
+  // NSFe only, empty when the file has no labels or times
+  pub track_labels: Vec<String>,
+  pub track_times: Vec<Option<u32>>
+}
+
+impl Nsf {
+  pub fn new(bytes: &[u8]) -> Result<Nsf, String> {
+    if bytes.starts_with(&NSF_TAG) {
+      return Nsf::from_nsf(bytes);
+    } else if bytes.starts_with(&NSFE_TAG) {
+      return Nsf::from_nsfe(bytes);
+    }
+    return Err("FILE IS NOT AN NSF OR NSFE".to_string());
+  }
+
+  pub fn load(path: &str) -> Result<Nsf, String> {
+    let bytes = fs::read(path).map_err(|error| format!("UNABLE TO READ {}: {}", path, error))?;
+    Nsf::new(&bytes)
+  }
+
+  pub fn bankswitched(&self) -> bool {
+    self.bankswitch.iter().any(|bank| *bank != 0)
+  }
+
+  // Tunes marked dual region are played at NTSC rates
+  pub fn pal(&self) -> bool {
+    self.region & (PAL_BIT | DUAL_REGION_BIT) == PAL_BIT
+  }
+
+  fn from_nsf(bytes: &[u8]) -> Result<Nsf, String> {
+    if bytes.len() < NSF_HEADER_LENGTH {
+      return Err("NSF HEADER IS TRUNCATED".to_string());
+    }
+
+    let word = |offset: usize| u16::from_le_bytes([bytes[offset], bytes[offset + 1]]);
+    let text = |offset: usize| null_terminated(&bytes[offset..(offset + 32)]);
+
+    // NSF2 gives the program length so metadata can follow it
+    let mut data = &bytes[NSF_HEADER_LENGTH..];
+    let program_length = bytes[0x7D] as usize | (bytes[0x7E] as usize) << 8 | (bytes[0x7F] as usize) << 16;
+    if bytes[5] >= 2 && program_length != 0 && program_length < data.len() {
+      data = &data[..program_length];
+    }
+
+    let mut bankswitch = [0; 8];
+    bankswitch.copy_from_slice(&bytes[0x70..0x78]);
+
+    Ok(Nsf {
+      title: text(0x0E),
+      artist: text(0x2E),
+      copyright: text(0x4E),
+      total_songs: bytes[6],
+      starting_song: bytes[7].saturating_sub(1),
+      load_addr: word(0x08),
+      init_addr: word(0x0A),
+      play_addr: word(0x0C),
+      ntsc_speed: speed_or_default(word(0x6E), DEFAULT_NTSC_SPEED),
+      pal_speed: speed_or_default(word(0x78), DEFAULT_PAL_SPEED),
+      region: bytes[0x7A],
+      expansion: bytes[0x7B],
+      bankswitch,
+      data: data.to_vec(),
+      track_labels: Vec::new(),
+      track_times: Vec::new()
+    })
+  }
+
+  // Chunks of a 4 byte length, 4 byte ID and data. Unknown chunks may be skipped when their ID
+  // starts with a lower case letter.
+  fn from_nsfe(bytes: &[u8]) -> Result<Nsf, String> {
+    let mut nsf = Nsf {
+      title: String::new(),
+      artist: String::new(),
+      copyright: String::new(),
+      total_songs: 1,
+      starting_song: 0,
+      load_addr: 0,
+      init_addr: 0,
+      play_addr: 0,
+      ntsc_speed: DEFAULT_NTSC_SPEED,
+      pal_speed: DEFAULT_PAL_SPEED,
+      region: 0,
+      expansion: 0,
+      bankswitch: [0; 8],
+      data: Vec::new(),
+      track_labels: Vec::new(),
+      track_times: Vec::new()
+    };
+
+    let mut has_info = false;
+    let mut position = NSFE_TAG.len();
+    loop {
+      if position + 8 > bytes.len() {
+        return Err("NSFE ENDS WITHOUT AN NEND CHUNK".to_string());
+      }
+      let length = u32::from_le_bytes([bytes[position], bytes[position + 1], bytes[position + 2], bytes[position + 3]]) as usize;
+      let id = &bytes[(position + 4)..(position + 8)];
+      let start = position + 8;
+      if start + length > bytes.len() {
+        return Err("NSFE CHUNK IS TRUNCATED".to_string());
+      }
+      let chunk = &bytes[start..(start + length)];
+      position = start + length;
+
+      match id {
+        b"INFO" => {
+          if chunk.len() < 10 {
+            return Err("NSFE INFO CHUNK IS TOO SHORT".to_string());
+          }
+          let word = |offset: usize| u16::from_le_bytes([chunk[offset], chunk[offset + 1]]);
+          nsf.load_addr = word(0);
+          nsf.init_addr = word(2);
+          nsf.play_addr = word(4);
+          nsf.region = chunk[6];
+          nsf.expansion = chunk[7];
+          nsf.total_songs = chunk.get(8).copied().unwrap_or(1);
+          nsf.starting_song = chunk.get(9).copied().unwrap_or(0);
+          has_info = true;
+        }
+        b"DATA" => nsf.data = chunk.to_vec(),
+        b"BANK" => {
+          for (bank, value) in nsf.bankswitch.iter_mut().zip(chunk) {
+            *bank = *value;
+          }
+        }
+        b"RATE" => {
+          if chunk.len() >= 2 {
+            nsf.ntsc_speed = speed_or_default(u16::from_le_bytes([chunk[0], chunk[1]]), DEFAULT_NTSC_SPEED);
+          }
+          if chunk.len() >= 4 {
+            nsf.pal_speed = speed_or_default(u16::from_le_bytes([chunk[2], chunk[3]]), DEFAULT_PAL_SPEED);
+          }
+        }
+        b"auth" => {
+          let mut strings = chunk.split(|byte| *byte == 0).map(null_terminated);
+          nsf.title = strings.next().unwrap_or_default();
+          nsf.artist = strings.next().unwrap_or_default();
+          nsf.copyright = strings.next().unwrap_or_default();
+        }
+        b"tlbl" => {
+          nsf.track_labels = chunk.split(|byte| *byte == 0).map(null_terminated).take(nsf.total_songs as usize).collect();
+        }
+        b"time" => {
+          // Milliseconds, negative for unknown
+          nsf.track_times = chunk.chunks_exact(4).map(|time| {
+            let time = i32::from_le_bytes([time[0], time[1], time[2], time[3]]);
+            if time < 0 { None } else { Some(time as u32) }
+          }).collect();
+        }
+        b"NEND" => break,
+        _ if id[0].is_ascii_uppercase() => {
+          return Err(format!("UNSUPPORTED NSFE CHUNK {}", String::from_utf8_lossy(id)));
+        }
+        _ => {}
+      }
+    }
+
+    if !has_info || nsf.data.is_empty() {
+      return Err("NSFE IS MISSING ITS INFO OR DATA CHUNK".to_string());
+    }
+    Ok(nsf)
+  }
+}
+
+fn null_terminated(bytes: &[u8]) -> String {
+  let end = bytes.iter().position(|byte| *byte == 0).unwrap_or(bytes.len());
+  String::from_utf8_lossy(&bytes[..end]).to_string()
+}
+
+fn speed_or_default(speed: u16, default: u16) -> u16 {
+  if speed == 0 { default } else { speed }
+}
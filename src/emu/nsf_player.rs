@@ -0,0 +1,372 @@
+use crate::audio::apu::{self, Apu};
+use crate::audio::blip::BlipBuffer;
+use crate::audio::expansion::ExpansionAudio;
+use crate::audio::fds::Fds;
+use crate::audio::mixer::Mixer;
+use crate::audio::mmc5::Mmc5;
+use crate::audio::namco163::Namco163;
+use crate::audio::sunsoft5b::Sunsoft5b;
+use crate::audio::vrc6::Vrc6;
+use crate::audio::vrc7::Vrc7;
+use crate::emu::cpu::{CPU, NTSC_CPU_CLOCK, PAL_CPU_CLOCK};
+use crate::emu::cpu_bus::CpuBus;
+use crate::emu::nsf::{self, Nsf};
+use crate::emu::recorder::SAMPLE_RATE;
+
+// INIT and PLAY return into `JMP IDLE_LOOP`, the player waits there for the next PLAY call
+const IDLE_LOOP: u16 = 0x3FF0;
+const IDLE_LOOP_CODE: [u8; 3] = [0x4C, (IDLE_LOOP & 0xFF) as u8, (IDLE_LOOP >> 8) as u8];
+
+// Longest INIT may run before the tune is given up on
+const INIT_CYCLE_LIMIT: u64 = 10_000_000;
+
+const RAM_SIZE: usize = 0x800;
+const BANK_SIZE: usize = 0x1000;
+// $6000-$FFFF as 10 windows of 4 KiB
+const MEMORY_BEGIN: u16 = 0x6000;
+const MEMORY_SIZE: usize = 0xA000;
+// FDS tunes may write over their own code up to $DFFF
+const FDS_RAM_END: u16 = 0xDFFF;
+
+const FDS_BANK_BEGIN: u16 = 0x5FF6;
+const BANK_BEGIN: u16 = 0x5FF8;
+const BANK_END: u16 = 0x5FFF;
+
+const MMC5_MULTIPLICAND: u16 = 0x5205;
+const MMC5_MULTIPLIER: u16 = 0x5206;
+const MMC5_EXRAM_BEGIN: u16 = 0x5C00;
+const MMC5_EXRAM_END: u16 = 0x5FF5;
+
+const APU_REGISTER_BEGIN: u16 = 0x4000;
+const APU_REGISTER_END: u16 = 0x4013;
+// CPU cycles a DMC sample fetch steals
+const DMC_FETCH_CYCLES: u16 = 4;
+
+// Memory map an NSF driver provides, with the tune's banks and the expansion chips it asks for
+pub struct NsfBus {
+  pub ram: Vec<u8>,
+  // $6000-$FFFF, banks are copied in so FDS tunes can write over them
+  memory: Vec<u8>,
+  // Tune data padded so that bank 0 starts on a 4 KiB boundary
+  banks: Vec<u8>,
+  bankswitched: bool,
+  fds: bool,
+  mmc5: bool,
+  exram: Vec<u8>,
+  multiplier: (u8, u8),
+  pub apu: Apu,
+  // CPU cycles DMC fetches still have to take
+  dma_cycles: u16,
+  pub expansion_audio: Vec<Box<dyn ExpansionAudio>>,
+  pub mixer: Mixer
+}
+
+impl NsfBus {
+  pub fn new(nsf: &Nsf) -> Result<NsfBus, String> {
+    let fds = nsf.expansion & nsf::EXPANSION_FDS != 0;
+    let lowest = if fds { MEMORY_BEGIN } else { 0x8000 };
+    if nsf.load_addr < lowest {
+      return Err(format!("NSF LOAD ADDRESS {:04X} IS BELOW {:04X}", nsf.load_addr, lowest));
+    }
+
+    let mut banks = vec![0; nsf.load_addr as usize & (BANK_SIZE - 1)];
+    banks.extend_from_slice(&nsf.data);
+    let expansion_audio = expansion_chips(nsf.expansion);
+    let mixer = Mixer::new(&expansion_audio);
+
+    let mut bus = NsfBus {
+      ram: vec![0; RAM_SIZE],
+      memory: vec![0; MEMORY_SIZE],
+      banks,
+      bankswitched: nsf.bankswitched(),
+      fds,
+      mmc5: nsf.expansion & nsf::EXPANSION_MMC5 != 0,
+      exram: vec![0; (MMC5_EXRAM_END - MMC5_EXRAM_BEGIN + 1) as usize],
+      multiplier: (0, 0),
+      apu: Apu::new(),
+      dma_cycles: 0,
+      expansion_audio,
+      mixer
+    };
+
+    if bus.bankswitched {
+      for (window, bank) in nsf.bankswitch.iter().enumerate() {
+        bus.load_bank(window + 2, *bank);
+      }
+      // FDS tunes start $6000 and $7000 with the banks given for $E000 and $F000
+      if fds {
+        bus.load_bank(0, nsf.bankswitch[6]);
+        bus.load_bank(1, nsf.bankswitch[7]);
+      }
+    } else {
+      let start = (nsf.load_addr - MEMORY_BEGIN) as usize;
+      let length = nsf.data.len().min(MEMORY_SIZE - start);
+      bus.memory[start..(start + length)].copy_from_slice(&nsf.data[..length]);
+    }
+    Ok(bus)
+  }
+
+  // Copies a 4 KiB bank into one of the windows at $6000-$FFFF, past the data reads as zero
+  fn load_bank(&mut self, window: usize, bank: u8) {
+    let start = bank as usize * BANK_SIZE;
+    let target = &mut self.memory[(window * BANK_SIZE)..((window + 1) * BANK_SIZE)];
+    for (index, byte) in target.iter_mut().enumerate() {
+      *byte = self.banks.get(start + index).copied().unwrap_or(0);
+    }
+  }
+
+  // The APU and the expansion chips through the mixer
+  pub fn output(&self) -> f32 {
+    self.mixer.mix(&self.apu, &self.expansion_audio)
+  }
+}
+
+fn expansion_chips(flags: u8) -> Vec<Box<dyn ExpansionAudio>> {
+  let mut chips: Vec<Box<dyn ExpansionAudio>> = Vec::new();
+  if flags & nsf::EXPANSION_VRC6 != 0 {
+    chips.push(Box::new(Vrc6::new(false)));
+  }
+  if flags & nsf::EXPANSION_VRC7 != 0 {
+    chips.push(Box::new(Vrc7::default()));
+  }
+  if flags & nsf::EXPANSION_FDS != 0 {
+    chips.push(Box::new(Fds::default()));
+  }
+  if flags & nsf::EXPANSION_MMC5 != 0 {
+    chips.push(Box::new(Mmc5::default()));
+  }
+  if flags & nsf::EXPANSION_N163 != 0 {
+    chips.push(Box::new(Namco163::default()));
+  }
+  if flags & nsf::EXPANSION_5B != 0 {
+    chips.push(Box::new(Sunsoft5b::default()));
+  }
+  chips
+}
+
+impl CpuBus for NsfBus {
+  fn read(&mut self, addr: u16) -> u8 {
+    if addr == apu::STATUS {
+      return self.apu.read_status();
+    }
+    for chip in &mut self.expansion_audio {
+      if let Some(value) = chip.read(addr) {
+        return value;
+      }
+    }
+    self.peek(addr)
+  }
+
+  fn write(&mut self, addr: u16, value: u8) {
+    for chip in &mut self.expansion_audio {
+      chip.write(addr, value);
+    }
+
+    match addr {
+      0x0000 ..= 0x1FFF => self.ram[addr as usize & (RAM_SIZE - 1)] = value,
+      MMC5_MULTIPLICAND if self.mmc5 => self.multiplier.0 = value,
+      MMC5_MULTIPLIER if self.mmc5 => self.multiplier.1 = value,
+      MMC5_EXRAM_BEGIN ..= MMC5_EXRAM_END if self.mmc5 => self.exram[(addr - MMC5_EXRAM_BEGIN) as usize] = value,
+      FDS_BANK_BEGIN ..= BANK_END if self.fds => {
+        self.load_bank((addr - FDS_BANK_BEGIN) as usize, value);
+      }
+      BANK_BEGIN ..= BANK_END if self.bankswitched => {
+        self.load_bank((addr - BANK_BEGIN) as usize + 2, value);
+      }
+      0x6000 ..= 0x7FFF => self.memory[(addr - MEMORY_BEGIN) as usize] = value,
+      0x8000 ..= FDS_RAM_END if self.fds => self.memory[(addr - MEMORY_BEGIN) as usize] = value,
+      APU_REGISTER_BEGIN ..= APU_REGISTER_END | apu::STATUS | apu::FRAME_COUNTER => self.apu.write(addr, value),
+      // ROM
+      _ => {}
+    }
+  }
+
+  fn peek(&self, addr: u16) -> u8 {
+    match addr {
+      0x0000 ..= 0x1FFF => self.ram[addr as usize & (RAM_SIZE - 1)],
+      IDLE_LOOP ..= 0x3FF2 => IDLE_LOOP_CODE[(addr - IDLE_LOOP) as usize],
+      MMC5_MULTIPLICAND if self.mmc5 => (self.multiplier.0 as u16 * self.multiplier.1 as u16) as u8,
+      MMC5_MULTIPLIER if self.mmc5 => ((self.multiplier.0 as u16 * self.multiplier.1 as u16) >> 8) as u8,
+      MMC5_EXRAM_BEGIN ..= MMC5_EXRAM_END if self.mmc5 => self.exram[(addr - MMC5_EXRAM_BEGIN) as usize],
+      0x6000 ..= 0xFFFF => self.memory[(addr - MEMORY_BEGIN) as usize],
+      _ => 0
+    }
+  }
+
+  fn tick(&mut self, cycles: u8) {
+    for _cycle in 0..cycles {
+      self.apu.clock();
+      if let Some(addr) = self.apu.dmc_fetch_address() {
+        let value = self.peek(addr);
+        self.apu.dmc_fetch(value);
+        self.dma_cycles += DMC_FETCH_CYCLES;
+      }
+    }
+
+    for chip in &mut self.expansion_audio {
+      for _cycle in 0..cycles {
+        chip.clock();
+      }
+    }
+  }
+
+  fn irq_pending(&self) -> bool {
+    self.apu.irq()
+  }
+
+  fn take_dma_cycles(&mut self) -> u16 {
+    std::mem::take(&mut self.dma_cycles)
+  }
+}
+
+// Plays one track of an NSF the way a hardware player cartridge would, calling INIT once and
+// PLAY at the tune's rate, and resamples the result for WAV output
+pub struct NsfPlayer {
+  pub nsf: Nsf,
+  pub cpu: CPU,
+  pub bus: NsfBus,
+  cpu_clock: f64,
+  cycles: u64,
+  // Cycle count at which the next PLAY call is due
+  next_play: f64,
+  blip: BlipBuffer,
+  // Output level last handed to `blip`
+  level: f32,
+  // One resampler and last level per mixer channel for `render_stems`
+  stems: Vec<BlipBuffer>,
+  stem_levels: Vec<f32>
+}
+
+impl NsfPlayer {
+  pub fn new(nsf: Nsf) -> Result<NsfPlayer, String> {
+    let bus = NsfBus::new(&nsf)?;
+    let cpu_clock = if nsf.pal() { PAL_CPU_CLOCK } else { NTSC_CPU_CLOCK };
+    let blip = BlipBuffer::new(cpu_clock, SAMPLE_RATE as f64);
+    Ok(NsfPlayer { nsf, cpu: CPU::new(None), bus, cpu_clock, cycles: 0, next_play: 0.0, blip, level: 0.0, stems: Vec::new(), stem_levels: Vec::new() })
+  }
+
+  // Resets the machine and runs INIT for a track counted from 0
+  pub fn start_track(&mut self, track: u8) -> Result<(), String> {
+    if track >= self.nsf.total_songs {
+      return Err(format!("TRACK {} IS OUT OF RANGE, THE FILE HAS {}", track + 1, self.nsf.total_songs));
+    }
+
+    // Mixer settings carry over from track to track
+    let channels = self.bus.mixer.channels.clone();
+    self.bus = NsfBus::new(&self.nsf)?;
+    self.bus.mixer.channels = channels;
+    self.cpu = CPU::new(None);
+    self.cpu.sp = 0xFD;
+    self.cpu.f_i = true;
+    self.cpu.f_u = true;
+    self.cycles = 0;
+
+    // Silence the APU the way the NSF spec asks before INIT
+    for addr in 0x4000..=0x4013 {
+      self.bus.write(addr, 0x00);
+    }
+    self.bus.write(0x4015, 0x00);
+    self.bus.write(0x4015, 0x0F);
+    self.bus.write(0x4017, 0x40);
+
+    self.cpu.r_a = track;
+    self.cpu.r_x = self.nsf.pal() as u8;
+    self.call(self.nsf.init_addr);
+    while !self.idle() {
+      if self.cycles >= INIT_CYCLE_LIMIT {
+        return Err("NSF INIT ROUTINE DID NOT RETURN".to_string());
+      }
+      self.cpu.try_step(&mut self.bus)?;
+      self.cycles += 1;
+    }
+
+    // Playback starts at whatever level INIT left the chips at
+    self.next_play = self.cycles as f64;
+    self.level = self.bus.output();
+    self.blip.reset(self.level);
+    self.bus.mixer.levels(&self.bus.apu, &self.bus.expansion_audio, &mut self.stem_levels);
+    self.stems = self.stem_levels.iter().map(|level| {
+      let mut blip = BlipBuffer::new(self.cpu_clock, SAMPLE_RATE as f64);
+      blip.reset(*level);
+      blip
+    }).collect();
+    Ok(())
+  }
+
+  // Runs the tune until `count` more mono samples at SAMPLE_RATE are made, stopping with an error
+  // if it runs into an opcode the CPU does not know
+  pub fn render(&mut self, count: usize) -> Result<Vec<i16>, String> {
+    let mut samples = Vec::with_capacity(count);
+    while samples.len() < count {
+      let clocks = self.blip.clocks_needed(count - samples.len());
+      for clock in 0..clocks {
+        self.step()?;
+        let level = self.bus.output();
+        if level != self.level {
+          self.blip.add_delta(clock, level - self.level);
+          self.level = level;
+        }
+      }
+      self.blip.end_frame(clocks);
+      samples.extend(self.blip.read_samples(count - samples.len()));
+    }
+    Ok(samples)
+  }
+
+  // Like `render` but with every mixer channel on its own at its gain, one list of samples per
+  // channel in mixer order. Use either this or `render` for a track, not both.
+  pub fn render_stems(&mut self, count: usize) -> Result<Vec<Vec<i16>>, String> {
+    // Stems are set up by `start_track`, one per mixer channel
+    if self.stems.is_empty() {
+      return Err("NO STEMS TO RENDER, START A TRACK FIRST".to_string());
+    }
+
+    let mut stems = vec![Vec::with_capacity(count); self.stems.len()];
+    let mut levels = Vec::with_capacity(self.stems.len());
+    while stems[0].len() < count {
+      let clocks = self.stems[0].clocks_needed(count - stems[0].len());
+      for clock in 0..clocks {
+        self.step()?;
+        self.bus.mixer.levels(&self.bus.apu, &self.bus.expansion_audio, &mut levels);
+        for (index, level) in levels.iter().enumerate() {
+          if *level != self.stem_levels[index] {
+            self.stems[index].add_delta(clock, level - self.stem_levels[index]);
+            self.stem_levels[index] = *level;
+          }
+        }
+      }
+      for (blip, samples) in self.stems.iter_mut().zip(&mut stems) {
+        blip.end_frame(clocks);
+        samples.extend(blip.read_samples(count - samples.len()));
+      }
+    }
+    Ok(stems)
+  }
+
+  // Runs one CPU cycle, calling PLAY first if it is due
+  fn step(&mut self) -> Result<(), String> {
+    // A PLAY that overruns its slot delays the next call rather than being interrupted
+    if self.idle() && self.cycles as f64 >= self.next_play {
+      let speed = if self.nsf.pal() { self.nsf.pal_speed } else { self.nsf.ntsc_speed };
+      self.next_play += speed as f64 * self.cpu_clock / 1_000_000.0;
+      self.call(self.nsf.play_addr);
+    }
+    self.cpu.try_step(&mut self.bus)?;
+    self.cycles += 1;
+    Ok(())
+  }
+
+  fn idle(&self) -> bool {
+    self.cpu.pc == IDLE_LOOP && self.cpu.skip_cycles == 0
+  }
+
+  // Enters a routine that returns to the idle loop
+  fn call(&mut self, addr: u16) {
+    let return_addr = IDLE_LOOP - 1;
+    self.bus.write(0x0100 + self.cpu.sp as u16, (return_addr >> 8) as u8);
+    self.cpu.sp = self.cpu.sp.wrapping_sub(1);
+    self.bus.write(0x0100 + self.cpu.sp as u16, return_addr as u8);
+    self.cpu.sp = self.cpu.sp.wrapping_sub(1);
+    self.cpu.pc = addr;
+  }
+}
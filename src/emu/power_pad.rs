@@ -0,0 +1,135 @@
+use std::any::Any;
+
+use crate::emu::input::{ExpansionDevice, InputDevice};
+use crate::emu::save_state::{StateReader, StateWriter};
+
+pub const BUTTON_COUNT: usize = 12;
+
+// The NES Power Pad shifts out two reports at once, on D3 and D4 of $4017. Buttons are counted
+// from 0 here, so these are buttons 2, 1, 5, 9, 6, 10, 11, 7 and 4, 3, 12, 8 on the mat.
+const LOW_REPORT: [usize; 8] = [1, 0, 4, 8, 5, 9, 10, 6];
+const HIGH_REPORT: [usize; 4] = [3, 2, 11, 7];
+const LOW_DATA_BIT: u8 = 0x08;
+const HIGH_DATA_BIT: u8 = 0x10;
+
+// The Family Trainer is scanned a row of 4 buttons at a time, a row is selected by clearing
+// its bit in a $4016 write: bit 2 for buttons 1-4, bit 1 for 5-8 and bit 0 for 9-12
+const ROW_COUNT: usize = 3;
+const ROW_LENGTH: usize = 4;
+// Buttons in selected rows read 0 on $4017 D1-D4 while stepped on, the row's first button on D4
+const ROW_BITS: u8 = 0x1E;
+
+// Bandai's exercise mat, sold as the Power Pad for the NES in port 2 and as the Family Trainer
+// on the Famicom expansion port. Buttons are numbered as on side B of the mat, side A has the
+// same sensors with fewer of them labelled.
+#[derive(Default)]
+pub struct PowerPad {
+  pub buttons: [bool; BUTTON_COUNT],
+  strobe: bool,
+  low: u8,
+  high: u8,
+  // Family Trainer rows left out of reads, from the last $4016 write
+  ignored_rows: u8
+}
+
+impl PowerPad {
+  fn latch(&mut self) {
+    self.low = report(&self.buttons, &LOW_REPORT);
+    self.high = report(&self.buttons, &HIGH_REPORT) | 0xF0;
+  }
+
+  // Same in either port
+  fn save_latches(&self, state: &mut StateWriter) {
+    state.bool(self.strobe);
+    state.u8(self.low);
+    state.u8(self.high);
+    state.u8(self.ignored_rows);
+  }
+
+  fn load_latches(&mut self, state: &mut StateReader) -> Result<(), String> {
+    self.strobe = state.bool()?;
+    self.low = state.u8()?;
+    self.high = state.u8()?;
+    self.ignored_rows = state.u8()?;
+    Ok(())
+  }
+}
+
+// Pressed buttons as 1s, first button in bit 0
+fn report(buttons: &[bool; BUTTON_COUNT], order: &[usize]) -> u8 {
+  order.iter().enumerate().fold(0, |report, (bit, button)| report | (buttons[*button] as u8) << bit)
+}
+
+impl InputDevice for PowerPad {
+  fn write(&mut self, value: u8) {
+    self.strobe = value & 0x01 != 0;
+    if self.strobe {
+      self.latch();
+    }
+  }
+
+  // Reads past the end of either report return 1
+  fn read(&mut self) -> u8 {
+    if self.strobe {
+      self.latch();
+    }
+    let mut value = 0;
+    if self.low & 0x01 != 0 {
+      value |= LOW_DATA_BIT;
+    }
+    if self.high & 0x01 != 0 {
+      value |= HIGH_DATA_BIT;
+    }
+    self.low = (self.low >> 1) | 0x80;
+    self.high = (self.high >> 1) | 0x80;
+    return value;
+  }
+
+  fn save_state(&self, state: &mut StateWriter) {
+    self.save_latches(state);
+  }
+
+  fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+    self.load_latches(state)
+  }
+
+  fn as_any_mut(&mut self) -> &mut dyn Any {
+    self
+  }
+}
+
+impl ExpansionDevice for PowerPad {
+  fn write(&mut self, value: u8) {
+    self.ignored_rows = value & 0x07;
+  }
+
+  fn read(&mut self, port: usize) -> u8 {
+    if port == 0 {
+      return 0;
+    }
+    let mut pressed = 0;
+    for row in 0..ROW_COUNT {
+      if self.ignored_rows >> (ROW_COUNT - 1 - row) & 0x01 != 0 {
+        continue;
+      }
+      for column in 0..ROW_LENGTH {
+        if self.buttons[row * ROW_LENGTH + column] {
+          pressed |= 0x10 >> column;
+        }
+      }
+    }
+    !pressed & ROW_BITS
+  }
+
+  fn save_state(&self, state: &mut StateWriter) {
+    self.save_latches(state);
+  }
+
+  fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+    self.load_latches(state)
+  }
+
+  fn as_any_mut(&mut self) -> &mut dyn Any {
+    self
+  }
+}
@@ -1,18 +1,89 @@
-use super::cartridge::Mirroring;
+use super::cartridge::{self, Mirroring};
+use super::save_state::{StateReader, StateWriter};
+use crate::graphics::frame::{WIDTH, HEIGHT};
 
+const NAMETABLE_SELECT_BITS : u8 = 0b11;
 const VRAM_ADD_INCREMENT_BIT : u8 = 0b100;
+const SPRITE_PATTERN_BIT : u8 = 0b1000;
+const BACKGROUND_PATTERN_BIT : u8 = 0b1_0000;
+const SPRITE_SIZE_BIT : u8 = 0b10_0000;
+const GENERATE_NMI_BIT : u8 = 0b1000_0000;
+
+const GRAYSCALE_BIT : u8 = 0b1;
+const BACKGROUND_LEFT_BIT : u8 = 0b10;
+const SPRITES_LEFT_BIT : u8 = 0b100;
+const SHOW_BACKGROUND_BIT : u8 = 0b1000;
+const SHOW_SPRITES_BIT : u8 = 0b1_0000;
+const EMPHASIS_SHIFT : u8 = 5;
+
+const SPRITE_OVERFLOW_STATUS_BIT : u8 = 0b10_0000;
+const SPRITE_ZERO_HIT_STATUS_BIT : u8 = 0b100_0000;
+const VBLANK_STATUS_BIT : u8 = 0b1000_0000;
+
+// Sprite attribute byte
+const SPRITE_PALETTE_BITS : u8 = 0b11;
+const SPRITE_BEHIND_BIT : u8 = 0b10_0000;
+const SPRITE_FLIP_X_BIT : u8 = 0b100_0000;
+const SPRITE_FLIP_Y_BIT : u8 = 0b1000_0000;
+const SPRITES_PER_LINE: usize = 8;
+
+// Fields of the VRAM address while rendering walks it, 0yyy NNYY YYYX XXXX
+const COARSE_X_BITS: u16 = 0x001F;
+const COARSE_Y_BITS: u16 = 0x03E0;
+const NAMETABLE_BITS: u16 = 0x0C00;
+const FINE_Y_BITS: u16 = 0x7000;
+const HORIZONTAL_BITS: u16 = COARSE_X_BITS | 0x0400;
+const VERTICAL_BITS: u16 = COARSE_Y_BITS | 0x0800 | FINE_Y_BITS;
+
+const NAMETABLE_SIZE: u16 = 0x400;
+const VRAM_SIZE: usize = 2048;
+
+const DOTS_PER_SCANLINE: u16 = 341;
+const SCANLINES_PER_FRAME: u16 = 262;
+const VBLANK_SCANLINE: u16 = 241;
+const PRE_RENDER_SCANLINE: u16 = 261;
+// Dots where the vertical scroll is reloaded on the pre-render line
+const VERTICAL_RELOAD_DOTS: std::ops::RangeInclusive<u16> = 280..=304;
+
+// Sprite found on the current scanline, with its row of the pattern already fetched
+#[derive(Clone, Copy, Default)]
+struct LineSprite {
+  x: u8,
+  attributes: u8,
+  low: u8,
+  high: u8,
+  zero: bool
+}
 
 pub struct PPU {
   pub chr_rom: Vec<u8>,
+  // Pattern tables can be written through PPUDATA
+  pub chr_ram: bool,
   pub palette_table: [u8; 32],
   pub vram: [u8; 2048],
+  // Second 2 KiB of nametables on four screen cartridges
+  pub cartridge_vram: Vec<u8>,
   pub oam_data: [u8; 256],
   pub mirroring: Mirroring,
+  // Palette RAM entries of the picture as it is drawn, read back by the console one dot at a time
+  pub pixels: Vec<u8>,
+
+  // Beam position, scanline 261 is the pre-render line
+  pub scanline: u16,
+  pub dot: u16,
+  pub frame: u64,
 
   byte_buffer: u8,
   
   mem_addr_reg: MemoryAddressRegister,
+  fine_x: u8,
+  oam_addr: u8,
+  line_sprites: [LineSprite; SPRITES_PER_LINE],
+  line_sprite_count: usize,
   control_reg: u8,
+  mask_reg: u8,
+  status_reg: u8,
+  nmi_pending: bool,
 
   cycles: usize
 }
@@ -21,89 +92,570 @@ impl PPU {
   pub fn new(chr_rom: Vec<u8>, mirroring: Mirroring) -> PPU {
     PPU {
       chr_rom,
+      chr_ram: false,
       mirroring,
       vram: [0; 2048],
+      cartridge_vram: if mirroring == Mirroring::FourScreen { vec![0; VRAM_SIZE] } else { Vec::new() },
       oam_data: [0; 256],
+      pixels: vec![0; WIDTH * HEIGHT],
       palette_table: [0; 32],
       mem_addr_reg: MemoryAddressRegister::default(),
+      fine_x: 0,
+      oam_addr: 0,
+      line_sprites: [LineSprite::default(); SPRITES_PER_LINE],
+      line_sprite_count: 0,
       control_reg: 0,
+      mask_reg: 0,
+      status_reg: 0,
+      nmi_pending: false,
+      scanline: 0,
+      dot: 0,
+      frame: 0,
       byte_buffer: 0,
       cycles: 0
     }
   }
 
+  // PPUDATA read, everything below the palette comes through the internal buffer one read late
   pub fn read(&mut self) -> u8 {
     let addr = self.mem_addr_reg.value;
     self.increment_addr();
 
+    let data = self.byte_buffer;
     match addr {
       0 ..= 0x1FFF => {
-        let data = self.byte_buffer;
-        self.byte_buffer = self.chr_rom[addr as usize];
+        self.byte_buffer = self.chr_byte(addr);
+        data
+      },
+      0x2000 ..= 0x3EFF => {
+        self.byte_buffer = self.read_nametable(self.mirror_addr(addr));
         data
       },
-      0x2000 ..= 0x2FFF => self.vram[self.mirror_addr(addr) as usize],
-      0x3F00 ..= 0x3FFF => self.palette_table[addr as usize & 0xFF],
+      0x3F00 ..= 0x3FFF => {
+        // Palette reads skip the buffer, which gets the nametable byte underneath instead
+        self.byte_buffer = self.read_nametable(self.mirror_addr(addr - 0x1000));
+        let entry = self.palette_table[palette_index(addr as u8)];
+        if self.grayscale() { entry & 0x30 } else { entry }
+      },
       _ => panic!("BAD MEMORY SPACE ACCESS")
     }
   }
 
   pub fn write(&mut self, addr: u16, data: u8) {
     match addr {
-      0x2016 => {
+      0x2000 => {
+        let was_enabled = self.control_reg & GENERATE_NMI_BIT != 0;
+        self.control_reg = data;
+        self.mem_addr_reg.set_nametable(data & NAMETABLE_SELECT_BITS);
+
+        // Enabling NMI during vblank fires one straight away
+        if !was_enabled && data & GENERATE_NMI_BIT != 0 && self.status_reg & VBLANK_STATUS_BIT != 0 {
+          self.nmi_pending = true;
+        }
+      }
+      0x2001 => {
+        self.mask_reg = data;
+      }
+      0x2003 => {
+        self.oam_addr = data;
+      }
+      0x2004 => {
+        self.oam_data[self.oam_addr as usize] = data;
+        self.oam_addr = self.oam_addr.wrapping_add(1);
+      }
+      0x2005 => {
+        // Shares the write latch with PPUADDR
+        if self.mem_addr_reg.toggle_latch() {
+          self.mem_addr_reg.set_scroll_y(data);
+        } else {
+          self.mem_addr_reg.set_scroll_x(data);
+          self.fine_x = data & 0b111;
+        }
+      }
+      0x2006 => {
         self.mem_addr_reg.write(data);
       }
+      0x2007 => {
+        self.write_data(data);
+      }
       _ => panic!("BAD MEMORY SPACE WRITE")
     }
   }
 
-  fn mirror_addr(&self, addr: u16) -> u16 {
-    let vram_index = addr & 0xEFF;
-    let name_table_index = vram_index / 0x400;
-
-    if name_table_index == 0 || (self.mirroring == Mirroring::Vertical && name_table_index == 1) {
-      return vram_index;
-    } else if self.mirroring == Mirroring::Horizontal {
-      return vram_index - (0x400 + (name_table_index - 1) * 0x400);
-    } else if self.mirroring == Mirroring::Vertical {
-      return vram_index - 0x800;
+  // PPUDATA write to the current VRAM address
+  fn write_data(&mut self, data: u8) {
+    let addr = self.mem_addr_reg.value;
+    self.increment_addr();
+
+    match addr {
+      0 ..= 0x1FFF => {
+        // CHR ROM ignores writes
+        if self.chr_ram {
+          let index = addr as usize % self.chr_rom.len();
+          self.chr_rom[index] = data;
+        }
+      }
+      0x2000 ..= 0x3EFF => {
+        self.write_nametable(self.mirror_addr(addr), data);
+      }
+      0x3F00 ..= 0x3FFF => {
+        self.palette_table[palette_index(addr as u8)] = data;
+      }
+      _ => panic!("BAD MEMORY SPACE WRITE")
+    }
+  }
+
+  // OAMDATA read, which leaves OAMADDR where it is
+  pub fn read_oam_data(&self) -> u8 {
+    self.oam_data[self.oam_addr as usize]
+  }
+
+  // PPUSTATUS, reading clears vblank and the address latch
+  pub fn read_status(&mut self) -> u8 {
+    let status = self.status_reg;
+    self.status_reg &= !VBLANK_STATUS_BIT;
+    self.mem_addr_reg.reset_latch();
+    status
+  }
+
+  // Top left of the visible window within the 512x480 nametable area, where the next frame
+  // starts drawing from
+  pub fn scroll(&self) -> (u16, u16) {
+    let addr = self.mem_addr_reg.temp;
+    let nametable = (addr & NAMETABLE_BITS) >> 10;
+    let x = (addr & COARSE_X_BITS) * 8 + self.fine_x as u16 + if nametable & 0b01 != 0 { 256 } else { 0 };
+    let y = ((addr & COARSE_Y_BITS) >> 5) * 8 + ((addr & FINE_Y_BITS) >> 12) + if nametable & 0b10 != 0 { 240 } else { 0 };
+    (x, y)
+  }
+
+  pub fn background_pattern_table(&self) -> u16 {
+    if self.control_reg & BACKGROUND_PATTERN_BIT != 0 { 0x1000 } else { 0 }
+  }
+
+  pub fn sprite_pattern_table(&self) -> u16 {
+    if self.control_reg & SPRITE_PATTERN_BIT != 0 { 0x1000 } else { 0 }
+  }
+
+  pub fn tall_sprites(&self) -> bool {
+    self.control_reg & SPRITE_SIZE_BIT != 0
+  }
+
+  // Byte of one of the four logical nametables, after mirroring
+  pub fn nametable_byte(&self, table: u16, offset: u16) -> u8 {
+    self.read_nametable(self.mirror_addr(0x2000 + table * NAMETABLE_SIZE + offset))
+  }
+
+  // Everything but the pattern tables, which only CHR RAM cartridges save
+  pub fn save_state(&self, state: &mut StateWriter) {
+    if self.chr_ram {
+      state.bytes(&self.chr_rom);
+    }
+    state.bytes(&self.palette_table);
+    state.bytes(&self.vram);
+    state.bytes(&self.cartridge_vram);
+    state.bytes(&self.oam_data);
+    state.u8(cartridge::mirroring_code(self.mirroring));
+    state.bytes(&self.pixels);
+    state.u16(self.scanline);
+    state.u16(self.dot);
+    state.u64(self.frame);
+    state.u8(self.byte_buffer);
+    state.u16(self.mem_addr_reg.value);
+    state.u16(self.mem_addr_reg.temp);
+    state.bool(self.mem_addr_reg.top_byte_set);
+    state.u8(self.fine_x);
+    state.u8(self.oam_addr);
+    for sprite in &self.line_sprites {
+      for value in &[sprite.x, sprite.attributes, sprite.low, sprite.high] {
+        state.u8(*value);
+      }
+      state.bool(sprite.zero);
+    }
+    state.u8(self.line_sprite_count as u8);
+    for value in &[self.control_reg, self.mask_reg, self.status_reg] {
+      state.u8(*value);
+    }
+    state.bool(self.nmi_pending);
+    state.u64(self.cycles as u64);
+  }
+
+  pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+    if self.chr_ram {
+      state.bytes_into(&mut self.chr_rom)?;
+    }
+    state.bytes_into(&mut self.palette_table)?;
+    state.bytes_into(&mut self.vram)?;
+    state.bytes_into(&mut self.cartridge_vram)?;
+    state.bytes_into(&mut self.oam_data)?;
+    self.mirroring = cartridge::mirroring_from_code(state.u8()?)?;
+    state.bytes_into(&mut self.pixels)?;
+    self.scanline = state.u16()?;
+    self.dot = state.u16()?;
+    self.frame = state.u64()?;
+    self.byte_buffer = state.u8()?;
+    self.mem_addr_reg.value = state.u16()?;
+    self.mem_addr_reg.temp = state.u16()?;
+    self.mem_addr_reg.top_byte_set = state.bool()?;
+    self.fine_x = state.u8()?;
+    self.oam_addr = state.u8()?;
+    for sprite in &mut self.line_sprites {
+      for value in [&mut sprite.x, &mut sprite.attributes, &mut sprite.low, &mut sprite.high] {
+        *value = state.u8()?;
+      }
+      sprite.zero = state.bool()?;
+    }
+    self.line_sprite_count = (state.u8()? as usize).min(SPRITES_PER_LINE);
+    for value in [&mut self.control_reg, &mut self.mask_reg, &mut self.status_reg] {
+      *value = state.u8()?;
+    }
+    self.nmi_pending = state.bool()?;
+    self.cycles = state.u64()? as usize;
+    Ok(())
+  }
+
+  // Pattern table byte, CHR RAM smaller than 8 KiB repeats through $0000-$1FFF
+  pub fn chr_byte(&self, addr: u16) -> u8 {
+    self.chr_rom[addr as usize % self.chr_rom.len()]
+  }
+
+  // Mappers call this when they switch mirroring
+  pub fn set_mirroring(&mut self, mirroring: Mirroring) {
+    self.mirroring = mirroring;
+  }
+
+  fn read_nametable(&self, index: u16) -> u8 {
+    let index = index as usize;
+    if index < VRAM_SIZE { self.vram[index] } else { self.cartridge_vram[index - VRAM_SIZE] }
+  }
+
+  fn write_nametable(&mut self, index: u16, data: u8) {
+    let index = index as usize;
+    if index < VRAM_SIZE { self.vram[index] = data } else { self.cartridge_vram[index - VRAM_SIZE] = data }
+  }
+
+  // Palette RAM entry as rendering sees it
+  pub fn palette_entry(&self, index: u8) -> u8 {
+    self.palette_table[palette_index(index)]
+  }
+
+  pub fn grayscale(&self) -> bool {
+    self.mask_reg & GRAYSCALE_BIT != 0
+  }
+
+  // PPUMASK emphasis bits as 0-7, red in bit 0, green in bit 1, blue in bit 2
+  pub fn emphasis(&self) -> u8 {
+    self.mask_reg >> EMPHASIS_SHIFT
+  }
+
+  pub fn poll_nmi(&mut self) -> bool {
+    let pending = self.nmi_pending;
+    self.nmi_pending = false;
+    pending
+  }
+
+  // Number of pixels of the current frame in `pixels` so far
+  pub fn pixels_drawn(&self) -> usize {
+    if self.scanline >= HEIGHT as u16 {
+      return WIDTH * HEIGHT;
+    }
+    self.scanline as usize * WIDTH + self.dot.saturating_sub(1).min(WIDTH as u16) as usize
+  }
+
+  pub fn rendering_enabled(&self) -> bool {
+    self.mask_reg & (SHOW_BACKGROUND_BIT | SHOW_SPRITES_BIT) != 0
+  }
+
+  pub fn tick(&mut self, dots: usize) {
+    self.cycles += dots;
+
+    for _x in 0..dots {
+      if self.scanline < HEIGHT as u16 || self.scanline == PRE_RENDER_SCANLINE {
+        self.render_dot();
+      }
+
+      if self.dot == 1 && self.scanline == VBLANK_SCANLINE {
+        self.status_reg |= VBLANK_STATUS_BIT;
+        if self.control_reg & GENERATE_NMI_BIT != 0 {
+          self.nmi_pending = true;
+        }
+      } else if self.dot == 1 && self.scanline == PRE_RENDER_SCANLINE {
+        self.status_reg &= !(VBLANK_STATUS_BIT | SPRITE_ZERO_HIT_STATUS_BIT | SPRITE_OVERFLOW_STATUS_BIT);
+      }
+
+      // Odd frames skip the last dot of the pre-render line while rendering is on
+      if self.dot == DOTS_PER_SCANLINE - 2 && self.scanline == PRE_RENDER_SCANLINE && self.frame % 2 == 1 && self.rendering_enabled() {
+        self.dot += 1;
+      }
+
+      self.dot += 1;
+      if self.dot == DOTS_PER_SCANLINE {
+        self.dot = 0;
+        self.scanline += 1;
+        if self.scanline == SCANLINES_PER_FRAME {
+          self.scanline = 0;
+          self.frame += 1;
+        }
+      }
+    }
+  }
+
+  // One dot of a visible or the pre-render scanline, the pixel and the VRAM address updates
+  // rendering makes on it
+  fn render_dot(&mut self) {
+    let visible = self.scanline < HEIGHT as u16;
+    if visible && self.dot == 0 {
+      self.evaluate_sprites();
+    }
+
+    if visible && self.dot >= 1 && self.dot <= WIDTH as u16 {
+      let x = self.dot - 1;
+      let index = self.pixel(x as u8);
+      self.pixels[self.scanline as usize * WIDTH + x as usize] = index;
+    }
+
+    if !self.rendering_enabled() {
+      return;
+    }
+
+    if self.dot >= 1 && self.dot <= WIDTH as u16 && self.dot.is_multiple_of(8) {
+      self.mem_addr_reg.increment_x();
+    }
+    if self.dot == WIDTH as u16 {
+      self.mem_addr_reg.increment_y();
+    } else if self.dot == WIDTH as u16 + 1 {
+      self.mem_addr_reg.copy_bits(HORIZONTAL_BITS);
+    } else if self.scanline == PRE_RENDER_SCANLINE && VERTICAL_RELOAD_DOTS.contains(&self.dot) {
+      self.mem_addr_reg.copy_bits(VERTICAL_BITS);
+    }
+  }
+
+  // Palette RAM entry for column `x` of the current scanline
+  fn pixel(&mut self, x: u8) -> u8 {
+    let background = if self.mask_reg & SHOW_BACKGROUND_BIT != 0 && (x >= 8 || self.mask_reg & BACKGROUND_LEFT_BIT != 0) {
+      self.background_pixel(x & 0b111)
+    } else {
+      0
+    };
+
+    let sprite = if self.mask_reg & SHOW_SPRITES_BIT != 0 && (x >= 8 || self.mask_reg & SPRITES_LEFT_BIT != 0) {
+      self.sprite_pixel(x)
     } else {
-      panic!("FOUR SCREEN VRAM MIRRORING NOT SUPPORTED");
+      None
+    };
+
+    let index = match sprite {
+      Some((color, behind, zero)) => {
+        // Sprite 0 hits wherever it overlaps background, except in the last column
+        if zero && background != 0 && x != 255 {
+          self.status_reg |= SPRITE_ZERO_HIT_STATUS_BIT;
+        }
+        if behind && background != 0 { background } else { color }
+      }
+      None => background
+    };
+
+    self.palette_table[palette_index(index)] & 0x3F
+  }
+
+  // Background color for the dot `column` pixels into the 8 the VRAM address points at, 0 when
+  // transparent
+  fn background_pixel(&self, column: u8) -> u8 {
+    let mut addr = self.mem_addr_reg.value;
+    let offset = self.fine_x + column;
+    // Fine X scroll reaches into the next tile for the rest of the group
+    if offset >= 8 {
+      addr = next_tile(addr);
+    }
+
+    let tile = self.read_nametable(self.mirror_addr(0x2000 | (addr & 0x0FFF))) as u16;
+    let pattern = self.background_pattern_table() + tile * 16 + ((addr & FINE_Y_BITS) >> 12);
+    let bit = 7 - (offset & 0b111);
+    let color = ((self.chr_byte(pattern) >> bit) & 1) | (((self.chr_byte(pattern + 8) >> bit) & 1) << 1);
+    if color == 0 {
+      return 0;
+    }
+
+    // One attribute byte covers 4x4 tiles, two bits for each 2x2 quarter
+    let attribute_addr = 0x23C0 | (addr & NAMETABLE_BITS) | ((addr >> 4) & 0x38) | ((addr >> 2) & 0x07);
+    let attribute = self.read_nametable(self.mirror_addr(attribute_addr));
+    let shift = ((addr >> 4) & 0b100) | (addr & 0b10);
+    (((attribute >> shift) & 0b11) << 2) | color
+  }
+
+  // Color, priority and whether it is sprite 0, for the first opaque sprite pixel in column `x`
+  fn sprite_pixel(&self, x: u8) -> Option<(u8, bool, bool)> {
+    for sprite in &self.line_sprites[..self.line_sprite_count] {
+      if x < sprite.x || x - sprite.x >= 8 {
+        continue;
+      }
+
+      let bit = 7 - (x - sprite.x);
+      let color = ((sprite.low >> bit) & 1) | (((sprite.high >> bit) & 1) << 1);
+      if color != 0 {
+        let index = 0x10 | ((sprite.attributes & SPRITE_PALETTE_BITS) << 2) | color;
+        return Some((index, sprite.attributes & SPRITE_BEHIND_BIT != 0, sprite.zero));
+      }
+    }
+    None
+  }
+
+  // Finds the first 8 sprites on the current scanline and fetches their pattern rows. Sprites
+  // are drawn one line below their OAM Y.
+  fn evaluate_sprites(&mut self) {
+    self.line_sprite_count = 0;
+    let height = if self.tall_sprites() { 16 } else { 8 };
+
+    for sprite in 0..64 {
+      let entry = &self.oam_data[sprite * 4..sprite * 4 + 4];
+      let top = entry[0] as u16 + 1;
+      if self.scanline < top || self.scanline >= top + height {
+        continue;
+      }
+
+      if self.line_sprite_count == SPRITES_PER_LINE {
+        self.status_reg |= SPRITE_OVERFLOW_STATUS_BIT;
+        break;
+      }
+
+      let (tile, attributes, x) = (entry[1] as u16, entry[2], entry[3]);
+      let mut row = self.scanline - top;
+      if attributes & SPRITE_FLIP_Y_BIT != 0 {
+        row = height - 1 - row;
+      }
+
+      // Tall sprites take their pattern table from bit 0 of the tile and use two tiles
+      let pattern = if height == 16 {
+        (tile & 1) * 0x1000 + (tile & 0xFE) * 16 + if row >= 8 { 16 + row - 8 } else { row }
+      } else {
+        self.sprite_pattern_table() + tile * 16 + row
+      };
+
+      let (mut low, mut high) = (self.chr_byte(pattern), self.chr_byte(pattern + 8));
+      if attributes & SPRITE_FLIP_X_BIT != 0 {
+        low = low.reverse_bits();
+        high = high.reverse_bits();
+      }
+
+      self.line_sprites[self.line_sprite_count] = LineSprite { x, attributes, low, high, zero: sprite == 0 };
+      self.line_sprite_count += 1;
     }
   }
 
+  // Index into 4 KiB of nametable memory, console VRAM first and then the cartridge's
+  fn mirror_addr(&self, addr: u16) -> u16 {
+    let vram_index = addr & 0xFFF;
+    let name_table_index = vram_index / NAMETABLE_SIZE;
+
+    let page = match self.mirroring {
+      Mirroring::Horizontal => name_table_index / 2,
+      Mirroring::Vertical => name_table_index % 2,
+      Mirroring::SingleScreenA => 0,
+      Mirroring::SingleScreenB => 1,
+      Mirroring::FourScreen => name_table_index
+    };
+    page * NAMETABLE_SIZE + (vram_index % NAMETABLE_SIZE)
+  }
+
   fn increment_addr(&mut self) {
-    let value = if self.control_reg & VRAM_ADD_INCREMENT_BIT != 0 { 1 } else { 32 };
+    // Set means going down a nametable row
+    let value = if self.control_reg & VRAM_ADD_INCREMENT_BIT != 0 { 32 } else { 1 };
     self.mem_addr_reg.increment(value);
   }
 }
 
-struct MemoryAddressRegister {
-  value: u16,
-  top_byte_set: bool
+// VRAM address of the tile to the right, wrapping into the next nametable across
+fn next_tile(addr: u16) -> u16 {
+  if addr & COARSE_X_BITS == COARSE_X_BITS {
+    (addr & !COARSE_X_BITS) ^ 0x0400
+  } else {
+    addr + 1
+  }
 }
 
-impl Default for MemoryAddressRegister {
-  fn default() -> Self {
-      MemoryAddressRegister { value: 0, top_byte_set: false }
+// Palette RAM index of a $3Fxx address, $10/$14/$18/$1C mirror the background entries below them
+fn palette_index(addr: u8) -> usize {
+  let mut index = addr & 0x1F;
+  if index & 0x13 == 0x10 {
+    index &= 0x0F;
   }
+  index as usize
+}
+
+#[derive(Default)]
+struct MemoryAddressRegister {
+  // Address PPUDATA uses and rendering walks through the nametables
+  value: u16,
+  // Address the next frame and each scanline start from, written by PPUCTRL, PPUSCROLL and
+  // the first PPUADDR write
+  temp: u16,
+  top_byte_set: bool
 }
 
 impl MemoryAddressRegister {
   pub fn write(&mut self, byte: u8) {
     if self.top_byte_set {
-      self.value = self.value | byte as u16;
+      self.temp = (self.temp & 0xFF00) | byte as u16;
+      self.value = self.temp;
     } else {
-      self.value = self.value | ((byte as u16) << 8);
+      // Only 14 address bits are wired
+      self.temp = (self.temp & 0x00FF) | (((byte & 0x3F) as u16) << 8);
+    }
+
+    self.top_byte_set = !self.top_byte_set;
+  }
+
+  pub fn set_nametable(&mut self, nametable: u8) {
+    self.temp = (self.temp & !NAMETABLE_BITS) | ((nametable as u16) << 10);
+  }
+
+  // Coarse X, fine X is kept by the PPU
+  pub fn set_scroll_x(&mut self, scroll: u8) {
+    self.temp = (self.temp & !COARSE_X_BITS) | (scroll as u16 >> 3);
+  }
+
+  pub fn set_scroll_y(&mut self, scroll: u8) {
+    self.temp = (self.temp & !(COARSE_Y_BITS | FINE_Y_BITS)) | ((scroll as u16 & 0xF8) << 2) | ((scroll as u16 & 0x07) << 12);
+  }
+
+  pub fn copy_bits(&mut self, bits: u16) {
+    self.value = (self.value & !bits) | (self.temp & bits);
+  }
+
+  pub fn increment_x(&mut self) {
+    self.value = next_tile(self.value);
+  }
+
+  // Next pixel row, moving down a tile after 8 and wrapping into the nametable below after row 29
+  pub fn increment_y(&mut self) {
+    if self.value & FINE_Y_BITS != FINE_Y_BITS {
+      self.value += 0x1000;
+      return;
     }
 
-    self.value |= 0x3FFF;
+    self.value &= !FINE_Y_BITS;
+    let coarse_y = (self.value & COARSE_Y_BITS) >> 5;
+    let coarse_y = match coarse_y {
+      29 => {
+        self.value ^= 0x0800;
+        0
+      }
+      31 => 0,
+      _ => coarse_y + 1
+    };
+    self.value = (self.value & !COARSE_Y_BITS) | (coarse_y << 5);
+  }
+
+  pub fn reset_latch(&mut self) {
+    self.top_byte_set = false;
+  }
 
+  // Flips the latch for a write that shares it, returning whether it was the second write
+  pub fn toggle_latch(&mut self) -> bool {
     self.top_byte_set = !self.top_byte_set;
+    !self.top_byte_set
   }
 
   pub fn increment(&mut self, value: u8) {
-    self.value += value as u16;
-    self.value |= 0x3FFF;
+    self.value = self.value.wrapping_add(value as u16) & 0x3FFF;
   }
 }
\ No newline at end of file
@@ -0,0 +1,111 @@
+use std::fs::File;
+use std::io::BufWriter;
+
+use crate::audio::wav::WavWriter;
+use crate::graphics::avi::AviWriter;
+use crate::graphics::frame::{Frame, WIDTH, HEIGHT};
+use crate::graphics::gif::GifWriter;
+use crate::graphics::palette::{Palette, COLOR_COUNT};
+use crate::graphics::y4m::Y4mWriter;
+
+// 236.25 MHz / 11 master clock over 4 clocks per dot and 89341.5 dots per frame
+pub const NTSC_FRAME_RATE_NUM: u32 = 39_375_000;
+pub const NTSC_FRAME_RATE_DEN: u32 = 655_171;
+
+pub const SAMPLE_RATE: u32 = 44_100;
+
+enum VideoSink {
+  Y4m(Y4mWriter<BufWriter<File>>),
+  Avi(AviWriter<BufWriter<File>>),
+  Gif(GifWriter<BufWriter<File>>)
+}
+
+// Writes frames and audio as they are produced, video as Y4M, AVI or GIF picked by extension
+pub struct Recorder {
+  video: Option<VideoSink>,
+  audio: Option<WavWriter<BufWriter<File>>>,
+  // Only frames from the first through the second are written, counted from the start of the
+  // recording
+  pub frame_range: Option<(u64, u64)>,
+  offered: u64,
+  frames: u64
+}
+
+impl Recorder {
+  // GIFs take their global color table from `palette`
+  pub fn start(video_path: Option<&str>, audio_path: Option<&str>, palette: &Palette) -> Result<Recorder, String> {
+    let create = |path: &str| File::create(path).map(BufWriter::new).map_err(|error| format!("UNABLE TO CREATE {}: {}", path, error));
+
+    let video = match video_path {
+      Some(path) if path.to_ascii_lowercase().ends_with(".avi") => {
+        let writer = AviWriter::new(create(path)?, NTSC_FRAME_RATE_NUM, NTSC_FRAME_RATE_DEN);
+        Some(VideoSink::Avi(writer.map_err(|error| format!("UNABLE TO WRITE {}: {}", path, error))?))
+      }
+      Some(path) if path.to_ascii_lowercase().ends_with(".gif") => {
+        let writer = GifWriter::new(create(path)?, WIDTH, HEIGHT, &palette.colors[..COLOR_COUNT], NTSC_FRAME_RATE_NUM, NTSC_FRAME_RATE_DEN);
+        Some(VideoSink::Gif(writer.map_err(|error| format!("UNABLE TO WRITE {}: {}", path, error))?))
+      }
+      Some(path) => {
+        let writer = Y4mWriter::new(create(path)?, NTSC_FRAME_RATE_NUM, NTSC_FRAME_RATE_DEN);
+        Some(VideoSink::Y4m(writer.map_err(|error| format!("UNABLE TO WRITE {}: {}", path, error))?))
+      }
+      None => None
+    };
+
+    let audio = match audio_path {
+      Some(path) => Some(WavWriter::new(create(path)?, SAMPLE_RATE, 1).map_err(|error| format!("UNABLE TO WRITE {}: {}", path, error))?),
+      None => None
+    };
+
+    Ok(Recorder { video, audio, frame_range: None, offered: 0, frames: 0 })
+  }
+
+  // Frames actually written
+  pub fn frames(&self) -> u64 {
+    self.frames
+  }
+
+  // Audio is padded with silence or trimmed so it never drifts from the video
+  pub fn record_frame(&mut self, frame: &Frame, samples: &[i16]) -> Result<(), String> {
+    let offered = self.offered;
+    self.offered += 1;
+    if let Some((first, last)) = self.frame_range {
+      if offered < first || offered > last {
+        return Ok(());
+      }
+    }
+    self.frames += 1;
+
+    match &mut self.video {
+      Some(VideoSink::Y4m(writer)) => writer.write_frame(frame),
+      Some(VideoSink::Avi(writer)) => writer.write_frame(frame),
+      Some(VideoSink::Gif(writer)) => writer.write_frame(&frame.data),
+      None => Ok(())
+    }.map_err(|error| format!("UNABLE TO WRITE VIDEO FRAME: {}", error))?;
+
+    if let Some(writer) = &mut self.audio {
+      let expected = self.frames * SAMPLE_RATE as u64 * NTSC_FRAME_RATE_DEN as u64 / NTSC_FRAME_RATE_NUM as u64;
+      let wanted = expected.saturating_sub(writer.length() as u64) as usize;
+
+      let mut samples = samples[..wanted.min(samples.len())].to_vec();
+      samples.resize(wanted, 0);
+      writer.write_samples(&samples).map_err(|error| format!("UNABLE TO WRITE AUDIO: {}", error))?;
+    }
+
+    Ok(())
+  }
+
+  pub fn stop(self) -> Result<(), String> {
+    match self.video {
+      Some(VideoSink::Y4m(writer)) => writer.finish(),
+      Some(VideoSink::Avi(writer)) => writer.finish(),
+      Some(VideoSink::Gif(writer)) => writer.finish(),
+      None => Ok(())
+    }.map_err(|error| format!("UNABLE TO FINISH VIDEO: {}", error))?;
+
+    if let Some(writer) = self.audio {
+      writer.finish().map_err(|error| format!("UNABLE TO FINISH AUDIO: {}", error))?;
+    }
+    Ok(())
+  }
+}
@@ -0,0 +1,110 @@
+// Byte layout of save states: little endian fields one after another in the order the parts of
+// the console write them, behind a tag, a version and the checksum of the ROM they belong to
+pub const STATE_TAG: [u8; 4] = *b"NESS";
+pub const STATE_VERSION: u8 = 2;
+
+#[derive(Default)]
+pub struct StateWriter {
+  pub bytes: Vec<u8>
+}
+
+impl StateWriter {
+  pub fn new() -> StateWriter {
+    StateWriter { bytes: Vec::new() }
+  }
+
+  pub fn u8(&mut self, value: u8) {
+    self.bytes.push(value);
+  }
+
+  pub fn bool(&mut self, value: bool) {
+    self.bytes.push(value as u8);
+  }
+
+  pub fn u16(&mut self, value: u16) {
+    self.bytes.extend_from_slice(&value.to_le_bytes());
+  }
+
+  pub fn u32(&mut self, value: u32) {
+    self.bytes.extend_from_slice(&value.to_le_bytes());
+  }
+
+  pub fn u64(&mut self, value: u64) {
+    self.bytes.extend_from_slice(&value.to_le_bytes());
+  }
+
+  pub fn f32(&mut self, value: f32) {
+    self.bytes.extend_from_slice(&value.to_le_bytes());
+  }
+
+  // Length first so a reader can tell memory of the wrong size
+  pub fn bytes(&mut self, value: &[u8]) {
+    self.u32(value.len() as u32);
+    self.bytes.extend_from_slice(value);
+  }
+}
+
+pub struct StateReader<'a> {
+  bytes: &'a [u8],
+  position: usize
+}
+
+impl<'a> StateReader<'a> {
+  pub fn new(bytes: &'a [u8]) -> StateReader<'a> {
+    StateReader { bytes, position: 0 }
+  }
+
+  fn take(&mut self, count: usize) -> Result<&'a [u8], String> {
+    if self.bytes.len() - self.position < count {
+      return Err("SAVE STATE IS TRUNCATED".to_string());
+    }
+    let bytes = &self.bytes[self.position..(self.position + count)];
+    self.position += count;
+    Ok(bytes)
+  }
+
+  pub fn u8(&mut self) -> Result<u8, String> {
+    Ok(self.take(1)?[0])
+  }
+
+  pub fn bool(&mut self) -> Result<bool, String> {
+    Ok(self.u8()? != 0)
+  }
+
+  pub fn u16(&mut self) -> Result<u16, String> {
+    let bytes = self.take(2)?;
+    Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+  }
+
+  pub fn u32(&mut self) -> Result<u32, String> {
+    let bytes = self.take(4)?;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+  }
+
+  pub fn u64(&mut self) -> Result<u64, String> {
+    Ok(self.u32()? as u64 | (self.u32()? as u64) << 32)
+  }
+
+  pub fn f32(&mut self) -> Result<f32, String> {
+    Ok(f32::from_bits(self.u32()?))
+  }
+
+  pub fn bytes(&mut self) -> Result<&'a [u8], String> {
+    let length = self.u32()? as usize;
+    self.take(length)
+  }
+
+  // Fills memory that has a fixed size on this cartridge
+  pub fn bytes_into(&mut self, out: &mut [u8]) -> Result<(), String> {
+    let bytes = self.bytes()?;
+    if bytes.len() != out.len() {
+      return Err("SAVE STATE DOES NOT MATCH THIS CARTRIDGE".to_string());
+    }
+    out.copy_from_slice(bytes);
+    Ok(())
+  }
+
+  pub fn is_finished(&self) -> bool {
+    self.position == self.bytes.len()
+  }
+}
@@ -0,0 +1,103 @@
+use crate::emu::cartridge::Cartridge;
+use crate::emu::nes::NES;
+
+// Status protocol used by blargg's test ROMs, see the readme shipped with them
+const STATUS_ADDR: u16 = 0x6000;
+const SIGNATURE_ADDR: u16 = 0x6001;
+const MESSAGE_ADDR: u16 = 0x6004;
+const MESSAGE_END: u16 = 0x7FFF;
+
+const SIGNATURE: [u8; 3] = [0xDE, 0xB0, 0x61];
+
+const STATUS_RUNNING: u8 = 0x80;
+const STATUS_NEEDS_RESET: u8 = 0x81;
+
+// The ROM wants reset pressed at least 100ms after asking
+const RESET_DELAY_FRAMES: u64 = 7;
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum TestRomStatus {
+  Passed,
+  // Result code written by the ROM
+  Failed(u8),
+  // Still running, or never wrote the signature, when the frame limit hit
+  TimedOut,
+  // The emulator panicked
+  Crashed(String)
+}
+
+pub struct TestRomResult {
+  pub status: TestRomStatus,
+  pub message: String,
+  pub frames: u64
+}
+
+impl NES {
+  // Status byte once the ROM has written the protocol signature
+  pub fn test_rom_status(&self) -> Option<u8> {
+    let signature = [self.bus.peek(SIGNATURE_ADDR), self.bus.peek(SIGNATURE_ADDR + 1), self.bus.peek(SIGNATURE_ADDR + 2)];
+    if signature != SIGNATURE {
+      return None;
+    }
+    Some(self.bus.peek(STATUS_ADDR))
+  }
+
+  // NUL terminated text the ROM has written so far
+  pub fn test_rom_message(&self) -> String {
+    let mut message = Vec::new();
+    for addr in MESSAGE_ADDR..=MESSAGE_END {
+      let byte = self.bus.peek(addr);
+      if byte == 0 {
+        break;
+      }
+      message.push(byte);
+    }
+    String::from_utf8_lossy(&message).trim_end().to_string()
+  }
+}
+
+// Run until the ROM reports a result, pressing reset when it asks for it
+pub fn run_test_rom(cartridge: Cartridge, max_frames: u64) -> TestRomResult {
+  let mut nes = NES::new(cartridge);
+  nes.reset();
+
+  let mut reset_at = None;
+
+  for frame in 0..max_frames {
+    nes.run_frame();
+
+    match nes.test_rom_status() {
+      Some(STATUS_RUNNING) | None => {}
+      Some(STATUS_NEEDS_RESET) => {
+        let due = *reset_at.get_or_insert(frame + RESET_DELAY_FRAMES);
+        if frame >= due {
+          reset_at = None;
+          nes.reset();
+        }
+      }
+      Some(code) => {
+        let status = if code == 0 { TestRomStatus::Passed } else { TestRomStatus::Failed(code) };
+        return TestRomResult { status, message: nes.test_rom_message(), frames: frame + 1 };
+      }
+    }
+  }
+
+  TestRomResult { status: TestRomStatus::TimedOut, message: nes.test_rom_message(), frames: max_frames }
+}
+
+// Same as `run_test_rom` but turns load errors and emulator panics into results
+pub fn run_test_rom_file(path: &str, max_frames: u64) -> TestRomResult {
+  let outcome = std::panic::catch_unwind(|| {
+    Cartridge::load(path).map(|cartridge| run_test_rom(cartridge, max_frames))
+  });
+
+  let reason = match outcome {
+    Ok(Ok(result)) => return result,
+    Ok(Err(error)) => error,
+    Err(panic) => panic.downcast_ref::<String>().cloned()
+      .or_else(|| panic.downcast_ref::<&str>().map(|reason| reason.to_string()))
+      .unwrap_or_else(|| "UNKNOWN PANIC".to_string())
+  };
+
+  TestRomResult { status: TestRomStatus::Crashed(reason), message: String::new(), frames: 0 }
+}
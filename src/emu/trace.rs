@@ -0,0 +1,262 @@
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+
+use strum_macros::EnumString;
+
+use crate::emu::cpu_bus::CpuBus;
+use crate::emu::cpu_opcodes::{AddressingMode, Instruction};
+
+// Line layouts matching the logs of other emulators so traces can be diffed directly
+#[derive(Clone, Copy, PartialEq, Debug, EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum TraceFormat {
+  Nestest,
+  Mesen,
+  Fceux
+}
+
+pub enum TraceSink {
+  File(File),
+  Stdout,
+  Memory(Vec<String>)
+}
+
+impl TraceSink {
+  pub fn file(path: &str) -> io::Result<TraceSink> {
+    let file = OpenOptions::new().write(true).truncate(true).create(true).open(path)?;
+    Ok(TraceSink::File(file))
+  }
+
+  fn write_line(&mut self, line: &str) -> io::Result<()> {
+    match self {
+      TraceSink::File(file) => writeln!(file, "{}", line),
+      TraceSink::Stdout => writeln!(io::stdout(), "{}", line),
+      TraceSink::Memory(lines) => {
+        lines.push(line.to_string());
+        Ok(())
+      }
+    }
+  }
+}
+
+// Machine state captured right before an instruction executes
+pub struct TraceState {
+  pub pc: u16,
+  pub bytes: Vec<u8>,
+  pub disassembly: String,
+  pub a: u8,
+  pub x: u8,
+  pub y: u8,
+  pub p: u8,
+  pub sp: u8,
+  pub cycles: u32,
+  pub ppu_position: Option<(u16, u16)>,
+  pub frame: u64
+}
+
+impl TraceState {
+  pub fn capture<B: CpuBus>(bus: &B, pc: u16, registers: (u8, u8, u8, u8, u8), cycles: u32) -> TraceState {
+    let (a, x, y, p, sp) = registers;
+    let (bytes, disassembly) = disassemble(bus, pc);
+
+    TraceState {
+      pc,
+      bytes,
+      disassembly,
+      a,
+      x,
+      y,
+      p,
+      sp,
+      cycles,
+      ppu_position: bus.ppu_position(),
+      frame: bus.frame()
+    }
+  }
+}
+
+pub struct Tracer {
+  pub format: TraceFormat,
+  pub sink: TraceSink,
+  // Append PPU scanline and dot columns when the bus has a PPU
+  pub ppu_columns: bool,
+  // Only trace instructions whose address falls in this inclusive range
+  pub address_range: Option<(u16, u16)>,
+  // Only trace during this inclusive range of frames
+  pub frame_range: Option<(u64, u64)>,
+  // First write error, tracing stops once one happens
+  pub error: Option<io::Error>,
+
+  ring: Option<VecDeque<String>>,
+  ring_size: usize
+}
+
+impl Tracer {
+  pub fn new(format: TraceFormat, sink: TraceSink) -> Tracer {
+    Tracer {
+      format,
+      sink,
+      ppu_columns: false,
+      address_range: None,
+      frame_range: None,
+      error: None,
+      ring: None,
+      ring_size: 0
+    }
+  }
+
+  // Keep only the last `size` lines in memory, they reach the sink on `flush_ring` or a panic
+  pub fn set_ring_buffer(&mut self, size: usize) {
+    self.ring_size = size;
+    self.ring = if size > 0 { Some(VecDeque::with_capacity(size)) } else { None };
+  }
+
+  pub fn wants(&self, pc: u16, frame: u64) -> bool {
+    if self.error.is_some() {
+      return false;
+    }
+
+    if let Some((start, end)) = self.address_range {
+      if pc < start || pc > end {
+        return false;
+      }
+    }
+
+    if let Some((start, end)) = self.frame_range {
+      if frame < start || frame > end {
+        return false;
+      }
+    }
+
+    return true;
+  }
+
+  pub fn log(&mut self, state: &TraceState) {
+    if !self.wants(state.pc, state.frame) {
+      return;
+    }
+
+    let line = self.format_line(state);
+
+    if let Some(ring) = self.ring.as_mut() {
+      if ring.len() == self.ring_size {
+        ring.pop_front();
+      }
+      ring.push_back(line);
+      return;
+    }
+
+    self.write_line(&line);
+  }
+
+  pub fn flush_ring(&mut self) {
+    let lines: Vec<String> = match self.ring.as_mut() {
+      Some(ring) => ring.drain(..).collect(),
+      None => return
+    };
+
+    for line in lines {
+      self.write_line(&line);
+    }
+  }
+
+  pub fn lines(&self) -> &[String] {
+    match &self.sink {
+      TraceSink::Memory(lines) => lines,
+      _ => &[]
+    }
+  }
+
+  fn write_line(&mut self, line: &str) {
+    if self.error.is_some() {
+      return;
+    }
+
+    if let Err(error) = self.sink.write_line(line) {
+      eprintln!("TRACE WRITE FAILED, TRACING STOPPED: {}", error);
+      self.error = Some(error);
+    }
+  }
+
+  fn format_line(&self, state: &TraceState) -> String {
+    let bytes = state.bytes.iter().map(|byte| format!("{:02X}", byte)).collect::<Vec<String>>().join(" ");
+    let ppu = match (self.ppu_columns, state.ppu_position) {
+      (true, Some(position)) => Some(position),
+      _ => None
+    };
+
+    match self.format {
+      TraceFormat::Nestest => {
+        let mut line = format!("{:04X}  {:8}  {:30}  A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X}",
+          state.pc, bytes, state.disassembly, state.a, state.x, state.y, state.p, state.sp);
+        if let Some((scanline, dot)) = ppu {
+          line += &format!(" PPU:{:3},{:3}", scanline, dot);
+        }
+        line + &format!(" CYC:{}", state.cycles)
+      }
+      TraceFormat::Mesen => {
+        let mut line = format!("{:04X}  {:8}  {:30}  A:{:02X} X:{:02X} Y:{:02X} S:{:02X} P:{}",
+          state.pc, bytes, state.disassembly, state.a, state.x, state.y, state.sp, flag_string(state.p));
+        if let Some((scanline, dot)) = ppu {
+          line += &format!(" V:{:<3} H:{:<3} Fr:{}", scanline, dot, state.frame);
+        }
+        line + &format!(" Cycle:{}", state.cycles)
+      }
+      TraceFormat::Fceux => {
+        let mut line = String::new();
+        if let Some((scanline, dot)) = ppu {
+          line += &format!("f{:<6} s{:<4} d{:<4} ", state.frame, scanline, dot);
+        }
+        line + &format!("c{:<10} A:{:02X} X:{:02X} Y:{:02X} S:{:02X} P:{}  ${:04X}:{:8}  {}",
+          state.cycles, state.a, state.x, state.y, state.sp, flag_string(state.p), state.pc, bytes, state.disassembly)
+      }
+    }
+  }
+}
+
+impl Drop for Tracer {
+  fn drop(&mut self) {
+    // The ring buffer exists to explain crashes, so write it out while unwinding
+    if std::thread::panicking() {
+      self.flush_ring();
+    }
+  }
+}
+
+// NV-BDIZC with set flags in upper case
+fn flag_string(p: u8) -> String {
+  "nvubdizc".chars().enumerate().map(|(index, flag)| {
+    if p & (0x80 >> index) != 0 { flag.to_ascii_uppercase() } else { flag }
+  }).collect()
+}
+
+// Instruction bytes and assembly text for the instruction at `pc`, without side effects
+pub fn disassemble<B: CpuBus>(bus: &B, pc: u16) -> (Vec<u8>, String) {
+  let opcode = bus.peek(pc);
+  let instruction = Instruction::from_u8(opcode);
+  let lo = bus.peek(pc.wrapping_add(1));
+  let hi = bus.peek(pc.wrapping_add(2));
+  let word = ((hi as u16) << 8) | lo as u16;
+
+  let (length, operand) = match instruction.addr_mode {
+    AddressingMode::Implied => (1, String::new()),
+    AddressingMode::Accumulator => (1, "A".to_string()),
+    AddressingMode::Immediate => (2, format!("#${:02X}", lo)),
+    AddressingMode::ZeroPage => (2, format!("${:02X}", lo)),
+    AddressingMode::ZeroPageX => (2, format!("${:02X},X", lo)),
+    AddressingMode::ZeroPageY => (2, format!("${:02X},Y", lo)),
+    AddressingMode::IndirectX => (2, format!("(${:02X},X)", lo)),
+    AddressingMode::IndirectY => (2, format!("(${:02X}),Y", lo)),
+    AddressingMode::Relative => (2, format!("${:04X}", pc.wrapping_add(2).wrapping_add(lo as i8 as u16))),
+    AddressingMode::Absolute => (3, format!("${:04X}", word)),
+    AddressingMode::AbsoluteX => (3, format!("${:04X},X", word)),
+    AddressingMode::AbsoluteY => (3, format!("${:04X},Y", word)),
+    AddressingMode::Indirect => (3, format!("(${:04X})", word))
+  };
+
+  let bytes = [opcode, lo, hi][..length].to_vec();
+  let text = if operand.is_empty() { instruction.opcode.to_string() } else { format!("{} {}", instruction.opcode, operand) };
+
+  (bytes, text)
+}
@@ -0,0 +1,108 @@
+use std::any::Any;
+
+use crate::emu::input::{ExpansionDevice, InputDevice};
+use crate::emu::save_state::{StateReader, StateWriter};
+
+// NES version, on $4017
+const NES_DATA_BIT: u8 = 0x08;
+const NES_BUTTON_BIT: u8 = 0x10;
+// Famicom version, button on $4016 and data on $4017
+const FAMICOM_DATA_BIT: u8 = 0x02;
+const FAMICOM_BUTTON_BIT: u8 = 0x02;
+
+// Arkanoid Vaus paddle. The strobe latches the knob's position, which is then shifted out 8
+// bits MSB first and inverted, with the fire button on its own line. The NES version goes in
+// port 2 and the Famicom version on the expansion port, both are the same device here.
+#[derive(Default)]
+pub struct Vaus {
+  // Knob position, games only use part of the range
+  pub position: u8,
+  pub button: bool,
+  strobe: bool,
+  shift: u8
+}
+
+impl Vaus {
+  fn write_strobe(&mut self, value: u8) {
+    self.strobe = value & 0x01 != 0;
+    if self.strobe {
+      self.shift = self.position;
+    }
+  }
+
+  // Next position bit as read, 1 once all 8 have gone
+  fn next_bit(&mut self) -> bool {
+    if self.strobe {
+      self.shift = self.position;
+    }
+    let bit = self.shift & 0x80 == 0;
+    self.shift <<= 1;
+    bit
+  }
+
+  // Same in either port
+  fn save_latch(&self, state: &mut StateWriter) {
+    state.bool(self.strobe);
+    state.u8(self.shift);
+  }
+
+  fn load_latch(&mut self, state: &mut StateReader) -> Result<(), String> {
+    self.strobe = state.bool()?;
+    self.shift = state.u8()?;
+    Ok(())
+  }
+}
+
+impl InputDevice for Vaus {
+  fn write(&mut self, value: u8) {
+    self.write_strobe(value);
+  }
+
+  fn read(&mut self) -> u8 {
+    let mut value = 0;
+    if self.next_bit() {
+      value |= NES_DATA_BIT;
+    }
+    if self.button {
+      value |= NES_BUTTON_BIT;
+    }
+    return value;
+  }
+
+  fn save_state(&self, state: &mut StateWriter) {
+    self.save_latch(state);
+  }
+
+  fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+    self.load_latch(state)
+  }
+
+  fn as_any_mut(&mut self) -> &mut dyn Any {
+    self
+  }
+}
+
+impl ExpansionDevice for Vaus {
+  fn write(&mut self, value: u8) {
+    self.write_strobe(value);
+  }
+
+  fn read(&mut self, port: usize) -> u8 {
+    if port == 0 {
+      return if self.button { FAMICOM_BUTTON_BIT } else { 0 };
+    }
+    if self.next_bit() { FAMICOM_DATA_BIT } else { 0 }
+  }
+
+  fn save_state(&self, state: &mut StateWriter) {
+    self.save_latch(state);
+  }
+
+  fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+    self.load_latch(state)
+  }
+
+  fn as_any_mut(&mut self) -> &mut dyn Any {
+    self
+  }
+}
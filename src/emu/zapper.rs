@@ -0,0 +1,80 @@
+use std::any::Any;
+
+use crate::emu::input::InputDevice;
+use crate::emu::save_state::{StateReader, StateWriter};
+use crate::graphics::frame::{Frame, WIDTH, HEIGHT};
+
+// Reads 0 while the photodiode sees light
+const LIGHT_NOT_SENSED_BIT: u8 = 0x08;
+const TRIGGER_BIT: u8 = 0x10;
+
+// Pixels either side of the aim point the photodiode takes in
+const SENSE_RADIUS: usize = 2;
+// Scanlines light keeps being reported after the beam passes the aim point
+const LIGHT_LINES: u16 = 20;
+// Luma out of 255 a pixel needs to register, a third of full white
+const BRIGHTNESS_THRESHOLD: u32 = 85;
+
+// NES Zapper light gun, normally in port 2. The host aims it and pulls the trigger, and light
+// is sensed from the picture as the PPU draws it.
+#[derive(Default)]
+pub struct Zapper {
+  // Aim point in frame pixels, None when pointed away from the screen
+  pub cursor: Option<(usize, usize)>,
+  pub trigger: bool,
+  light: bool
+}
+
+impl Zapper {
+  // Whether the photodiode sees light with the beam at the start of `scanline`. Only lines
+  // the beam has already drawn this frame count.
+  fn senses_light(&self, frame: &Frame, scanline: u16) -> bool {
+    let (x, y) = match self.cursor {
+      Some((x, y)) if x < WIDTH && y < HEIGHT => (x, y),
+      _ => return false
+    };
+    let scanline = scanline as usize;
+    if scanline <= y || scanline > y + LIGHT_LINES as usize {
+      return false;
+    }
+
+    let mut rows = y.saturating_sub(SENSE_RADIUS)..=(y + SENSE_RADIUS).min(scanline - 1).min(HEIGHT - 1);
+    let columns = x.saturating_sub(SENSE_RADIUS)..=(x + SENSE_RADIUS).min(WIDTH - 1);
+    rows.any(|row| columns.clone().any(|column| {
+      let (r, g, b) = frame.pixel(column, row);
+      (r as u32 * 299 + g as u32 * 587 + b as u32 * 114) / 1000 >= BRIGHTNESS_THRESHOLD
+    }))
+  }
+}
+
+impl InputDevice for Zapper {
+  fn write(&mut self, _value: u8) {}
+
+  fn read(&mut self) -> u8 {
+    let mut value = 0;
+    if !self.light {
+      value |= LIGHT_NOT_SENSED_BIT;
+    }
+    if self.trigger {
+      value |= TRIGGER_BIT;
+    }
+    return value;
+  }
+
+  fn scanline(&mut self, frame: &Frame, scanline: u16) {
+    self.light = self.senses_light(frame, scanline);
+  }
+
+  fn save_state(&self, state: &mut StateWriter) {
+    state.bool(self.light);
+  }
+
+  fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+    self.light = state.bool()?;
+    Ok(())
+  }
+
+  fn as_any_mut(&mut self) -> &mut dyn Any {
+    self
+  }
+}
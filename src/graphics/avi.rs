@@ -0,0 +1,120 @@
+use std::io::{self, Seek, SeekFrom, Write};
+
+use crate::graphics::frame::{Frame, WIDTH, HEIGHT};
+
+const FRAME_SIZE: u32 = (WIDTH * HEIGHT * 3) as u32;
+
+// Offsets of the fields patched once the frame count is known
+const AVIH_FRAMES_OFFSET: u64 = 48;
+const STRH_LENGTH_OFFSET: u64 = 140;
+const HEADER_SIZE: u32 = 224;
+
+const AVIF_HASINDEX: u32 = 0x10;
+const AVIIF_KEYFRAME: u32 = 0x10;
+
+// Uncompressed 24 bit RGB AVI, every frame a key frame
+pub struct AviWriter<W: Write + Seek> {
+  out: W,
+  frames: u32,
+  row: Vec<u8>
+}
+
+impl<W: Write + Seek> AviWriter<W> {
+  pub fn new(mut out: W, rate_num: u32, rate_den: u32) -> io::Result<AviWriter<W>> {
+    let micros_per_frame = (1_000_000u64 * rate_den as u64 / rate_num as u64) as u32;
+    let bytes_per_second = (FRAME_SIZE as u64 * rate_num as u64 / rate_den as u64) as u32;
+
+    let mut header = Vec::with_capacity(HEADER_SIZE as usize);
+    header.extend_from_slice(b"RIFF");
+    put_u32(&mut header, 0);
+    header.extend_from_slice(b"AVI LIST");
+    put_u32(&mut header, 192);
+    header.extend_from_slice(b"hdrlavih");
+    put_u32(&mut header, 56);
+    for value in &[micros_per_frame, bytes_per_second, 0, AVIF_HASINDEX, 0, 0, 1, FRAME_SIZE,
+                   WIDTH as u32, HEIGHT as u32, 0, 0, 0, 0] {
+      put_u32(&mut header, *value);
+    }
+
+    header.extend_from_slice(b"LIST");
+    put_u32(&mut header, 116);
+    header.extend_from_slice(b"strlstrh");
+    put_u32(&mut header, 56);
+    header.extend_from_slice(b"vidsDIB ");
+    for value in &[0, 0, 0, rate_den, rate_num, 0, 0, FRAME_SIZE, u32::MAX, 0] {
+      put_u32(&mut header, *value);
+    }
+    for value in &[0u16, 0, WIDTH as u16, HEIGHT as u16] {
+      header.extend_from_slice(&value.to_le_bytes());
+    }
+
+    // BITMAPINFOHEADER, positive height means rows are stored bottom up
+    header.extend_from_slice(b"strf");
+    put_u32(&mut header, 40);
+    for value in &[40, WIDTH as u32, HEIGHT as u32] {
+      put_u32(&mut header, *value);
+    }
+    header.extend_from_slice(&1u16.to_le_bytes());
+    header.extend_from_slice(&24u16.to_le_bytes());
+    for value in &[0, FRAME_SIZE, 0, 0, 0, 0] {
+      put_u32(&mut header, *value);
+    }
+
+    header.extend_from_slice(b"LIST");
+    put_u32(&mut header, 0);
+    header.extend_from_slice(b"movi");
+
+    out.write_all(&header)?;
+    Ok(AviWriter { out, frames: 0, row: vec![0; WIDTH * 3] })
+  }
+
+  pub fn write_frame(&mut self, frame: &Frame) -> io::Result<()> {
+    self.out.write_all(b"00db")?;
+    self.out.write_all(&FRAME_SIZE.to_le_bytes())?;
+
+    for y in (0..HEIGHT).rev() {
+      let line = &frame.data[(y * WIDTH * 3)..((y + 1) * WIDTH * 3)];
+      for (bgr, rgb) in self.row.chunks_mut(3).zip(line.chunks(3)) {
+        bgr.copy_from_slice(&[rgb[2], rgb[1], rgb[0]]);
+      }
+      self.out.write_all(&self.row)?;
+    }
+
+    self.frames += 1;
+    Ok(())
+  }
+
+  // Writes the index and fills in the sizes and frame counts
+  pub fn finish(mut self) -> io::Result<()> {
+    let movi_size = 4 + self.frames * (FRAME_SIZE + 8);
+
+    self.out.write_all(b"idx1")?;
+    self.out.write_all(&(self.frames * 16).to_le_bytes())?;
+    for frame in 0..self.frames {
+      let mut entry = Vec::with_capacity(16);
+      entry.extend_from_slice(b"00db");
+      put_u32(&mut entry, AVIIF_KEYFRAME);
+      put_u32(&mut entry, 4 + frame * (FRAME_SIZE + 8));
+      put_u32(&mut entry, FRAME_SIZE);
+      self.out.write_all(&entry)?;
+    }
+
+    let riff_size = HEADER_SIZE - 8 + movi_size - 4 + 8 + self.frames * 16;
+    self.patch(4, riff_size)?;
+    self.patch(AVIH_FRAMES_OFFSET, self.frames)?;
+    self.patch(STRH_LENGTH_OFFSET, self.frames)?;
+    self.patch((HEADER_SIZE - 8) as u64, movi_size)?;
+
+    self.out.seek(SeekFrom::End(0))?;
+    self.out.flush()
+  }
+
+  fn patch(&mut self, offset: u64, value: u32) -> io::Result<()> {
+    self.out.seek(SeekFrom::Start(offset))?;
+    self.out.write_all(&value.to_le_bytes())
+  }
+}
+
+fn put_u32(out: &mut Vec<u8>, value: u32) {
+  out.extend_from_slice(&value.to_le_bytes());
+}
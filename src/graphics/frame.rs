@@ -1,5 +1,14 @@
-const WIDTH: usize = 256;
-const HEIGHT: usize = 240;
+use std::fs::File;
+use std::io::{self, BufWriter};
+
+use crate::graphics::{png, ppm};
+
+pub const WIDTH: usize = 256;
+pub const HEIGHT: usize = 240;
+
+// FNV-1a 64 bit parameters
+const FNV_OFFSET: u64 = 0xCBF2_9CE4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01B3;
 
 pub struct Frame {
   pub data: Vec<u8>
@@ -19,4 +28,55 @@ impl Frame {
     self.data[first + 1] = color.1;
     self.data[first + 2] = color.2;
   }
-}
\ No newline at end of file
+
+  pub fn pixel(&self, x: usize, y: usize) -> (u8, u8, u8) {
+    let first = (y * 3 * WIDTH) + (x * 3);
+    (self.data[first], self.data[first + 1], self.data[first + 2])
+  }
+
+  // Stable across platforms and runs, so it can be stored as a golden value
+  pub fn hash(&self) -> u64 {
+    let mut hash = FNV_OFFSET;
+    for byte in &self.data {
+      hash ^= *byte as u64;
+      hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    hash
+  }
+
+  // Number of pixels that differ from `other`
+  pub fn diff(&self, other: &Frame) -> usize {
+    self.data.chunks(3).zip(other.data.chunks(3)).filter(|(a, b)| a != b).count()
+  }
+
+  pub fn save_png(&self, path: &str) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    png::write_rgb(&mut out, WIDTH as u32, HEIGHT as u32, &self.data)
+  }
+
+  pub fn save_ppm(&self, path: &str) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    ppm::write_rgb(&mut out, WIDTH as u32, HEIGHT as u32, &self.data)
+  }
+
+  // Picks PPM for .ppm paths and PNG for everything else
+  pub fn save(&self, path: &str) -> io::Result<()> {
+    if path.to_ascii_lowercase().ends_with(".ppm") {
+      self.save_ppm(path)
+    } else {
+      self.save_png(path)
+    }
+  }
+
+  // Loads a 256x240 PNG or PPM, for comparing against golden images
+  pub fn load(path: &str) -> Result<Frame, String> {
+    let bytes = std::fs::read(path).map_err(|error| format!("UNABLE TO READ {}: {}", path, error))?;
+    let (width, height, data) = if bytes.starts_with(b"P6") { ppm::read_rgb(&bytes)? } else { png::read_rgb(&bytes)? };
+
+    if width as usize != WIDTH || height as usize != HEIGHT {
+      return Err(format!("{} IS {}x{}, EXPECTED {}x{}", path, width, height, WIDTH, HEIGHT));
+    }
+
+    Ok(Frame { data })
+  }
+}
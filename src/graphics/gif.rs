@@ -0,0 +1,245 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+type Color = (u8, u8, u8);
+
+const MAX_CODE_BITS: u8 = 12;
+const MAX_COLORS: usize = 256;
+
+// Browsers show shorter delays as 1/10 s, so images that would get less are dropped and their
+// time handed to the next one
+const MIN_DELAY: u64 = 2;
+
+// Longest run of bytes in one image data sub-block
+const SUB_BLOCK_SIZE: usize = 255;
+
+// GIF89a encoder, colors are looked up exactly so NES frames never need quantizing
+pub struct GifWriter<W: Write> {
+  out: W,
+  width: usize,
+  height: usize,
+  global: HashMap<Color, u8>,
+  // Frame rate as a fraction, delays are rounded against the running total so they never drift
+  rate_num: u64,
+  rate_den: u64,
+  frames: u64,
+  // Frames the last written image stands for, merged while the picture does not change
+  pending: Option<(Vec<u8>, u64)>,
+  // Frames of dropped images still to be covered
+  dropped: u64
+}
+
+impl<W: Write> GifWriter<W> {
+  // `palette` becomes the global color table, frames with other colors carry their own table
+  pub fn new(mut out: W, width: usize, height: usize, palette: &[Color], rate_num: u32, rate_den: u32) -> io::Result<GifWriter<W>> {
+    let palette = &palette[..palette.len().min(MAX_COLORS)];
+    let table_bits = table_bits(palette.len());
+
+    out.write_all(b"GIF89a")?;
+    out.write_all(&(width as u16).to_le_bytes())?;
+    out.write_all(&(height as u16).to_le_bytes())?;
+    out.write_all(&[0x80 | ((table_bits - 1) << 4) | (table_bits - 1), 0, 0])?;
+    write_table(&mut out, palette, table_bits)?;
+
+    // NETSCAPE2.0 extension, loop forever
+    out.write_all(&[0x21, 0xFF, 0x0B])?;
+    out.write_all(b"NETSCAPE2.0")?;
+    out.write_all(&[0x03, 0x01, 0x00, 0x00, 0x00])?;
+
+    let global = palette.iter().enumerate().rev().map(|(index, color)| (*color, index as u8)).collect();
+    Ok(GifWriter {
+      out, width, height, global,
+      rate_num: rate_num as u64, rate_den: rate_den as u64,
+      frames: 0, pending: None, dropped: 0
+    })
+  }
+
+  // RGB24 pixels, `width` by `height`
+  pub fn write_frame(&mut self, rgb: &[u8]) -> io::Result<()> {
+    if let Some((previous, count)) = &mut self.pending {
+      if previous.as_slice() == rgb {
+        *count += 1;
+        return Ok(());
+      }
+    }
+
+    self.flush_pending(false)?;
+    self.pending = Some((rgb.to_vec(), 1));
+    Ok(())
+  }
+
+  pub fn finish(mut self) -> io::Result<()> {
+    self.flush_pending(true)?;
+    self.out.write_all(&[0x3B])?;
+    self.out.flush()
+  }
+
+  fn flush_pending(&mut self, last: bool) -> io::Result<()> {
+    let (rgb, count) = match self.pending.take() {
+      Some(pending) => pending,
+      None => return Ok(())
+    };
+
+    let count = count + self.dropped;
+    let delay = self.centiseconds(self.frames + count) - self.centiseconds(self.frames);
+    if delay < MIN_DELAY && !last {
+      self.dropped = count;
+      return Ok(());
+    }
+    self.dropped = 0;
+    self.frames += count;
+    let delay = delay.max(MIN_DELAY);
+
+    let global: Option<Vec<u8>> = rgb.chunks(3).map(|pixel| self.global.get(&(pixel[0], pixel[1], pixel[2])).copied()).collect();
+    let (indices, local) = match global {
+      Some(indices) => (indices, None),
+      None => {
+        let (colors, map) = local_table(&rgb)?;
+        (rgb.chunks(3).map(|pixel| map[&(pixel[0], pixel[1], pixel[2])]).collect(), Some(colors))
+      }
+    };
+
+    // Graphic control extension with the delay in hundredths of a second
+    self.out.write_all(&[0x21, 0xF9, 0x04, 0x00])?;
+    self.out.write_all(&(delay.min(u16::MAX as u64) as u16).to_le_bytes())?;
+    self.out.write_all(&[0x00, 0x00])?;
+
+    self.out.write_all(&[0x2C, 0, 0, 0, 0])?;
+    self.out.write_all(&(self.width as u16).to_le_bytes())?;
+    self.out.write_all(&(self.height as u16).to_le_bytes())?;
+
+    let color_bits = match &local {
+      Some(colors) => {
+        let bits = table_bits(colors.len());
+        self.out.write_all(&[0x80 | (bits - 1)])?;
+        write_table(&mut self.out, colors, bits)?;
+        bits
+      }
+      None => {
+        self.out.write_all(&[0x00])?;
+        table_bits(self.global.len())
+      }
+    };
+
+    let min_code_size = color_bits.max(2);
+    self.out.write_all(&[min_code_size])?;
+    for block in lzw_encode(&indices, min_code_size).chunks(SUB_BLOCK_SIZE) {
+      self.out.write_all(&[block.len() as u8])?;
+      self.out.write_all(block)?;
+    }
+    self.out.write_all(&[0x00])
+  }
+
+  fn centiseconds(&self, frames: u64) -> u64 {
+    (frames * 100 * self.rate_den + self.rate_num / 2) / self.rate_num
+  }
+}
+
+fn local_table(rgb: &[u8]) -> io::Result<(Vec<Color>, HashMap<Color, u8>)> {
+  let mut colors = Vec::new();
+  let mut map = HashMap::new();
+  for pixel in rgb.chunks(3) {
+    let color = (pixel[0], pixel[1], pixel[2]);
+    if let Entry::Vacant(entry) = map.entry(color) {
+      if colors.len() == MAX_COLORS {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "FRAME HAS MORE THAN 256 COLORS"));
+      }
+      entry.insert(colors.len() as u8);
+      colors.push(color);
+    }
+  }
+  Ok((colors, map))
+}
+
+// Smallest power of two table holding `count` colors, as a bit count from 1 to 8
+fn table_bits(count: usize) -> u8 {
+  let mut bits = 1;
+  while (1 << bits) < count {
+    bits += 1;
+  }
+  bits
+}
+
+fn write_table<W: Write>(out: &mut W, colors: &[Color], bits: u8) -> io::Result<()> {
+  let mut table = vec![0; (1 << bits) * 3];
+  for (index, color) in colors.iter().enumerate() {
+    table[index * 3..index * 3 + 3].copy_from_slice(&[color.0, color.1, color.2]);
+  }
+  out.write_all(&table)
+}
+
+struct BitWriter {
+  bytes: Vec<u8>,
+  buffer: u32,
+  bits: u8
+}
+
+impl BitWriter {
+  fn write(&mut self, code: u16, size: u8) {
+    self.buffer |= (code as u32) << self.bits;
+    self.bits += size;
+    while self.bits >= 8 {
+      self.bytes.push(self.buffer as u8);
+      self.buffer >>= 8;
+      self.bits -= 8;
+    }
+  }
+
+  fn finish(mut self) -> Vec<u8> {
+    if self.bits > 0 {
+      self.bytes.push(self.buffer as u8);
+    }
+    self.bytes
+  }
+}
+
+// Variable width LZW as GIF uses it, least significant bit first
+fn lzw_encode(indices: &[u8], min_code_size: u8) -> Vec<u8> {
+  let clear = 1u16 << min_code_size;
+  let end = clear + 1;
+
+  let mut writer = BitWriter { bytes: Vec::new(), buffer: 0, bits: 0 };
+  let mut dictionary: HashMap<(u16, u8), u16> = HashMap::new();
+  let mut next_code = end + 1;
+  let mut code_size = min_code_size + 1;
+
+  writer.write(clear, code_size);
+
+  let mut current: Option<u16> = None;
+  for index in indices {
+    let prefix = match current {
+      Some(prefix) => prefix,
+      None => {
+        current = Some(*index as u16);
+        continue;
+      }
+    };
+
+    if let Some(code) = dictionary.get(&(prefix, *index)) {
+      current = Some(*code);
+      continue;
+    }
+
+    writer.write(prefix, code_size);
+    if next_code == 1 << MAX_CODE_BITS {
+      writer.write(clear, code_size);
+      dictionary.clear();
+      next_code = end + 1;
+      code_size = min_code_size + 1;
+    } else {
+      dictionary.insert((prefix, *index), next_code);
+      if next_code == 1 << code_size && code_size < MAX_CODE_BITS {
+        code_size += 1;
+      }
+      next_code += 1;
+    }
+    current = Some(*index as u16);
+  }
+
+  if let Some(prefix) = current {
+    writer.write(prefix, code_size);
+  }
+  writer.write(end, code_size);
+  writer.finish()
+}
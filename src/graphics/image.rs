@@ -0,0 +1,46 @@
+use std::fs::File;
+use std::io::{self, BufWriter};
+
+use crate::graphics::frame::{Frame, WIDTH, HEIGHT};
+use crate::graphics::{png, ppm};
+
+// RGB24 picture of any size, for filter and scaler output that no longer fits a `Frame`
+#[derive(Clone, PartialEq, Debug)]
+pub struct Image {
+  pub width: usize,
+  pub height: usize,
+  pub data: Vec<u8>
+}
+
+impl Image {
+  pub fn new(width: usize, height: usize) -> Image {
+    Image { width, height, data: vec![0; width * height * 3] }
+  }
+
+  pub fn from_frame(frame: &Frame) -> Image {
+    Image { width: WIDTH, height: HEIGHT, data: frame.data.clone() }
+  }
+
+  pub fn get_pixel(&self, x: usize, y: usize) -> (u8, u8, u8) {
+    let first = (y * self.width + x) * 3;
+    (self.data[first], self.data[first + 1], self.data[first + 2])
+  }
+
+  pub fn set_pixel(&mut self, x: usize, y: usize, color: (u8, u8, u8)) {
+    let first = (y * self.width + x) * 3;
+
+    self.data[first] = color.0;
+    self.data[first + 1] = color.1;
+    self.data[first + 2] = color.2;
+  }
+
+  // Picks PPM for .ppm paths and PNG for everything else
+  pub fn save(&self, path: &str) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    if path.to_ascii_lowercase().ends_with(".ppm") {
+      ppm::write_rgb(&mut out, self.width as u32, self.height as u32, &self.data)
+    } else {
+      png::write_rgb(&mut out, self.width as u32, self.height as u32, &self.data)
+    }
+  }
+}
@@ -0,0 +1,65 @@
+use crate::graphics::frame::{Frame, WIDTH, HEIGHT};
+use crate::graphics::palette::{self, Palette};
+
+// Layouts `IndexFrame::convert` can produce, multi byte pixels are little endian
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PixelFormat {
+  Rgb24,
+  Rgba8888,
+  Bgra8888,
+  Rgb565
+}
+
+impl PixelFormat {
+  pub fn bytes_per_pixel(&self) -> usize {
+    match self {
+      PixelFormat::Rgb24 => 3,
+      PixelFormat::Rgba8888 | PixelFormat::Bgra8888 => 4,
+      PixelFormat::Rgb565 => 2
+    }
+  }
+}
+
+// Frame as palette entries, the 6 bit color index in the low bits and the 3 emphasis bits above
+pub struct IndexFrame {
+  pub data: Vec<u16>
+}
+
+impl Default for IndexFrame {
+  fn default() -> Self {
+    IndexFrame { data: vec![0; WIDTH * HEIGHT] }
+  }
+}
+
+impl IndexFrame {
+  pub fn set_pixel(&mut self, x: usize, y: usize, index: u8, grayscale: bool, emphasis: u8) {
+    self.data[y * WIDTH + x] = palette::entry(index, grayscale, emphasis);
+  }
+
+  pub fn get_pixel(&self, x: usize, y: usize) -> u16 {
+    self.data[y * WIDTH + x]
+  }
+
+  pub fn convert(&self, palette: &Palette, format: PixelFormat) -> Vec<u8> {
+    let mut out = Vec::with_capacity(self.data.len() * format.bytes_per_pixel());
+
+    for entry in &self.data {
+      let (red, green, blue) = palette.colors[*entry as usize];
+      match format {
+        PixelFormat::Rgb24 => out.extend_from_slice(&[red, green, blue]),
+        PixelFormat::Rgba8888 => out.extend_from_slice(&[red, green, blue, 0xFF]),
+        PixelFormat::Bgra8888 => out.extend_from_slice(&[blue, green, red, 0xFF]),
+        PixelFormat::Rgb565 => {
+          let pixel = ((red as u16 >> 3) << 11) | ((green as u16 >> 2) << 5) | (blue as u16 >> 3);
+          out.extend_from_slice(&pixel.to_le_bytes());
+        }
+      }
+    }
+
+    out
+  }
+
+  pub fn to_frame(&self, palette: &Palette) -> Frame {
+    Frame { data: self.convert(palette, PixelFormat::Rgb24) }
+  }
+}
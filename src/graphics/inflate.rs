@@ -0,0 +1,228 @@
+// Minimal zlib/deflate decoder (RFC 1950/1951), enough to read back PNG files
+
+const MAX_BITS: usize = 15;
+
+const LENGTH_BASE: [u16; 29] = [
+  3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258
+];
+const LENGTH_EXTRA: [u8; 29] = [
+  0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0
+];
+const DISTANCE_BASE: [u16; 30] = [
+  1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073,
+  4097, 6145, 8193, 12289, 16385, 24577
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+  0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13
+];
+
+// Order code length code lengths are stored in for dynamic blocks
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+struct BitReader<'a> {
+  data: &'a [u8],
+  position: usize,
+  bit: u8
+}
+
+impl<'a> BitReader<'a> {
+  fn bits(&mut self, count: u8) -> Result<u32, String> {
+    let mut value = 0;
+    for index in 0..count {
+      if self.position >= self.data.len() {
+        return Err("UNEXPECTED END OF DEFLATE STREAM".to_string());
+      }
+      let bit = (self.data[self.position] >> self.bit) & 0x01;
+      value |= (bit as u32) << index;
+      self.bit += 1;
+      if self.bit == 8 {
+        self.bit = 0;
+        self.position += 1;
+      }
+    }
+    Ok(value)
+  }
+
+  fn align(&mut self) {
+    if self.bit != 0 {
+      self.bit = 0;
+      self.position += 1;
+    }
+  }
+}
+
+// Canonical Huffman code stored as symbol counts per length and symbols sorted by code
+struct Huffman {
+  counts: [u16; MAX_BITS + 1],
+  symbols: Vec<u16>
+}
+
+impl Huffman {
+  fn new(lengths: &[u8]) -> Huffman {
+    let mut counts = [0u16; MAX_BITS + 1];
+    for length in lengths {
+      counts[*length as usize] += 1;
+    }
+    counts[0] = 0;
+
+    let mut offsets = [0u16; MAX_BITS + 2];
+    for length in 1..=MAX_BITS {
+      offsets[length + 1] = offsets[length] + counts[length];
+    }
+
+    let mut symbols = vec![0; lengths.len()];
+    for (symbol, length) in lengths.iter().enumerate() {
+      if *length != 0 {
+        symbols[offsets[*length as usize] as usize] = symbol as u16;
+        offsets[*length as usize] += 1;
+      }
+    }
+
+    Huffman { counts, symbols }
+  }
+
+  fn decode(&self, reader: &mut BitReader) -> Result<u16, String> {
+    let mut code: i32 = 0;
+    let mut first: i32 = 0;
+    let mut index: i32 = 0;
+
+    for length in 1..=MAX_BITS {
+      code |= reader.bits(1)? as i32;
+      let count = self.counts[length] as i32;
+      if code - count < first {
+        return Ok(self.symbols[(index + (code - first)) as usize]);
+      }
+      index += count;
+      first = (first + count) << 1;
+      code <<= 1;
+    }
+
+    Err("INVALID HUFFMAN CODE".to_string())
+  }
+}
+
+fn fixed_tables() -> (Huffman, Huffman) {
+  let mut lengths = [0u8; 288];
+  for (symbol, length) in lengths.iter_mut().enumerate() {
+    *length = match symbol {
+      0 ..= 143 => 8,
+      144 ..= 255 => 9,
+      256 ..= 279 => 7,
+      _ => 8
+    };
+  }
+  (Huffman::new(&lengths), Huffman::new(&[5; 30]))
+}
+
+fn dynamic_tables(reader: &mut BitReader) -> Result<(Huffman, Huffman), String> {
+  let literal_count = reader.bits(5)? as usize + 257;
+  let distance_count = reader.bits(5)? as usize + 1;
+  let code_length_count = reader.bits(4)? as usize + 4;
+
+  let mut code_lengths = [0u8; 19];
+  for index in CODE_LENGTH_ORDER.iter().take(code_length_count) {
+    code_lengths[*index] = reader.bits(3)? as u8;
+  }
+  let code_length_table = Huffman::new(&code_lengths);
+
+  let mut lengths = Vec::with_capacity(literal_count + distance_count);
+  while lengths.len() < literal_count + distance_count {
+    let symbol = code_length_table.decode(reader)?;
+    let (value, repeat) = match symbol {
+      0 ..= 15 => (symbol as u8, 1),
+      16 => {
+        let previous = *lengths.last().ok_or_else(|| "REPEAT WITH NO PREVIOUS LENGTH".to_string())?;
+        (previous, 3 + reader.bits(2)?)
+      }
+      17 => (0, 3 + reader.bits(3)?),
+      _ => (0, 11 + reader.bits(7)?)
+    };
+    for _x in 0..repeat {
+      lengths.push(value);
+    }
+  }
+
+  if lengths.len() > literal_count + distance_count {
+    return Err("CODE LENGTHS OVERRUN".to_string());
+  }
+
+  Ok((Huffman::new(&lengths[..literal_count]), Huffman::new(&lengths[literal_count..])))
+}
+
+fn inflate_block(reader: &mut BitReader, out: &mut Vec<u8>, literals: &Huffman, distances: &Huffman) -> Result<(), String> {
+  loop {
+    let symbol = literals.decode(reader)?;
+    match symbol {
+      0 ..= 255 => out.push(symbol as u8),
+      256 => return Ok(()),
+      _ => {
+        let index = (symbol - 257) as usize;
+        if index >= LENGTH_BASE.len() {
+          return Err("BAD LENGTH SYMBOL".to_string());
+        }
+        let length = LENGTH_BASE[index] as usize + reader.bits(LENGTH_EXTRA[index])? as usize;
+
+        let index = distances.decode(reader)? as usize;
+        if index >= DISTANCE_BASE.len() {
+          return Err("BAD DISTANCE SYMBOL".to_string());
+        }
+        let distance = DISTANCE_BASE[index] as usize + reader.bits(DISTANCE_EXTRA[index])? as usize;
+        if distance > out.len() {
+          return Err("DISTANCE TOO FAR BACK".to_string());
+        }
+
+        let start = out.len() - distance;
+        for offset in 0..length {
+          out.push(out[start + offset]);
+        }
+      }
+    }
+  }
+}
+
+// Decompresses a raw deflate stream
+pub fn inflate(data: &[u8]) -> Result<Vec<u8>, String> {
+  let mut reader = BitReader { data, position: 0, bit: 0 };
+  let mut out = Vec::new();
+
+  loop {
+    let last = reader.bits(1)? == 1;
+    match reader.bits(2)? {
+      0 => {
+        reader.align();
+        let position = reader.position;
+        if position + 4 > data.len() {
+          return Err("TRUNCATED STORED BLOCK".to_string());
+        }
+        let length = u16::from_le_bytes([data[position], data[position + 1]]) as usize;
+        let start = position + 4;
+        if start + length > data.len() {
+          return Err("TRUNCATED STORED BLOCK".to_string());
+        }
+        out.extend_from_slice(&data[start..(start + length)]);
+        reader.position = start + length;
+      }
+      1 => {
+        let (literals, distances) = fixed_tables();
+        inflate_block(&mut reader, &mut out, &literals, &distances)?;
+      }
+      2 => {
+        let (literals, distances) = dynamic_tables(&mut reader)?;
+        inflate_block(&mut reader, &mut out, &literals, &distances)?;
+      }
+      _ => return Err("BAD DEFLATE BLOCK TYPE".to_string())
+    }
+
+    if last {
+      return Ok(out);
+    }
+  }
+}
+
+// Decompresses a zlib wrapped deflate stream
+pub fn zlib_decompress(data: &[u8]) -> Result<Vec<u8>, String> {
+  if data.len() < 2 || data[0] & 0x0F != 8 || data[1] & 0x20 != 0 {
+    return Err("UNSUPPORTED ZLIB HEADER".to_string());
+  }
+  inflate(&data[2..])
+}
@@ -1 +1,13 @@
-pub mod frame;
\ No newline at end of file
+pub mod avi;
+pub mod frame;
+pub mod gif;
+pub mod image;
+pub mod index_frame;
+pub mod inflate;
+pub mod ntsc;
+pub mod palette;
+pub mod png;
+pub mod ppm;
+pub mod ppu_viewer;
+pub mod scale;
+pub mod y4m;
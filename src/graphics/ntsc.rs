@@ -0,0 +1,212 @@
+use std::f32::consts::PI;
+
+use crate::graphics::frame::{WIDTH, HEIGHT};
+use crate::graphics::image::Image;
+use crate::graphics::index_frame::IndexFrame;
+use crate::graphics::palette::{COLOR_COUNT, EMPHASIS_COUNT};
+
+// The PPU outputs 8 samples per pixel and the color subcarrier repeats every 12
+pub const SAMPLES_PER_PIXEL: usize = 8;
+const SAMPLES_PER_CYCLE: usize = 12;
+
+// 341 dots of 8 samples leave the next scanline 4 samples further along the subcarrier
+const SCANLINE_PHASE_STEP: usize = (341 * SAMPLES_PER_PIXEL) % SAMPLES_PER_CYCLE;
+
+// 7 output pixels for every 3 input pixels, the same width nes_ntsc produces
+pub const OUTPUT_WIDTH: usize = ((WIDTH - 1) / 3 + 1) * 7;
+
+// Composite voltages relative to sync, from the NTSC video page on the nesdev wiki
+const BLACK: f32 = 0.518;
+const WHITE: f32 = 1.962;
+const ATTENUATION: f32 = 0.746;
+const LEVELS_LOW: [f32; 4] = [0.350, 0.518, 0.962, 1.550];
+const LEVELS_HIGH: [f32; 4] = [1.094, 1.506, 1.962, 1.962];
+
+// Lines the decoder's color reference up with the PPU's color phases, in samples
+const HUE_OFFSET: f32 = 3.9;
+// Shift applied by a hue setting of 1.0, in samples
+const HUE_RANGE: f32 = 1.5;
+
+// Raw samples averaged into the luma that carries artifacts
+const ARTIFACT_WINDOW: usize = 4;
+
+// Decoded signal is treated as gamma 2.2 and displayed at 1.8
+const GAMMA: f32 = 2.2 / 1.8;
+
+// Knobs in the same -1.0 to 1.0 range nes_ntsc uses, 0.0 is the default look
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct NtscSetup {
+  pub hue: f32,
+  pub saturation: f32,
+  // Positive sharpens luma edges, negative blurs them
+  pub sharpness: f32,
+  // Subcarrier left in luma, seen as dot crawl and checkerboards
+  pub artifacts: f32,
+  // Luma edges decoded as color
+  pub fringing: f32,
+  // Color smeared into neighbouring pixels
+  pub bleed: f32
+}
+
+impl Default for NtscSetup {
+  fn default() -> Self {
+    NtscSetup::composite()
+  }
+}
+
+impl NtscSetup {
+  pub fn composite() -> NtscSetup {
+    NtscSetup { hue: 0.0, saturation: 0.0, sharpness: 0.0, artifacts: 0.0, fringing: 0.0, bleed: 0.0 }
+  }
+
+  pub fn svideo() -> NtscSetup {
+    NtscSetup { hue: 0.0, saturation: 0.0, sharpness: 0.2, artifacts: -1.0, fringing: -1.0, bleed: 0.0 }
+  }
+
+  pub fn rgb() -> NtscSetup {
+    NtscSetup { hue: 0.0, saturation: 0.0, sharpness: 0.2, artifacts: -1.0, fringing: -1.0, bleed: -1.0 }
+  }
+
+  pub fn monochrome() -> NtscSetup {
+    NtscSetup { hue: 0.0, saturation: -1.0, sharpness: 0.2, artifacts: -1.0, fringing: -1.0, bleed: -1.0 }
+  }
+}
+
+pub struct NtscFilter {
+  pub setup: NtscSetup,
+  // Normalized signal level for every palette entry at every subcarrier phase
+  levels: Vec<[f32; SAMPLES_PER_CYCLE]>
+}
+
+impl NtscFilter {
+  pub fn new(setup: NtscSetup) -> NtscFilter {
+    let levels = (0..COLOR_COUNT * EMPHASIS_COUNT).map(|entry| {
+      let mut levels = [0.0; SAMPLES_PER_CYCLE];
+      for (phase, level) in levels.iter_mut().enumerate() {
+        *level = signal_level(entry as u16, phase);
+      }
+      levels
+    }).collect();
+
+    NtscFilter { setup, levels }
+  }
+
+  // `phase` is where the subcarrier starts for the frame, advancing it every frame gives dot crawl
+  pub fn render(&self, frame: &IndexFrame, phase: usize) -> Image {
+    let mut image = Image::new(OUTPUT_WIDTH, HEIGHT);
+    for y in 0..HEIGHT {
+      let line_phase = (phase + y * SCANLINE_PHASE_STEP) % SAMPLES_PER_CYCLE;
+      self.render_line(frame, y, line_phase, &mut image);
+    }
+    image
+  }
+
+  fn render_line(&self, frame: &IndexFrame, y: usize, phase: usize, image: &mut Image) {
+    // Black padding on both sides so the windows never run off the line, a whole cycle so
+    // signal indices keep the subcarrier phase
+    let padding = SAMPLES_PER_CYCLE;
+    let mut signal = vec![0.0; OUTPUT_WIDTH / 7 * 3 * SAMPLES_PER_PIXEL + padding * 2];
+    for x in 0..WIDTH {
+      let levels = &self.levels[frame.get_pixel(x, y) as usize];
+      for sample in 0..SAMPLES_PER_PIXEL {
+        let position = x * SAMPLES_PER_PIXEL + sample;
+        signal[padding + position] = levels[(phase + position) % SAMPLES_PER_CYCLE];
+      }
+    }
+
+    // Averaging a whole subcarrier cycle removes the color from luma
+    let mut sums = vec![0.0; signal.len() + 1];
+    for (index, sample) in signal.iter().enumerate() {
+      sums[index + 1] = sums[index] + sample;
+    }
+    let average = |start: usize, length: usize| (sums[start + length] - sums[start]) / length as f32;
+    let luma: Vec<f32> = (0..signal.len()).map(|index| {
+      if index < SAMPLES_PER_CYCLE / 2 || index + SAMPLES_PER_CYCLE / 2 > signal.len() {
+        return 0.0;
+      }
+      average(index - SAMPLES_PER_CYCLE / 2, SAMPLES_PER_CYCLE)
+    }).collect();
+
+    let artifacts = (self.setup.artifacts + 1.0) / 2.0;
+    let fringing = (self.setup.fringing + 1.0) / 2.0;
+    let saturation = self.setup.saturation + 1.0;
+    let hue = HUE_OFFSET + self.setup.hue * HUE_RANGE;
+
+    let mut lumas = Vec::with_capacity(OUTPUT_WIDTH);
+    let mut chromas = Vec::with_capacity(OUTPUT_WIDTH);
+    for x in 0..OUTPUT_WIDTH {
+      let center = padding + (x * 3 * SAMPLES_PER_PIXEL + 3 * SAMPLES_PER_PIXEL / 2) / 7;
+
+      let raw = average(center - ARTIFACT_WINDOW / 2, ARTIFACT_WINDOW);
+      lumas.push(luma[center] + artifacts * (raw - luma[center]));
+
+      let mut i = 0.0;
+      let mut q = 0.0;
+      for index in (center - SAMPLES_PER_CYCLE / 2)..(center + SAMPLES_PER_CYCLE / 2) {
+        let chroma = signal[index] - (1.0 - fringing) * luma[index];
+        let angle = PI * ((phase + index) as f32 + hue) / 6.0;
+        i += chroma * angle.cos();
+        q += chroma * angle.sin();
+      }
+      let scale = saturation * 2.0 / SAMPLES_PER_CYCLE as f32;
+      chromas.push((i * scale, q * scale));
+    }
+
+    let sharpness = self.setup.sharpness / 2.0;
+    let bleed = (self.setup.bleed + 1.0) / 2.0;
+    for x in 0..OUTPUT_WIDTH {
+      let left = x.saturating_sub(1);
+      let right = (x + 1).min(OUTPUT_WIDTH - 1);
+      let luma = lumas[x] + sharpness * (lumas[x] - (lumas[left] + lumas[right]) / 2.0);
+
+      let (mut i, mut q) = chromas[x];
+      i += bleed * ((chromas[left].0 + chromas[right].0) / 2.0 - i);
+      q += bleed * ((chromas[left].1 + chromas[right].1) / 2.0 - q);
+
+      image.set_pixel(x, y, (
+        to_channel(luma + 0.946_882 * i + 0.623_557 * q),
+        to_channel(luma - 0.274_788 * i - 0.635_691 * q),
+        to_channel(luma - 1.108_545 * i + 1.709_007 * q)
+      ));
+    }
+  }
+}
+
+fn to_channel(value: f32) -> u8 {
+  if value <= 0.0 {
+    return 0;
+  }
+  (value.powf(GAMMA) * 255.0).round().min(255.0) as u8
+}
+
+// Level of the square wave the PPU generates for a palette entry at one subcarrier phase
+fn signal_level(entry: u16, phase: usize) -> f32 {
+  let color = (entry & 0x0F) as usize;
+  let mut level = ((entry >> 4) & 0x03) as usize;
+  let emphasis = entry >> 6;
+
+  // Columns $E and $F are always the darkest black
+  if color > 13 {
+    level = 1;
+  }
+
+  let in_phase = |color: usize| (color + phase) % SAMPLES_PER_CYCLE < 6;
+
+  let mut low = LEVELS_LOW[level];
+  let mut high = LEVELS_HIGH[level];
+  if color == 0 {
+    low = high;
+  }
+  if color > 12 {
+    high = low;
+  }
+
+  let mut signal = if in_phase(color) { high } else { low };
+  if (emphasis & 0b001 != 0 && in_phase(0))
+    || (emphasis & 0b010 != 0 && in_phase(4))
+    || (emphasis & 0b100 != 0 && in_phase(8)) {
+    signal *= ATTENUATION;
+  }
+
+  (signal - BLACK) / (WHITE - BLACK)
+}
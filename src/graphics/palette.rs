@@ -0,0 +1,121 @@
+use std::fs;
+
+pub const COLOR_COUNT: usize = 64;
+pub const EMPHASIS_COUNT: usize = 8;
+
+// .pal files come as 64 colors, or 64 colors for each of the 8 emphasis combinations
+const PAL_SIZE: usize = COLOR_COUNT * 3;
+const PAL_EMPHASIS_SIZE: usize = COLOR_COUNT * EMPHASIS_COUNT * 3;
+
+// Grayscale keeps only the luma column of the palette index
+const GRAYSCALE_MASK: u8 = 0x30;
+
+// Emphasized channels keep their level while the others are dimmed to roughly this fraction
+const ATTENUATION_NUM: u16 = 3;
+const ATTENUATION_DEN: u16 = 4;
+
+// 2C02 colors as commonly measured from NTSC hardware
+const NTSC_2C02: [(u8, u8, u8); COLOR_COUNT] = [
+  (0x80, 0x80, 0x80), (0x00, 0x3D, 0xA6), (0x00, 0x12, 0xB0), (0x44, 0x00, 0x96),
+  (0xA1, 0x00, 0x5E), (0xC7, 0x00, 0x28), (0xBA, 0x06, 0x00), (0x8C, 0x17, 0x00),
+  (0x5C, 0x2F, 0x00), (0x10, 0x45, 0x00), (0x05, 0x4A, 0x00), (0x00, 0x47, 0x2E),
+  (0x00, 0x41, 0x66), (0x00, 0x00, 0x00), (0x05, 0x05, 0x05), (0x05, 0x05, 0x05),
+  (0xC7, 0xC7, 0xC7), (0x00, 0x77, 0xFF), (0x21, 0x55, 0xFF), (0x82, 0x37, 0xFA),
+  (0xEB, 0x2F, 0xB5), (0xFF, 0x29, 0x50), (0xFF, 0x22, 0x00), (0xD6, 0x32, 0x00),
+  (0xC4, 0x62, 0x00), (0x35, 0x80, 0x00), (0x05, 0x8F, 0x00), (0x00, 0x8A, 0x55),
+  (0x00, 0x99, 0xCC), (0x21, 0x21, 0x21), (0x09, 0x09, 0x09), (0x09, 0x09, 0x09),
+  (0xFF, 0xFF, 0xFF), (0x0F, 0xD7, 0xFF), (0x69, 0xA2, 0xFF), (0xD4, 0x80, 0xFF),
+  (0xFF, 0x45, 0xF3), (0xFF, 0x61, 0x8B), (0xFF, 0x88, 0x33), (0xFF, 0x9C, 0x12),
+  (0xFA, 0xBC, 0x20), (0x9F, 0xE3, 0x0E), (0x2B, 0xF0, 0x35), (0x0C, 0xF0, 0xA4),
+  (0x05, 0xFB, 0xFF), (0x5E, 0x5E, 0x5E), (0x0D, 0x0D, 0x0D), (0x0D, 0x0D, 0x0D),
+  (0xFF, 0xFF, 0xFF), (0xA6, 0xFC, 0xFF), (0xB3, 0xEC, 0xFF), (0xDA, 0xAB, 0xEB),
+  (0xFF, 0xA8, 0xF9), (0xFF, 0xAB, 0xB3), (0xFF, 0xD2, 0xB0), (0xFF, 0xEF, 0xA6),
+  (0xFF, 0xF7, 0x9C), (0xD7, 0xE8, 0x95), (0xA6, 0xED, 0xAF), (0xA2, 0xF2, 0xDA),
+  (0x99, 0xFF, 0xFC), (0xDD, 0xDD, 0xDD), (0x11, 0x11, 0x11), (0x11, 0x11, 0x11)
+];
+
+// Maps 6 bit palette indices plus PPUMASK emphasis to RGB
+pub struct Palette {
+  // Emphasis major, 64 colors for each of the 8 emphasis values
+  pub colors: Vec<(u8, u8, u8)>
+}
+
+impl Default for Palette {
+  fn default() -> Self {
+    Palette::from_colors(&NTSC_2C02)
+  }
+}
+
+impl Palette {
+  // Builds the emphasis variants from a plain 64 color palette
+  pub fn from_colors(colors: &[(u8, u8, u8); COLOR_COUNT]) -> Palette {
+    let mut table = Vec::with_capacity(COLOR_COUNT * EMPHASIS_COUNT);
+    for emphasis in 0..EMPHASIS_COUNT as u8 {
+      for color in colors.iter() {
+        table.push(emphasize(*color, emphasis));
+      }
+    }
+    Palette { colors: table }
+  }
+
+  pub fn from_bytes(bytes: &[u8]) -> Result<Palette, String> {
+    if bytes.len() != PAL_SIZE && bytes.len() != PAL_EMPHASIS_SIZE {
+      return Err(format!("PALETTE MUST BE {} OR {} BYTES, GOT {}", PAL_SIZE, PAL_EMPHASIS_SIZE, bytes.len()));
+    }
+
+    let colors: Vec<(u8, u8, u8)> = bytes.chunks(3).map(|rgb| (rgb[0], rgb[1], rgb[2])).collect();
+    if colors.len() == COLOR_COUNT {
+      let mut base = [(0, 0, 0); COLOR_COUNT];
+      base.copy_from_slice(&colors);
+      return Ok(Palette::from_colors(&base));
+    }
+    Ok(Palette { colors })
+  }
+
+  pub fn load(path: &str) -> Result<Palette, String> {
+    let bytes = fs::read(path).map_err(|error| format!("UNABLE TO READ PALETTE {}: {}", path, error))?;
+    Palette::from_bytes(&bytes)
+  }
+
+  // `emphasis` is the PPUMASK emphasis bits shifted down to 0-7
+  pub fn rgb(&self, index: u8, grayscale: bool, emphasis: u8) -> (u8, u8, u8) {
+    self.colors[entry(index, grayscale, emphasis) as usize]
+  }
+}
+
+// Position in `Palette::colors`, the 6 bit color index with the emphasis bits above it
+pub fn entry(index: u8, grayscale: bool, emphasis: u8) -> u16 {
+  let mut index = index as u16 & (COLOR_COUNT as u16 - 1);
+  if grayscale {
+    index &= GRAYSCALE_MASK as u16;
+  }
+  ((emphasis as u16 & (EMPHASIS_COUNT as u16 - 1)) << 6) | index
+}
+
+fn attenuate(level: u8) -> u8 {
+  (level as u16 * ATTENUATION_NUM / ATTENUATION_DEN) as u8
+}
+
+// Emphasis bits are red, green, blue from bit 0, setting one dims the other two channels.
+// Each bit darkens the part of the signal away from its own hue, so with all three set there
+// is nothing left undimmed.
+fn emphasize(color: (u8, u8, u8), emphasis: u8) -> (u8, u8, u8) {
+  if emphasis == 0 {
+    return color;
+  }
+
+  let (mut red, mut green, mut blue) = color;
+  if emphasis == 0b111 {
+    return (attenuate(red), attenuate(green), attenuate(blue));
+  }
+  if emphasis & 0b001 == 0 {
+    red = attenuate(red);
+  }
+  if emphasis & 0b010 == 0 {
+    green = attenuate(green);
+  }
+  if emphasis & 0b100 == 0 {
+    blue = attenuate(blue);
+  }
+  (red, green, blue)
+}
@@ -0,0 +1,169 @@
+use std::io::{self, Write};
+
+use crate::graphics::inflate;
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A];
+
+// Largest payload of a stored (uncompressed) deflate block
+const MAX_STORED_BLOCK: usize = 0xFFFF;
+
+// Writes 8 bit RGB pixel data as a PNG using uncompressed deflate blocks
+pub fn write_rgb<W: Write>(out: &mut W, width: u32, height: u32, rgb: &[u8]) -> io::Result<()> {
+  let mut header = Vec::with_capacity(13);
+  header.extend_from_slice(&width.to_be_bytes());
+  header.extend_from_slice(&height.to_be_bytes());
+  // Bit depth 8, color type 2 (RGB), default compression, filter and interlace
+  header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+  // Every scanline starts with its filter type, 0 means none
+  let row_length = width as usize * 3;
+  let mut raw = Vec::with_capacity((row_length + 1) * height as usize);
+  for row in rgb.chunks(row_length).take(height as usize) {
+    raw.push(0);
+    raw.extend_from_slice(row);
+  }
+
+  out.write_all(&PNG_SIGNATURE)?;
+  write_chunk(out, b"IHDR", &header)?;
+  write_chunk(out, b"IDAT", &zlib_stored(&raw))?;
+  write_chunk(out, b"IEND", &[])
+}
+
+// Reads an 8 bit RGB or RGBA, non interlaced PNG, returning width, height and RGB data
+pub fn read_rgb(bytes: &[u8]) -> Result<(u32, u32, Vec<u8>), String> {
+  if bytes.len() < PNG_SIGNATURE.len() || bytes[..PNG_SIGNATURE.len()] != PNG_SIGNATURE {
+    return Err("FILE IS NOT A PNG".to_string());
+  }
+
+  let mut position = PNG_SIGNATURE.len();
+  let mut header = None;
+  let mut compressed = Vec::new();
+
+  while position + 12 <= bytes.len() {
+    let length = u32::from_be_bytes([bytes[position], bytes[position + 1], bytes[position + 2], bytes[position + 3]]) as usize;
+    let kind = &bytes[(position + 4)..(position + 8)];
+    let data_start = position + 8;
+    if data_start + length + 4 > bytes.len() {
+      return Err("TRUNCATED PNG CHUNK".to_string());
+    }
+    let data = &bytes[data_start..(data_start + length)];
+
+    match kind {
+      b"IHDR" if length == 13 => header = Some(data.to_vec()),
+      b"IDAT" => compressed.extend_from_slice(data),
+      b"IEND" => break,
+      _ => {}
+    }
+
+    position = data_start + length + 4;
+  }
+
+  let header = header.ok_or_else(|| "PNG HAS NO HEADER".to_string())?;
+  let width = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+  let height = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+  let channels = match (header[8], header[9], header[12]) {
+    (8, 2, 0) => 3,
+    (8, 6, 0) => 4,
+    _ => return Err("ONLY 8 BIT NON INTERLACED RGB AND RGBA PNG FILES ARE SUPPORTED".to_string())
+  };
+
+  let raw = inflate::zlib_decompress(&compressed)?;
+  let stride = width as usize * channels;
+  if raw.len() < (stride + 1) * height as usize {
+    return Err("TRUNCATED PNG IMAGE DATA".to_string());
+  }
+
+  let mut pixels = vec![0u8; stride * height as usize];
+  for row in 0..height as usize {
+    let filter = raw[row * (stride + 1)];
+    let line = &raw[(row * (stride + 1) + 1)..((row + 1) * (stride + 1))];
+    for column in 0..stride {
+      let left = if column >= channels { pixels[row * stride + column - channels] } else { 0 };
+      let up = if row > 0 { pixels[(row - 1) * stride + column] } else { 0 };
+      let up_left = if row > 0 && column >= channels { pixels[(row - 1) * stride + column - channels] } else { 0 };
+
+      let predicted = match filter {
+        0 => 0,
+        1 => left,
+        2 => up,
+        3 => ((left as u16 + up as u16) / 2) as u8,
+        4 => paeth(left, up, up_left),
+        _ => return Err(format!("BAD PNG FILTER TYPE {}", filter))
+      };
+      pixels[row * stride + column] = line[column].wrapping_add(predicted);
+    }
+  }
+
+  if channels == 4 {
+    pixels = pixels.chunks(4).flat_map(|pixel| pixel[..3].to_vec()).collect();
+  }
+
+  Ok((width, height, pixels))
+}
+
+fn paeth(left: u8, up: u8, up_left: u8) -> u8 {
+  let estimate = left as i16 + up as i16 - up_left as i16;
+  let distance_left = (estimate - left as i16).abs();
+  let distance_up = (estimate - up as i16).abs();
+  let distance_up_left = (estimate - up_left as i16).abs();
+
+  if distance_left <= distance_up && distance_left <= distance_up_left {
+    left
+  } else if distance_up <= distance_up_left {
+    up
+  } else {
+    up_left
+  }
+}
+
+fn write_chunk<W: Write>(out: &mut W, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+  out.write_all(&(data.len() as u32).to_be_bytes())?;
+  out.write_all(kind)?;
+  out.write_all(data)?;
+
+  let mut crc = crc32_update(0xFFFF_FFFF, kind);
+  crc = crc32_update(crc, data);
+  out.write_all(&(crc ^ 0xFFFF_FFFF).to_be_bytes())
+}
+
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+  // CMF/FLG for deflate with a 32K window and no preset dictionary
+  let mut out = vec![0x78, 0x01];
+
+  let mut blocks = data.chunks(MAX_STORED_BLOCK).peekable();
+  if blocks.peek().is_none() {
+    out.extend_from_slice(&[0x01, 0x00, 0x00, 0xFF, 0xFF]);
+  }
+
+  while let Some(block) = blocks.next() {
+    let last = blocks.peek().is_none();
+    let length = block.len() as u16;
+    out.push(if last { 0x01 } else { 0x00 });
+    out.extend_from_slice(&length.to_le_bytes());
+    out.extend_from_slice(&(!length).to_le_bytes());
+    out.extend_from_slice(block);
+  }
+
+  out.extend_from_slice(&adler32(data).to_be_bytes());
+  out
+}
+
+fn adler32(data: &[u8]) -> u32 {
+  let mut a: u32 = 1;
+  let mut b: u32 = 0;
+  for byte in data {
+    a = (a + *byte as u32) % 65521;
+    b = (b + a) % 65521;
+  }
+  (b << 16) | a
+}
+
+fn crc32_update(mut crc: u32, data: &[u8]) -> u32 {
+  for byte in data {
+    crc ^= *byte as u32;
+    for _x in 0..8 {
+      crc = if crc & 1 != 0 { 0xEDB8_8320 ^ (crc >> 1) } else { crc >> 1 };
+    }
+  }
+  crc
+}
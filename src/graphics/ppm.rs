@@ -0,0 +1,56 @@
+use std::io::{self, Write};
+
+// Writes 8 bit RGB pixel data as a binary (P6) PPM
+pub fn write_rgb<W: Write>(out: &mut W, width: u32, height: u32, rgb: &[u8]) -> io::Result<()> {
+  write!(out, "P6\n{} {}\n255\n", width, height)?;
+  out.write_all(&rgb[..(width * height * 3) as usize])
+}
+
+// Reads a binary (P6) PPM with a maximum value of 255, returning width, height and RGB data
+pub fn read_rgb(bytes: &[u8]) -> Result<(u32, u32, Vec<u8>), String> {
+  let mut position = 0;
+  let mut fields = Vec::new();
+
+  // Magic, width, height and max value are whitespace separated, with # comments allowed
+  while fields.len() < 4 {
+    while position < bytes.len() && (bytes[position].is_ascii_whitespace() || bytes[position] == b'#') {
+      if bytes[position] == b'#' {
+        while position < bytes.len() && bytes[position] != b'\n' {
+          position += 1;
+        }
+      } else {
+        position += 1;
+      }
+    }
+
+    let start = position;
+    while position < bytes.len() && !bytes[position].is_ascii_whitespace() {
+      position += 1;
+    }
+    if start == position {
+      return Err("TRUNCATED PPM HEADER".to_string());
+    }
+    fields.push(String::from_utf8_lossy(&bytes[start..position]).to_string());
+  }
+
+  // Exactly one whitespace byte separates the header from the pixels
+  position += 1;
+
+  if fields[0] != "P6" {
+    return Err("ONLY BINARY P6 PPM FILES ARE SUPPORTED".to_string());
+  }
+
+  let parse = |field: &String| field.parse::<u32>().map_err(|_| format!("BAD PPM HEADER FIELD \"{}\"", field));
+  let width = parse(&fields[1])?;
+  let height = parse(&fields[2])?;
+  if parse(&fields[3])? != 255 {
+    return Err("ONLY 8 BIT PPM FILES ARE SUPPORTED".to_string());
+  }
+
+  let length = (width * height * 3) as usize;
+  if bytes.len() < position + length {
+    return Err("TRUNCATED PPM PIXEL DATA".to_string());
+  }
+
+  Ok((width, height, bytes[position..(position + length)].to_vec()))
+}
@@ -0,0 +1,158 @@
+use crate::emu::ppu::PPU;
+use crate::graphics::image::Image;
+use crate::graphics::palette::Palette;
+
+const TILE_SIZE: usize = 8;
+const TILE_BYTES: u16 = 16;
+const TILES_PER_ROW: usize = 16;
+const PATTERN_TABLE_SIZE: usize = TILES_PER_ROW * TILE_SIZE;
+
+const NAMETABLE_WIDTH: usize = 256;
+const NAMETABLE_HEIGHT: usize = 240;
+const ATTRIBUTE_OFFSET: u16 = 0x3C0;
+
+const SPRITE_COUNT: usize = 64;
+const SPRITES_PER_ROW: usize = 8;
+// Every cell is tall enough for 8x16 sprites, with a gap between cells
+const SPRITE_CELL_WIDTH: usize = TILE_SIZE + 2;
+const SPRITE_CELL_HEIGHT: usize = TILE_SIZE * 2 + 2;
+const SPRITE_PALETTE_BITS: u8 = 0b11;
+const SPRITE_FLIP_H_BIT: u8 = 0b0100_0000;
+const SPRITE_FLIP_V_BIT: u8 = 0b1000_0000;
+
+const PALETTE_SWATCH: usize = 16;
+
+// Color of one of the 4 pixel values under one of the 8 palettes, value 0 is the backdrop
+fn palette_color(ppu: &PPU, palette: &Palette, palette_number: u8, value: u8) -> (u8, u8, u8) {
+  let entry = if value == 0 { 0 } else { palette_number * 4 + value };
+  palette.rgb(ppu.palette_entry(entry), false, 0)
+}
+
+// 2 bit value of a pixel in a tile
+fn tile_pixel(ppu: &PPU, tile_addr: u16, x: usize, y: usize) -> u8 {
+  let low = ppu.chr_byte(tile_addr + y as u16);
+  let high = ppu.chr_byte(tile_addr + y as u16 + 8);
+  let bit = 7 - x;
+  (((high >> bit) & 1) << 1) | ((low >> bit) & 1)
+}
+
+// Both pattern tables side by side, 256x128, drawn with one of the 8 palettes
+pub fn pattern_tables(ppu: &PPU, palette: &Palette, palette_number: u8) -> Image {
+  let mut image = Image::new(PATTERN_TABLE_SIZE * 2, PATTERN_TABLE_SIZE);
+  for table in 0..2 {
+    for tile in 0..(TILES_PER_ROW * TILES_PER_ROW) {
+      let tile_addr = table as u16 * 0x1000 + tile as u16 * TILE_BYTES;
+      let left = table * PATTERN_TABLE_SIZE + (tile % TILES_PER_ROW) * TILE_SIZE;
+      let top = (tile / TILES_PER_ROW) * TILE_SIZE;
+
+      for y in 0..TILE_SIZE {
+        for x in 0..TILE_SIZE {
+          let value = tile_pixel(ppu, tile_addr, x, y);
+          image.set_pixel(left + x, top + y, palette_color(ppu, palette, palette_number & 0x07, value));
+        }
+      }
+    }
+  }
+  image
+}
+
+// All four logical nametables as a 512x480 image with the scroll window outlined
+pub fn nametables(ppu: &PPU, palette: &Palette) -> Image {
+  let mut image = Image::new(NAMETABLE_WIDTH * 2, NAMETABLE_HEIGHT * 2);
+  let pattern_table = ppu.background_pattern_table();
+
+  for table in 0..4u16 {
+    let left = (table as usize % 2) * NAMETABLE_WIDTH;
+    let top = (table as usize / 2) * NAMETABLE_HEIGHT;
+
+    for row in 0..(NAMETABLE_HEIGHT / TILE_SIZE) {
+      for column in 0..(NAMETABLE_WIDTH / TILE_SIZE) {
+        let tile = ppu.nametable_byte(table, (row * 32 + column) as u16);
+        let attribute = ppu.nametable_byte(table, ATTRIBUTE_OFFSET + (row / 4 * 8 + column / 4) as u16);
+        let shift = ((row % 4) / 2 * 2 + (column % 4) / 2) * 2;
+        let palette_number = (attribute >> shift) & 0b11;
+
+        for y in 0..TILE_SIZE {
+          for x in 0..TILE_SIZE {
+            let value = tile_pixel(ppu, pattern_table + tile as u16 * TILE_BYTES, x, y);
+            let color = palette_color(ppu, palette, palette_number, value);
+            image.set_pixel(left + column * TILE_SIZE + x, top + row * TILE_SIZE + y, color);
+          }
+        }
+      }
+    }
+  }
+
+  outline_scroll(&mut image, ppu.scroll());
+  image
+}
+
+// Inverts the border of the visible area, wrapping around the edges like scrolling does
+fn outline_scroll(image: &mut Image, (scroll_x, scroll_y): (u16, u16)) {
+  let mut invert = |x: usize, y: usize| {
+    let x = x % image.width;
+    let y = y % image.height;
+    let (red, green, blue) = image.get_pixel(x, y);
+    image.set_pixel(x, y, (!red, !green, !blue));
+  };
+
+  let (left, top) = (scroll_x as usize, scroll_y as usize);
+  for x in 0..NAMETABLE_WIDTH {
+    invert(left + x, top);
+    invert(left + x, top + NAMETABLE_HEIGHT - 1);
+  }
+  for y in 1..(NAMETABLE_HEIGHT - 1) {
+    invert(left, top + y);
+    invert(left + NAMETABLE_WIDTH - 1, top + y);
+  }
+}
+
+// OAM entries in index order, 8 per row, with their palette and flips applied
+pub fn sprites(ppu: &PPU, palette: &Palette) -> Image {
+  let rows = SPRITE_COUNT / SPRITES_PER_ROW;
+  let mut image = Image::new(SPRITES_PER_ROW * SPRITE_CELL_WIDTH, rows * SPRITE_CELL_HEIGHT);
+  let height = if ppu.tall_sprites() { TILE_SIZE * 2 } else { TILE_SIZE };
+
+  for sprite in 0..SPRITE_COUNT {
+    let tile = ppu.oam_data[sprite * 4 + 1];
+    let attributes = ppu.oam_data[sprite * 4 + 2];
+    let palette_number = 4 + (attributes & SPRITE_PALETTE_BITS);
+
+    // 8x16 sprites take the table from bit 0 of the tile number
+    let (table, tile) = if ppu.tall_sprites() {
+      ((tile as u16 & 1) * 0x1000, tile & 0xFE)
+    } else {
+      (ppu.sprite_pattern_table(), tile)
+    };
+
+    let left = (sprite % SPRITES_PER_ROW) * SPRITE_CELL_WIDTH + 1;
+    let top = (sprite / SPRITES_PER_ROW) * SPRITE_CELL_HEIGHT + 1;
+    for y in 0..height {
+      for x in 0..TILE_SIZE {
+        let source_y = if attributes & SPRITE_FLIP_V_BIT != 0 { height - 1 - y } else { y };
+        let source_x = if attributes & SPRITE_FLIP_H_BIT != 0 { TILE_SIZE - 1 - x } else { x };
+        let tile_addr = table + (tile as u16 + (source_y / TILE_SIZE) as u16) * TILE_BYTES;
+
+        let value = tile_pixel(ppu, tile_addr, source_x, source_y % TILE_SIZE);
+        image.set_pixel(left + x, top + y, palette_color(ppu, palette, palette_number, value));
+      }
+    }
+  }
+  image
+}
+
+// Palette RAM as two rows of 16 swatches, background on top and sprites below
+pub fn palettes(ppu: &PPU, palette: &Palette) -> Image {
+  let mut image = Image::new(16 * PALETTE_SWATCH, 2 * PALETTE_SWATCH);
+  for entry in 0..32u8 {
+    let color = palette.rgb(ppu.palette_entry(entry), false, 0);
+    let left = (entry as usize % 16) * PALETTE_SWATCH;
+    let top = (entry as usize / 16) * PALETTE_SWATCH;
+    for y in 0..PALETTE_SWATCH {
+      for x in 0..PALETTE_SWATCH {
+        image.set_pixel(left + x, top + y, color);
+      }
+    }
+  }
+  image
+}
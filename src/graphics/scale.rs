@@ -0,0 +1,518 @@
+use std::str::FromStr;
+
+use crate::graphics::image::Image;
+
+type Color = (u8, u8, u8);
+
+// Thresholds hqx uses to call two colors different, on its own YUV scale
+const HQX_Y_THRESHOLD: i32 = 48;
+const HQX_U_THRESHOLD: i32 = 7;
+const HQX_V_THRESHOLD: i32 = 6;
+
+// xBR treats colors closer than this as equal
+const XBR_EQUAL_DISTANCE: i32 = 155;
+
+// hq2x blend rule for each pattern of neighbours that differ from the center, seen from the top
+// left corner. Bits from 0 up are the top left, top, top right, left, right, bottom left, bottom
+// and bottom right neighbours. Rules are numbered as in `hqx_rule`.
+const HQX_RULES: [u8; 256] = [
+  4, 4, 6, 2, 4, 4, 6, 2, 5, 3, 15, 12, 5, 3, 17, 13,
+  4, 4, 6, 18, 4, 4, 6, 18, 5, 3, 12, 12, 5, 3, 1, 12,
+  4, 4, 6, 2, 4, 4, 6, 2, 5, 3, 17, 13, 5, 3, 16, 14,
+  4, 4, 6, 18, 4, 4, 6, 18, 5, 3, 16, 12, 5, 3, 1, 14,
+  4, 4, 6, 2, 4, 4, 6, 2, 5, 19, 12, 12, 5, 19, 16, 12,
+  4, 4, 6, 2, 4, 4, 6, 2, 5, 3, 16, 12, 5, 3, 16, 12,
+  4, 4, 6, 2, 4, 4, 6, 2, 5, 19, 1, 12, 5, 19, 1, 14,
+  4, 4, 6, 2, 4, 4, 6, 18, 5, 3, 16, 12, 5, 19, 1, 14,
+  4, 4, 6, 2, 4, 4, 6, 2, 5, 3, 15, 12, 5, 3, 17, 13,
+  4, 4, 6, 2, 4, 4, 6, 2, 5, 3, 16, 12, 5, 3, 16, 12,
+  4, 4, 6, 2, 4, 4, 6, 2, 5, 3, 17, 13, 5, 3, 16, 14,
+  4, 4, 6, 2, 4, 4, 6, 2, 5, 3, 16, 13, 5, 3, 1, 14,
+  4, 4, 6, 2, 4, 4, 6, 2, 5, 3, 16, 12, 5, 3, 16, 13,
+  4, 4, 6, 2, 4, 4, 6, 2, 5, 3, 16, 12, 5, 3, 1, 12,
+  4, 4, 6, 2, 4, 4, 6, 2, 5, 3, 16, 12, 5, 3, 1, 14,
+  4, 4, 6, 2, 4, 4, 6, 2, 5, 3, 1, 12, 5, 3, 1, 14
+];
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Scaler {
+  // Integer pixel repeat
+  Nearest(usize),
+  Scale2x,
+  Scale3x,
+  Hq2x,
+  Hq3x,
+  Hq4x,
+  // xBR level 2 at 2x, 3x or 4x
+  Xbr(usize)
+}
+
+impl Scaler {
+  pub fn factor(&self) -> usize {
+    match self {
+      Scaler::Nearest(factor) | Scaler::Xbr(factor) => *factor,
+      Scaler::Scale2x | Scaler::Hq2x => 2,
+      Scaler::Scale3x | Scaler::Hq3x => 3,
+      Scaler::Hq4x => 4
+    }
+  }
+
+  pub fn apply(&self, image: &Image) -> Image {
+    match self {
+      Scaler::Nearest(factor) => nearest(image, *factor),
+      Scaler::Scale2x => scale2x(image),
+      Scaler::Scale3x => scale3x(image),
+      Scaler::Hq2x | Scaler::Hq3x | Scaler::Hq4x => hqx(image, self.factor()),
+      Scaler::Xbr(factor) => xbr(image, *factor)
+    }
+  }
+}
+
+// Names as accepted on the command line, "nearest3", "scale2x", "hq4x", "xbr2" and so on
+impl FromStr for Scaler {
+  type Err = String;
+
+  fn from_str(name: &str) -> Result<Self, Self::Err> {
+    let name = name.to_ascii_lowercase();
+    let scaler = match name.as_str() {
+      "scale2x" => Scaler::Scale2x,
+      "scale3x" => Scaler::Scale3x,
+      "hq2x" => Scaler::Hq2x,
+      "hq3x" => Scaler::Hq3x,
+      "hq4x" => Scaler::Hq4x,
+      _ if name.starts_with("nearest") => Scaler::Nearest(parse_factor(&name["nearest".len()..], 1..=8)?),
+      _ if name.starts_with("xbr") => Scaler::Xbr(parse_factor(&name["xbr".len()..], 2..=4)?),
+      _ => return Err(format!("UNKNOWN SCALER {}", name))
+    };
+    Ok(scaler)
+  }
+}
+
+fn parse_factor(text: &str, range: std::ops::RangeInclusive<usize>) -> Result<usize, String> {
+  let factor = text.trim_end_matches('x').parse::<usize>().map_err(|_| format!("BAD SCALE FACTOR {}", text))?;
+  if !range.contains(&factor) {
+    return Err(format!("SCALE FACTOR {} OUT OF RANGE", factor));
+  }
+  Ok(factor)
+}
+
+// Lines and columns to hide, most TVs cut off about 8 lines at the top and bottom
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Overscan {
+  pub top: usize,
+  pub bottom: usize,
+  pub left: usize,
+  pub right: usize
+}
+
+impl Default for Overscan {
+  fn default() -> Self {
+    Overscan { top: 8, bottom: 8, left: 0, right: 0 }
+  }
+}
+
+impl Overscan {
+  pub fn crop(&self, image: &Image) -> Image {
+    let width = image.width.saturating_sub(self.left + self.right);
+    let height = image.height.saturating_sub(self.top + self.bottom);
+
+    let mut cropped = Image::new(width, height);
+    for y in 0..height {
+      let start = ((y + self.top) * image.width + self.left) * 3;
+      cropped.data[(y * width * 3)..((y + 1) * width * 3)].copy_from_slice(&image.data[start..(start + width * 3)]);
+    }
+    cropped
+  }
+}
+
+// Pixel at an offset from (x, y), repeating the edge for offsets outside the image
+fn neighbour(image: &Image, x: usize, y: usize, dx: i32, dy: i32) -> Color {
+  let x = (x as i32 + dx).clamp(0, image.width as i32 - 1) as usize;
+  let y = (y as i32 + dy).clamp(0, image.height as i32 - 1) as usize;
+  image.get_pixel(x, y)
+}
+
+fn nearest(image: &Image, factor: usize) -> Image {
+  let mut scaled = Image::new(image.width * factor, image.height * factor);
+  for y in 0..scaled.height {
+    for x in 0..scaled.width {
+      scaled.set_pixel(x, y, image.get_pixel(x / factor, y / factor));
+    }
+  }
+  scaled
+}
+
+// AdvanceMAME Scale2x, see scale2x.it for the rules
+fn scale2x(image: &Image) -> Image {
+  let mut scaled = Image::new(image.width * 2, image.height * 2);
+  for y in 0..image.height {
+    for x in 0..image.width {
+      let b = neighbour(image, x, y, 0, -1);
+      let d = neighbour(image, x, y, -1, 0);
+      let e = image.get_pixel(x, y);
+      let f = neighbour(image, x, y, 1, 0);
+      let h = neighbour(image, x, y, 0, 1);
+
+      let mut block = [e; 4];
+      if b != h && d != f {
+        block[0] = if d == b { d } else { e };
+        block[1] = if b == f { f } else { e };
+        block[2] = if d == h { d } else { e };
+        block[3] = if h == f { f } else { e };
+      }
+
+      for (index, color) in block.iter().enumerate() {
+        scaled.set_pixel(x * 2 + index % 2, y * 2 + index / 2, *color);
+      }
+    }
+  }
+  scaled
+}
+
+fn scale3x(image: &Image) -> Image {
+  let mut scaled = Image::new(image.width * 3, image.height * 3);
+  for y in 0..image.height {
+    for x in 0..image.width {
+      let a = neighbour(image, x, y, -1, -1);
+      let b = neighbour(image, x, y, 0, -1);
+      let c = neighbour(image, x, y, 1, -1);
+      let d = neighbour(image, x, y, -1, 0);
+      let e = image.get_pixel(x, y);
+      let f = neighbour(image, x, y, 1, 0);
+      let g = neighbour(image, x, y, -1, 1);
+      let h = neighbour(image, x, y, 0, 1);
+      let i = neighbour(image, x, y, 1, 1);
+
+      let mut block = [e; 9];
+      if b != h && d != f {
+        block[0] = if d == b { d } else { e };
+        block[1] = if (d == b && e != c) || (b == f && e != a) { b } else { e };
+        block[2] = if b == f { f } else { e };
+        block[3] = if (d == b && e != g) || (d == h && e != a) { d } else { e };
+        block[5] = if (b == f && e != i) || (h == f && e != c) { f } else { e };
+        block[6] = if d == h { d } else { e };
+        block[7] = if (d == h && e != i) || (h == f && e != g) { h } else { e };
+        block[8] = if h == f { f } else { e };
+      }
+
+      for (index, color) in block.iter().enumerate() {
+        scaled.set_pixel(x * 3 + index % 3, y * 3 + index / 3, *color);
+      }
+    }
+  }
+  scaled
+}
+
+// Neighbourhood around a pixel seen from one of its corners, offsets are given for the bottom
+// right corner and rotated for the others
+struct Corner<'a> {
+  image: &'a Image,
+  x: usize,
+  y: usize,
+  rotation: u8
+}
+
+impl<'a> Corner<'a> {
+  fn rotate(&self, dx: i32, dy: i32) -> (i32, i32) {
+    match self.rotation {
+      0 => (dx, dy),
+      1 => (-dy, dx),
+      2 => (-dx, -dy),
+      _ => (dy, -dx)
+    }
+  }
+
+  fn at(&self, dx: i32, dy: i32) -> Color {
+    let (dx, dy) = self.rotate(dx, dy);
+    neighbour(self.image, self.x, self.y, dx, dy)
+  }
+
+  // Index in a row major `factor` x `factor` block of the sub pixel at (x, y), counted with
+  // this corner at the bottom right
+  fn sub_index(&self, factor: usize, x: usize, y: usize) -> usize {
+    let last = factor as i32 - 1;
+    let (x, y) = self.rotate(2 * x as i32 - last, 2 * y as i32 - last);
+    ((y + last) / 2) as usize * factor + ((x + last) / 2) as usize
+  }
+}
+
+// Scales pixel by pixel, `block` fills in the factor x factor colors for one source pixel
+fn blocks(image: &Image, factor: usize, block: fn(&Image, usize, usize, usize) -> Vec<Color>) -> Image {
+  let mut scaled = Image::new(image.width * factor, image.height * factor);
+  for y in 0..image.height {
+    for x in 0..image.width {
+      for (index, color) in block(image, x, y, factor).into_iter().enumerate() {
+        scaled.set_pixel(x * factor + index % factor, y * factor + index / factor, color);
+      }
+    }
+  }
+  scaled
+}
+
+// Neighbours hqx reads for a corner, named for the top left corner as the reference does: `a`
+// is the corner, `b` and `d` the sides next to it, `f` and `h` the far sides
+struct HqNeighbours {
+  e: Color,
+  a: Color,
+  b: Color,
+  d: Color,
+  f: Color,
+  h: Color
+}
+
+// Blend an hqx rule comes to once its neighbour checks are made
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum HqBlend {
+  Center,
+  TowardsA,
+  TowardsB,
+  TowardsD,
+  TowardsBD,
+  TowardsAB,
+  TowardsAD,
+  // An edge cuts the corner between `b` and `d`, from weakest to strongest
+  Diagonal(u8),
+  // Shallow edges carrying on past `b` towards `f`, or past `d` towards `h`
+  AlongB,
+  AlongD
+}
+
+// hqx from the hq2x pattern table. Each corner of a pixel looks up the rule for which of its
+// neighbours differ. HQ2x draws the rule as one pixel, HQ4x as a 2x2 quadrant with finer
+// steps, and HQ3x takes its corners from the HQ4x quadrants and averages the edge pixels two
+// corners share. The center of HQ3x stays the source color.
+fn hqx(image: &Image, factor: usize) -> Image {
+  blocks(image, factor, |image, x, y, factor| {
+    let center = image.get_pixel(x, y);
+    let mut block = vec![center; factor * factor];
+    let mut shared = vec![false; factor * factor];
+    for rotation in 0..4 {
+      // Seen from the bottom right, so the hqx top left names are turned half way round
+      let corner = Corner { image, x, y, rotation };
+      let neighbours = [(1, 1), (0, 1), (-1, 1), (1, 0), (-1, 0), (1, -1), (0, -1), (-1, -1)];
+      let mut pattern = 0;
+      for (bit, (dx, dy)) in neighbours.iter().enumerate() {
+        if !hqx_similar(center, corner.at(*dx, *dy)) {
+          pattern |= 1 << bit;
+        }
+      }
+      let n = HqNeighbours { e: center, a: corner.at(1, 1), b: corner.at(0, 1), d: corner.at(1, 0), f: corner.at(-1, 0), h: corner.at(0, -1) };
+      let rule = hqx_rule(HQX_RULES[pattern], &n);
+
+      if factor == 2 {
+        block[corner.sub_index(2, 1, 1)] = hq2x_pixel(rule, &n);
+        continue;
+      }
+      let quadrant = hq4x_quadrant(rule, &n);
+      let last = factor - 1;
+      let positions = [(last, last), (last - 1, last), (last, last - 1), (last - 1, last - 1)];
+      for (color, (sub_x, sub_y)) in quadrant.iter().zip(positions.iter()) {
+        if factor == 3 && *sub_x != last && *sub_y != last {
+          continue;
+        }
+        let index = corner.sub_index(factor, *sub_x, *sub_y);
+        block[index] = if shared[index] { blend(&[(1, block[index]), (1, *color)]) } else { *color };
+        shared[index] = true;
+      }
+    }
+    block
+  })
+}
+
+// Rules 0-6 always blend the same way, 12-17 cut the corner when both sides match and 18 and
+// 19 follow a shallow edge when the far side matches
+fn hqx_rule(rule: u8, n: &HqNeighbours) -> HqBlend {
+  let diagonal = hqx_similar(n.b, n.d);
+  match rule {
+    0 => HqBlend::Center,
+    1 => HqBlend::TowardsA,
+    2 => HqBlend::TowardsD,
+    3 => HqBlend::TowardsB,
+    4 => HqBlend::TowardsBD,
+    5 => HqBlend::TowardsAB,
+    6 => HqBlend::TowardsAD,
+    12 ..= 17 if diagonal => HqBlend::Diagonal([2, 0, 3, 2, 1, 3][(rule - 12) as usize]),
+    12 ..= 14 => HqBlend::Center,
+    15 ..= 17 => HqBlend::TowardsA,
+    18 if hqx_similar(n.b, n.f) => HqBlend::AlongB,
+    18 => HqBlend::TowardsD,
+    19 if hqx_similar(n.d, n.h) => HqBlend::AlongD,
+    _ => HqBlend::TowardsB
+  }
+}
+
+fn hq2x_pixel(rule: HqBlend, n: &HqNeighbours) -> Color {
+  let (e, a, b, d) = (n.e, n.a, n.b, n.d);
+  match rule {
+    HqBlend::Center => e,
+    HqBlend::TowardsA => blend(&[(3, e), (1, a)]),
+    HqBlend::TowardsB => blend(&[(3, e), (1, b)]),
+    HqBlend::TowardsD => blend(&[(3, e), (1, d)]),
+    HqBlend::TowardsBD => blend(&[(2, e), (1, b), (1, d)]),
+    HqBlend::TowardsAB => blend(&[(2, e), (1, a), (1, b)]),
+    HqBlend::TowardsAD => blend(&[(2, e), (1, a), (1, d)]),
+    HqBlend::Diagonal(0) => blend(&[(14, e), (1, b), (1, d)]),
+    HqBlend::Diagonal(1) => blend(&[(6, e), (1, b), (1, d)]),
+    HqBlend::Diagonal(2) => blend(&[(2, e), (1, b), (1, d)]),
+    HqBlend::Diagonal(_) => blend(&[(2, e), (3, b), (3, d)]),
+    HqBlend::AlongB => blend(&[(5, e), (2, b), (1, d)]),
+    HqBlend::AlongD => blend(&[(5, e), (2, d), (1, b)])
+  }
+}
+
+// The corner pixel, the pixel beside it along the `b` side, the one beside it along the `d`
+// side and the inner pixel
+fn hq4x_quadrant(rule: HqBlend, n: &HqNeighbours) -> [Color; 4] {
+  let (e, a, b, d) = (n.e, n.a, n.b, n.d);
+  match rule {
+    HqBlend::Center => [e; 4],
+    HqBlend::TowardsA => [blend(&[(5, e), (3, a)]), blend(&[(3, e), (1, a)]), blend(&[(3, e), (1, a)]), blend(&[(7, e), (1, a)])],
+    HqBlend::TowardsB => [blend(&[(5, e), (3, b)]), blend(&[(5, e), (3, b)]), blend(&[(7, e), (1, b)]), blend(&[(7, e), (1, b)])],
+    HqBlend::TowardsD => [blend(&[(5, e), (3, d)]), blend(&[(7, e), (1, d)]), blend(&[(5, e), (3, d)]), blend(&[(7, e), (1, d)])],
+    HqBlend::TowardsBD => [
+      blend(&[(2, e), (1, b), (1, d)]),
+      blend(&[(5, e), (2, b), (1, d)]),
+      blend(&[(5, e), (2, d), (1, b)]),
+      blend(&[(6, e), (1, b), (1, d)])
+    ],
+    HqBlend::TowardsAB => [blend(&[(5, e), (3, a)]), blend(&[(5, e), (2, b), (1, a)]), blend(&[(3, e), (1, a)]), blend(&[(7, e), (1, a)])],
+    HqBlend::TowardsAD => [blend(&[(5, e), (3, a)]), blend(&[(3, e), (1, a)]), blend(&[(5, e), (2, d), (1, a)]), blend(&[(7, e), (1, a)])],
+    HqBlend::Diagonal(0) => [blend(&[(6, e), (1, b), (1, d)]), blend(&[(7, e), (1, b)]), blend(&[(7, e), (1, d)]), e],
+    HqBlend::Diagonal(1) => [blend(&[(2, e), (1, b), (1, d)]), blend(&[(7, e), (1, b)]), blend(&[(7, e), (1, d)]), e],
+    HqBlend::Diagonal(2) => [blend(&[(2, e), (1, b), (1, d)]), blend(&[(3, e), (1, b)]), blend(&[(3, e), (1, d)]), e],
+    HqBlend::Diagonal(_) => [blend(&[(1, b), (1, d)]), blend(&[(1, e), (1, b)]), blend(&[(1, e), (1, d)]), e],
+    HqBlend::AlongB => [blend(&[(3, e), (1, b)]), blend(&[(1, e), (3, b)]), blend(&[(5, e), (3, d)]), blend(&[(7, e), (1, d)])],
+    HqBlend::AlongD => [blend(&[(3, e), (1, d)]), blend(&[(5, e), (3, b)]), blend(&[(1, e), (3, d)]), blend(&[(7, e), (1, b)])]
+  }
+}
+
+// How xBR level 2 blends a corner, by the slope of the edge it found
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum XbrShape {
+  Diagonal,
+  // Shallow edges that run along the bottom or up the right of the block
+  Left,
+  Up,
+  LeftUp
+}
+
+// Sub pixels as (x, y, alpha out of 256) counted with the corner at the bottom right, then
+// sub pixels copied from one to another once the blends are done
+type XbrSteps = (&'static [(usize, usize, u32)], &'static [((usize, usize), (usize, usize))]);
+
+fn xbr_steps(factor: usize, shape: XbrShape) -> XbrSteps {
+  match (factor, shape) {
+    (2, XbrShape::Diagonal) => (&[(1, 1, 128)], &[]),
+    (2, XbrShape::Left) => (&[(1, 1, 192), (0, 1, 64)], &[]),
+    (2, XbrShape::Up) => (&[(1, 1, 192), (1, 0, 64)], &[]),
+    (2, _) => (&[(1, 1, 224), (0, 1, 64)], &[((0, 1), (1, 0))]),
+    (3, XbrShape::Diagonal) => (&[(2, 2, 224), (2, 1, 32), (1, 2, 32)], &[]),
+    (3, XbrShape::Left) => (&[(1, 2, 192), (2, 1, 64), (0, 2, 64), (2, 2, 256)], &[]),
+    (3, XbrShape::Up) => (&[(2, 1, 192), (1, 2, 64), (2, 0, 64), (2, 2, 256)], &[]),
+    (3, _) => (&[(1, 2, 192), (0, 2, 64), (2, 2, 256)], &[((1, 2), (2, 1)), ((0, 2), (2, 0))]),
+    (_, XbrShape::Diagonal) => (&[(3, 2, 128), (2, 3, 128), (3, 3, 256)], &[]),
+    (_, XbrShape::Left) => (&[(3, 2, 192), (1, 3, 192), (2, 2, 64), (0, 3, 64), (2, 3, 256), (3, 3, 256)], &[]),
+    (_, XbrShape::Up) => (&[(2, 3, 192), (3, 1, 192), (2, 2, 64), (3, 0, 64), (3, 2, 256), (3, 3, 256)], &[]),
+    (_, _) => (
+      &[(1, 3, 192), (0, 3, 64), (3, 3, 256), (2, 3, 256), (3, 2, 256)],
+      &[((0, 3), (2, 2)), ((0, 3), (3, 0)), ((1, 3), (3, 1))]
+    )
+  }
+}
+
+// Hyllian's xBR level 2. Each corner weighs the color differences along both diagonals through
+// it and, when the edge runs across the corner, works out its slope from the pixels beyond and
+// blends the sub pixels it covers towards the closer side.
+fn xbr(image: &Image, factor: usize) -> Image {
+  blocks(image, factor, |image, x, y, factor| {
+    let mut block = vec![image.get_pixel(x, y); factor * factor];
+    // Corners in the order the reference runs them, later ones blend over earlier ones
+    for rotation in &[0, 3, 2, 1] {
+      xbr_corner(&Corner { image, x, y, rotation: *rotation }, factor, &mut block);
+    }
+    block
+  })
+}
+
+fn xbr_corner(corner: &Corner, factor: usize, block: &mut [Color]) {
+  let e = corner.at(0, 0);
+  let h = corner.at(0, 1);
+  let f = corner.at(1, 0);
+  if e == h || e == f {
+    return;
+  }
+  let b = corner.at(0, -1);
+  let c = corner.at(1, -1);
+  let d = corner.at(-1, 0);
+  let g = corner.at(-1, 1);
+  let i = corner.at(1, 1);
+  let f4 = corner.at(2, 0);
+  let i4 = corner.at(2, 1);
+  let h5 = corner.at(0, 2);
+  let i5 = corner.at(1, 2);
+
+  let edge = distance(e, c) + distance(e, g) + distance(i, h5) + distance(i, f4) + 4 * distance(h, f);
+  let across = distance(h, d) + distance(h, i5) + distance(f, i4) + distance(f, b) + 4 * distance(e, i);
+  let color = if distance(e, f) <= distance(e, h) { f } else { h };
+
+  let steps = if edge < across
+    && ((!xbr_equal(f, b) && !xbr_equal(h, d)) || (xbr_equal(e, i) && !xbr_equal(f, i4) && !xbr_equal(h, i5)) || xbr_equal(e, g) || xbr_equal(e, c)) {
+    let ke = distance(f, g);
+    let ki = distance(h, c);
+    let left = ke * 2 <= ki && e != g && d != g;
+    let up = ke >= ki * 2 && e != c && b != c;
+    let shape = match (left, up) {
+      (true, true) => XbrShape::LeftUp,
+      (true, false) => XbrShape::Left,
+      (false, true) => XbrShape::Up,
+      (false, false) => XbrShape::Diagonal
+    };
+    xbr_steps(factor, shape)
+  } else if edge <= across {
+    // Too unsure of the slope, only the corner itself gets half way
+    let last = factor - 1;
+    let index = corner.sub_index(factor, last, last);
+    block[index] = blend(&[(1, block[index]), (1, color)]);
+    return;
+  } else {
+    return;
+  };
+
+  let (blends, copies) = steps;
+  for (sub_x, sub_y, alpha) in blends {
+    let index = corner.sub_index(factor, *sub_x, *sub_y);
+    block[index] = blend(&[(256 - alpha, block[index]), (*alpha, color)]);
+  }
+  for ((from_x, from_y), (to_x, to_y)) in copies {
+    block[corner.sub_index(factor, *to_x, *to_y)] = block[corner.sub_index(factor, *from_x, *from_y)];
+  }
+}
+
+// hqx YUV, kept in integers so the thresholds match the reference implementation
+fn yuv(color: Color) -> (i32, i32, i32) {
+  let (red, green, blue) = (color.0 as i32, color.1 as i32, color.2 as i32);
+  ((red + green + blue) >> 2, 128 + ((red - blue) >> 2), 128 + ((2 * green - red - blue) >> 3))
+}
+
+fn hqx_similar(a: Color, b: Color) -> bool {
+  let (y1, u1, v1) = yuv(a);
+  let (y2, u2, v2) = yuv(b);
+  (y1 - y2).abs() <= HQX_Y_THRESHOLD && (u1 - u2).abs() <= HQX_U_THRESHOLD && (v1 - v2).abs() <= HQX_V_THRESHOLD
+}
+
+fn distance(a: Color, b: Color) -> i32 {
+  let (y1, u1, v1) = yuv(a);
+  let (y2, u2, v2) = yuv(b);
+  HQX_Y_THRESHOLD * (y1 - y2).abs() + HQX_U_THRESHOLD * (u1 - u2).abs() + HQX_V_THRESHOLD * (v1 - v2).abs()
+}
+
+fn xbr_equal(a: Color, b: Color) -> bool {
+  distance(a, b) < XBR_EQUAL_DISTANCE
+}
+
+// Weighted average of colors, rounding down like the reference interpolations
+fn blend(parts: &[(u32, Color)]) -> Color {
+  let total: u32 = parts.iter().map(|(weight, _)| weight).sum();
+  let channel = |pick: fn(Color) -> u8| (parts.iter().map(|(weight, color)| weight * pick(*color) as u32).sum::<u32>() / total) as u8;
+  (channel(|color| color.0), channel(|color| color.1), channel(|color| color.2))
+}
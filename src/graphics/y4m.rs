@@ -0,0 +1,43 @@
+use std::io::{self, Write};
+
+use crate::graphics::frame::{Frame, WIDTH, HEIGHT};
+
+// YUV4MPEG2 stream, 4:4:4 so no chroma is thrown away before the encoder sees it
+pub struct Y4mWriter<W: Write> {
+  out: W,
+  plane: Vec<u8>
+}
+
+impl<W: Write> Y4mWriter<W> {
+  // Frame rate is a fraction, e.g. 39375000:655171 for NTSC
+  pub fn new(mut out: W, rate_num: u32, rate_den: u32) -> io::Result<Y4mWriter<W>> {
+    writeln!(out, "YUV4MPEG2 W{} H{} F{}:{} Ip A1:1 C444", WIDTH, HEIGHT, rate_num, rate_den)?;
+    Ok(Y4mWriter { out, plane: vec![0; WIDTH * HEIGHT * 3] })
+  }
+
+  pub fn write_frame(&mut self, frame: &Frame) -> io::Result<()> {
+    let pixels = WIDTH * HEIGHT;
+    for (index, rgb) in frame.data.chunks(3).enumerate() {
+      let (y, u, v) = rgb_to_yuv(rgb[0], rgb[1], rgb[2]);
+      self.plane[index] = y;
+      self.plane[pixels + index] = u;
+      self.plane[pixels * 2 + index] = v;
+    }
+
+    self.out.write_all(b"FRAME\n")?;
+    self.out.write_all(&self.plane)
+  }
+
+  pub fn finish(mut self) -> io::Result<()> {
+    self.out.flush()
+  }
+}
+
+// BT.601 studio range, in fixed point
+fn rgb_to_yuv(red: u8, green: u8, blue: u8) -> (u8, u8, u8) {
+  let (red, green, blue) = (red as i32, green as i32, blue as i32);
+  let y = ((66 * red + 129 * green + 25 * blue + 128) >> 8) + 16;
+  let u = ((-38 * red - 74 * green + 112 * blue + 128) >> 8) + 128;
+  let v = ((112 * red - 94 * green - 18 * blue + 128) >> 8) + 128;
+  (y as u8, u as u8, v as u8)
+}
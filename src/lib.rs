@@ -1,3 +1,5 @@
 #![allow(dead_code)]
+#![allow(clippy::needless_return)]
+pub mod audio;
 pub mod emu;
 pub mod graphics;
\ No newline at end of file
@@ -1,39 +1,469 @@
-#![allow(dead_code)]
-mod emu;
-mod graphics;
-
 use clap::Clap;
+use std::fs::File;
+use std::io::BufWriter;
 use std::time::Instant;
-use crate::emu::cpu::CPU;
-use crate::emu::bus::Bus;
-use crate::emu::cartridge::Cartridge;
+use nes_emu::audio::mixer::Mixer;
+use nes_emu::audio::wav::WavWriter;
+use nes_emu::emu::bus::Bus;
+use nes_emu::emu::cartridge::Cartridge;
+use nes_emu::emu::input;
+use nes_emu::emu::movie::Movie;
+use nes_emu::emu::nes::{ExitCondition, NES};
+use nes_emu::emu::nsf::Nsf;
+use nes_emu::emu::nsf_player::NsfPlayer;
+use nes_emu::emu::recorder::SAMPLE_RATE;
+use nes_emu::emu::trace::{Tracer, TraceFormat, TraceSink};
+use nes_emu::graphics::image::Image;
+use nes_emu::graphics::palette::Palette;
+use nes_emu::graphics::ppu_viewer;
+use nes_emu::graphics::scale::{Overscan, Scaler};
 
 #[derive(Clap)]
 struct Opts {
+  #[clap(subcommand)]
+  pub command: Command
+}
+
+// Parsed once at startup, so the variant size difference does not matter
+#[allow(clippy::large_enum_variant)]
+#[derive(Clap)]
+enum Command {
+  /// Run a ROM headlessly and report how it stopped
+  Run(RunOpts),
+  /// Measure raw emulation speed
+  Bench(BenchOpts),
+  /// List the tracks of an NSF or NSFe file, or render one to WAV
+  Nsf(NsfOpts)
+}
+
+#[derive(Clap)]
+struct RunOpts {
   #[clap(short, long)]
-  pub rom_path: String
+  pub rom_path: String,
+  /// Maximum number of frames to run
+  #[clap(long, default_value = "60")]
+  pub frames: u64,
+  /// FM2 movie supplying controller input, its devices replace the ones in the ports
+  #[clap(long)]
+  pub input: Option<String>,
+  /// Record controller input as an FM2 movie, from power on or from --load-state
+  #[clap(long)]
+  pub record_movie: Option<String>,
+  /// Start from a save state instead of power on
+  #[clap(long, conflicts_with = "input")]
+  pub load_state: Option<String>,
+  /// Save the state the run ends in
+  #[clap(long)]
+  pub save_state: Option<String>,
+  /// Write the final frame as a PNG, or a PPM for .ppm paths
+  #[clap(long)]
+  pub screenshot: Option<String>,
+  /// Scaler for the screenshot, e.g. nearest3, scale2x, hq4x or xbr2
+  #[clap(long)]
+  pub scale: Option<Scaler>,
+  /// Hide the top and bottom 8 lines of the screenshot like a TV would
+  #[clap(long)]
+  pub crop_overscan: bool,
+  /// Palette file, 192 bytes or 1536 bytes with emphasis variants
+  #[clap(long)]
+  pub palette: Option<String>,
+  /// Record video as Y4M, or uncompressed AVI or animated GIF for .avi and .gif paths
+  #[clap(long)]
+  pub record_video: Option<String>,
+  /// Only record frames in this range, as START-END with END included
+  #[clap(long, parse(try_from_str = parse_frame_range))]
+  pub record_frames: Option<(u64, u64)>,
+  /// Record audio as a 16 bit WAV
+  #[clap(long)]
+  pub record_audio: Option<String>,
+  /// Write pattern table, nametable, sprite and palette images to this directory
+  #[clap(long)]
+  pub dump_ppu: Option<String>,
+  /// Write the 2 KiB of internal RAM when the run ends
+  #[clap(long)]
+  pub dump_ram: Option<String>,
+  /// Stop once the program counter reaches this hex address
+  #[clap(long, parse(try_from_str = parse_hex_u16))]
+  pub until_pc: Option<u16>,
+  /// Stop once memory matches, as hex ADDR=VALUE
+  #[clap(long, parse(try_from_str = parse_memory_value))]
+  pub until_mem: Vec<(u16, u8)>,
+  /// Stop once a frame hashes to this hex value
+  #[clap(long, parse(try_from_str = parse_hex_u64))]
+  pub until_hash: Option<u64>,
+  /// Device in controller port 1: joypad, zapper, vaus or power-pad
+  #[clap(long)]
+  pub port1: Option<String>,
+  /// Device in controller port 2, instead of what the ROM header asks for
+  #[clap(long)]
+  pub port2: Option<String>,
+  /// Famicom expansion port device: none, hori, vaus, family-trainer or keyboard
+  #[clap(long)]
+  pub expansion: Option<String>,
+  #[clap(flatten)]
+  pub mixer: MixerOpts,
+  #[clap(flatten)]
+  pub trace: TraceOpts
 }
 
-fn main() {
-  let opts = Opts::parse();
-  let mut cpu = CPU::new(Some("blah".to_string()));
+#[derive(Clap)]
+struct BenchOpts {
+  #[clap(short, long)]
+  pub rom_path: String,
+  #[clap(long, default_value = "20000")]
+  pub cycles: u32,
+  #[clap(flatten)]
+  pub trace: TraceOpts
+}
 
-  let cycle_count = 20_000;
-  let mut cycles = 0;
+#[derive(Clap)]
+struct NsfOpts {
+  #[clap(short, long)]
+  pub nsf_path: String,
+  /// Track to render, counted from 1, defaults to the file's starting track
+  #[clap(long)]
+  pub track: Option<u8>,
+  /// Seconds to render, defaults to the NSFe track time or 150
+  #[clap(long)]
+  pub seconds: Option<f64>,
+  /// Render the track to a 16 bit WAV
+  #[clap(long)]
+  pub wav: Option<String>,
+  /// Render every mixer channel to its own WAV in this directory
+  #[clap(long, conflicts_with = "wav")]
+  pub stems: Option<String>,
+  #[clap(flatten)]
+  pub mixer: MixerOpts
+}
+
+#[derive(Clap)]
+struct MixerOpts {
+  /// Silence a mixer channel, e.g. "pulse1" or "vrc6-saw"
+  #[clap(long)]
+  pub mute: Vec<String>,
+  /// Only play the soloed mixer channels
+  #[clap(long)]
+  pub solo: Vec<String>,
+  /// Scale a mixer channel, as CHANNEL=GAIN
+  #[clap(long, parse(try_from_str = parse_gain))]
+  pub gain: Vec<(String, f32)>
+}
+
+#[derive(Clap)]
+struct TraceOpts {
+  /// Trace destination, a file path or "-" for stdout
+  #[clap(long)]
+  pub trace: Option<String>,
+  /// nestest, mesen or fceux
+  #[clap(long, default_value = "nestest")]
+  pub trace_format: TraceFormat,
+  /// Add PPU scanline and dot columns to the trace
+  #[clap(long)]
+  pub trace_ppu: bool,
+  /// Only keep the last N instructions, written out if the emulator crashes
+  #[clap(long)]
+  pub trace_last: Option<usize>,
+  /// Only trace instructions in this address range, as hex START-END with END included
+  #[clap(long, parse(try_from_str = parse_address_range))]
+  pub trace_addr: Option<(u16, u16)>,
+  /// Only trace during these frames, as START-END with END included
+  #[clap(long, parse(try_from_str = parse_frame_range))]
+  pub trace_frames: Option<(u64, u64)>
+}
+
+fn parse_hex_u16(value: &str) -> Result<u16, String> {
+  u16::from_str_radix(value.trim_start_matches('$'), 16).map_err(|error| error.to_string())
+}
+
+fn parse_hex_u64(value: &str) -> Result<u64, String> {
+  u64::from_str_radix(value, 16).map_err(|error| error.to_string())
+}
 
-  let mut bus = Bus::new(Cartridge::load(&opts.rom_path.as_str()).unwrap());
-  cpu.reset(&mut bus);
+fn parse_gain(value: &str) -> Result<(String, f32), String> {
+  let mut parts = value.splitn(2, '=');
+  let channel = parts.next().unwrap_or("").to_string();
+  let gain = parts.next().ok_or_else(|| "EXPECTED CHANNEL=GAIN".to_string())?;
+  Ok((channel, gain.parse().map_err(|error: std::num::ParseFloatError| error.to_string())?))
+}
+
+fn parse_memory_value(value: &str) -> Result<(u16, u8), String> {
+  let mut parts = value.splitn(2, '=');
+  let addr = parse_hex_u16(parts.next().unwrap_or(""))?;
+  let data = parts.next().ok_or_else(|| "EXPECTED ADDR=VALUE".to_string())?;
+  let data = u8::from_str_radix(data.trim_start_matches('$'), 16).map_err(|error| error.to_string())?;
+  Ok((addr, data))
+}
+
+// START-END with both ends included, like address ranges, so 5-5 is the single frame 5
+fn parse_frame_range(value: &str) -> Result<(u64, u64), String> {
+  let mut parts = value.splitn(2, '-');
+  let start = parts.next().unwrap_or("").parse::<u64>().map_err(|error| error.to_string())?;
+  let end = parts.next().ok_or_else(|| "EXPECTED START-END".to_string())?;
+  let end = end.parse::<u64>().map_err(|error| error.to_string())?;
+  if end < start {
+    return Err("FRAME RANGE IS EMPTY".to_string());
+  }
+  Ok((start, end))
+}
+
+fn parse_address_range(value: &str) -> Result<(u16, u16), String> {
+  let mut parts = value.splitn(2, '-');
+  let start = parse_hex_u16(parts.next().unwrap_or(""))?;
+  let end = parse_hex_u16(parts.next().ok_or_else(|| "EXPECTED START-END".to_string())?)?;
+  if end < start {
+    return Err("ADDRESS RANGE IS EMPTY".to_string());
+  }
+  Ok((start, end))
+}
+
+fn build_tracer(opts: &TraceOpts) -> Option<Tracer> {
+  let path = opts.trace.as_ref()?;
+  let sink = if path == "-" {
+    TraceSink::Stdout
+  } else {
+    TraceSink::file(path).unwrap_or_else(|error| panic!("UNABLE TO OPEN TRACE FILE {}: {}", path, error))
+  };
+
+  let mut tracer = Tracer::new(opts.trace_format, sink);
+  tracer.ppu_columns = opts.trace_ppu;
+  tracer.address_range = opts.trace_addr;
+  tracer.frame_range = opts.trace_frames;
+  if let Some(size) = opts.trace_last {
+    tracer.set_ring_buffer(size);
+  }
+  Some(tracer)
+}
+
+fn load_nes(rom_path: &str, trace: &TraceOpts) -> NES {
+  let cartridge = Cartridge::load(rom_path).unwrap_or_else(|error| panic!("UNABLE TO LOAD {}: {}", rom_path, error));
+  let mut nes = NES::new(cartridge);
+  nes.cpu.tracer = build_tracer(trace);
+  nes.reset();
+  nes
+}
+
+fn apply_mixer(mixer: &mut Mixer, opts: &MixerOpts) -> Result<(), String> {
+  for name in &opts.mute {
+    mixer.channel(name)?.muted = true;
+  }
+  for name in &opts.solo {
+    mixer.channel(name)?.solo = true;
+  }
+  for (name, gain) in &opts.gain {
+    mixer.channel(name)?.gain = *gain;
+  }
+  Ok(())
+}
+
+fn dump_ppu(nes: &NES, dir: &str) {
+  let images = [
+    ("pattern_tables.png", ppu_viewer::pattern_tables(&nes.bus.ppu, &nes.palette, 0)),
+    ("nametables.png", ppu_viewer::nametables(&nes.bus.ppu, &nes.palette)),
+    ("sprites.png", ppu_viewer::sprites(&nes.bus.ppu, &nes.palette)),
+    ("palettes.png", ppu_viewer::palettes(&nes.bus.ppu, &nes.palette))
+  ];
+
+  std::fs::create_dir_all(dir).unwrap_or_else(|error| panic!("UNABLE TO CREATE {}: {}", dir, error));
+  for (name, image) in &images {
+    let path = std::path::Path::new(dir).join(name);
+    image.save(path.to_str().unwrap()).unwrap_or_else(|error| panic!("UNABLE TO WRITE {}: {}", path.display(), error));
+  }
+}
+
+// Devices picked on the command line replace the ones from the header
+fn plug_devices(bus: &mut Bus, opts: &RunOpts) -> Result<(), String> {
+  for (port, name) in [&opts.port1, &opts.port2].iter().enumerate() {
+    if let Some(name) = name {
+      bus.ports[port] = input::port_device(name)?;
+    }
+  }
+  if let Some(name) = &opts.expansion {
+    bus.expansion_port = input::expansion_device(name)?;
+  }
+  Ok(())
+}
+
+fn run(opts: RunOpts) -> i32 {
+  let mut nes = load_nes(&opts.rom_path, &opts.trace);
+  if let Some(path) = &opts.palette {
+    nes.palette = Palette::load(path).unwrap_or_else(|error| panic!("{}", error));
+  }
+  apply_mixer(&mut nes.bus.mixer, &opts.mixer).unwrap_or_else(|error| panic!("{}", error));
+  plug_devices(&mut nes.bus, &opts).unwrap_or_else(|error| panic!("{}", error));
+  let movie = opts.input.as_ref().map(|path| Movie::load(path).unwrap_or_else(|error| panic!("{}", error)));
+  if let Some(movie) = &movie {
+    if !movie.matches_rom(&nes.bus.cartridge) {
+      eprintln!("WARNING: THE MOVIE WAS RECORDED WITH A DIFFERENT ROM");
+    }
+    nes.start_movie_playback(movie).unwrap_or_else(|error| panic!("{}", error));
+  }
+  if let Some(path) = &opts.load_state {
+    let state = std::fs::read(path).unwrap_or_else(|error| panic!("UNABLE TO READ SAVE STATE {}: {}", path, error));
+    nes.load_state(&state).unwrap_or_else(|error| panic!("{}", error));
+  }
+  if opts.record_movie.is_some() {
+    let started = if opts.load_state.is_some() { nes.start_movie_recording_from_state() } else { nes.start_movie_recording() };
+    started.unwrap_or_else(|error| panic!("{}", error));
+  }
+
+  let mut conditions = Vec::new();
+  if let Some(addr) = opts.until_pc {
+    conditions.push(ExitCondition::PcReached(addr));
+  }
+  for (addr, value) in &opts.until_mem {
+    conditions.push(ExitCondition::MemoryValue(*addr, *value));
+  }
+  if let Some(hash) = opts.until_hash {
+    conditions.push(ExitCondition::FrameHash(hash));
+  }
+
+  if opts.record_video.is_some() || opts.record_audio.is_some() {
+    nes.start_recording(opts.record_video.as_deref(), opts.record_audio.as_deref()).unwrap_or_else(|error| panic!("{}", error));
+    if let Some(recorder) = &mut nes.recorder {
+      recorder.frame_range = opts.record_frames;
+    }
+  }
+
+  let result = nes.run(opts.frames, &conditions, movie.as_ref());
+  nes.stop_recording().unwrap_or_else(|error| panic!("{}", error));
+
+  if let (Some(path), Some(mut recording)) = (&opts.record_movie, nes.stop_movie_recording()) {
+    let rom_name = std::path::Path::new(&opts.rom_path).file_stem().and_then(|stem| stem.to_str()).unwrap_or("");
+    recording.set_header("romFilename", rom_name);
+    recording.save(path).unwrap_or_else(|error| panic!("{}", error));
+  }
+
+  if let Some(path) = &opts.save_state {
+    let state = nes.save_state().unwrap_or_else(|error| panic!("{}", error));
+    std::fs::write(path, state).unwrap_or_else(|error| panic!("UNABLE TO WRITE SAVE STATE {}: {}", path, error));
+  }
+
+  if let Some(path) = &opts.screenshot {
+    let mut image = Image::from_frame(&nes.frame);
+    if opts.crop_overscan {
+      image = Overscan::default().crop(&image);
+    }
+    if let Some(scaler) = opts.scale {
+      image = scaler.apply(&image);
+    }
+    image.save(path).unwrap_or_else(|error| panic!("UNABLE TO WRITE {}: {}", path, error));
+  }
+  if let Some(dir) = &opts.dump_ppu {
+    dump_ppu(&nes, dir);
+  }
+  if let Some(path) = &opts.dump_ram {
+    std::fs::write(path, &nes.bus.ram).unwrap_or_else(|error| panic!("UNABLE TO WRITE {}: {}", path, error));
+  }
+
+  println!("FRAMES: {} PC: {:04X} FRAME HASH: {:016X}", result.frames, nes.cpu.pc, nes.frame.hash());
+
+  match result.condition {
+    Some(ExitCondition::UnknownOpcode(addr, opcode)) => {
+      println!("UNKNOWN CPU OPERATION {:02x} AT {:04x}", opcode, addr);
+      1
+    }
+    Some(condition) => {
+      println!("STOPPED ON {:?}", condition);
+      0
+    }
+    None if conditions.is_empty() => 0,
+    None => {
+      println!("NO EXIT CONDITION MET");
+      1
+    }
+  }
+}
+
+fn bench(opts: BenchOpts) {
+  let mut nes = load_nes(&opts.rom_path, &opts.trace);
 
   let start = Instant::now();
-  while cycles < cycle_count {
-    cpu.step(&mut bus);
-    cycles += 1;
+  for _x in 0..opts.cycles {
+    nes.cpu.step(&mut nes.bus);
   }
   let end = Instant::now();
 
   let duration = end - start;
 
-  let cycles_per_second = cycle_count as f64 / duration.as_secs_f64();
+  let cycles_per_second = opts.cycles as f64 / duration.as_secs_f64();
 
   println!("{} CYCLES PER SECOND", cycles_per_second);
 }
+
+const DEFAULT_TRACK_SECONDS: f64 = 150.0;
+
+fn play_nsf(opts: NsfOpts) {
+  let nsf = Nsf::load(&opts.nsf_path).unwrap_or_else(|error| panic!("{}", error));
+  let track = match opts.track {
+    Some(0) => panic!("TRACKS ARE COUNTED FROM 1"),
+    Some(track) => track - 1,
+    None => nsf.starting_song
+  };
+
+  let mut player = NsfPlayer::new(nsf).unwrap_or_else(|error| panic!("{}", error));
+  apply_mixer(&mut player.bus.mixer, &opts.mixer).unwrap_or_else(|error| panic!("{}", error));
+
+  if opts.wav.is_none() && opts.stems.is_none() {
+    let nsf = &player.nsf;
+    println!("TITLE: {}", nsf.title);
+    println!("ARTIST: {}", nsf.artist);
+    println!("COPYRIGHT: {}", nsf.copyright);
+    println!("EXPANSION: {:02X}", nsf.expansion);
+    for track in 0..nsf.total_songs {
+      let label = nsf.track_labels.get(track as usize).map(|label| label.as_str()).unwrap_or("");
+      println!("TRACK {}: {}", track + 1, label);
+    }
+    let channels: Vec<&str> = player.bus.mixer.channels.iter().map(|channel| channel.name.as_str()).collect();
+    println!("CHANNELS: {}", channels.join(", "));
+    return;
+  }
+
+  let time = player.nsf.track_times.get(track as usize).copied().flatten();
+  let seconds = opts.seconds.unwrap_or_else(|| time.map(|time| time as f64 / 1000.0).unwrap_or(DEFAULT_TRACK_SECONDS));
+  player.start_track(track).unwrap_or_else(|error| panic!("{}", error));
+
+  let create = |path: &str| {
+    let file = File::create(path).unwrap_or_else(|error| panic!("UNABLE TO CREATE {}: {}", path, error));
+    WavWriter::new(BufWriter::new(file), SAMPLE_RATE, 1).unwrap_or_else(|error| panic!("UNABLE TO WRITE {}: {}", path, error))
+  };
+
+  // Stems are rendered in one pass, one file per mixer channel named after it
+  let mut writers = Vec::new();
+  match (&opts.wav, &opts.stems) {
+    (None, Some(dir)) => {
+      std::fs::create_dir_all(dir).unwrap_or_else(|error| panic!("UNABLE TO CREATE {}: {}", dir, error));
+      for (index, channel) in player.bus.mixer.channels.iter().enumerate() {
+        let name = format!("{:02}-{}.wav", index + 1, channel.name.to_lowercase().replace(' ', "-"));
+        let path = std::path::Path::new(dir).join(name).to_str().unwrap().to_string();
+        writers.push((create(&path), path));
+      }
+    }
+    (Some(path), _) => writers.push((create(path), path.clone())),
+    (None, None) => unreachable!()
+  }
+
+  // A second at a time to keep memory flat on long renders
+  let mut remaining = (seconds * SAMPLE_RATE as f64) as usize;
+  while remaining > 0 {
+    let count = remaining.min(SAMPLE_RATE as usize);
+    let outputs = if opts.stems.is_some() { player.render_stems(count) } else { player.render(count).map(|samples| vec![samples]) };
+    let outputs = outputs.unwrap_or_else(|error| panic!("{}", error));
+    for ((writer, path), samples) in writers.iter_mut().zip(&outputs) {
+      writer.write_samples(samples).unwrap_or_else(|error| panic!("UNABLE TO WRITE {}: {}", path, error));
+    }
+    remaining -= count;
+  }
+  for (writer, path) in writers {
+    writer.finish().unwrap_or_else(|error| panic!("UNABLE TO WRITE {}: {}", path, error));
+  }
+}
+
+fn main() {
+  let opts = Opts::parse();
+
+  match opts.command {
+    Command::Run(run_opts) => std::process::exit(run(run_opts)),
+    Command::Bench(bench_opts) => bench(bench_opts),
+    Command::Nsf(nsf_opts) => play_nsf(nsf_opts)
+  }
+}
@@ -0,0 +1,311 @@
+#![allow(dead_code)]
+extern crate nes_emu;
+
+mod apu_tests {
+  use nes_emu::audio::apu::Apu;
+  use nes_emu::emu::bus::Bus;
+  use nes_emu::emu::cartridge::Cartridge;
+  use nes_emu::emu::cpu_bus::CpuBus;
+  use nes_emu::emu::nes::NES;
+  use nes_emu::emu::save_state::{StateReader, StateWriter};
+
+  // CPU cycles in a 4 step frame counter sequence
+  const FRAME_CYCLES: usize = 29830;
+
+  fn close(a: f32, b: f32) -> bool {
+    (a - b).abs() < 0.0001
+  }
+
+  fn clock(apu: &mut Apu, cycles: usize) {
+    for _cycle in 0..cycles {
+      apu.clock();
+    }
+  }
+
+  // Distinct values a channel puts out over some cycles
+  fn levels(apu: &mut Apu, channel: usize, cycles: usize) -> Vec<f32> {
+    let mut levels: Vec<f32> = Vec::new();
+    for _cycle in 0..cycles {
+      apu.clock();
+      let level = apu.channel_output(channel);
+      if !levels.iter().any(|seen| close(*seen, level)) {
+        levels.push(level);
+      }
+    }
+    levels
+  }
+
+  fn silent(apu: &mut Apu, channel: usize) -> bool {
+    levels(apu, channel, 200) == [0.0]
+  }
+
+  #[test]
+  fn length_counter_test() {
+    let mut apu = Apu::new();
+    // Lengths only load while the channel is enabled
+    apu.write(0x4003, 0x00);
+    assert_eq!(apu.read_status() & 0x01, 0x00);
+
+    apu.write(0x4015, 0x01);
+    apu.write(0x4000, 0x0F);
+    apu.write(0x4003, 0x00);
+    assert_eq!(apu.read_status() & 0x01, 0x01);
+
+    // Length 10 counts down twice a frame
+    clock(&mut apu, FRAME_CYCLES * 4);
+    assert_eq!(apu.read_status() & 0x01, 0x01);
+    clock(&mut apu, FRAME_CYCLES);
+    assert_eq!(apu.read_status() & 0x01, 0x00);
+
+    // Halted lengths hold, disabling the channel clears them
+    apu.write(0x4000, 0x2F);
+    apu.write(0x4003, 0x00);
+    clock(&mut apu, FRAME_CYCLES * 6);
+    assert_eq!(apu.read_status() & 0x01, 0x01);
+    apu.write(0x4015, 0x00);
+    assert_eq!(apu.read_status() & 0x01, 0x00);
+  }
+
+  #[test]
+  fn frame_irq_test() {
+    let mut apu = Apu::new();
+    clock(&mut apu, FRAME_CYCLES - 2);
+    assert!(!apu.irq());
+    clock(&mut apu, 1);
+    assert!(apu.irq());
+    // Reading the status acknowledges it
+    assert_eq!(apu.read_status() & 0x40, 0x40);
+    assert!(!apu.irq());
+
+    // Inhibited or in 5 step mode there is none
+    apu.write(0x4017, 0x40);
+    clock(&mut apu, FRAME_CYCLES * 2);
+    assert!(!apu.irq());
+    apu.write(0x4017, 0x80);
+    clock(&mut apu, FRAME_CYCLES * 2);
+    assert!(!apu.irq());
+  }
+
+  #[test]
+  fn pulse_test() {
+    let mut apu = Apu::new();
+    apu.write(0x4015, 0x01);
+    // 50% duty at constant volume 15, one sequence step every 18 CPU cycles
+    apu.write(0x4000, 0xBF);
+    apu.write(0x4002, 0x08);
+    apu.write(0x4003, 0x00);
+    let levels = levels(&mut apu, 0, 144);
+    assert_eq!(levels.len(), 2);
+    assert!(levels.iter().any(|level| close(*level, 95.88 / (8128.0 / 15.0 + 100.0))));
+
+    // Periods under 8 mute the channel
+    apu.write(0x4002, 0x07);
+    assert!(silent(&mut apu, 0));
+
+    // So do sweeps that would overflow, even disabled ones
+    apu.write(0x4002, 0xFF);
+    apu.write(0x4003, 0x07);
+    apu.write(0x4001, 0x01);
+    assert!(silent(&mut apu, 0));
+  }
+
+  #[test]
+  fn sweep_test() {
+    let mut apu = Apu::new();
+    apu.write(0x4015, 0x03);
+    for base in &[0x4000, 0x4004] {
+      apu.write(*base, 0xBF);
+      // Sweep down by period >> 1 on the next half frame
+      apu.write(*base + 1, 0x89);
+      apu.write(*base + 2, 0x10);
+      apu.write(*base + 3, 0x00);
+    }
+    assert!(!silent(&mut apu, 0));
+
+    // Pulse 1 subtracts one more and lands on 7, which mutes it, pulse 2 stops at 8
+    clock(&mut apu, FRAME_CYCLES / 2);
+    assert!(silent(&mut apu, 0));
+    assert!(!silent(&mut apu, 1));
+  }
+
+  #[test]
+  fn triangle_and_noise_test() {
+    let mut apu = Apu::new();
+    apu.write(0x4015, 0x0C);
+    // Triangle steps through all 16 levels while both counters run
+    apu.write(0x4008, 0xFF);
+    apu.write(0x400A, 0x10);
+    apu.write(0x400B, 0x00);
+    clock(&mut apu, FRAME_CYCLES / 4);
+    assert_eq!(levels(&mut apu, 2, 2000).len(), 16);
+
+    // Its linear counter stops it where it is
+    apu.write(0x4008, 0x01);
+    apu.write(0x400B, 0x00);
+    clock(&mut apu, FRAME_CYCLES);
+    assert_eq!(levels(&mut apu, 2, 2000).len(), 1);
+
+    // Noise at constant volume 15 toggles between silence and full
+    apu.write(0x400C, 0x3F);
+    apu.write(0x400E, 0x00);
+    apu.write(0x400F, 0x00);
+    let noise = levels(&mut apu, 3, 200);
+    assert_eq!(noise.len(), 2);
+    assert!(noise.contains(&0.0));
+  }
+
+  #[test]
+  fn dmc_test() {
+    let mut apu = Apu::new();
+    // One byte sample at $C000 with the IRQ on, at the fastest rate
+    apu.write(0x4010, 0x8F);
+    apu.write(0x4012, 0x00);
+    apu.write(0x4013, 0x00);
+    apu.write(0x4015, 0x10);
+    assert_eq!(apu.read_status() & 0x10, 0x10);
+    assert_eq!(apu.dmc_fetch_address(), Some(0xC000));
+
+    apu.dmc_fetch(0xFF);
+    assert_eq!(apu.dmc_fetch_address(), None);
+    assert!(apu.irq());
+    assert_eq!(apu.read_status() & 0x90, 0x80);
+
+    // All ones raise the level 2 a bit once the byte is in the shifter, its share of the DAC
+    // is next to the idle triangle's 15
+    clock(&mut apu, 54 * 16);
+    let inputs = 15.0 / 8227.0 + 16.0 / 22638.0;
+    assert!(close(apu.channel_output(4), 159.79 / (1.0 / inputs + 100.0) * (16.0 / 22638.0) / inputs));
+
+    // Writing $4015 acknowledges the IRQ
+    apu.write(0x4015, 0x00);
+    assert!(!apu.irq());
+  }
+
+  #[test]
+  fn dmc_wrap_test() {
+    let mut apu = Apu::new();
+    // 81 bytes from $FFC0 run off the end of memory and carry on at $8000
+    apu.write(0x4010, 0x4F);
+    apu.write(0x4012, 0xFF);
+    apu.write(0x4013, 0x05);
+    apu.write(0x4015, 0x10);
+
+    let mut addresses = Vec::new();
+    while addresses.len() < 0x52 {
+      apu.clock();
+      if let Some(addr) = apu.dmc_fetch_address() {
+        addresses.push(addr);
+        apu.dmc_fetch(0x00);
+      }
+    }
+    assert_eq!(addresses[0x3F], 0xFFFF);
+    assert_eq!(addresses[0x40], 0x8000);
+    // Looping starts the sample over
+    assert_eq!(addresses[0x51], 0xFFC0);
+    assert_eq!(apu.read_status() & 0x80, 0x00);
+  }
+
+  #[test]
+  fn mix_test() {
+    let mut apu = Apu::new();
+    apu.write(0x4015, 0x0F);
+    for base in &[0x4000, 0x4004] {
+      apu.write(*base, 0xBF);
+      apu.write(*base + 2, 0x20);
+      apu.write(*base + 3, 0x00);
+    }
+    apu.write(0x4008, 0xFF);
+    apu.write(0x400B, 0x00);
+    apu.write(0x400C, 0x37);
+    apu.write(0x400F, 0x00);
+    apu.write(0x4011, 0x40);
+
+    // Each channel's share adds up to the nonlinear DAC outputs
+    for _cycle in 0..5000 {
+      apu.clock();
+      let pulses = apu.channel_output(0) + apu.channel_output(1);
+      let others = apu.channel_output(2) + apu.channel_output(3) + apu.channel_output(4);
+      assert!(close(apu.channel_output(0), apu.channel_output(1)));
+      if pulses > 0.0 {
+        assert!(close(pulses, 95.88 / (8128.0 / 30.0 + 100.0)));
+      }
+      assert!(others > 0.0);
+      assert!(close(apu.output(), pulses + others));
+    }
+  }
+
+  #[test]
+  fn save_state_test() {
+    let mut apu = Apu::new();
+    apu.write(0x4015, 0x0F);
+    apu.write(0x4000, 0x3F);
+    apu.write(0x4002, 0x40);
+    apu.write(0x4003, 0x00);
+    apu.write(0x400C, 0x3F);
+    apu.write(0x400F, 0x00);
+    clock(&mut apu, 1000);
+
+    let mut state = StateWriter::new();
+    apu.save_state(&mut state);
+    let mut copy = Apu::new();
+    let mut reader = StateReader::new(&state.bytes);
+    copy.load_state(&mut reader).unwrap();
+    assert!(reader.is_finished());
+
+    for _cycle in 0..FRAME_CYCLES {
+      apu.clock();
+      copy.clock();
+      assert_eq!(apu.output(), copy.output());
+    }
+    assert_eq!(apu.read_status(), copy.read_status());
+  }
+
+  #[test]
+  fn dmc_bus_fetch_test() {
+    // A sample of all ones at $C000, which the 16 KiB PRG ROM mirrors from $8000
+    let mut bytes = vec![0x4E, 0x45, 0x53, 0x1A, 0x01, 0x01, 0x00, 0x00];
+    bytes.resize(16, 0x00);
+    bytes.extend_from_slice(&[0xFF; 0x4000]);
+    bytes.extend_from_slice(&[0x00; 0x2000]);
+    let mut bus = Bus::new(Cartridge::new(&bytes).unwrap());
+
+    bus.write(0x4010, 0x0F);
+    bus.write(0x4012, 0x00);
+    bus.write(0x4013, 0x00);
+    bus.write(0x4015, 0x10);
+    bus.tick(1);
+    // The fetch holds the CPU up and empties the sample
+    assert_eq!(bus.take_dma_cycles(), 4);
+    assert_eq!(bus.read(0x4015) & 0x10, 0x00);
+
+    for _cycle in 0..(54 * 16) {
+      bus.tick(1);
+    }
+    assert!(bus.apu.channel_output(4) > 0.0);
+  }
+
+  #[test]
+  fn frame_irq_interrupts_cpu_test() {
+    // Main: start the 4 step sequence with IRQs on, CLI, then spin
+    let mut program = vec![0xA9, 0x00, 0x8D, 0x17, 0x40, 0x58, 0x4C, 0x06, 0x80];
+    program.resize(0x100, 0xEA);
+    // Handler at $8100: count in $10, acknowledge through $4015
+    program.extend_from_slice(&[0xE6, 0x10, 0xAD, 0x15, 0x40, 0x40]);
+
+    let mut bytes = vec![0x4E, 0x45, 0x53, 0x1A, 0x01, 0x01, 0x00, 0x00];
+    bytes.resize(16, 0x00);
+    let mut prg_rom = vec![0xEA; 0x4000];
+    prg_rom[..program.len()].copy_from_slice(&program);
+    prg_rom[0x3FFC..0x4000].copy_from_slice(&[0x00, 0x80, 0x00, 0x81]);
+    bytes.extend_from_slice(&prg_rom);
+    bytes.extend_from_slice(&[0x00; 0x2000]);
+
+    let mut nes = NES::new(Cartridge::new(&bytes).unwrap());
+    nes.reset();
+    // A frame counter sequence is a little longer than a video frame
+    for _frame in 0..10 {
+      nes.run_frame();
+    }
+    assert!((8..=9).contains(&nes.bus.ram[0x10]), "{}", nes.bus.ram[0x10]);
+  }
+}
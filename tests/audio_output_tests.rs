@@ -0,0 +1,208 @@
+#![allow(dead_code)]
+extern crate nes_emu;
+
+mod audio_output_tests {
+  use nes_emu::audio::blip::BlipBuffer;
+  use nes_emu::audio::output::{rate_ratio, AudioOutput, AudioSink, NullSink, WavSink};
+  use nes_emu::audio::ring_buffer::{ring_buffer, RingConsumer};
+  use nes_emu::emu::cpu::NTSC_CPU_CLOCK;
+
+  const CLOCKS_PER_FRAME: u32 = 29781;
+
+  // Largest distance from the mean, the square waves below sit on a DC offset
+  fn swing(samples: &[i16]) -> i32 {
+    let mean = samples.iter().map(|sample| *sample as i32).sum::<i32>() / samples.len() as i32;
+    samples.iter().map(|sample| (*sample as i32 - mean).abs()).max().unwrap()
+  }
+
+  // Square wave of `period` clocks between 0 and half scale for `frames` frames
+  fn square(blip: &mut BlipBuffer, period: u32, frames: usize) -> Vec<i16> {
+    let mut samples = Vec::new();
+    let mut high = false;
+    let mut clock_in_period = 0;
+    for _frame in 0..frames {
+      for clock in 0..CLOCKS_PER_FRAME {
+        if clock_in_period == 0 {
+          high = !high;
+          blip.add_delta(clock, if high { 0.5 } else { -0.5 });
+        }
+        clock_in_period = (clock_in_period + 1) % (period / 2);
+      }
+      blip.end_frame(CLOCKS_PER_FRAME);
+      samples.extend(blip.read_samples(usize::MAX));
+    }
+    samples
+  }
+
+  #[test]
+  fn blip_rate_test() {
+    let mut blip = BlipBuffer::new(NTSC_CPU_CLOCK, 44_100.0);
+    let clocks = blip.clocks_needed(44_100);
+    blip.end_frame(clocks);
+    assert_eq!(blip.samples_available(), 44_100);
+    // A second of clocks is a second of samples
+    assert!((clocks as f64 - NTSC_CPU_CLOCK).abs() < 2.0);
+    assert_eq!(blip.read_samples(100).len(), 100);
+    assert_eq!(blip.samples_available(), 44_000);
+  }
+
+  #[test]
+  fn blip_step_test() {
+    let mut blip = BlipBuffer::new(NTSC_CPU_CLOCK, 44_100.0);
+    blip.add_delta(1000, 0.5);
+    blip.end_frame(10_000);
+    let samples = blip.read_samples(usize::MAX);
+    // Settles on the level after the kernel has passed
+    assert_eq!(*samples.last().unwrap(), (0.5 * i16::MAX as f32) as i16);
+    assert_eq!(samples[0], 0);
+
+    blip.reset(0.25);
+    blip.end_frame(1000);
+    assert!(blip.read_samples(usize::MAX).iter().all(|sample| *sample == (0.25 * i16::MAX as f32) as i16));
+  }
+
+  #[test]
+  fn blip_band_limit_test() {
+    // 1 kHz passes at full swing
+    let mut blip = BlipBuffer::new(NTSC_CPU_CLOCK, 44_100.0);
+    let samples = square(&mut blip, 1790, 10);
+    assert!(swing(&samples[100..]) > 8_000);
+
+    // 60 kHz is above the output Nyquist frequency, sampling it directly would alias at full swing
+    let mut blip = BlipBuffer::new(NTSC_CPU_CLOCK, 44_100.0);
+    let samples = square(&mut blip, 30, 10);
+    assert!(swing(&samples[100..]) < 1_000);
+  }
+
+  #[test]
+  fn ring_buffer_test() {
+    let (mut producer, mut consumer) = ring_buffer(4);
+    assert_eq!(producer.push(&[1, 2, 3]), 3);
+    let mut out = [0; 2];
+    assert_eq!(consumer.pop(&mut out), 2);
+    assert_eq!(out, [1, 2]);
+
+    // Wraps around and drops what does not fit
+    assert_eq!(producer.push(&[4, 5, 6, 7]), 3);
+    assert_eq!(producer.fill(), 1.0);
+    let mut out = [0; 5];
+    assert_eq!(consumer.pop(&mut out), 4);
+    assert_eq!(out, [3, 4, 5, 6, 0]);
+    assert!(producer.is_empty());
+
+    assert!(!producer.is_abandoned());
+    drop(consumer);
+    assert!(producer.is_abandoned());
+  }
+
+  #[test]
+  fn ring_buffer_threads_test() {
+    // Everything pushed on one thread comes out in order on the other
+    let (mut producer, mut consumer) = ring_buffer(64);
+    let reader = std::thread::spawn(move || {
+      let mut read = Vec::new();
+      let mut out = [0; 7];
+      while read.len() < 10_000 {
+        let count = consumer.pop(&mut out);
+        read.extend_from_slice(&out[..count]);
+      }
+      read
+    });
+
+    let samples: Vec<i16> = (0..10_000).map(|sample| sample as i16).collect();
+    let mut written = 0;
+    while written < samples.len() {
+      written += producer.push(&samples[written..(written + 5).min(samples.len())]);
+    }
+    assert_eq!(reader.join().unwrap(), samples);
+  }
+
+  #[test]
+  fn rate_ratio_test() {
+    assert_eq!(rate_ratio(0.5, 0.005), 1.0);
+    assert!((rate_ratio(0.0, 0.005) - 1.005).abs() < 1e-9);
+    assert!((rate_ratio(1.0, 0.005) - 0.995).abs() < 1e-9);
+  }
+
+  // Hands the ring over to the test, which plays the device so time is under its control
+  struct TestSink {
+    ring: std::sync::Arc<std::sync::Mutex<Option<RingConsumer>>>
+  }
+
+  impl AudioSink for TestSink {
+    fn sample_rate(&self) -> u32 {
+      48_000
+    }
+
+    fn start(&mut self, ring: RingConsumer) -> Result<(), String> {
+      *self.ring.lock().unwrap() = Some(ring);
+      Ok(())
+    }
+  }
+
+  // Runs a minute of frames into a device playing 0.3% fast, returning the buffer fill after each
+  fn run_drifting(rate_control: bool) -> Vec<f64> {
+    let ring = std::sync::Arc::new(std::sync::Mutex::new(None));
+    let mut output = AudioOutput::new(Box::new(TestSink { ring: ring.clone() }), NTSC_CPU_CLOCK, 0.05).unwrap();
+    output.rate_control = rate_control;
+    let mut consumer = ring.lock().unwrap().take().unwrap();
+
+    let mut fills = Vec::new();
+    let mut owed = 0.0;
+    for _frame in 0..3600 {
+      output.end_frame(CLOCKS_PER_FRAME).unwrap();
+      owed += CLOCKS_PER_FRAME as f64 / NTSC_CPU_CLOCK * 48_000.0 * 1.003;
+      let mut samples = vec![0; owed as usize];
+      owed -= samples.len() as f64;
+      consumer.pop(&mut samples);
+      fills.push(consumer.fill());
+    }
+    fills
+  }
+
+  #[test]
+  fn rate_control_test() {
+    // Without rate control the device slowly empties the buffer
+    let fills = run_drifting(false);
+    assert!(*fills.last().unwrap() < 0.1);
+
+    // With it the buffer settles where the adjustment matches the drift, well clear of empty
+    let fills = run_drifting(true);
+    assert!(fills.iter().all(|fill| *fill > 0.15));
+    assert!((fills[3000] - fills[3599]).abs() < 0.01);
+  }
+
+  #[test]
+  fn null_sink_test() {
+    // The device plays on its own in real time, running dry once the queued silence is gone
+    let mut sink = NullSink::new(44_100);
+    let (_producer, consumer) = ring_buffer(100);
+    sink.start(consumer).unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(100));
+    sink.finish().unwrap();
+    assert!(sink.played() >= 4_000, "{}", sink.played());
+    assert!(sink.underruns() > 0);
+  }
+
+  #[test]
+  fn wav_sink_test() {
+    let path = std::env::temp_dir().join("audio_output_wav_sink_test.wav");
+    let sink = WavSink::create(path.to_str().unwrap(), 44_100).unwrap();
+    let mut output = AudioOutput::new(Box::new(sink), NTSC_CPU_CLOCK, 0.05).unwrap();
+    // Half a second of frames at the pace a frontend would run them
+    for _frame in 0..30 {
+      output.add_delta(0, 0.25);
+      output.add_delta(CLOCKS_PER_FRAME / 2, -0.25);
+      output.end_frame(CLOCKS_PER_FRAME).unwrap();
+      std::thread::sleep(std::time::Duration::from_micros(16_639));
+    }
+    output.finish().unwrap();
+
+    // About as long as it ran, starting with the buffered silence
+    let bytes = std::fs::read(&path).unwrap();
+    let frames = (bytes.len() - 44) / 2;
+    assert!((20_000..=40_000).contains(&frames), "{}", frames);
+    assert_eq!(i16::from_le_bytes([bytes[44], bytes[45]]), 0);
+    let _ = std::fs::remove_file(&path);
+  }
+}
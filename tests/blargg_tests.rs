@@ -0,0 +1,112 @@
+#![allow(dead_code)]
+extern crate nes_emu;
+
+// Runs every .nes file under BLARGG_ROMS (default ./ROMS/blargg) with the $6000 status
+// protocol and prints a pass/fail table.
+mod blargg_tests {
+  use std::path::{Path, PathBuf};
+
+  use nes_emu::emu::cartridge::Cartridge;
+  use nes_emu::emu::test_rom::{run_test_rom, run_test_rom_file, TestRomStatus};
+
+  const DEFAULT_DIR: &str = "./ROMS/blargg";
+  const MAX_FRAMES: u64 = 60 * 60;
+
+  fn collect_roms(dir: &Path, roms: &mut Vec<PathBuf>) {
+    if let Ok(entries) = std::fs::read_dir(dir) {
+      for path in entries.filter_map(|entry| entry.ok().map(|entry| entry.path())) {
+        if path.is_dir() {
+          collect_roms(&path, roms);
+        } else if path.extension().map(|ext| ext == "nes").unwrap_or(false) {
+          roms.push(path);
+        }
+      }
+    }
+  }
+
+  // Writes `bytes` to consecutive addresses starting at `addr` with LDA #/STA abs pairs
+  fn store_bytes(program: &mut Vec<u8>, addr: u16, bytes: &[u8]) {
+    for (offset, byte) in bytes.iter().enumerate() {
+      let target = addr + offset as u16;
+      program.extend_from_slice(&[0xA9, *byte, 0x8D, target as u8, (target >> 8) as u8]);
+    }
+  }
+
+  fn protocol_cartridge(result: u8, message: &str) -> Cartridge {
+    let mut program = Vec::new();
+    store_bytes(&mut program, 0x6000, &[0x80]);
+    store_bytes(&mut program, 0x6001, &[0xDE, 0xB0, 0x61]);
+    let mut text = message.as_bytes().to_vec();
+    text.push(0);
+    store_bytes(&mut program, 0x6004, &text);
+    store_bytes(&mut program, 0x6000, &[result]);
+    let spin = 0x8000 + program.len() as u16;
+    program.extend_from_slice(&[0x4C, spin as u8, (spin >> 8) as u8]);
+
+    let mut bytes = vec![0x4E, 0x45, 0x53, 0x1A, 0x01, 0x01, 0x00, 0x00];
+    bytes.resize(16, 0x00);
+    let mut prg_rom = vec![0xEA; 0x4000];
+    prg_rom[..program.len()].copy_from_slice(&program);
+    prg_rom[0x3FFC] = 0x00;
+    prg_rom[0x3FFD] = 0x80;
+    bytes.extend_from_slice(&prg_rom);
+    bytes.extend_from_slice(&[0x00; 0x2000]);
+    Cartridge::new(&bytes).unwrap()
+  }
+
+  #[test]
+  fn protocol_pass_test() {
+    let result = run_test_rom(protocol_cartridge(0x00, "Passed\n"), 10);
+
+    assert_eq!(result.status, TestRomStatus::Passed);
+    assert_eq!(result.message, "Passed");
+  }
+
+  #[test]
+  fn protocol_fail_test() {
+    let result = run_test_rom(protocol_cartridge(0x03, "Failed #3"), 10);
+
+    assert_eq!(result.status, TestRomStatus::Failed(0x03));
+    assert_eq!(result.message, "Failed #3");
+  }
+
+  #[test]
+  fn missing_rom_test() {
+    let result = run_test_rom_file("./ROMS/does_not_exist.nes", 10);
+
+    assert!(matches!(result.status, TestRomStatus::Crashed(_)));
+  }
+
+  #[test]
+  fn suite_test() {
+    let dir = std::env::var("BLARGG_ROMS").unwrap_or_else(|_| DEFAULT_DIR.to_string());
+    let mut roms = Vec::new();
+    collect_roms(Path::new(&dir), &mut roms);
+    if roms.is_empty() {
+      println!("SKIPPING, NO ROMS UNDER {} (SET BLARGG_ROMS)", dir);
+      return;
+    }
+    roms.sort();
+
+    let mut failures = 0;
+    println!("{:<50} {:<12} MESSAGE", "ROM", "RESULT");
+    for rom in &roms {
+      let result = run_test_rom_file(rom.to_str().unwrap(), MAX_FRAMES);
+      let status = match &result.status {
+        TestRomStatus::Passed => "PASS".to_string(),
+        TestRomStatus::Failed(code) => format!("FAIL ({})", code),
+        TestRomStatus::TimedOut => "TIMEOUT".to_string(),
+        TestRomStatus::Crashed(reason) => format!("CRASH: {}", reason)
+      };
+      if result.status != TestRomStatus::Passed {
+        failures += 1;
+      }
+
+      let name = rom.strip_prefix(&dir).unwrap_or(rom).display().to_string();
+      println!("{:<50} {:<12} {}", name, status, result.message.replace('\n', " | "));
+    }
+
+    println!("{} OF {} PASSED", roms.len() - failures, roms.len());
+    assert_eq!(failures, 0);
+  }
+}
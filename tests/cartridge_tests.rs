@@ -0,0 +1,220 @@
+#![allow(dead_code)]
+extern crate nes_emu;
+
+mod cartridge_tests {
+  use nes_emu::emu::bus::Bus;
+  use nes_emu::emu::cartridge::{Cartridge, Mirroring};
+  use nes_emu::emu::nes::NES;
+
+  // 16 KiB PRG and 8 KiB CHR image with the given mapper and header byte 6 flags
+  fn test_cartridge(mapper: u8, flags: u8) -> Cartridge {
+    let mut bytes = vec![0x4E, 0x45, 0x53, 0x1A, 0x01, 0x01, (mapper << 4) | flags, mapper & 0xF0];
+    bytes.resize(16, 0x00);
+    bytes.extend_from_slice(&[0x00; 0x4000 + 0x2000]);
+    Cartridge::new(&bytes).unwrap()
+  }
+
+  // PRG image of `pages` 16 KiB pages where every 8 KiB bank is filled with its own number
+  fn banked_cartridge(mapper: u8, pages: u8) -> Cartridge {
+    let mut bytes = vec![0x4E, 0x45, 0x53, 0x1A, pages, 0x01, mapper << 4, mapper & 0xF0];
+    bytes.resize(16, 0x00);
+    for bank in 0..pages * 2 {
+      bytes.extend_from_slice(&[bank; 0x2000]);
+    }
+    bytes.extend_from_slice(&[0x00; 0x2000]);
+    Cartridge::new(&bytes).unwrap()
+  }
+
+  // Bank number seen at each 8 KiB slot of $8000-$FFFF
+  fn prg_banks(bus: &mut Bus) -> [u8; 4] {
+    [bus.read(0x8000), bus.read(0xA000), bus.read(0xC000), bus.read(0xE000)]
+  }
+
+  // Control register value written one bit at a time, as MMC1 games do
+  fn write_mmc1(cartridge: &mut Cartridge, addr: u16, value: u8) -> Option<Mirroring> {
+    let mut mirroring = None;
+    for bit in 0..5 {
+      mirroring = cartridge.write_register(addr, (value >> bit) & 0x01);
+    }
+    mirroring
+  }
+
+  // 16 KiB PRG image without CHR ROM, `header` overrides bytes 7 onward
+  fn chr_ram_cartridge(header: &[u8]) -> Cartridge {
+    let mut bytes = vec![0x4E, 0x45, 0x53, 0x1A, 0x01, 0x00, 0x00];
+    bytes.extend_from_slice(header);
+    bytes.resize(16, 0x00);
+    bytes.extend_from_slice(&[0x00; 0x4000]);
+    Cartridge::new(&bytes).unwrap()
+  }
+
+  #[test]
+  fn chr_ram_test() {
+    let cartridge = chr_ram_cartridge(&[]);
+    assert!(cartridge.chr_ram);
+    assert_eq!(cartridge.chr_rom.len(), 0x2000);
+    assert!(!test_cartridge(0, 0x00).chr_ram);
+
+    // NES 2.0 header declaring 32 KiB of CHR RAM
+    let cartridge = chr_ram_cartridge(&[0x08, 0x00, 0x00, 0x00, 0x09]);
+    assert_eq!(cartridge.chr_rom.len(), 0x8000);
+  }
+
+  #[test]
+  fn chr_ram_write_test() {
+    let mut bus = Bus::new(chr_ram_cartridge(&[]));
+    bus.write(0x2006, 0x00);
+    bus.write(0x2006, 0x10);
+    bus.write(0x2007, 0xAA);
+    bus.write(0x2007, 0x55);
+    assert_eq!(&bus.ppu.chr_rom[0x10..0x12], &[0xAA, 0x55]);
+
+    // CHR ROM stays as it is
+    let mut bus = Bus::new(test_cartridge(0, 0x00));
+    bus.write(0x2006, 0x00);
+    bus.write(0x2006, 0x10);
+    bus.write(0x2007, 0xAA);
+    assert_eq!(bus.ppu.chr_rom[0x10], 0x00);
+  }
+
+  #[test]
+  fn small_chr_ram_test() {
+    // NES 2.0 header declaring 2 KiB of CHR RAM, which repeats through the pattern tables
+    let cartridge = chr_ram_cartridge(&[0x08, 0x00, 0x00, 0x00, 0x05]);
+    assert_eq!(cartridge.chr_rom.len(), 0x0800);
+
+    let mut nes = NES::new(cartridge);
+    nes.bus.write(0x2006, 0x18);
+    nes.bus.write(0x2006, 0x10);
+    nes.bus.write(0x2007, 0xAA);
+    assert_eq!(nes.bus.ppu.chr_rom[0x10], 0xAA);
+    assert_eq!(nes.bus.ppu.chr_byte(0x0810), 0xAA);
+    nes.bus.write(0x2006, 0x00);
+    nes.bus.write(0x2006, 0x10);
+    nes.bus.read(0x2007);
+    assert_eq!(nes.bus.read(0x2007), 0xAA);
+
+    // Rendering fetches patterns from the upper table too
+    nes.bus.write(0x2000, 0x18);
+    nes.bus.write(0x2001, 0x1E);
+    assert_eq!(nes.run(2, &[], None).frames, 2);
+  }
+
+  #[test]
+  fn no_prg_rom_test() {
+    let mut bytes = vec![0x4E, 0x45, 0x53, 0x1A, 0x00, 0x01, 0x00, 0x00];
+    bytes.resize(16, 0x00);
+    bytes.extend_from_slice(&[0x00; 0x2000]);
+    assert_eq!(Cartridge::new(&bytes).err(), Some("CARTRIDGE HAS NO PRG ROM".to_string()));
+  }
+
+  #[test]
+  fn header_mirroring_test() {
+    assert_eq!(test_cartridge(0, 0x00).mirroring, Mirroring::Horizontal);
+    assert_eq!(test_cartridge(0, 0x01).mirroring, Mirroring::Vertical);
+    assert_eq!(test_cartridge(0, 0x08).mirroring, Mirroring::FourScreen);
+  }
+
+  #[test]
+  fn mmc1_mirroring_test() {
+    let mut cartridge = test_cartridge(1, 0x00);
+
+    assert_eq!(write_mmc1(&mut cartridge, 0x8000, 0b01110), Some(Mirroring::Vertical));
+    assert_eq!(write_mmc1(&mut cartridge, 0x9FFF, 0b00001), Some(Mirroring::SingleScreenB));
+    assert_eq!(cartridge.mirroring, Mirroring::SingleScreenB);
+    // Other registers leave mirroring alone
+    assert_eq!(write_mmc1(&mut cartridge, 0xE000, 0b00011), None);
+
+    // A reset write drops the bits shifted in so far
+    cartridge.write_register(0x8000, 0x01);
+    cartridge.write_register(0x8000, 0x80);
+    assert_eq!(write_mmc1(&mut cartridge, 0x8000, 0b00011), Some(Mirroring::Horizontal));
+  }
+
+  #[test]
+  fn mmc3_mirroring_test() {
+    let mut cartridge = test_cartridge(4, 0x01);
+
+    assert_eq!(cartridge.write_register(0xA000, 0x01), Some(Mirroring::Horizontal));
+    assert_eq!(cartridge.write_register(0xBFFE, 0x00), Some(Mirroring::Vertical));
+    assert_eq!(cartridge.write_register(0xA001, 0x01), None);
+
+    // Four screen boards ignore the mirroring register
+    let mut cartridge = test_cartridge(4, 0x08);
+    assert_eq!(cartridge.write_register(0xA000, 0x01), None);
+    assert_eq!(cartridge.mirroring, Mirroring::FourScreen);
+  }
+
+  #[test]
+  fn axrom_mirroring_test() {
+    let mut cartridge = test_cartridge(7, 0x00);
+
+    assert_eq!(cartridge.write_register(0x8000, 0x10), Some(Mirroring::SingleScreenB));
+    assert_eq!(cartridge.write_register(0xFFFF, 0x07), Some(Mirroring::SingleScreenA));
+  }
+
+  #[test]
+  fn nrom_prg_mirroring_test() {
+    let mut bus = Bus::new(banked_cartridge(0, 1));
+    assert_eq!(prg_banks(&mut bus), [0, 1, 0, 1]);
+  }
+
+  #[test]
+  fn mmc1_prg_banks_test() {
+    // 128 KiB, powers up with the last bank fixed at $C000
+    let mut bus = Bus::new(banked_cartridge(1, 8));
+    assert_eq!(prg_banks(&mut bus), [0, 1, 14, 15]);
+
+    for bit in 0..5 {
+      bus.write(0xE000, (3 >> bit) & 0x01);
+    }
+    assert_eq!(prg_banks(&mut bus), [6, 7, 14, 15]);
+
+    // First bank fixed at $8000
+    for bit in 0..5 {
+      bus.write(0x8000, (0b01000 >> bit) & 0x01);
+    }
+    assert_eq!(prg_banks(&mut bus), [0, 1, 6, 7]);
+
+    // 32 KiB mode drops the low bit of the bank
+    for bit in 0..5 {
+      bus.write(0x8000, (0b00000 >> bit) & 0x01);
+    }
+    assert_eq!(prg_banks(&mut bus), [4, 5, 6, 7]);
+
+    // A reset write goes back to the last bank fixed
+    bus.write(0x8000, 0x80);
+    assert_eq!(prg_banks(&mut bus), [6, 7, 14, 15]);
+  }
+
+  #[test]
+  fn mmc3_prg_banks_test() {
+    let mut bus = Bus::new(banked_cartridge(4, 8));
+    bus.write(0x8000, 6);
+    bus.write(0x8001, 3);
+    bus.write(0x8000, 7);
+    bus.write(0x8001, 5);
+    assert_eq!(prg_banks(&mut bus), [3, 5, 14, 15]);
+
+    // Other registers leave the PRG banks alone
+    bus.write(0x8000, 0);
+    bus.write(0x8001, 9);
+    assert_eq!(prg_banks(&mut bus), [3, 5, 14, 15]);
+
+    // PRG mode 1 swaps $8000 and $C000
+    bus.write(0x8000, 0x40);
+    assert_eq!(prg_banks(&mut bus), [14, 5, 3, 15]);
+
+    bus.power();
+    assert_eq!(prg_banks(&mut bus), [0, 1, 14, 15]);
+  }
+
+  #[test]
+  fn axrom_prg_banks_test() {
+    let mut bus = Bus::new(banked_cartridge(7, 8));
+    assert_eq!(prg_banks(&mut bus), [0, 1, 2, 3]);
+    bus.write(0x8000, 0x12);
+    assert_eq!(prg_banks(&mut bus), [8, 9, 10, 11]);
+    assert_eq!(bus.cartridge.mirroring, Mirroring::SingleScreenB);
+  }
+}
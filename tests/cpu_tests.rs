@@ -3,9 +3,9 @@ extern crate nes_emu;
 
 mod cpu_tests {
   use nes_emu::emu;
-  use nes_emu::emu::cartridge::Cartridge;
+  use nes_emu::emu::cpu_bus::{CpuBus, FlatBus};
 
-  fn run_cpu_cycles(cpu: &mut emu::cpu::CPU, cycles: u32, bus: &mut emu::bus::Bus) {
+  fn run_cpu_cycles<B: CpuBus>(cpu: &mut emu::cpu::CPU, cycles: u32, bus: &mut B) {
     for _x in 0..cycles {
       cpu.step(bus)
     }
@@ -14,7 +14,7 @@ mod cpu_tests {
   #[test]
   fn adc_imm_test() {
     let mut cpu = emu::cpu::CPU::new(None);
-    let mut bus = emu::bus::Bus::new(Cartridge::load("./ROMS/snake.nes").unwrap());
+    let mut bus = FlatBus::new();
     bus.write(0x0000, 0x69);
     bus.write(0x0001, 0x24);
     bus.write(0x0002, 0x69);
@@ -26,7 +26,7 @@ mod cpu_tests {
   #[test]
   fn adc_abs_test() {
     let mut cpu = emu::cpu::CPU::new(None);
-    let mut bus = emu::bus::Bus::new(Cartridge::load("./ROMS/snake.nes").unwrap());
+    let mut bus = FlatBus::new();
     bus.write(0x0000, 0x6D);
     bus.write(0x0001, 0x00);
     bus.write(0x0002, 0x04);
@@ -45,8 +45,10 @@ mod cpu_tests {
   #[test]
   fn sbc_imm_test() {
     let mut cpu = emu::cpu::CPU::new(None);
-    let mut bus = emu::bus::Bus::new(Cartridge::load("./ROMS/snake.nes").unwrap());
+    let mut bus = FlatBus::new();
     cpu.r_a = 0x69;
+    // Carry set means no borrow going in
+    cpu.f_c = true;
     bus.write(0x0000, 0xE9);
     bus.write(0x0001, 0x42);
 
@@ -54,4 +56,74 @@ mod cpu_tests {
 
     assert_eq!(cpu.r_a, 0x27);
   }
+
+  #[test]
+  fn reset_vector_test() {
+    let mut cpu = emu::cpu::CPU::new(None);
+    let mut bus = FlatBus::new();
+    bus.load(0xFFFC, &[0x00, 0x80]);
+
+    cpu.reset(&mut bus);
+
+    assert_eq!(cpu.pc, 0x8000);
+    assert!(cpu.f_i);
+  }
+
+  #[test]
+  fn bus_tick_test() {
+    let mut cpu = emu::cpu::CPU::new(None);
+    let mut bus = FlatBus::new();
+    bus.load(0x0000, &[0xEA, 0xEA]);
+
+    run_cpu_cycles(&mut cpu, 4, &mut bus);
+
+    assert_eq!(bus.cycles, 4);
+    assert_eq!(cpu.pc, 0x0002);
+  }
+
+  #[test]
+  fn adc_decimal_test() {
+    let mut cpu = emu::cpu::CPU::new(None);
+    let mut bus = FlatBus::new();
+    cpu.variant = emu::cpu::CpuVariant::Nmos6502;
+    cpu.f_d = true;
+    cpu.r_a = 0x58;
+    // ADC #$46
+    bus.load(0x0000, &[0x69, 0x46]);
+
+    run_cpu_cycles(&mut cpu, 2, &mut bus);
+
+    assert_eq!(cpu.r_a, 0x04);
+    assert!(cpu.f_c);
+  }
+
+  #[test]
+  fn sbc_decimal_test() {
+    let mut cpu = emu::cpu::CPU::new(None);
+    let mut bus = FlatBus::new();
+    cpu.variant = emu::cpu::CpuVariant::Nmos6502;
+    cpu.f_d = true;
+    cpu.f_c = true;
+    cpu.r_a = 0x12;
+    // SBC #$21
+    bus.load(0x0000, &[0xE9, 0x21]);
+
+    run_cpu_cycles(&mut cpu, 2, &mut bus);
+
+    assert_eq!(cpu.r_a, 0x91);
+    assert!(!cpu.f_c);
+  }
+
+  #[test]
+  fn ricoh_ignores_decimal_test() {
+    let mut cpu = emu::cpu::CPU::new(None);
+    let mut bus = FlatBus::new();
+    cpu.f_d = true;
+    cpu.r_a = 0x58;
+    bus.load(0x0000, &[0x69, 0x46]);
+
+    run_cpu_cycles(&mut cpu, 2, &mut bus);
+
+    assert_eq!(cpu.r_a, 0x9E);
+  }
 }
@@ -0,0 +1,194 @@
+#![allow(dead_code)]
+extern crate nes_emu;
+
+mod expansion_audio_tests {
+  use nes_emu::audio::expansion::{self, ExpansionAudio, APU_PULSE};
+  use nes_emu::audio::fds::Fds;
+  use nes_emu::audio::mmc5::Mmc5;
+  use nes_emu::audio::namco163::Namco163;
+  use nes_emu::audio::sunsoft5b::Sunsoft5b;
+  use nes_emu::audio::vrc6::Vrc6;
+  use nes_emu::audio::vrc7::Vrc7;
+  use nes_emu::emu::bus::Bus;
+  use nes_emu::emu::cartridge::Cartridge;
+
+  // Output after every one of `cycles` CPU cycles
+  fn run(chip: &mut dyn ExpansionAudio, cycles: usize) -> Vec<f32> {
+    (0..cycles).map(|_| {
+      chip.clock();
+      chip.output()
+    }).collect()
+  }
+
+  fn peak(outputs: &[f32]) -> f32 {
+    outputs.iter().fold(0.0, |peak, output| peak.max(output.abs()))
+  }
+
+  fn close(a: f32, b: f32) -> bool {
+    (a - b).abs() < 0.0001
+  }
+
+  #[test]
+  fn vrc6_test() {
+    let mut vrc6 = Vrc6::new(false);
+    // 50% duty at full volume
+    vrc6.write(0x9000, 0x7F);
+    vrc6.write(0x9001, 0x10);
+    vrc6.write(0x9002, 0x80);
+
+    let outputs = run(&mut vrc6, 17 * 16);
+    let high = outputs.iter().filter(|output| close(**output, APU_PULSE)).count();
+    assert_eq!(high, 17 * 8);
+
+    // Seven adds of 42 peak at 252, which is 31 out of the saw
+    let mut vrc6 = Vrc6::new(false);
+    vrc6.write(0xB000, 42);
+    vrc6.write(0xB002, 0x80);
+    assert!(close(peak(&run(&mut vrc6, 100)), APU_PULSE / 15.0 * 31.0));
+
+    // VRC6b takes the enable at $9001
+    let mut vrc6b = expansion::for_mapper(26).unwrap();
+    vrc6b.write(0x9000, 0x8F);
+    vrc6b.write(0x9001, 0x80);
+    assert!(close(peak(&run(vrc6b.as_mut(), 10)), APU_PULSE));
+  }
+
+  #[test]
+  fn vrc7_test() {
+    let mut vrc7 = Vrc7::default();
+    let mut write = |register: u8, value: u8| {
+      vrc7.write(0x9010, register);
+      vrc7.write(0x9030, value);
+    };
+    // Instrument 3 at full volume, key on at block 4
+    write(0x30, 0x30);
+    write(0x10, 0xAC);
+    write(0x20, 0x18);
+
+    let playing = peak(&run(&mut vrc7, 36 * 2000));
+    assert!(playing > 0.01);
+
+    vrc7.write(0xE000, 0x40);
+    assert_eq!(run(&mut vrc7, 36).last(), Some(&0.0));
+    assert!(!vrc7.write(0x8000, 0x00));
+  }
+
+  #[test]
+  fn sunsoft5b_test() {
+    let mut chip = Sunsoft5b::default();
+    let mut write = |register: u8, value: u8| {
+      chip.write(0xC000, register);
+      chip.write(0xE000, value);
+    };
+    // Tone A only, fixed volume 15
+    write(0, 1);
+    write(7, 0b11_1110);
+    write(8, 0x0F);
+
+    let outputs = run(&mut chip, 64);
+    assert!(outputs.contains(&0.0));
+    let loud = peak(&outputs);
+    assert!(loud > 0.0);
+
+    // Envelope attacks and holds at the top with tone and noise both off
+    let mut chip = Sunsoft5b::default();
+    let mut write = |register: u8, value: u8| {
+      chip.write(0xC000, register);
+      chip.write(0xE000, value);
+    };
+    write(7, 0b11_1111);
+    write(8, 0x10);
+    write(11, 1);
+    write(13, 0b1101);
+    let outputs = run(&mut chip, 16 * 40);
+    assert!(outputs[0] < loud);
+    assert!(close(*outputs.last().unwrap(), loud));
+  }
+
+  #[test]
+  fn namco163_test() {
+    let mut chip = Namco163::default();
+    // Square wave in the first 4 bytes, auto incrementing from address 0
+    chip.write(0xF800, 0x80);
+    for byte in &[0xFF, 0xFF, 0x00, 0x00] {
+      chip.write(0x4800, *byte);
+    }
+
+    // Channel 7 alone, 8 samples long, volume 15
+    chip.write(0xF800, 0xF8);
+    for byte in &[0x00, 0x00, 0x40, 0x00, 0xF8, 0x00, 0x00, 0x0F] {
+      chip.write(0x4800, *byte);
+    }
+
+    chip.write(0xF800, 0x80);
+    assert_eq!(chip.read(0x4800), Some(0xFF));
+    assert_eq!(chip.read(0x4800), Some(0xFF));
+    assert_eq!(chip.read(0x4000), None);
+
+    let outputs = run(&mut chip, 15 * 64);
+    assert!(outputs.iter().any(|output| *output > 0.0));
+    assert!(outputs.iter().any(|output| *output < 0.0));
+
+    chip.write(0xE000, 0x40);
+    assert_eq!(chip.output(), 0.0);
+  }
+
+  #[test]
+  fn mmc5_test() {
+    let mut mmc5 = Mmc5::default();
+    mmc5.write(0x5015, 0x01);
+    mmc5.write(0x5000, 0xBF);
+    mmc5.write(0x5002, 0x20);
+    mmc5.write(0x5003, 0x08);
+    assert_eq!(mmc5.read(0x5015), Some(0x01));
+    assert!(close(peak(&run(&mut mmc5, 200)), APU_PULSE));
+
+    // Disabling clears the length counter
+    mmc5.write(0x5015, 0x00);
+    assert_eq!(mmc5.read(0x5015), Some(0x00));
+    assert_eq!(peak(&run(&mut mmc5, 200)), 0.0);
+
+    mmc5.write(0x5011, 0xFF);
+    mmc5.write(0x5011, 0x00);
+    assert!(mmc5.output() > APU_PULSE);
+  }
+
+  #[test]
+  fn fds_test() {
+    let mut fds = Fds::default();
+    fds.write(0x4089, 0x80);
+    for index in 0..64 {
+      fds.write(0x4040 + index, 63);
+    }
+    fds.write(0x4089, 0x00);
+    assert_eq!(fds.read(0x4040), Some(0x7F));
+
+    // Wave RAM is read only while writes are disabled
+    fds.write(0x4040, 0);
+    assert_eq!(fds.read(0x4040), Some(0x7F));
+
+    fds.write(0x4080, 0x80 | 32);
+    assert_eq!(fds.read(0x4090), Some(0x40 | 32));
+    fds.write(0x4082, 0x00);
+    fds.write(0x4083, 0x08);
+
+    // Climbs towards full scale through the output filter
+    let outputs = run(&mut fds, 5000);
+    assert!(outputs.last().unwrap() > &(APU_PULSE * 2.0));
+  }
+
+  #[test]
+  fn bus_routes_expansion_audio_test() {
+    let mut bytes = vec![0x4E, 0x45, 0x53, 0x1A, 0x01, 0x01, 0x80, 0x10];
+    bytes.resize(16, 0x00);
+    bytes.extend_from_slice(&[0x00; 0x4000 + 0x2000]);
+    let mut bus = Bus::new(Cartridge::new(&bytes).unwrap());
+    assert_eq!(bus.cartridge.mapper, 24);
+
+    bus.write(0x9000, 0x8F);
+    bus.write(0x9002, 0x80);
+    bus.tick(2);
+    // On top of the idle APU, whose triangle rests on a nonzero step
+    assert!(close(bus.audio_output() - bus.apu.output(), APU_PULSE));
+  }
+}
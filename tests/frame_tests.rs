@@ -0,0 +1,194 @@
+#![allow(dead_code)]
+extern crate nes_emu;
+
+mod frame_tests {
+  use nes_emu::graphics::frame::{Frame, WIDTH, HEIGHT};
+  use nes_emu::graphics::index_frame::{IndexFrame, PixelFormat};
+  use nes_emu::graphics::ntsc::{NtscFilter, NtscSetup, OUTPUT_WIDTH};
+  use nes_emu::graphics::palette::Palette;
+  use nes_emu::graphics::{inflate, png, ppm};
+
+  fn filled_index_frame(index: u8) -> IndexFrame {
+    let mut frame = IndexFrame::default();
+    for y in 0..HEIGHT {
+      for x in 0..WIDTH {
+        frame.set_pixel(x, y, index, false, 0);
+      }
+    }
+    frame
+  }
+
+  fn test_frame() -> Frame {
+    let mut frame = Frame::default();
+    for y in 0..HEIGHT {
+      for x in 0..WIDTH {
+        frame.set_pixel(x, y, (x as u8, y as u8, (x ^ y) as u8));
+      }
+    }
+    frame
+  }
+
+  #[test]
+  fn blank_frame_hash_test() {
+    // Golden value, changing the hash function invalidates every stored hash
+    assert_eq!(Frame::default().hash(), 0x96D6_3225_EA92_6325);
+  }
+
+  #[test]
+  fn hash_detects_change_test() {
+    let mut frame = test_frame();
+    let hash = frame.hash();
+    frame.set_pixel(100, 100, (0, 0, 0));
+
+    assert_ne!(frame.hash(), hash);
+    assert_eq!(frame.diff(&test_frame()), 1);
+  }
+
+  #[test]
+  fn png_round_trip_test() {
+    let frame = test_frame();
+    let mut bytes = Vec::new();
+    png::write_rgb(&mut bytes, WIDTH as u32, HEIGHT as u32, &frame.data).unwrap();
+
+    let (width, height, data) = png::read_rgb(&bytes).unwrap();
+
+    assert_eq!((width, height), (WIDTH as u32, HEIGHT as u32));
+    assert_eq!(data, frame.data);
+  }
+
+  #[test]
+  fn ppm_round_trip_test() {
+    let frame = test_frame();
+    let mut bytes = Vec::new();
+    ppm::write_rgb(&mut bytes, WIDTH as u32, HEIGHT as u32, &frame.data).unwrap();
+
+    assert!(bytes.starts_with(b"P6\n256 240\n255\n"));
+
+    let (width, height, data) = ppm::read_rgb(&bytes).unwrap();
+
+    assert_eq!((width, height), (WIDTH as u32, HEIGHT as u32));
+    assert_eq!(data, frame.data);
+  }
+
+  #[test]
+  fn compressed_rgba_png_test() {
+    // 3x2 RGBA image written by zlib at level 9
+    let bytes = [
+      0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A, 0x00, 0x00, 0x00, 0x0D, 0x49, 0x48, 0x44, 0x52,
+      0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x02, 0x08, 0x06, 0x00, 0x00, 0x00, 0x9D, 0x74, 0x66,
+      0x1A, 0x00, 0x00, 0x00, 0x22, 0x49, 0x44, 0x41, 0x54, 0x78, 0xDA, 0x63, 0xE0, 0x12, 0x91, 0xFB,
+      0xAF, 0x61, 0x64, 0xF3, 0xDF, 0x2D, 0x20, 0xAA, 0x81, 0x81, 0x91, 0x89, 0x99, 0x85, 0x95, 0x8D,
+      0x9D, 0xE3, 0x44, 0x8A, 0x11, 0x03, 0x00, 0x54, 0xC6, 0x05, 0xC3, 0xF1, 0x29, 0xEF, 0xDB, 0x00,
+      0x00, 0x00, 0x00, 0x49, 0x45, 0x4E, 0x44, 0xAE, 0x42, 0x60, 0x82
+    ];
+
+    let (width, height, data) = png::read_rgb(&bytes).unwrap();
+
+    assert_eq!((width, height), (3, 2));
+    assert_eq!(data, vec![10, 20, 30, 40, 50, 60, 70, 80, 90, 1, 2, 3, 5, 6, 7, 200, 100, 50]);
+  }
+
+  #[test]
+  fn dynamic_huffman_inflate_test() {
+    let compressed = [
+      0x78, 0xDA, 0xD5, 0xCD, 0xDB, 0x09, 0x80, 0x30, 0x10, 0x44, 0xD1, 0x56, 0xA6, 0x00, 0x11, 0x7C,
+      0x60, 0x03, 0x36, 0xE0, 0x87, 0x16, 0x10, 0x36, 0x13, 0x22, 0x84, 0x0D, 0x6C, 0x54, 0xB0, 0x7B,
+      0xA3, 0x5D, 0xF8, 0x7F, 0x0F, 0x77, 0x8D, 0xC4, 0xB2, 0x6C, 0x30, 0xAA, 0xA7, 0x15, 0xF4, 0x53,
+      0x8F, 0x22, 0x4E, 0xD3, 0xAE, 0x2C, 0xC8, 0x01, 0xC3, 0xD8, 0xC1, 0xE7, 0xA3, 0x80, 0x4E, 0x62,
+      0x03, 0xA7, 0x1E, 0x47, 0x35, 0xF3, 0x6B, 0x4E, 0xAD, 0x89, 0x12, 0x72, 0x4B, 0x22, 0x42, 0x36,
+      0xF0, 0xA2, 0xDD, 0x35, 0x30, 0xF2, 0x53, 0x2D, 0xD6, 0xDF, 0x0F, 0x1E, 0x5E, 0x1C, 0x61, 0xAE
+    ];
+    let text = "The PPU renders 262 scanlines of 341 dots each, and the CPU runs one cycle for every three dots. ".repeat(3);
+
+    assert_eq!(inflate::zlib_decompress(&compressed).unwrap(), text.as_bytes());
+  }
+
+  #[test]
+  fn palette_grayscale_test() {
+    let palette = Palette::default();
+
+    assert_eq!(palette.rgb(0x16, false, 0), (0xFF, 0x22, 0x00));
+    assert_eq!(palette.rgb(0x16, true, 0), palette.rgb(0x10, false, 0));
+  }
+
+  #[test]
+  fn palette_emphasis_test() {
+    let palette = Palette::default();
+
+    // Red emphasis leaves red alone and dims green and blue
+    assert_eq!(palette.rgb(0x20, false, 0b001), (0xFF, 0xBF, 0xBF));
+    // All three dim everything, the same as the NTSC decoder
+    assert_eq!(palette.rgb(0x20, false, 0b111), (0xBF, 0xBF, 0xBF));
+  }
+
+  #[test]
+  fn palette_file_test() {
+    let mut bytes: Vec<u8> = (0..192).map(|byte| byte as u8).collect();
+    let palette = Palette::from_bytes(&bytes).unwrap();
+    assert_eq!(palette.rgb(0x01, false, 0), (3, 4, 5));
+    assert_eq!(palette.colors.len(), 512);
+
+    bytes.resize(1536, 0x7F);
+    let palette = Palette::from_bytes(&bytes).unwrap();
+    assert_eq!(palette.rgb(0x01, false, 0b100), (0x7F, 0x7F, 0x7F));
+
+    assert!(Palette::from_bytes(&[0; 100]).is_err());
+  }
+
+  #[test]
+  fn index_frame_entry_test() {
+    let mut frame = IndexFrame::default();
+    frame.set_pixel(1, 0, 0x16, false, 0b101);
+    frame.set_pixel(2, 0, 0x16, true, 0);
+
+    assert_eq!(frame.get_pixel(1, 0), 0x156);
+    assert_eq!(frame.get_pixel(2, 0), 0x10);
+  }
+
+  #[test]
+  fn index_frame_formats_test() {
+    let palette = Palette::default();
+    let mut frame = IndexFrame::default();
+    frame.set_pixel(0, 0, 0x16, false, 0);
+
+    assert_eq!(&frame.convert(&palette, PixelFormat::Rgb24)[..3], &[0xFF, 0x22, 0x00]);
+    assert_eq!(&frame.convert(&palette, PixelFormat::Rgba8888)[..4], &[0xFF, 0x22, 0x00, 0xFF]);
+    assert_eq!(&frame.convert(&palette, PixelFormat::Bgra8888)[..4], &[0x00, 0x22, 0xFF, 0xFF]);
+    assert_eq!(&frame.convert(&palette, PixelFormat::Rgb565)[..2], &0xF900u16.to_le_bytes());
+    assert_eq!(frame.convert(&palette, PixelFormat::Rgb565).len(), WIDTH * HEIGHT * 2);
+    assert_eq!(frame.to_frame(&palette).data[..3], [0xFF, 0x22, 0x00]);
+  }
+
+  #[test]
+  fn ntsc_colors_test() {
+    let filter = NtscFilter::new(NtscSetup::rgb());
+
+    let image = filter.render(&filled_index_frame(0x10), 0);
+    assert_eq!((image.width, image.height), (OUTPUT_WIDTH, HEIGHT));
+    let (red, green, blue) = image.get_pixel(300, 100);
+    assert!(red == green && green == blue);
+
+    let (red, green, blue) = filter.render(&filled_index_frame(0x16), 0).get_pixel(300, 100);
+    assert!(red > green && red > blue);
+
+    let (red, green, blue) = filter.render(&filled_index_frame(0x12), 0).get_pixel(300, 100);
+    assert!(blue > red && blue > green);
+  }
+
+  #[test]
+  fn ntsc_dot_crawl_test() {
+    let mut frame = filled_index_frame(0x30);
+    for y in 0..HEIGHT {
+      for x in (0..WIDTH).step_by(2) {
+        frame.set_pixel(x, y, 0x0F, false, 0);
+      }
+    }
+
+    let composite = NtscFilter::new(NtscSetup::composite());
+    assert_ne!(composite.render(&frame, 0), composite.render(&frame, 4));
+
+    let monochrome = NtscFilter::new(NtscSetup::monochrome());
+    let (red, green, blue) = monochrome.render(&frame, 0).get_pixel(100, 100);
+    assert!(red == green && green == blue);
+  }
+}
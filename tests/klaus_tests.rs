@@ -0,0 +1,94 @@
+#![allow(dead_code)]
+extern crate nes_emu;
+
+// Klaus Dormann's 6502 functional and decimal tests. The binaries are not shipped with the
+// repo, point KLAUS_FUNCTIONAL_TEST / KLAUS_DECIMAL_TEST at them (or drop them in ROMS/).
+mod klaus_tests {
+  use nes_emu::emu::cpu::{CPU, CpuVariant};
+  use nes_emu::emu::cpu_bus::FlatBus;
+
+  const FUNCTIONAL_START: u16 = 0x0400;
+  const FUNCTIONAL_SUCCESS: u16 = 0x3469;
+
+  const DECIMAL_START: u16 = 0x0200;
+  const DECIMAL_ERROR: u16 = 0x000B;
+
+  const MAX_INSTRUCTIONS: u64 = 100_000_000;
+
+  // Run until the program counter stops moving, returning the address it got stuck on
+  fn run_until_trap(cpu: &mut CPU, bus: &mut FlatBus, max_instructions: u64) -> Option<u16> {
+    for _x in 0..max_instructions {
+      let pc = cpu.pc;
+      cpu.step_instruction(bus);
+      if cpu.pc == pc {
+        return Some(pc);
+      }
+    }
+    None
+  }
+
+  fn load_image(env_var: &str, default_path: &str) -> Option<Vec<u8>> {
+    let path = std::env::var(env_var).unwrap_or_else(|_| default_path.to_string());
+    match std::fs::read(&path) {
+      Ok(bytes) => Some(bytes),
+      Err(_) => {
+        println!("SKIPPING, {} NOT FOUND (SET {})", path, env_var);
+        None
+      }
+    }
+  }
+
+  fn nmos_cpu(start: u16) -> CPU {
+    let mut cpu = CPU::new(None);
+    cpu.variant = CpuVariant::Nmos6502;
+    cpu.pc = start;
+    cpu.sp = 0xFF;
+    cpu.f_u = true;
+    cpu.f_i = true;
+    cpu
+  }
+
+  #[test]
+  fn trap_detection_test() {
+    let mut bus = FlatBus::new();
+    // LDA #$01, JMP $0402
+    bus.load(0x0400, &[0xA9, 0x01, 0x4C, 0x02, 0x04]);
+    let mut cpu = nmos_cpu(0x0400);
+
+    assert_eq!(run_until_trap(&mut cpu, &mut bus, 100), Some(0x0402));
+    assert_eq!(cpu.r_a, 0x01);
+  }
+
+  #[test]
+  fn functional_test() {
+    let image = match load_image("KLAUS_FUNCTIONAL_TEST", "./ROMS/6502_functional_test.bin") {
+      Some(image) => image,
+      None => return
+    };
+
+    let mut bus = FlatBus::new();
+    bus.load(0x0000, &image);
+    let mut cpu = nmos_cpu(FUNCTIONAL_START);
+
+    let trap = run_until_trap(&mut cpu, &mut bus, MAX_INSTRUCTIONS);
+    assert_eq!(trap, Some(FUNCTIONAL_SUCCESS), "FUNCTIONAL TEST TRAPPED AT {:04x?}", trap);
+  }
+
+  #[test]
+  fn decimal_test() {
+    let image = match load_image("KLAUS_DECIMAL_TEST", "./ROMS/6502_decimal_test.bin") {
+      Some(image) => image,
+      None => return
+    };
+
+    let mut bus = FlatBus::new();
+    // Full 64 KiB images carry their own layout, bare builds are assembled at $0200
+    let load_addr = if image.len() == 0x10000 { 0x0000 } else { DECIMAL_START };
+    bus.load(load_addr, &image);
+    let mut cpu = nmos_cpu(DECIMAL_START);
+
+    let trap = run_until_trap(&mut cpu, &mut bus, MAX_INSTRUCTIONS);
+    assert!(trap.is_some(), "DECIMAL TEST DID NOT FINISH");
+    assert_eq!(bus.ram[DECIMAL_ERROR as usize], 0x00, "DECIMAL TEST FAILED, TRAPPED AT {:04x?}", trap);
+  }
+}
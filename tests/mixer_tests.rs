@@ -0,0 +1,106 @@
+#![allow(dead_code)]
+extern crate nes_emu;
+
+mod mixer_tests {
+  use nes_emu::audio::expansion::{ExpansionAudio, APU_PULSE};
+  use nes_emu::audio::apu::Apu;
+  use nes_emu::audio::mixer::{Mixer, APU_CHANNELS};
+  use nes_emu::audio::vrc6::Vrc6;
+  use nes_emu::emu::nsf::{Nsf, EXPANSION_VRC6};
+  use nes_emu::emu::nsf_player::NsfPlayer;
+
+  // Both VRC6 pulses at full constant volume
+  fn chips() -> Vec<Box<dyn ExpansionAudio>> {
+    let mut vrc6 = Vrc6::new(false);
+    for addr in &[0x9000, 0xA000] {
+      vrc6.write(*addr, 0x8F);
+      vrc6.write(*addr + 2, 0x80);
+    }
+    vrc6.clock();
+    vec![Box::new(vrc6)]
+  }
+
+  fn close(a: f32, b: f32) -> bool {
+    (a - b).abs() < 0.0001
+  }
+
+  #[test]
+  fn channel_names_test() {
+    let mut mixer = Mixer::new(&chips());
+    let names: Vec<&str> = mixer.channels.iter().map(|channel| channel.name.as_str()).collect();
+    assert_eq!(names[..APU_CHANNELS.len()], APU_CHANNELS);
+    assert_eq!(names[APU_CHANNELS.len()..], ["VRC6 Pulse 1", "VRC6 Pulse 2", "VRC6 Saw"]);
+
+    assert_eq!(mixer.channel("vrc6-pulse2").unwrap().name, "VRC6 Pulse 2");
+    assert!(mixer.channel("vrc7 fm1").is_err());
+    assert_eq!(mixer.channel("pulse1").unwrap().name, "Pulse 1");
+    assert_eq!(mixer.channel("DMC").unwrap().name, "DMC");
+  }
+
+  #[test]
+  fn mute_solo_gain_test() {
+    let chips = chips();
+    let mut mixer = Mixer::new(&chips);
+    let apu = Apu::new();
+    // The idle triangle rests on a nonzero step
+    mixer.channel("triangle").unwrap().muted = true;
+    assert!(close(mixer.mix(&apu, &chips), APU_PULSE * 2.0));
+    assert!(close(mixer.mix(&apu, &chips), chips[0].output()));
+
+    mixer.channel("vrc6 pulse 1").unwrap().muted = true;
+    assert!(close(mixer.mix(&apu, &chips), APU_PULSE));
+
+    mixer.channel("vrc6 pulse 2").unwrap().gain = 0.5;
+    assert!(close(mixer.mix(&apu, &chips), APU_PULSE * 0.5));
+
+    // Solo wins over everything not soloed, mute wins over solo
+    mixer.channel("vrc6 pulse 1").unwrap().muted = false;
+    mixer.channel("vrc6 saw").unwrap().solo = true;
+    assert_eq!(mixer.mix(&apu, &chips), 0.0);
+    mixer.channel("vrc6 pulse 1").unwrap().solo = true;
+    assert!(close(mixer.mix(&apu, &chips), APU_PULSE));
+    assert_eq!(mixer.volume(APU_CHANNELS.len() + 1), 0.0);
+    mixer.channel("vrc6 pulse 1").unwrap().muted = true;
+    assert_eq!(mixer.mix(&apu, &chips), 0.0);
+
+    // Levels for stems keep the gain and ignore the rest
+    let mut levels = Vec::new();
+    mixer.levels(&apu, &chips, &mut levels);
+    assert_eq!(levels.len(), APU_CHANNELS.len() + 3);
+    assert!(close(levels[APU_CHANNELS.len()], APU_PULSE));
+    assert!(close(levels[APU_CHANNELS.len() + 1], APU_PULSE * 0.5));
+  }
+
+  #[test]
+  fn stems_test() {
+    // INIT starts VRC6 pulse 1 at full constant volume, PLAY does nothing
+    let mut bytes = vec![0; 0x80];
+    bytes[0..5].copy_from_slice(b"NESM\x1A");
+    bytes[5] = 1;
+    bytes[6] = 1;
+    bytes[7] = 1;
+    bytes[0x08..0x0E].copy_from_slice(&[0x00, 0x80, 0x00, 0x80, 0x0B, 0x80]);
+    bytes[0x6E..0x70].copy_from_slice(&16639u16.to_le_bytes());
+    bytes[0x7B] = EXPANSION_VRC6;
+    bytes.extend_from_slice(&[0xA9, 0x8F, 0x8D, 0x00, 0x90, 0xA9, 0x80, 0x8D, 0x02, 0x90, 0x60, 0x60]);
+
+    let mut player = NsfPlayer::new(Nsf::new(&bytes).unwrap()).unwrap();
+    // Stems need a started track
+    assert!(player.render_stems(100).is_err());
+    player.bus.mixer.channel("vrc6 pulse 1").unwrap().muted = true;
+    player.bus.mixer.channel("triangle").unwrap().muted = true;
+    player.start_track(0).unwrap();
+    let stems = player.render_stems(100).unwrap();
+    assert_eq!(stems.len(), APU_CHANNELS.len() + 3);
+    assert!(stems.iter().all(|stem| stem.len() == 100));
+
+    // The muted channel still gets its stem, the others are silent
+    let level = (APU_PULSE * i16::MAX as f32) as i16;
+    assert!(stems[APU_CHANNELS.len()].iter().all(|sample| (sample - level).abs() <= 1));
+    assert!(stems[APU_CHANNELS.len() + 1].iter().all(|sample| *sample == 0));
+
+    // Mixer settings survive starting a track and muting applies to the mix
+    player.start_track(0).unwrap();
+    assert!(player.render(100).unwrap().iter().all(|sample| *sample == 0));
+  }
+}
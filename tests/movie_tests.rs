@@ -0,0 +1,214 @@
+#![allow(dead_code)]
+extern crate nes_emu;
+
+mod movie_tests {
+  use nes_emu::emu::base64;
+  use nes_emu::emu::cartridge::Cartridge;
+  use nes_emu::emu::input;
+  use nes_emu::emu::joypad::{BUTTON_A, BUTTON_START, BUTTON_UP};
+  use nes_emu::emu::md5::md5;
+  use nes_emu::emu::movie::{Movie, PortDevice, ZapperInput, COMMAND_POWER, COMMAND_RESET};
+  use nes_emu::emu::multitap::FourScore;
+  use nes_emu::emu::nes::NES;
+  use nes_emu::emu::zapper::Zapper;
+
+  fn test_cartridge(program: &[u8]) -> Cartridge {
+    let mut bytes = vec![0x4E, 0x45, 0x53, 0x1A, 0x01, 0x01, 0x00, 0x00];
+    bytes.resize(16, 0x00);
+
+    let mut prg_rom = vec![0xEA; 0x4000];
+    prg_rom[..program.len()].copy_from_slice(program);
+    prg_rom[0x3FFC] = 0x00;
+    prg_rom[0x3FFD] = 0x80;
+
+    bytes.extend_from_slice(&prg_rom);
+    bytes.extend_from_slice(&[0x00; 0x2000]);
+    Cartridge::new(&bytes).unwrap()
+  }
+
+  // Strobes the controller, ORs the A button into $11, counts loops in $10 and starts over
+  const BUTTON_LOGGER: [u8; 24] = [
+    0xA9, 0x01, 0x8D, 0x16, 0x40, 0xA9, 0x00, 0x8D, 0x16, 0x40,
+    0xAD, 0x16, 0x40, 0x29, 0x01, 0x05, 0x11, 0x85, 0x11,
+    0xE6, 0x10, 0x4C, 0x00, 0x80
+  ];
+
+  #[test]
+  fn parse_test() {
+    let text = "version 3\nemuVersion 22020\nrerecordCount 7\nromFilename game\nfourscore 0\nport0 1\nport1 2\nport2 0\n\
+      |0|R..UT..A| 12  34 1 0 0||\n|1|........|255 255 0 0 0||\n";
+    let movie = Movie::parse_fm2(text).unwrap();
+    assert_eq!(movie.rerecord_count, 7);
+    assert_eq!(movie.ports, [PortDevice::Gamepad, PortDevice::Zapper]);
+    assert_eq!(movie.frames.len(), 2);
+    assert_eq!(movie.frames[0].buttons[0], 0x80 | BUTTON_UP | BUTTON_START | BUTTON_A);
+    assert_eq!(movie.frames[0].zappers[1], ZapperInput { x: 12, y: 34, trigger: true });
+    assert_eq!(movie.frames[1].commands, COMMAND_RESET);
+
+    // Saving gives back the same text apart from where the known keys go
+    let saved = movie.to_fm2();
+    assert!(saved.contains("emuVersion 22020\nromFilename game\n"));
+    assert_eq!(Movie::parse_fm2(&saved).unwrap().frames, movie.frames);
+    assert!(saved.ends_with("|0|R..UT..A| 12  34 1 0 0||\n|1|........|255 255 0 0 0||\n"));
+
+    assert_eq!(Movie::parse_fm2("version 3\nsavestate base64:AAEC\n").unwrap().savestate, Some(vec![0x00, 0x01, 0x02]));
+    assert_eq!(Movie::parse_fm2("version 3\nsavestate 0x0001ff\n").unwrap().savestate, Some(vec![0x00, 0x01, 0xFF]));
+    assert!(Movie::parse_fm2("version 3\nsavestate base64:A\n").is_err());
+    assert!(Movie::parse_fm2("version 3\nport0 2\n|0|........||\n").is_err());
+    assert!(Movie::parse_fm2("version 2\n").is_err());
+  }
+
+  #[test]
+  fn four_score_test() {
+    let movie = Movie::parse_fm2("version 3\nfourscore 1\n|0|.......A|........|...U....|...UT...||\n").unwrap();
+    assert!(movie.four_score);
+    assert_eq!(movie.frames[0].buttons, [BUTTON_A, 0, BUTTON_UP, BUTTON_UP | BUTTON_START]);
+    assert!(movie.to_fm2().ends_with("|0|.......A|........|...U....|...UT...||\n"));
+
+    let mut nes = NES::new(test_cartridge(&BUTTON_LOGGER));
+    movie.plug_devices(&mut nes.bus);
+    assert!(nes.bus.device::<FourScore>(1).is_some());
+    movie.frames[0].apply(&mut nes.bus);
+    assert_eq!(nes.bus.controller(3).unwrap().buttons, BUTTON_UP | BUTTON_START);
+
+    let mut zapper_movie = Movie::new();
+    zapper_movie.ports[1] = PortDevice::Zapper;
+    zapper_movie.plug_devices(&mut nes.bus);
+    assert!(nes.bus.device::<Zapper>(1).is_some());
+    assert_eq!(Movie::for_bus(&mut nes.bus).unwrap().ports, [PortDevice::Gamepad, PortDevice::Zapper]);
+  }
+
+  #[test]
+  fn for_bus_test() {
+    let mut nes = NES::new(test_cartridge(&BUTTON_LOGGER));
+    let movie = Movie::for_bus(&mut nes.bus).unwrap();
+    let header = |key: &str| movie.header.iter().find(|(existing, _)| existing == key).map(|(_, value)| value.clone()).unwrap();
+    assert_eq!(header("emuVersion"), "100");
+    assert_eq!(header("romChecksum"), format!("base64:{}", base64::encode(&nes.bus.cartridge.checksum())));
+    assert!(movie.matches_rom(&nes.bus.cartridge));
+    assert!(!movie.matches_rom(&test_cartridge(&[0x4C, 0x00, 0x80])));
+
+    // 8-4-4-4-12 hex digits, different for every movie
+    let guid = header("guid");
+    let groups: Vec<usize> = guid.split('-').map(|group| group.len()).collect();
+    assert_eq!(groups, [8, 4, 4, 4, 12]);
+    assert!(guid.chars().all(|character| character == '-' || character.is_ascii_hexdigit()));
+    let other = Movie::for_bus(&mut nes.bus).unwrap();
+    assert!(!other.header.contains(&("guid".to_string(), guid)));
+
+    // Devices FM2 has no input log for
+    nes.bus.ports[1] = input::port_device("vaus").unwrap();
+    assert!(Movie::for_bus(&mut nes.bus).is_err());
+    nes.bus.ports[1] = input::port_device("power-pad").unwrap();
+    assert!(nes.start_movie_recording().is_err());
+    nes.bus.ports[1] = input::port_device("joypad").unwrap();
+    nes.bus.expansion_port = input::expansion_device("family-trainer").unwrap();
+    assert!(Movie::for_bus(&mut nes.bus).is_err());
+    nes.bus.expansion_port = input::expansion_device("hori").unwrap();
+    assert!(Movie::for_bus(&mut nes.bus).is_err());
+  }
+
+  #[test]
+  fn checksum_test() {
+    assert_eq!(md5(b""), [0xD4, 0x1D, 0x8C, 0xD9, 0x8F, 0x00, 0xB2, 0x04, 0xE9, 0x80, 0x09, 0x98, 0xEC, 0xF8, 0x42, 0x7E]);
+    assert_eq!(md5(b"abc"), [0x90, 0x01, 0x50, 0x98, 0x3C, 0xD2, 0x4F, 0xB0, 0xD6, 0x96, 0x3F, 0x7D, 0x28, 0xE1, 0x7F, 0x72]);
+    // Longer than one block
+    assert_eq!(md5(&[b'a'; 100])[..4], [0x36, 0xA9, 0x2C, 0xC9]);
+
+    assert_eq!(base64::encode(b"Man"), "TWFu");
+    assert_eq!(base64::encode(b"Ma"), "TWE=");
+    assert_eq!(base64::encode(b"M"), "TQ==");
+    let data: Vec<u8> = (0..=255).collect();
+    assert_eq!(base64::decode(&base64::encode(&data)).unwrap(), data);
+    assert!(base64::decode("TW!u").is_err());
+  }
+
+  #[test]
+  fn record_playback_test() {
+    let mut nes = NES::new(test_cartridge(&BUTTON_LOGGER));
+    nes.start_movie_recording().unwrap();
+    nes.run(2, &[], None);
+    nes.bus.controller(0).unwrap().buttons = BUTTON_A;
+    nes.run(1, &[], None);
+    assert_eq!(nes.bus.ram[0x11], 0x01);
+
+    // A power cycle clears RAM, a reset does not
+    nes.bus.controller(0).unwrap().buttons = 0;
+    nes.queue_command(COMMAND_RESET);
+    nes.run(1, &[], None);
+    assert_eq!(nes.bus.ram[0x11], 0x01);
+    nes.queue_command(COMMAND_POWER);
+    nes.run(2, &[], None);
+    assert_eq!(nes.bus.ram[0x11], 0x00);
+
+    let movie = nes.stop_movie_recording().unwrap();
+    assert_eq!(movie.frames.len(), 6);
+    assert_eq!(movie.frames[2].buttons[0], BUTTON_A);
+    assert_eq!(movie.frames[3].commands, COMMAND_RESET);
+    assert_eq!(movie.frames[4].commands, COMMAND_POWER);
+
+    // Played back from power on through the FM2 text it ends up in the same place
+    let movie = Movie::parse_fm2(&movie.to_fm2()).unwrap();
+    let mut replay = NES::new(test_cartridge(&BUTTON_LOGGER));
+    replay.start_movie_playback(&movie).unwrap();
+    let result = replay.run(6, &[], Some(&movie));
+    assert_eq!(result.frames, 6);
+    assert_eq!(replay.bus.ram, nes.bus.ram);
+    assert_eq!(replay.cpu.pc, nes.cpu.pc);
+    assert_eq!(replay.cpu.cycles, nes.cpu.cycles);
+  }
+
+  #[test]
+  fn save_state_playback_test() {
+    let mut nes = NES::new(test_cartridge(&BUTTON_LOGGER));
+    nes.reset();
+    nes.bus.controller(0).unwrap().buttons = BUTTON_A;
+    nes.run(3, &[], None);
+    assert_eq!(nes.bus.ram[0x11], 0x01);
+
+    // Recording picks up where the console is, without a power cycle
+    nes.start_movie_recording_from_state().unwrap();
+    nes.bus.controller(0).unwrap().buttons = 0;
+    nes.run(2, &[], None);
+    nes.queue_command(COMMAND_RESET);
+    nes.run(1, &[], None);
+    assert_eq!(nes.bus.ram[0x11], 0x01);
+    let movie = nes.stop_movie_recording().unwrap();
+    assert_eq!(movie.frames.len(), 3);
+
+    let text = movie.to_fm2();
+    assert!(text.contains("savestate base64:"));
+    let movie = Movie::parse_fm2(&text).unwrap();
+    let mut replay = NES::new(test_cartridge(&BUTTON_LOGGER));
+    replay.start_movie_playback(&movie).unwrap();
+    assert_eq!(replay.bus.ram[0x11], 0x01);
+    let result = replay.run(3, &[], Some(&movie));
+    assert_eq!(result.frames, 3);
+    assert_eq!(replay.bus.ram, nes.bus.ram);
+    assert_eq!(replay.bus.ppu.vram, nes.bus.ppu.vram);
+    assert_eq!(replay.cpu.pc, nes.cpu.pc);
+    assert_eq!(replay.cpu.cycles, nes.cpu.cycles);
+    assert_eq!(replay.frame.hash(), nes.frame.hash());
+  }
+
+  #[test]
+  fn save_state_test() {
+    let mut nes = NES::new(test_cartridge(&BUTTON_LOGGER));
+    nes.reset();
+    nes.run(2, &[], None);
+    let state = nes.save_state().unwrap();
+    nes.run(2, &[], None);
+
+    let mut copy = NES::new(test_cartridge(&BUTTON_LOGGER));
+    copy.load_state(&state).unwrap();
+    copy.run(2, &[], None);
+    assert_eq!(copy.bus.ram, nes.bus.ram);
+    assert_eq!(copy.cpu.cycles, nes.cpu.cycles);
+
+    // States only load into the same ROM and whole
+    let mut other = NES::new(test_cartridge(&[0x4C, 0x00, 0x80]));
+    assert_eq!(other.load_state(&state), Err("SAVE STATE IS FOR A DIFFERENT ROM".to_string()));
+    assert!(copy.load_state(&state[..(state.len() - 1)]).is_err());
+    assert!(copy.load_state(b"NOPE").is_err());
+  }
+}
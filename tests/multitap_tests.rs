@@ -0,0 +1,107 @@
+#![allow(dead_code)]
+extern crate nes_emu;
+
+mod multitap_tests {
+  use nes_emu::emu::bus::Bus;
+  use nes_emu::emu::cartridge::Cartridge;
+  use nes_emu::emu::joypad::{BUTTON_A, BUTTON_B, BUTTON_START, BUTTON_RIGHT};
+  use nes_emu::emu::multitap::{FourScore, HoriAdapter};
+
+  fn test_bus() -> Bus {
+    let mut bytes = vec![0x4E, 0x45, 0x53, 0x1A, 0x01, 0x01, 0x00, 0x00];
+    bytes.resize(16, 0x00);
+    bytes.extend_from_slice(&[0x00; 0x4000 + 0x2000]);
+    Bus::new(Cartridge::new(&bytes).unwrap())
+  }
+
+  // Strobes and reads `count` bits of `addr`, masked to `mask`
+  fn read_bits(bus: &mut Bus, addr: u16, mask: u8, count: usize) -> Vec<u8> {
+    bus.write(0x4016, 1);
+    bus.write(0x4016, 0);
+    (0..count).map(|_| if bus.read(addr) & mask != 0 { 1 } else { 0 }).collect()
+  }
+
+  // Bits of a byte in the order they are shifted out
+  fn bits(value: u8) -> Vec<u8> {
+    (0..8).map(|bit| (value >> bit) & 0x01).collect()
+  }
+
+  #[test]
+  fn four_score_test() {
+    let mut bus = test_bus();
+    bus.plug_four_score();
+    let buttons = [BUTTON_A, BUTTON_B, BUTTON_START, BUTTON_RIGHT];
+    for (player, value) in buttons.iter().enumerate() {
+      bus.controller(player).unwrap().buttons = *value;
+    }
+
+    let port_1 = read_bits(&mut bus, 0x4016, 0x01, 26);
+    let expected: Vec<u8> = [bits(BUTTON_A), bits(BUTTON_START), vec![0, 0, 0, 1, 0, 0, 0, 0], vec![1, 1]].concat();
+    assert_eq!(port_1, expected);
+
+    let port_2 = read_bits(&mut bus, 0x4017, 0x01, 24);
+    let expected: Vec<u8> = [bits(BUTTON_B), bits(BUTTON_RIGHT), vec![0, 0, 1, 0, 0, 0, 0, 0]].concat();
+    assert_eq!(port_2, expected);
+
+    // Two player mode reads like a plain controller
+    bus.device::<FourScore>(0).unwrap().four_player = false;
+    let port_1 = read_bits(&mut bus, 0x4016, 0x01, 10);
+    assert_eq!(port_1, [bits(BUTTON_A), vec![1, 1]].concat());
+    assert!(bus.controller(4).is_none());
+  }
+
+  #[test]
+  fn hori_adapter_test() {
+    let mut bus = test_bus();
+    bus.expansion_port = Some(Box::new(HoriAdapter::new(true)));
+    let adapter = bus.expansion_device::<HoriAdapter>().unwrap();
+    adapter.controllers[0].buttons = BUTTON_A;
+    adapter.controllers[1].buttons = BUTTON_B;
+    adapter.controllers[2].buttons = BUTTON_START;
+    adapter.controllers[3].buttons = BUTTON_RIGHT;
+    // Built in controller 1 stays on D0
+    bus.controller(0).unwrap().buttons = BUTTON_RIGHT;
+
+    let port_1 = read_bits(&mut bus, 0x4016, 0x02, 24);
+    let expected: Vec<u8> = [bits(BUTTON_A), bits(BUTTON_START), vec![0, 0, 1, 0, 0, 0, 0, 0]].concat();
+    assert_eq!(port_1, expected);
+    assert_eq!(read_bits(&mut bus, 0x4016, 0x01, 8), bits(BUTTON_RIGHT));
+
+    let port_2 = read_bits(&mut bus, 0x4017, 0x02, 24);
+    let expected: Vec<u8> = [bits(BUTTON_B), bits(BUTTON_RIGHT), vec![0, 0, 0, 1, 0, 0, 0, 0]].concat();
+    assert_eq!(port_2, expected);
+
+    // Simple expansion controllers for players 3 and 4
+    bus.expansion_device::<HoriAdapter>().unwrap().four_player = false;
+    assert_eq!(read_bits(&mut bus, 0x4017, 0x02, 10), [bits(BUTTON_B), vec![1, 1]].concat());
+  }
+
+  #[test]
+  fn hori_adapter_players_test() {
+    // Player 3 is the expansion controller on $4016 D1, player 1 stays on D0
+    let mut bus = test_bus();
+    bus.expansion_port = Some(Box::new(HoriAdapter::new(false)));
+    bus.controller(0).unwrap().buttons = BUTTON_B;
+    bus.controller(2).unwrap().buttons = BUTTON_START;
+    bus.controller(3).unwrap().buttons = BUTTON_RIGHT;
+    assert_eq!(read_bits(&mut bus, 0x4016, 0x02, 8), bits(BUTTON_START));
+    assert_eq!(read_bits(&mut bus, 0x4017, 0x02, 8), bits(BUTTON_RIGHT));
+    assert_eq!(read_bits(&mut bus, 0x4016, 0x01, 8), bits(BUTTON_B));
+    assert!(bus.controller(4).is_none());
+
+    // In four player mode player 3 is second in the $4016 D1 report
+    bus.expansion_device::<HoriAdapter>().unwrap().four_player = true;
+    bus.controller(2).unwrap().buttons = BUTTON_A;
+    let port_1 = read_bits(&mut bus, 0x4016, 0x02, 16);
+    assert_eq!(port_1, [bits(BUTTON_START), bits(BUTTON_A)].concat());
+  }
+
+  #[test]
+  fn microphone_test() {
+    let mut bus = test_bus();
+    assert_eq!(bus.read(0x4016) & 0x04, 0);
+    bus.microphone = true;
+    assert_eq!(bus.read(0x4016) & 0x04, 0x04);
+    assert_eq!(bus.read(0x4017) & 0x04, 0);
+  }
+}
@@ -0,0 +1,159 @@
+#![allow(dead_code)]
+extern crate nes_emu;
+
+mod nes_tests {
+  use nes_emu::emu::cartridge::Cartridge;
+  use nes_emu::emu::movie::Movie;
+  use nes_emu::emu::nes::{ExitCondition, NES};
+
+  // Single 16 KiB PRG bank mapper 0 image with `program` at $8000 and the reset vector on it
+  fn test_cartridge(program: &[u8]) -> Cartridge {
+    test_cartridge_with_chr(program, &[0x00; 0x2000])
+  }
+
+  fn test_cartridge_with_chr(program: &[u8], chr_rom: &[u8]) -> Cartridge {
+    let mut bytes = vec![0x4E, 0x45, 0x53, 0x1A, 0x01, 0x01, 0x00, 0x00];
+    bytes.resize(16, 0x00);
+
+    let mut prg_rom = vec![0xEA; 0x4000];
+    prg_rom[..program.len()].copy_from_slice(program);
+    prg_rom[0x3FFC] = 0x00;
+    prg_rom[0x3FFD] = 0x80;
+
+    bytes.extend_from_slice(&prg_rom);
+    bytes.extend_from_slice(chr_rom);
+    Cartridge::new(&bytes).unwrap()
+  }
+
+  // LDA #value, STA addr
+  fn store(program: &mut Vec<u8>, addr: u16, value: u8) {
+    program.extend_from_slice(&[0xA9, value, 0x8D, addr as u8, (addr >> 8) as u8]);
+  }
+
+  // INC $10, JMP $8000
+  const COUNTER: [u8; 5] = [0xE6, 0x10, 0x4C, 0x00, 0x80];
+
+  #[test]
+  fn frame_limit_test() {
+    let mut nes = NES::new(test_cartridge(&COUNTER));
+    nes.reset();
+
+    let result = nes.run(3, &[], None);
+
+    assert_eq!(result.frames, 3);
+    assert_eq!(result.condition, None);
+    assert_eq!(nes.frame_count(), 3);
+  }
+
+  #[test]
+  fn unknown_opcode_test() {
+    // INC $10, then a JAM opcode
+    let mut nes = NES::new(test_cartridge(&[0xE6, 0x10, 0x02]));
+    nes.reset();
+
+    let result = nes.run(10, &[], None);
+
+    assert_eq!(result.condition, Some(ExitCondition::UnknownOpcode(0x8002, 0x02)));
+    assert_eq!(result.frames, 0);
+    assert_eq!(nes.bus.ram[0x10], 0x01);
+    assert_eq!(nes.try_step_instruction(), Err("UNKNOWN CPU OPERATION 02 AT 8002".to_string()));
+  }
+
+  #[test]
+  fn memory_condition_test() {
+    let mut nes = NES::new(test_cartridge(&COUNTER));
+    nes.reset();
+
+    let condition = ExitCondition::MemoryValue(0x0010, 0x20);
+    let result = nes.run(10, &[condition], None);
+
+    assert_eq!(result.condition, Some(condition));
+    assert_eq!(result.frames, 0);
+    assert_eq!(nes.bus.ram[0x10], 0x20);
+  }
+
+  #[test]
+  fn pc_condition_test() {
+    let mut nes = NES::new(test_cartridge(&COUNTER));
+    nes.reset();
+
+    let condition = ExitCondition::PcReached(0x8002);
+    let result = nes.run(10, &[condition], None);
+
+    assert_eq!(result.condition, Some(condition));
+    assert_eq!(nes.cpu.pc, 0x8002);
+  }
+
+  #[test]
+  fn movie_input_test() {
+    // Strobe the controller, store the first bit read at $10 and spin
+    let program = [
+      0xA9, 0x01, 0x8D, 0x16, 0x40, 0xA9, 0x00, 0x8D, 0x16, 0x40,
+      0xAD, 0x16, 0x40, 0x85, 0x10, 0x4C, 0x0F, 0x80
+    ];
+    let mut nes = NES::new(test_cartridge(&program));
+    nes.reset();
+
+    let movie = Movie::parse_fm2("version 3\n|0|.......A|........||\n").unwrap();
+    let condition = ExitCondition::MemoryValue(0x0010, 0x41);
+    let result = nes.run(2, &[condition], Some(&movie));
+
+    assert_eq!(result.condition, Some(condition));
+  }
+
+  #[test]
+  fn rendering_test() {
+    // Tile 1 is solid color 1
+    let mut chr_rom = vec![0x00; 0x2000];
+    for byte in &mut chr_rom[0x10..0x18] {
+      *byte = 0xFF;
+    }
+
+    // Wait for the PPU to warm up, then write palettes, a background tile at column 3 row 2,
+    // sprite 0 behind it and sprite 1 at (100, 50), and turn rendering on
+    let mut program = vec![0x2C, 0x02, 0x20, 0x10, 0xFB, 0x2C, 0x02, 0x20, 0x10, 0xFB];
+    for (addr, value) in [(0x2006, 0x3F), (0x2006, 0x00), (0x2007, 0x0F), (0x2007, 0x30)].iter() {
+      store(&mut program, *addr, *value);
+    }
+    for (addr, value) in [(0x2006, 0x3F), (0x2006, 0x11), (0x2007, 0x16)].iter() {
+      store(&mut program, *addr, *value);
+    }
+    for (addr, value) in [(0x2006, 0x20), (0x2006, 0x43), (0x2007, 0x01)].iter() {
+      store(&mut program, *addr, *value);
+    }
+    store(&mut program, 0x2003, 0x00);
+    for value in [15, 1, 0x20, 24, 49, 1, 0, 100].iter() {
+      store(&mut program, 0x2004, *value);
+    }
+    for (addr, value) in [(0x2005, 0x00), (0x2005, 0x00), (0x2000, 0x00), (0x2001, 0x1E)].iter() {
+      store(&mut program, *addr, *value);
+    }
+    let spin = 0x8000 + program.len() as u16;
+    program.extend_from_slice(&[0x4C, spin as u8, (spin >> 8) as u8]);
+
+    let mut nes = NES::new(test_cartridge_with_chr(&program, &chr_rom));
+    nes.reset();
+    nes.run(4, &[], None);
+
+    let black = nes.palette.rgb(0x0F, false, 0);
+    let white = nes.palette.rgb(0x30, false, 0);
+    let red = nes.palette.rgb(0x16, false, 0);
+    assert_eq!(nes.frame.pixel(0, 0), black);
+    assert_eq!(nes.frame.pixel(24, 16), white);
+    assert_eq!(nes.frame.pixel(31, 23), white);
+    assert_eq!(nes.frame.pixel(32, 16), black);
+    assert_eq!(nes.frame.pixel(100, 50), red);
+    assert_eq!(nes.frame.pixel(107, 57), red);
+    assert_eq!(nes.frame.pixel(108, 50), black);
+
+    // Sprite 0 hit is set once the beam reaches the overlap and cleared on the pre-render line
+    while nes.bus.ppu.scanline != 10 {
+      nes.step_instruction();
+    }
+    assert_eq!(nes.bus.read(0x2002) & 0x40, 0);
+    while nes.bus.ppu.scanline != 30 {
+      nes.step_instruction();
+    }
+    assert_eq!(nes.bus.read(0x2002) & 0x40, 0x40);
+  }
+}
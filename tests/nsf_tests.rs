@@ -0,0 +1,163 @@
+#![allow(dead_code)]
+extern crate nes_emu;
+
+mod nsf_tests {
+  use nes_emu::emu::cpu_bus::CpuBus;
+  use nes_emu::emu::nsf::{Nsf, EXPANSION_VRC6};
+  use nes_emu::emu::nsf_player::{NsfBus, NsfPlayer};
+  use nes_emu::emu::recorder::SAMPLE_RATE;
+
+  // INIT at $8000 stores the track in $00, PLAY at $8003 counts calls in $01
+  const PROGRAM: [u8; 6] = [0x85, 0x00, 0x60, 0xE6, 0x01, 0x60];
+
+  fn nsf_bytes(program: &[u8], expansion: u8, bankswitch: [u8; 8]) -> Vec<u8> {
+    let mut bytes = vec![0; 0x80];
+    bytes[0..5].copy_from_slice(b"NESM\x1A");
+    bytes[5] = 1;
+    bytes[6] = 3;
+    bytes[7] = 2;
+    bytes[0x08..0x0E].copy_from_slice(&[0x00, 0x80, 0x00, 0x80, 0x03, 0x80]);
+    bytes[0x0E..0x13].copy_from_slice(b"Title");
+    bytes[0x6E..0x70].copy_from_slice(&16639u16.to_le_bytes());
+    bytes[0x70..0x78].copy_from_slice(&bankswitch);
+    bytes[0x7B] = expansion;
+    bytes.extend_from_slice(program);
+    bytes
+  }
+
+  fn chunk(id: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut bytes = (data.len() as u32).to_le_bytes().to_vec();
+    bytes.extend_from_slice(id);
+    bytes.extend_from_slice(data);
+    bytes
+  }
+
+  #[test]
+  fn nsf_header_test() {
+    let nsf = Nsf::new(&nsf_bytes(&PROGRAM, 0, [0; 8])).unwrap();
+
+    assert_eq!(nsf.title, "Title");
+    assert_eq!(nsf.total_songs, 3);
+    assert_eq!(nsf.starting_song, 1);
+    assert_eq!((nsf.load_addr, nsf.init_addr, nsf.play_addr), (0x8000, 0x8000, 0x8003));
+    assert!(!nsf.bankswitched());
+    assert_eq!(nsf.data, PROGRAM);
+
+    assert!(Nsf::new(b"NES\x1A").is_err());
+  }
+
+  #[test]
+  fn nsfe_test() {
+    let mut bytes = b"NSFE".to_vec();
+    bytes.extend(chunk(b"INFO", &[0x00, 0x80, 0x00, 0x80, 0x03, 0x80, 0x00, 0x00, 0x02, 0x01]));
+    bytes.extend(chunk(b"DATA", &PROGRAM));
+    bytes.extend(chunk(b"auth", b"Song\0Someone\0\0Ripper\0"));
+    bytes.extend(chunk(b"tlbl", b"Intro\0Theme\0"));
+    bytes.extend(chunk(b"time", &[0xE8, 0x03, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0xFF]));
+    bytes.extend(chunk(b"plst", &[0x01, 0x00]));
+    bytes.extend(chunk(b"NEND", &[]));
+    let nsf = Nsf::new(&bytes).unwrap();
+
+    assert_eq!((nsf.title.as_str(), nsf.artist.as_str()), ("Song", "Someone"));
+    assert_eq!(nsf.total_songs, 2);
+    assert_eq!(nsf.starting_song, 1);
+    assert_eq!(nsf.track_labels, vec!["Intro", "Theme"]);
+    assert_eq!(nsf.track_times, vec![Some(1000), None]);
+    assert_eq!(nsf.data, PROGRAM);
+
+    // Upper case chunks must be understood
+    let mut bytes = b"NSFE".to_vec();
+    bytes.extend(chunk(b"INFO", &[0x00, 0x80, 0x00, 0x80, 0x03, 0x80, 0x00, 0x00]));
+    bytes.extend(chunk(b"WHAT", &[]));
+    assert!(Nsf::new(&bytes).is_err());
+  }
+
+  #[test]
+  fn init_and_play_test() {
+    let mut player = NsfPlayer::new(Nsf::new(&nsf_bytes(&PROGRAM, 0, [0; 8])).unwrap()).unwrap();
+    player.start_track(2).unwrap();
+    assert_eq!(player.bus.ram[0x00], 2);
+
+    let samples = player.render(SAMPLE_RATE as usize).unwrap();
+    assert_eq!(samples.len(), SAMPLE_RATE as usize);
+    // About 60 PLAY calls a second
+    let calls = player.bus.ram[0x01];
+    assert!((59..=61).contains(&calls), "{}", calls);
+
+    assert!(player.start_track(3).is_err());
+  }
+
+  #[test]
+  fn bankswitch_test() {
+    // Bank 0 holds the program and bank 1 a marker byte at its start
+    let mut data = PROGRAM.to_vec();
+    data.resize(0x1000, 0x00);
+    data.push(0xAB);
+    let nsf = Nsf::new(&nsf_bytes(&data, 0, [0, 1, 0, 0, 0, 0, 0, 0])).unwrap();
+    assert!(nsf.bankswitched());
+
+    let mut bus = NsfBus::new(&nsf).unwrap();
+    assert_eq!(bus.read(0x9000), 0xAB);
+    assert_eq!(bus.read(0xA000), PROGRAM[0]);
+
+    bus.write(0x5FFA, 1);
+    assert_eq!(bus.read(0xA000), 0xAB);
+    // Banks past the end of the data are empty
+    bus.write(0x5FFA, 9);
+    assert_eq!(bus.read(0xA000), 0x00);
+  }
+
+  #[test]
+  fn expansion_test() {
+    // INIT starts a VRC6 pulse at full constant volume, PLAY does nothing
+    let program = [0xA9, 0x8F, 0x8D, 0x00, 0x90, 0xA9, 0x80, 0x8D, 0x02, 0x90, 0x60, 0x60];
+    let mut bytes = nsf_bytes(&program, EXPANSION_VRC6, [0; 8]);
+    bytes[0x0C] = 0x0B;
+
+    let mut player = NsfPlayer::new(Nsf::new(&bytes).unwrap()).unwrap();
+    player.start_track(0).unwrap();
+    let samples = player.render(100).unwrap();
+    assert!(samples.iter().all(|sample| *sample > 0));
+
+    // Without the flag the chip is not there, only the idle APU's flat level
+    let mut bytes = nsf_bytes(&program, 0, [0; 8]);
+    bytes[0x0C] = 0x0B;
+    let mut player = NsfPlayer::new(Nsf::new(&bytes).unwrap()).unwrap();
+    player.start_track(0).unwrap();
+    let level = (player.bus.apu.output() * i16::MAX as f32) as i16;
+    assert!(player.render(100).unwrap().iter().all(|sample| (sample - level).abs() <= 1));
+  }
+
+  #[test]
+  fn apu_test() {
+    // INIT plays pulse 1 at 50% duty and constant volume 15 with period $FD, about 440 Hz
+    let mut program = Vec::new();
+    for (addr, value) in &[(0x4000u16, 0xBF), (0x4002, 0xFD), (0x4003, 0x00)] {
+      program.extend_from_slice(&[0xA9, *value, 0x8D, *addr as u8, (*addr >> 8) as u8]);
+    }
+    program.extend_from_slice(&[0x60, 0x60]);
+    let mut bytes = nsf_bytes(&program, 0, [0; 8]);
+    bytes[0x0C] = 0x10;
+
+    let mut player = NsfPlayer::new(Nsf::new(&bytes).unwrap()).unwrap();
+    player.start_track(0).unwrap();
+    let samples = player.render(SAMPLE_RATE as usize).unwrap();
+
+    // Two crossings of the middle per cycle
+    let middle = (samples.iter().map(|sample| *sample as i64).sum::<i64>() / samples.len() as i64) as i16;
+    let crossings = samples.windows(2).filter(|pair| (pair[0] > middle) != (pair[1] > middle)).count();
+    assert!((875..=885).contains(&crossings), "{}", crossings);
+  }
+
+  #[test]
+  fn unknown_opcode_test() {
+    // INIT runs into a JAM opcode
+    let mut player = NsfPlayer::new(Nsf::new(&nsf_bytes(&[0x02, 0x60, 0x60, 0x60], 0, [0; 8])).unwrap()).unwrap();
+    assert_eq!(player.start_track(0), Err("UNKNOWN CPU OPERATION 02 AT 8000".to_string()));
+
+    // So does PLAY at $8003
+    let mut player = NsfPlayer::new(Nsf::new(&nsf_bytes(&[0x60, 0x60, 0x60, 0x02], 0, [0; 8])).unwrap()).unwrap();
+    player.start_track(0).unwrap();
+    assert!(player.render(100).is_err());
+  }
+}
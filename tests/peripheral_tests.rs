@@ -0,0 +1,218 @@
+#![allow(dead_code)]
+extern crate nes_emu;
+
+mod peripheral_tests {
+  use nes_emu::emu::bus::Bus;
+  use nes_emu::emu::cartridge::Cartridge;
+  use nes_emu::emu::input::{self, ExpansionDevice};
+  use nes_emu::emu::joypad::Joypad;
+  use nes_emu::emu::keyboard::{FamilyBasicKeyboard, TapeMode};
+  use nes_emu::emu::power_pad::PowerPad;
+  use nes_emu::emu::save_state::{StateReader, StateWriter};
+  use nes_emu::emu::vaus::Vaus;
+  use nes_emu::emu::zapper::Zapper;
+  use nes_emu::graphics::frame::Frame;
+
+  // NROM cartridge, an NES 2.0 header when given a default expansion device
+  fn test_bus(device: Option<u8>) -> Bus {
+    let mut bytes = vec![0x4E, 0x45, 0x53, 0x1A, 0x01, 0x01, 0x00, 0x00];
+    bytes.resize(16, 0x00);
+    if let Some(device) = device {
+      bytes[7] = 0x08;
+      bytes[15] = device;
+    }
+    bytes.extend_from_slice(&[0x00; 0x4000 + 0x2000]);
+    Bus::new(Cartridge::new(&bytes).unwrap())
+  }
+
+  fn read_bits(bus: &mut Bus, addr: u16, mask: u8, count: usize) -> Vec<u8> {
+    bus.write(0x4016, 1);
+    bus.write(0x4016, 0);
+    (0..count).map(|_| if bus.read(addr) & mask != 0 { 1 } else { 0 }).collect()
+  }
+
+  #[test]
+  fn vaus_test() {
+    let mut bus = test_bus(Some(input::DEFAULT_DEVICE_VAUS_NES));
+    let vaus = bus.device::<Vaus>(1).unwrap();
+    vaus.position = 0b1010_0110;
+    vaus.button = true;
+
+    // Position comes out MSB first and inverted, then 1s
+    assert_eq!(read_bits(&mut bus, 0x4017, 0x08, 10), [0, 1, 0, 1, 1, 0, 0, 1, 1, 1]);
+    assert_eq!(read_bits(&mut bus, 0x4017, 0x10, 2), [1, 1]);
+
+    // The Famicom version splits the button and the data across $4016 and $4017 D1
+    let mut bus = test_bus(Some(input::DEFAULT_DEVICE_VAUS_FAMICOM));
+    let vaus = bus.expansion_device::<Vaus>().unwrap();
+    vaus.position = 0xF0;
+    assert_eq!(read_bits(&mut bus, 0x4017, 0x02, 8), [0, 0, 0, 0, 1, 1, 1, 1]);
+    assert_eq!(bus.read(0x4016) & 0x02, 0);
+    bus.expansion_device::<Vaus>().unwrap().button = true;
+    assert_eq!(bus.read(0x4016) & 0x02, 0x02);
+  }
+
+  #[test]
+  fn power_pad_test() {
+    let mut bus = test_bus(Some(input::DEFAULT_DEVICE_POWER_PAD_B));
+    let pad = bus.device::<PowerPad>(1).unwrap();
+    // Buttons 1, 9 and 12 on the mat
+    pad.buttons[0] = true;
+    pad.buttons[8] = true;
+    pad.buttons[11] = true;
+
+    assert_eq!(read_bits(&mut bus, 0x4017, 0x08, 9), [0, 1, 0, 1, 0, 0, 0, 0, 1]);
+    assert_eq!(read_bits(&mut bus, 0x4017, 0x10, 6), [0, 0, 1, 0, 1, 1]);
+
+    // Family Trainer rows are selected by clearing their bit, buttons 1 and 9 are first in theirs
+    let mut bus = test_bus(Some(input::DEFAULT_DEVICE_FAMILY_TRAINER_B));
+    let pad = bus.expansion_device::<PowerPad>().unwrap();
+    pad.buttons[0] = true;
+    pad.buttons[8] = true;
+    pad.buttons[11] = true;
+    bus.write(0x4016, 0b011);
+    assert_eq!(bus.read(0x4017) & 0x1E, 0x0E);
+    bus.write(0x4016, 0b101);
+    assert_eq!(bus.read(0x4017) & 0x1E, 0x1E);
+    bus.write(0x4016, 0b110);
+    assert_eq!(bus.read(0x4017) & 0x1E, 0x0C);
+    bus.write(0x4016, 0b111);
+    assert_eq!(bus.read(0x4017) & 0x1E, 0x1E);
+  }
+
+  // Scans the whole matrix the way Family BASIC does, returning what each row and column read
+  fn scan(bus: &mut Bus) -> Vec<u8> {
+    let mut reads = Vec::new();
+    bus.write(0x4016, 0x05);
+    for _row in 0..10 {
+      bus.write(0x4016, 0x04);
+      reads.push(bus.read(0x4017) & 0x1E);
+      bus.write(0x4016, 0x06);
+      reads.push(bus.read(0x4017) & 0x1E);
+    }
+    reads
+  }
+
+  #[test]
+  fn keyboard_test() {
+    let mut bus = test_bus(Some(input::DEFAULT_DEVICE_FAMILY_BASIC_KEYBOARD));
+    let keyboard = bus.expansion_device::<FamilyBasicKeyboard>().unwrap();
+    keyboard.set_key("a", true).unwrap();
+    keyboard.set_key("RETURN", true).unwrap();
+    keyboard.set_key("space", true).unwrap();
+    assert!(keyboard.set_key("ANY", true).is_err());
+
+    let mut expected = vec![0x1E; 20];
+    // RETURN is D2 of row 0, A is D4 of row 6 and SPACE is D2 of row 8's second column
+    expected[0] = 0x1A;
+    expected[12] = 0x0E;
+    expected[17] = 0x1A;
+    assert_eq!(scan(&mut bus), expected);
+
+    bus.expansion_device::<FamilyBasicKeyboard>().unwrap().set_key("RETURN", false).unwrap();
+    assert_eq!(scan(&mut bus)[0], 0x1E);
+
+    // Nothing reads pressed while the matrix is disabled
+    bus.write(0x4016, 0x00);
+    assert_eq!(bus.read(0x4017) & 0x1E, 0);
+  }
+
+  #[test]
+  fn data_recorder_test() {
+    let frame = Frame::default();
+    let mut keyboard = FamilyBasicKeyboard::new();
+    keyboard.recorder.record();
+    for level in &[1, 0, 0, 1, 1] {
+      ExpansionDevice::write(&mut keyboard, *level);
+      keyboard.scanline(&frame, 0);
+    }
+    keyboard.recorder.stop();
+    assert_eq!(keyboard.recorder.tape, [1, 0, 0, 1, 1]);
+
+    keyboard.recorder.rewind();
+    keyboard.recorder.play();
+    let mut played = Vec::new();
+    for _line in 0..6 {
+      played.push(ExpansionDevice::read(&mut keyboard, 0) >> 1);
+      keyboard.scanline(&frame, 0);
+    }
+    assert_eq!(played, [1, 0, 0, 1, 1, 0]);
+    assert_eq!(keyboard.recorder.mode, TapeMode::Stopped);
+  }
+
+  fn save(bus: &Bus) -> Vec<u8> {
+    let mut state = StateWriter::new();
+    bus.save_state(&mut state);
+    state.bytes
+  }
+
+  fn load(bus: &mut Bus, state: &[u8]) -> Result<(), String> {
+    bus.load_state(&mut StateReader::new(state))
+  }
+
+  #[test]
+  fn device_state_test() {
+    // The Vaus picks up mid report
+    let mut bus = test_bus(Some(input::DEFAULT_DEVICE_VAUS_NES));
+    bus.device::<Vaus>(1).unwrap().position = 0b1010_0110;
+    read_bits(&mut bus, 0x4017, 0x08, 3);
+    let state = save(&bus);
+    let rest: Vec<u8> = (0..5).map(|_| bus.read(0x4017) & 0x08).collect();
+    load(&mut bus, &state).unwrap();
+    assert_eq!((0..5).map(|_| bus.read(0x4017) & 0x08).collect::<Vec<u8>>(), rest);
+
+    // The Family Trainer keeps its selected rows
+    let mut bus = test_bus(Some(input::DEFAULT_DEVICE_FAMILY_TRAINER_B));
+    bus.expansion_device::<PowerPad>().unwrap().buttons[0] = true;
+    bus.write(0x4016, 0b011);
+    let state = save(&bus);
+    bus.write(0x4016, 0b111);
+    load(&mut bus, &state).unwrap();
+    assert_eq!(bus.read(0x4017) & 0x1E, 0x0E);
+
+    // The keyboard keeps its row and column, and the recorder its tape
+    let mut bus = test_bus(Some(input::DEFAULT_DEVICE_FAMILY_BASIC_KEYBOARD));
+    let keyboard = bus.expansion_device::<FamilyBasicKeyboard>().unwrap();
+    keyboard.set_key("A", true).unwrap();
+    keyboard.recorder.tape = vec![1, 0, 1];
+    keyboard.recorder.position = 2;
+    keyboard.recorder.play();
+    bus.write(0x4016, 0x05);
+    for _row in 0..6 {
+      bus.write(0x4016, 0x06);
+      bus.write(0x4016, 0x04);
+    }
+    let state = save(&bus);
+    bus.write(0x4016, 0x05);
+    bus.expansion_device::<FamilyBasicKeyboard>().unwrap().recorder = Default::default();
+    load(&mut bus, &state).unwrap();
+    assert_eq!(bus.read(0x4017) & 0x1E, 0x0E);
+    assert_eq!(bus.read(0x4016) & 0x02, 0x02);
+    assert_eq!(bus.expansion_device::<FamilyBasicKeyboard>().unwrap().recorder.tape, [1, 0, 1]);
+
+    // States only load with the same devices plugged in
+    let state = save(&test_bus(Some(input::DEFAULT_DEVICE_POWER_PAD_B)));
+    assert!(load(&mut test_bus(None), &state).is_err());
+    assert!(load(&mut test_bus(Some(input::DEFAULT_DEVICE_FAMILY_TRAINER_B)), &state).is_err());
+  }
+
+  #[test]
+  fn default_devices_test() {
+    let mut bus = test_bus(None);
+    assert!(bus.device::<Joypad>(1).is_some());
+    assert!(bus.expansion_port.is_none());
+
+    let mut bus = test_bus(Some(input::DEFAULT_DEVICE_ZAPPER));
+    assert!(bus.device::<Joypad>(0).is_some());
+    assert!(bus.device::<Zapper>(1).is_some());
+
+    // Devices this emulator lacks fall back to controllers
+    let mut bus = test_bus(Some(0x13));
+    assert!(bus.device::<Joypad>(1).is_some());
+
+    assert!(input::port_device("vaus").is_ok());
+    assert!(input::port_device("keyboard").is_err());
+    assert!(input::expansion_device("none").unwrap().is_none());
+    assert!(input::expansion_device("keyboard").unwrap().is_some());
+  }
+}
@@ -0,0 +1,109 @@
+#![allow(dead_code)]
+extern crate nes_emu;
+
+mod ppu_tests {
+  use nes_emu::emu::cartridge::Mirroring;
+  use nes_emu::emu::ppu::PPU;
+
+  fn test_ppu() -> PPU {
+    let mut chr_rom = vec![0; 0x2000];
+    chr_rom[0x0123] = 0x77;
+    PPU::new(chr_rom, Mirroring::Vertical)
+  }
+
+  fn set_addr(ppu: &mut PPU, addr: u16) {
+    ppu.write(0x2006, (addr >> 8) as u8);
+    ppu.write(0x2006, addr as u8);
+  }
+
+  #[test]
+  fn read_buffer_test() {
+    let mut ppu = test_ppu();
+    ppu.vram[0x005] = 0x11;
+    ppu.vram[0x006] = 0x22;
+
+    set_addr(&mut ppu, 0x0123);
+    ppu.read();
+    assert_eq!(ppu.read(), 0x77);
+
+    // Nametable reads are a read behind too, $3000-$3EFF mirrors $2000-$2EFF
+    set_addr(&mut ppu, 0x3005);
+    ppu.read();
+    assert_eq!(ppu.read(), 0x11);
+    assert_eq!(ppu.read(), 0x22);
+  }
+
+  #[test]
+  fn address_register_test() {
+    let mut ppu = test_ppu();
+    ppu.vram[0x005] = 0x11;
+
+    // Bits above $3FFF are dropped and earlier addresses do not leak into the new one
+    set_addr(&mut ppu, 0x2FFF);
+    set_addr(&mut ppu, 0x6005);
+    ppu.read();
+    assert_eq!(ppu.read(), 0x11);
+
+    // Incrementing past $3FFF wraps to $0000
+    ppu.write(0x2000, 0x04);
+    set_addr(&mut ppu, 0x3FF0);
+    ppu.read();
+    ppu.write(0x2000, 0x00);
+    ppu.read();
+    assert_eq!(ppu.read(), 0x00);
+  }
+
+  #[test]
+  fn palette_read_test() {
+    let mut ppu = test_ppu();
+    ppu.vram[0x705] = 0x33;
+
+    set_addr(&mut ppu, 0x3F01);
+    ppu.write(0x2007, 0x2A);
+    set_addr(&mut ppu, 0x3F10);
+    ppu.write(0x2007, 0x16);
+
+    // Immediate, mirrored every 32 bytes, and $3F10 is $3F00
+    set_addr(&mut ppu, 0x3F21);
+    assert_eq!(ppu.read(), 0x2A);
+    set_addr(&mut ppu, 0x3F00);
+    assert_eq!(ppu.read(), 0x16);
+    assert_eq!(ppu.palette_entry(0x10), 0x16);
+
+    // The buffer picks up the nametable byte at $2F05
+    set_addr(&mut ppu, 0x3F05);
+    ppu.read();
+    set_addr(&mut ppu, 0x0000);
+    assert_eq!(ppu.read(), 0x33);
+
+    // Grayscale strips the hue from what is read back
+    ppu.write(0x2001, 0x01);
+    set_addr(&mut ppu, 0x3F01);
+    assert_eq!(ppu.read(), 0x20);
+  }
+
+  #[test]
+  fn scroll_rendering_test() {
+    let mut ppu = test_ppu();
+    // Tile 1 is solid color 1, placed at column 3 of row 0 and column 0 of the next nametable
+    for byte in &mut ppu.chr_rom[0x10..0x18] {
+      *byte = 0xFF;
+    }
+    ppu.vram[0x003] = 0x01;
+    ppu.vram[0x400] = 0x01;
+    ppu.palette_table[1] = 0x30;
+    ppu.palette_table[0] = 0x0F;
+
+    // 4 pixels of fine X scroll, then run to the end of the first scanline
+    ppu.write(0x2005, 4);
+    ppu.write(0x2005, 0);
+    ppu.write(0x2001, 0x0A);
+    ppu.tick(341 * 262);
+    ppu.tick(341);
+
+    let row: Vec<u8> = ppu.pixels[..256].to_vec();
+    assert_eq!(&row[19..29], &[0x0F, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x0F]);
+    // The next nametable shows up at the right edge
+    assert_eq!(&row[251..256], &[0x0F, 0x30, 0x30, 0x30, 0x30]);
+  }
+}
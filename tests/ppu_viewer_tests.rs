@@ -0,0 +1,135 @@
+#![allow(dead_code)]
+extern crate nes_emu;
+
+mod ppu_viewer_tests {
+  use nes_emu::emu::cartridge::Mirroring;
+  use nes_emu::emu::ppu::PPU;
+  use nes_emu::graphics::palette::Palette;
+  use nes_emu::graphics::ppu_viewer;
+
+  // Tile 1 has a solid top row of value 3 and a single value 1 pixel in its left column
+  fn test_ppu(mirroring: Mirroring) -> PPU {
+    let mut chr_rom = vec![0; 0x2000];
+    chr_rom[16] = 0xFF;
+    chr_rom[24] = 0xFF;
+    chr_rom[16 + 7] = 0x80;
+
+    let mut ppu = PPU::new(chr_rom, mirroring);
+    ppu.palette_table[0] = 0x0F;
+    ppu.palette_table[1] = 0x16;
+    ppu.palette_table[3] = 0x30;
+    ppu.palette_table[0x11] = 0x12;
+    ppu.palette_table[0x13] = 0x2A;
+    ppu
+  }
+
+  #[test]
+  fn palette_mirror_test() {
+    let mut ppu = test_ppu(Mirroring::Vertical);
+    ppu.palette_table[0x10] = 0x20;
+
+    assert_eq!(ppu.palette_entry(0x10), 0x0F);
+    assert_eq!(ppu.palette_entry(0x11), 0x12);
+    assert_eq!(ppu.palette_entry(0x30), 0x0F);
+  }
+
+  #[test]
+  fn nametable_mirroring_test() {
+    let mut ppu = test_ppu(Mirroring::Horizontal);
+    ppu.vram[0x005] = 0x11;
+    ppu.vram[0x405] = 0x22;
+
+    assert_eq!(ppu.nametable_byte(1, 5), 0x11);
+    assert_eq!(ppu.nametable_byte(2, 5), 0x22);
+    assert_eq!(ppu.nametable_byte(3, 5), 0x22);
+
+    let mut ppu = test_ppu(Mirroring::Vertical);
+    ppu.vram[0x005] = 0x11;
+    assert_eq!(ppu.nametable_byte(2, 5), 0x11);
+    assert_eq!(ppu.nametable_byte(1, 5), 0x00);
+  }
+
+  #[test]
+  fn single_screen_and_four_screen_test() {
+    let mut ppu = test_ppu(Mirroring::SingleScreenB);
+    ppu.vram[0x405] = 0x22;
+    for table in 0..4 {
+      assert_eq!(ppu.nametable_byte(table, 5), 0x22);
+    }
+    ppu.set_mirroring(Mirroring::SingleScreenA);
+    assert_eq!(ppu.nametable_byte(3, 5), 0x00);
+
+    let mut ppu = test_ppu(Mirroring::FourScreen);
+    ppu.vram[0x405] = 0x22;
+    ppu.cartridge_vram[0x005] = 0x33;
+    ppu.cartridge_vram[0x405] = 0x44;
+    assert_eq!(ppu.nametable_byte(1, 5), 0x22);
+    assert_eq!(ppu.nametable_byte(2, 5), 0x33);
+    assert_eq!(ppu.nametable_byte(3, 5), 0x44);
+  }
+
+  #[test]
+  fn pattern_tables_test() {
+    let ppu = test_ppu(Mirroring::Vertical);
+    let palette = Palette::default();
+    let image = ppu_viewer::pattern_tables(&ppu, &palette, 0);
+
+    assert_eq!((image.width, image.height), (256, 128));
+    assert_eq!(image.get_pixel(8, 0), palette.rgb(0x30, false, 0));
+    assert_eq!(image.get_pixel(8, 7), palette.rgb(0x16, false, 0));
+    assert_eq!(image.get_pixel(9, 7), palette.rgb(0x0F, false, 0));
+  }
+
+  #[test]
+  fn nametables_test() {
+    let mut ppu = test_ppu(Mirroring::Vertical);
+    ppu.vram[33] = 0x01;
+    // Scroll to the second nametable, 16 pixels in
+    ppu.write(0x2000, 0x01);
+    ppu.write(0x2005, 16);
+    ppu.write(0x2005, 0);
+    assert_eq!(ppu.scroll(), (272, 0));
+
+    let palette = Palette::default();
+    let image = ppu_viewer::nametables(&ppu, &palette);
+
+    assert_eq!((image.width, image.height), (512, 480));
+    // Tile 1 at row 1 column 1, repeated in the mirrored table below
+    assert_eq!(image.get_pixel(8, 8), palette.rgb(0x30, false, 0));
+    assert_eq!(image.get_pixel(8, 248), palette.rgb(0x30, false, 0));
+
+    // The outline inverts the backdrop and wraps past the right edge
+    let (red, green, blue) = palette.rgb(0x0F, false, 0);
+    let inverted = (!red, !green, !blue);
+    assert_eq!(image.get_pixel(272, 100), inverted);
+    assert_eq!(image.get_pixel(15, 100), inverted);
+    assert_eq!(image.get_pixel(16, 100), palette.rgb(0x0F, false, 0));
+  }
+
+  #[test]
+  fn sprites_test() {
+    let mut ppu = test_ppu(Mirroring::Vertical);
+    // Sprite 1 uses tile 1 with sprite palette 0, flipped both ways
+    ppu.oam_data[4..8].copy_from_slice(&[0, 1, 0b1100_0000, 0]);
+
+    let palette = Palette::default();
+    let image = ppu_viewer::sprites(&ppu, &palette);
+
+    // Cells are 10x18 with a 1 pixel border
+    assert_eq!((image.width, image.height), (80, 144));
+    assert_eq!(image.get_pixel(11, 8), palette.rgb(0x2A, false, 0));
+    assert_eq!(image.get_pixel(18, 1), palette.rgb(0x12, false, 0));
+    assert_eq!(image.get_pixel(11, 1), palette.rgb(0x0F, false, 0));
+  }
+
+  #[test]
+  fn palettes_test() {
+    let ppu = test_ppu(Mirroring::Vertical);
+    let palette = Palette::default();
+    let image = ppu_viewer::palettes(&ppu, &palette);
+
+    assert_eq!((image.width, image.height), (256, 32));
+    assert_eq!(image.get_pixel(16, 0), palette.rgb(0x16, false, 0));
+    assert_eq!(image.get_pixel(16, 16), palette.rgb(0x12, false, 0));
+  }
+}
@@ -0,0 +1,289 @@
+#![allow(dead_code)]
+extern crate nes_emu;
+
+mod recording_tests {
+  use std::io::Cursor;
+
+  use nes_emu::audio::wav::WavWriter;
+  use nes_emu::emu::recorder::{Recorder, SAMPLE_RATE};
+  use nes_emu::graphics::avi::AviWriter;
+  use nes_emu::graphics::frame::{Frame, WIDTH, HEIGHT};
+  use nes_emu::graphics::gif::GifWriter;
+  use nes_emu::graphics::palette::Palette;
+  use nes_emu::graphics::y4m::Y4mWriter;
+
+  fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]])
+  }
+
+  struct GifImage {
+    delay: u16,
+    local: bool,
+    // RGB24 pixels decoded through the image's color table
+    rgb: Vec<u8>
+  }
+
+  // Every image with the delay of its graphic control extension, decoded to RGB
+  fn gif_images(bytes: &[u8]) -> Vec<GifImage> {
+    let table_size = |flags: u8| 3 << ((flags & 0x07) + 1);
+    let global = bytes[13..(13 + table_size(bytes[10]))].to_vec();
+    let mut images = Vec::new();
+    let mut position = 13 + global.len();
+    let mut delay = 0;
+    loop {
+      let mut data = Vec::new();
+      let mut table = None;
+      let is_image = bytes[position] == 0x2C;
+      match bytes[position] {
+        0x21 => {
+          if bytes[position + 1] == 0xF9 {
+            delay = u16::from_le_bytes([bytes[position + 4], bytes[position + 5]]);
+          }
+          position += 2;
+        }
+        0x2C => {
+          let flags = bytes[position + 9];
+          position += 10;
+          if flags & 0x80 != 0 {
+            table = Some(bytes[position..(position + table_size(flags))].to_vec());
+            position += table_size(flags);
+          }
+          data.push(bytes[position]);
+          position += 1;
+        }
+        _ => return images
+      }
+      while bytes[position] != 0 {
+        data.extend_from_slice(&bytes[(position + 1)..(position + 1 + bytes[position] as usize)]);
+        position += bytes[position] as usize + 1;
+      }
+      position += 1;
+
+      if is_image {
+        let colors = table.as_ref().unwrap_or(&global);
+        let rgb = lzw_decode(&data[1..], data[0]).iter()
+          .flat_map(|index| colors[(*index as usize * 3)..(*index as usize * 3 + 3)].to_vec())
+          .collect();
+        images.push(GifImage { delay, local: table.is_some(), rgb });
+      }
+    }
+  }
+
+  // GIF's variable width LZW, written apart from the encoder so the two check each other
+  fn lzw_decode(data: &[u8], min_code_size: u8) -> Vec<u8> {
+    let clear = 1usize << min_code_size;
+    let end = clear + 1;
+    let reset = || -> Vec<Vec<u8>> { (0..(clear + 2)).map(|index| vec![index as u8]).collect() };
+
+    let mut table = reset();
+    let mut code_size = min_code_size + 1;
+    let mut previous: Option<Vec<u8>> = None;
+    let mut indices = Vec::new();
+    let mut bit = 0;
+    loop {
+      let mut code = 0;
+      for offset in 0..code_size as usize {
+        let byte = data[(bit + offset) / 8];
+        code |= ((byte >> ((bit + offset) % 8)) as usize & 1) << offset;
+      }
+      bit += code_size as usize;
+
+      if code == clear {
+        table = reset();
+        code_size = min_code_size + 1;
+        previous = None;
+        continue;
+      }
+      if code == end {
+        return indices;
+      }
+
+      let entry = match &previous {
+        _ if code < table.len() => table[code].clone(),
+        Some(previous) if code == table.len() => [previous.clone(), vec![previous[0]]].concat(),
+        _ => panic!("LZW CODE {} PAST THE TABLE", code)
+      };
+      indices.extend_from_slice(&entry);
+      if let Some(previous) = previous {
+        if table.len() < 4096 {
+          table.push([previous, vec![entry[0]]].concat());
+        }
+      }
+      if table.len() == 1 << code_size && code_size < 12 {
+        code_size += 1;
+      }
+      previous = Some(entry);
+    }
+  }
+
+  fn temp_path(name: &str) -> String {
+    std::env::temp_dir().join(format!("nes_emu_{}_{}", std::process::id(), name)).to_str().unwrap().to_string()
+  }
+
+  #[test]
+  fn y4m_test() {
+    let mut frame = Frame::default();
+    frame.set_pixel(0, 0, (255, 255, 255));
+
+    let mut bytes = Vec::new();
+    let mut writer = Y4mWriter::new(&mut bytes, 39_375_000, 655_171).unwrap();
+    writer.write_frame(&frame).unwrap();
+    writer.write_frame(&frame).unwrap();
+    writer.finish().unwrap();
+
+    let header = b"YUV4MPEG2 W256 H240 F39375000:655171 Ip A1:1 C444\n";
+    assert!(bytes.starts_with(header));
+    assert_eq!(bytes.len(), header.len() + 2 * (6 + WIDTH * HEIGHT * 3));
+
+    // Studio range luma, white is 235 and black 16
+    assert_eq!(bytes[header.len() + 6], 235);
+    assert_eq!(bytes[header.len() + 7], 16);
+  }
+
+  #[test]
+  fn avi_test() {
+    let mut frame = Frame::default();
+    frame.set_pixel(0, HEIGHT - 1, (1, 2, 3));
+
+    let mut out = Cursor::new(Vec::new());
+    let mut writer = AviWriter::new(&mut out, 39_375_000, 655_171).unwrap();
+    for _x in 0..3 {
+      writer.write_frame(&frame).unwrap();
+    }
+    writer.finish().unwrap();
+    let bytes = out.into_inner();
+
+    assert_eq!(&bytes[0..4], b"RIFF");
+    assert_eq!(u32_at(&bytes, 4) as usize, bytes.len() - 8);
+    assert_eq!(&bytes[8..12], b"AVI ");
+    assert_eq!(u32_at(&bytes, 48), 3);
+    assert_eq!(u32_at(&bytes, 140), 3);
+    assert_eq!(&bytes[220..224], b"movi");
+    assert_eq!(&bytes[224..228], b"00db");
+
+    // Bottom row first, stored as BGR
+    assert_eq!(&bytes[232..235], &[3, 2, 1]);
+
+    let index = 216 + 8 + u32_at(&bytes, 216) as usize - 4;
+    assert_eq!(&bytes[index..(index + 4)], b"idx1");
+    assert_eq!(u32_at(&bytes, index + 4), 48);
+  }
+
+  #[test]
+  fn wav_test() {
+    let mut out = Cursor::new(Vec::new());
+    let mut writer = WavWriter::new(&mut out, 44_100, 1).unwrap();
+    writer.write_samples(&[0, 1000, -1000]).unwrap();
+    assert_eq!(writer.length(), 3);
+    writer.finish().unwrap();
+    let bytes = out.into_inner();
+
+    assert_eq!(bytes.len(), 44 + 6);
+    assert_eq!(u32_at(&bytes, 4), 42);
+    assert_eq!(u32_at(&bytes, 24), 44_100);
+    assert_eq!(u32_at(&bytes, 40), 6);
+    assert_eq!(&bytes[46..48], &1000i16.to_le_bytes());
+  }
+
+  #[test]
+  fn recorder_keeps_audio_in_sync_test() {
+    let video = temp_path("sync.y4m");
+    let audio = temp_path("sync.wav");
+
+    let mut recorder = Recorder::start(Some(&video), Some(&audio), &Palette::default()).unwrap();
+    let frame = Frame::default();
+    // Too few samples on one frame and too many on the next
+    recorder.record_frame(&frame, &[100; 10]).unwrap();
+    recorder.record_frame(&frame, &[100; 5000]).unwrap();
+    for _x in 0..58 {
+      recorder.record_frame(&frame, &[]).unwrap();
+    }
+    assert_eq!(recorder.frames(), 60);
+    recorder.stop().unwrap();
+
+    let samples = (std::fs::metadata(&audio).unwrap().len() - 44) / 2;
+    let expected = 60 * SAMPLE_RATE as u64 * 655_171 / 39_375_000;
+    assert_eq!(samples, expected);
+
+    let frames = std::fs::read(&video).unwrap().windows(6).filter(|window| window == b"FRAME\n").count();
+    assert_eq!(frames, 60);
+
+    std::fs::remove_file(&video).unwrap();
+    std::fs::remove_file(&audio).unwrap();
+  }
+
+  #[test]
+  fn gif_test() {
+    let palette = Palette::default();
+    let mut frames = Vec::new();
+    for shade in 0..6 {
+      let mut frame = Frame::default();
+      frame.set_pixel(shade, 0, palette.colors[0x20]);
+      frames.push(frame);
+    }
+    // Not in the palette, so it needs a local color table
+    frames[5].set_pixel(10, 10, (1, 2, 3));
+
+    let mut bytes = Vec::new();
+    let mut writer = GifWriter::new(&mut bytes, WIDTH, HEIGHT, &palette.colors[..64], 39_375_000, 655_171).unwrap();
+    for frame in &frames {
+      writer.write_frame(&frame.data).unwrap();
+    }
+    // Unchanged frames extend the previous image
+    for _x in 0..3 {
+      writer.write_frame(&frames[5].data).unwrap();
+    }
+    writer.finish().unwrap();
+
+    assert!(bytes.starts_with(b"GIF89a"));
+    assert_eq!(bytes.last(), Some(&0x3B));
+
+    let images = gif_images(&bytes);
+    let total: u16 = images.iter().map(|image| image.delay).sum();
+    // 9 frames at 60.0988 fps, rounded to hundredths without drifting
+    assert_eq!(total, 15);
+    assert!(images.iter().all(|image| image.delay >= 2));
+    assert!(images.iter().any(|image| image.local));
+    assert!(images.len() < 9);
+    // Short images are merged into the next, so the last one shows the last frame
+    assert_eq!(images.last().unwrap().rgb, frames[5].data);
+  }
+
+  #[test]
+  fn gif_pixels_test() {
+    let palette = Palette::default();
+    // Noise that overflows the 12 bit code table several times, then a flat frame of long runs
+    let mut noise = Frame::default();
+    let mut seed: u32 = 1;
+    for y in 0..HEIGHT {
+      for x in 0..WIDTH {
+        seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+        noise.set_pixel(x, y, palette.colors[(seed >> 16) as usize % 64]);
+      }
+    }
+    let mut flat = Frame::default();
+    flat.set_pixel(WIDTH - 1, HEIGHT - 1, palette.colors[0x30]);
+    // Colors beyond the global table go through a local one
+    let mut local = Frame::default();
+    for y in 0..HEIGHT {
+      for x in 0..WIDTH {
+        local.set_pixel(x, y, ((x % 16 * 16) as u8, (y % 8 * 32) as u8, 1));
+      }
+    }
+
+    let mut bytes = Vec::new();
+    // Slow enough that every frame gets its own image
+    let mut writer = GifWriter::new(&mut bytes, WIDTH, HEIGHT, &palette.colors[..64], 10, 1).unwrap();
+    for frame in &[&noise, &flat, &local] {
+      writer.write_frame(&frame.data).unwrap();
+    }
+    writer.finish().unwrap();
+
+    let images = gif_images(&bytes);
+    assert_eq!(images.len(), 3);
+    assert_eq!(images[0].rgb, noise.data);
+    assert_eq!(images[1].rgb, flat.data);
+    assert!(images[2].local);
+    assert_eq!(images[2].rgb, local.data);
+  }
+}
@@ -0,0 +1,155 @@
+#![allow(dead_code)]
+extern crate nes_emu;
+
+mod scale_tests {
+  use nes_emu::graphics::frame::{Frame, WIDTH, HEIGHT};
+  use nes_emu::graphics::image::Image;
+  use nes_emu::graphics::scale::{Overscan, Scaler};
+
+  const BLACK: (u8, u8, u8) = (0, 0, 0);
+  const WHITE: (u8, u8, u8) = (255, 255, 255);
+
+  // White lower left triangle on black, the staircase every smoothing scaler should round off
+  fn diagonal(size: usize) -> Image {
+    let mut image = Image::new(size, size);
+    for y in 0..size {
+      for x in 0..=y {
+        image.set_pixel(x, y, WHITE);
+      }
+    }
+    image
+  }
+
+  #[test]
+  fn parse_test() {
+    assert_eq!("nearest3".parse::<Scaler>(), Ok(Scaler::Nearest(3)));
+    assert_eq!("Scale2x".parse::<Scaler>(), Ok(Scaler::Scale2x));
+    assert_eq!("hq4x".parse::<Scaler>(), Ok(Scaler::Hq4x));
+    assert_eq!("xbr3x".parse::<Scaler>(), Ok(Scaler::Xbr(3)));
+    assert!("xbr9".parse::<Scaler>().is_err());
+    assert!("bilinear".parse::<Scaler>().is_err());
+  }
+
+  #[test]
+  fn nearest_test() {
+    let image = Scaler::Nearest(2).apply(&diagonal(2));
+
+    assert_eq!((image.width, image.height), (4, 4));
+    assert_eq!(image.get_pixel(1, 1), WHITE);
+    assert_eq!(image.get_pixel(3, 0), BLACK);
+    assert_eq!(image.get_pixel(3, 2), WHITE);
+  }
+
+  #[test]
+  fn scale2x_test() {
+    let source = diagonal(4);
+    let image = Scaler::Scale2x.apply(&source);
+
+    assert_eq!((image.width, image.height), (8, 8));
+    // The black pixel above the diagonal gets its lower left corner filled in
+    assert_eq!(image.get_pixel(2, 0), BLACK);
+    assert_eq!(image.get_pixel(2, 1), WHITE);
+    assert_eq!(image.get_pixel(3, 1), BLACK);
+  }
+
+  #[test]
+  fn scale3x_test() {
+    let image = Scaler::Scale3x.apply(&diagonal(4));
+
+    assert_eq!((image.width, image.height), (12, 12));
+    assert_eq!(image.get_pixel(3, 5), WHITE);
+    assert_eq!(image.get_pixel(5, 3), BLACK);
+  }
+
+  #[test]
+  fn flat_image_unchanged_test() {
+    let mut source = Image::new(4, 4);
+    for y in 0..4 {
+      for x in 0..4 {
+        source.set_pixel(x, y, (10, 20, 30));
+      }
+    }
+
+    for scaler in &[Scaler::Scale2x, Scaler::Scale3x, Scaler::Hq2x, Scaler::Hq3x, Scaler::Hq4x, Scaler::Xbr(2), Scaler::Xbr(4)] {
+      let image = scaler.apply(&source);
+      assert_eq!(image, Scaler::Nearest(scaler.factor()).apply(&source), "{:?}", scaler);
+    }
+  }
+
+  #[test]
+  fn smoothing_scalers_blend_edges_test() {
+    let source = diagonal(6);
+
+    for scaler in &[Scaler::Hq2x, Scaler::Hq4x, Scaler::Xbr(2), Scaler::Xbr(3)] {
+      let image = scaler.apply(&source);
+      let blended = image.data.chunks(3).any(|pixel| pixel[0] != 0 && pixel[0] != 255);
+      assert!(blended, "{:?}", scaler);
+    }
+  }
+
+  #[test]
+  fn straight_edge_unchanged_test() {
+    let mut source = Image::new(4, 4);
+    for y in 0..4 {
+      for x in 2..4 {
+        source.set_pixel(x, y, WHITE);
+      }
+    }
+
+    for scaler in &[Scaler::Hq2x, Scaler::Hq3x, Scaler::Hq4x, Scaler::Xbr(2), Scaler::Xbr(3), Scaler::Xbr(4)] {
+      let image = scaler.apply(&source);
+      assert_eq!(image, Scaler::Nearest(scaler.factor()).apply(&source), "{:?}", scaler);
+    }
+  }
+
+  #[test]
+  fn hq2x_dot_test() {
+    let mut source = Image::new(3, 3);
+    source.set_pixel(1, 1, WHITE);
+    let image = Scaler::Hq2x.apply(&source);
+
+    // Every neighbour differs, which the table cuts down to an eighth of a dot on each corner
+    for y in 0..6 {
+      for x in 0..6 {
+        let expected = if (2..4).contains(&x) && (2..4).contains(&y) { (63, 63, 63) } else { BLACK };
+        assert_eq!(image.get_pixel(x, y), expected, "{} {}", x, y);
+      }
+    }
+  }
+
+  #[test]
+  fn hq4x_dot_test() {
+    let mut source = Image::new(3, 3);
+    source.set_pixel(1, 1, WHITE);
+    let image = Scaler::Hq4x.apply(&source);
+
+    // Rounded off to a diamond, black at the corners and half way along the sides
+    let gray = (127, 127, 127);
+    let rows = [
+      [BLACK, gray, gray, BLACK],
+      [gray, WHITE, WHITE, gray],
+      [gray, WHITE, WHITE, gray],
+      [BLACK, gray, gray, BLACK]
+    ];
+    for (y, row) in rows.iter().enumerate() {
+      for (x, color) in row.iter().enumerate() {
+        assert_eq!(image.get_pixel(x + 4, y + 4), *color, "{} {}", x, y);
+      }
+    }
+    assert_eq!(image.get_pixel(3, 4), BLACK);
+  }
+
+  #[test]
+  fn overscan_test() {
+    let image = Image::from_frame(&Frame::default());
+
+    let cropped = Overscan::default().crop(&image);
+    assert_eq!((cropped.width, cropped.height), (WIDTH, HEIGHT - 16));
+
+    let mut source = Image::new(4, 4);
+    source.set_pixel(1, 1, WHITE);
+    let cropped = Overscan { top: 1, bottom: 1, left: 1, right: 2 }.crop(&source);
+    assert_eq!((cropped.width, cropped.height), (1, 2));
+    assert_eq!(cropped.get_pixel(0, 0), WHITE);
+  }
+}
@@ -0,0 +1,282 @@
+#![allow(dead_code)]
+extern crate nes_emu;
+
+// Runner for the SingleStepTests ("ProcessorTests") 6502 JSON suites. Point SINGLE_STEP_TESTS
+// at the directory holding the per-opcode files (00.json ... ff.json), SINGLE_STEP_OPCODES can
+// limit the run to a comma separated list of opcodes and SINGLE_STEP_CHECK_BUS=0 leaves out the
+// cycle count and bus traffic comparisons.
+mod single_step_tests {
+  use serde::Deserialize;
+
+  use nes_emu::emu::cpu::CPU;
+  use nes_emu::emu::cpu_bus::{BusAccess, RecordingBus};
+  use nes_emu::emu::cpu_opcodes::{Instruction, Opcode};
+
+  const DEFAULT_DIR: &str = "./ROMS/single_step";
+  const MAX_REPORTED_FAILURES: usize = 5;
+
+  // Unofficial opcodes whose results depend on the chip, its temperature or analog effects, the
+  // suite's answers for them are not something the core tries to match
+  const SKIPPED_OPCODES: [(u8, &str); 19] = [
+    (0x02, "JAM"), (0x12, "JAM"), (0x22, "JAM"), (0x32, "JAM"), (0x42, "JAM"), (0x52, "JAM"),
+    (0x62, "JAM"), (0x72, "JAM"), (0x92, "JAM"), (0xB2, "JAM"), (0xD2, "JAM"), (0xF2, "JAM"),
+    (0x8B, "ANE"), (0xAB, "LXA"), (0x93, "SHA"), (0x9F, "SHA"), (0x9B, "TAS"), (0x9C, "SHY"),
+    (0x9E, "SHX")
+  ];
+
+  // B and bit 5 are not real flags, so they are left out of comparisons
+  const STATUS_MASK: u8 = 0xCF;
+
+  #[derive(Deserialize)]
+  struct CpuState {
+    pc: u16,
+    s: u8,
+    a: u8,
+    x: u8,
+    y: u8,
+    p: u8,
+    ram: Vec<(u16, u8)>
+  }
+
+  #[derive(Deserialize)]
+  struct TestCase {
+    name: String,
+    initial: CpuState,
+    #[serde(rename = "final")]
+    final_state: CpuState,
+    cycles: Vec<(u16, u8, String)>
+  }
+
+  fn run_case(case: &TestCase, check_bus: bool) -> Vec<String> {
+    let mut errors = Vec::new();
+    let mut bus = RecordingBus::new();
+    let mut cpu = CPU::new(None);
+
+    for &(addr, value) in &case.initial.ram {
+      bus.ram[addr as usize] = value;
+    }
+
+    cpu.pc = case.initial.pc;
+    cpu.sp = case.initial.s;
+    cpu.r_a = case.initial.a;
+    cpu.r_x = case.initial.x;
+    cpu.r_y = case.initial.y;
+    cpu.set_status_register(case.initial.p);
+    cpu.skip_cycles = 0;
+
+    let opcode = bus.ram[case.initial.pc as usize];
+    if Instruction::from_u8(opcode).opcode == Opcode::UnknownOperation {
+      errors.push(format!("UNIMPLEMENTED OPCODE {:02x}", opcode));
+      return errors;
+    }
+
+    cpu.step(&mut bus);
+
+    let expected = &case.final_state;
+    let registers = [
+      ("PC", cpu.pc, expected.pc),
+      ("S", cpu.sp as u16, expected.s as u16),
+      ("A", cpu.r_a as u16, expected.a as u16),
+      ("X", cpu.r_x as u16, expected.x as u16),
+      ("Y", cpu.r_y as u16, expected.y as u16),
+      ("P", (cpu.status() & STATUS_MASK) as u16, (expected.p & STATUS_MASK) as u16)
+    ];
+
+    for (name, actual, wanted) in registers.iter() {
+      if actual != wanted {
+        errors.push(format!("{} IS {:04x}, EXPECTED {:04x}", name, actual, wanted));
+      }
+    }
+
+    for &(addr, value) in &expected.ram {
+      let actual = bus.ram[addr as usize];
+      if actual != value {
+        errors.push(format!("RAM[{:04x}] IS {:02x}, EXPECTED {:02x}", addr, actual, value));
+      }
+    }
+
+    if check_bus {
+      let cycles = cpu.skip_cycles as usize + 1;
+      if cycles != case.cycles.len() {
+        errors.push(format!("TOOK {} CYCLES, EXPECTED {}", cycles, case.cycles.len()));
+      }
+
+      let wanted: Vec<BusAccess> = case.cycles.iter().map(|(addr, value, kind)| match kind.as_str() {
+        "write" => BusAccess::Write { addr: *addr, value: *value },
+        _ => BusAccess::Read { addr: *addr, value: *value }
+      }).collect();
+
+      for index in 0..wanted.len().max(bus.accesses.len()) {
+        let actual = bus.accesses.get(index);
+        let expected = wanted.get(index);
+        if actual != expected {
+          errors.push(format!("BUS CYCLE {} IS {:?}, EXPECTED {:?}", index, actual, expected));
+          break;
+        }
+      }
+    }
+
+    errors
+  }
+
+  fn run_file(path: &std::path::Path, check_bus: bool) -> (usize, usize) {
+    let text = std::fs::read_to_string(path).unwrap();
+    let cases: Vec<TestCase> = serde_json::from_str(&text).unwrap();
+    let mut failures = 0;
+
+    for case in &cases {
+      let errors = run_case(case, check_bus);
+      if !errors.is_empty() {
+        if failures < MAX_REPORTED_FAILURES {
+          println!("{:?} \"{}\": {}", path.file_name().unwrap(), case.name, errors.join(", "));
+        }
+        failures += 1;
+      }
+    }
+
+    (cases.len(), failures)
+  }
+
+  #[test]
+  fn inline_case_test() {
+    // LDA #$7B from $0200
+    let json = r#"{
+      "name": "a9 7b",
+      "initial": { "pc": 512, "s": 253, "a": 0, "x": 0, "y": 0, "p": 36, "ram": [[512, 169], [513, 123]] },
+      "final": { "pc": 514, "s": 253, "a": 123, "x": 0, "y": 0, "p": 36, "ram": [[512, 169], [513, 123]] },
+      "cycles": [[512, 169, "read"], [513, 123, "read"]]
+    }"#;
+    let case: TestCase = serde_json::from_str(json).unwrap();
+
+    assert_eq!(run_case(&case, true), Vec::<String>::new());
+  }
+
+  #[test]
+  fn inline_mismatch_test() {
+    // STA $10 with the expected result deliberately wrong
+    let json = r#"{
+      "name": "85 10",
+      "initial": { "pc": 512, "s": 253, "a": 66, "x": 0, "y": 0, "p": 36, "ram": [[512, 133], [513, 16]] },
+      "final": { "pc": 514, "s": 253, "a": 66, "x": 0, "y": 0, "p": 36, "ram": [[16, 67]] },
+      "cycles": [[512, 133, "read"], [513, 16, "read"], [16, 67, "write"]]
+    }"#;
+    let case: TestCase = serde_json::from_str(json).unwrap();
+    let errors = run_case(&case, true);
+
+    assert_eq!(errors, vec![
+      "RAM[0010] IS 42, EXPECTED 43".to_string(),
+      "BUS CYCLE 2 IS Some(Write { addr: 16, value: 66 }), EXPECTED Some(Write { addr: 16, value: 67 })".to_string()
+    ]);
+  }
+
+  fn parse_case(json: &str) -> TestCase {
+    serde_json::from_str(json).unwrap()
+  }
+
+  #[test]
+  fn page_cross_test() {
+    // LDA $02F0,X with X=$20 reads $0210 before fixing the high byte
+    let case = parse_case(r#"{
+      "name": "bd f0 02",
+      "initial": { "pc": 512, "s": 253, "a": 0, "x": 32, "y": 0, "p": 36, "ram": [[512, 189], [513, 240], [514, 2], [784, 5]] },
+      "final": { "pc": 515, "s": 253, "a": 5, "x": 32, "y": 0, "p": 36, "ram": [[784, 5]] },
+      "cycles": [[512, 189, "read"], [513, 240, "read"], [514, 2, "read"], [528, 0, "read"], [784, 5, "read"]]
+    }"#);
+    assert_eq!(run_case(&case, true), Vec::<String>::new());
+
+    // BNE +$10 from $02F0 is taken onto the next page
+    let case = parse_case(r#"{
+      "name": "d0 10",
+      "initial": { "pc": 752, "s": 253, "a": 0, "x": 0, "y": 0, "p": 36, "ram": [[752, 208], [753, 16], [754, 0], [514, 0]] },
+      "final": { "pc": 770, "s": 253, "a": 0, "x": 0, "y": 0, "p": 36, "ram": [] },
+      "cycles": [[752, 208, "read"], [753, 16, "read"], [754, 0, "read"], [514, 0, "read"]]
+    }"#);
+    assert_eq!(run_case(&case, true), Vec::<String>::new());
+  }
+
+  #[test]
+  fn read_modify_write_test() {
+    // INC $10 writes the old value back before the new one
+    let case = parse_case(r#"{
+      "name": "e6 10",
+      "initial": { "pc": 512, "s": 253, "a": 0, "x": 0, "y": 0, "p": 36, "ram": [[512, 230], [513, 16], [16, 127]] },
+      "final": { "pc": 514, "s": 253, "a": 0, "x": 0, "y": 0, "p": 164, "ram": [[16, 128]] },
+      "cycles": [[512, 230, "read"], [513, 16, "read"], [16, 127, "read"], [16, 127, "write"], [16, 128, "write"]]
+    }"#);
+    assert_eq!(run_case(&case, true), Vec::<String>::new());
+
+    // SLO $10 shifts memory and ORs the result into A
+    let case = parse_case(r#"{
+      "name": "07 10",
+      "initial": { "pc": 512, "s": 253, "a": 1, "x": 0, "y": 0, "p": 36, "ram": [[512, 7], [513, 16], [16, 129]] },
+      "final": { "pc": 514, "s": 253, "a": 3, "x": 0, "y": 0, "p": 37, "ram": [[16, 2]] },
+      "cycles": [[512, 7, "read"], [513, 16, "read"], [16, 129, "read"], [16, 129, "write"], [16, 2, "write"]]
+    }"#);
+    assert_eq!(run_case(&case, true), Vec::<String>::new());
+  }
+
+  #[test]
+  fn jsr_test() {
+    // JSR $1234 pushes $0202 before it reads the high byte of the target
+    let case = parse_case(r#"{
+      "name": "20 34 12",
+      "initial": { "pc": 512, "s": 253, "a": 0, "x": 0, "y": 0, "p": 36, "ram": [[512, 32], [513, 52], [514, 18]] },
+      "final": { "pc": 4660, "s": 251, "a": 0, "x": 0, "y": 0, "p": 36, "ram": [[509, 2], [508, 2]] },
+      "cycles": [[512, 32, "read"], [513, 52, "read"], [509, 0, "read"], [509, 2, "write"], [508, 2, "write"], [514, 18, "read"]]
+    }"#);
+    assert_eq!(run_case(&case, true), Vec::<String>::new());
+  }
+
+  #[test]
+  fn skipped_opcodes_test() {
+    // Everything the suite covers is decoded apart from the opcodes skipped on purpose
+    for opcode in 0..=0xFFu8 {
+      let skipped = SKIPPED_OPCODES.iter().any(|(skipped, _)| *skipped == opcode);
+      assert_eq!(Instruction::from_u8(opcode).opcode == Opcode::UnknownOperation, skipped, "OPCODE {:02x}", opcode);
+    }
+  }
+
+  #[test]
+  fn suite_test() {
+    let dir = std::env::var("SINGLE_STEP_TESTS").unwrap_or_else(|_| DEFAULT_DIR.to_string());
+    let entries = match std::fs::read_dir(&dir) {
+      Ok(entries) => entries,
+      Err(_) => {
+        println!("SKIPPING, {} NOT FOUND (SET SINGLE_STEP_TESTS)", dir);
+        return;
+      }
+    };
+
+    let check_bus = std::env::var("SINGLE_STEP_CHECK_BUS").map(|value| value != "0").unwrap_or(true);
+    let filter: Option<Vec<String>> = std::env::var("SINGLE_STEP_OPCODES").ok()
+      .map(|value| value.split(',').map(|op| op.trim().to_ascii_lowercase()).collect());
+
+    let mut paths: Vec<_> = entries.filter_map(|entry| entry.ok().map(|entry| entry.path()))
+      .filter(|path| path.extension().map(|ext| ext == "json").unwrap_or(false))
+      .filter(|path| match &filter {
+        Some(ops) => ops.iter().any(|op| path.file_stem().map(|stem| stem.to_string_lossy() == op.as_str()).unwrap_or(false)),
+        None => true
+      })
+      .collect();
+    paths.sort();
+
+    let skipped: Vec<String> = SKIPPED_OPCODES.iter().map(|(opcode, _)| format!("{:02x}", opcode)).collect();
+    let (skipped_paths, paths): (Vec<_>, Vec<_>) = paths.into_iter()
+      .partition(|path| skipped.iter().any(|op| path.file_stem().map(|stem| stem.to_string_lossy() == op.as_str()).unwrap_or(false)));
+    if !skipped_paths.is_empty() {
+      println!("SKIPPED {} UNSTABLE OPCODES: {}", skipped_paths.len(),
+        skipped_paths.iter().map(|path| path.file_stem().unwrap().to_string_lossy().to_string()).collect::<Vec<_>>().join(" "));
+    }
+
+    let mut failed_opcodes = Vec::new();
+    for path in &paths {
+      let (total, failures) = run_file(path, check_bus);
+      if failures > 0 {
+        println!("{:?}: {} OF {} CASES FAILED", path.file_name().unwrap(), failures, total);
+        failed_opcodes.push(path.file_stem().unwrap().to_string_lossy().to_string());
+      }
+    }
+
+    assert!(failed_opcodes.is_empty(), "FAILED OPCODES: {}", failed_opcodes.join(" "));
+  }
+}
@@ -0,0 +1,72 @@
+#![allow(dead_code)]
+extern crate nes_emu;
+
+mod trace_tests {
+  use nes_emu::emu::cpu::CPU;
+  use nes_emu::emu::cpu_bus::FlatBus;
+  use nes_emu::emu::trace::{Tracer, TraceFormat, TraceSink};
+
+  fn run_traced(tracer: Tracer, program: &[u8], instructions: u32) -> Tracer {
+    let mut bus = FlatBus::new();
+    bus.load(0x0200, program);
+
+    let mut cpu = CPU::new(Some(tracer));
+    cpu.pc = 0x0200;
+    cpu.sp = 0xFD;
+    cpu.f_u = true;
+
+    for _x in 0..instructions {
+      cpu.step_instruction(&mut bus);
+    }
+
+    cpu.tracer.take().unwrap()
+  }
+
+  // LDA #$10, STA $0300, BNE -5
+  const PROGRAM: [u8; 7] = [0xA9, 0x10, 0x8D, 0x00, 0x03, 0xD0, 0xF9];
+
+  #[test]
+  fn nestest_format_test() {
+    let tracer = run_traced(Tracer::new(TraceFormat::Nestest, TraceSink::Memory(Vec::new())), &PROGRAM, 3);
+
+    assert_eq!(tracer.lines(), &[
+      "0200  A9 10     LDA #$10                        A:00 X:00 Y:00 P:20 SP:FD CYC:0".to_string(),
+      "0202  8D 00 03  STA $0300                       A:10 X:00 Y:00 P:20 SP:FD CYC:2".to_string(),
+      "0205  D0 F9     BNE $0200                       A:10 X:00 Y:00 P:20 SP:FD CYC:6".to_string()
+    ]);
+  }
+
+  #[test]
+  fn mesen_format_test() {
+    let tracer = run_traced(Tracer::new(TraceFormat::Mesen, TraceSink::Memory(Vec::new())), &PROGRAM, 1);
+
+    assert_eq!(tracer.lines(), &[
+      "0200  A9 10     LDA #$10                        A:00 X:00 Y:00 S:FD P:nvUbdizc Cycle:0".to_string()
+    ]);
+  }
+
+  #[test]
+  fn address_filter_test() {
+    let mut tracer = Tracer::new(TraceFormat::Fceux, TraceSink::Memory(Vec::new()));
+    tracer.address_range = Some((0x0202, 0x0204));
+    let tracer = run_traced(tracer, &PROGRAM, 6);
+
+    assert_eq!(tracer.lines().len(), 2);
+    assert!(tracer.lines().iter().all(|line| line.contains("$0202:8D 00 03")));
+  }
+
+  #[test]
+  fn ring_buffer_test() {
+    let mut tracer = Tracer::new(TraceFormat::Nestest, TraceSink::Memory(Vec::new()));
+    tracer.set_ring_buffer(2);
+    let mut tracer = run_traced(tracer, &PROGRAM, 6);
+
+    assert!(tracer.lines().is_empty());
+
+    tracer.flush_ring();
+
+    assert_eq!(tracer.lines().len(), 2);
+    assert!(tracer.lines()[0].starts_with("0202"));
+    assert!(tracer.lines()[1].starts_with("0205"));
+  }
+}
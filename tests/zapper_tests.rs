@@ -0,0 +1,131 @@
+#![allow(dead_code)]
+extern crate nes_emu;
+
+mod zapper_tests {
+  use nes_emu::emu::cartridge::Cartridge;
+  use nes_emu::emu::input::InputDevice;
+  use nes_emu::emu::joypad::{Joypad, BUTTON_A};
+  use nes_emu::emu::nes::NES;
+  use nes_emu::emu::zapper::Zapper;
+  use nes_emu::graphics::frame::Frame;
+
+  const WHITE: (u8, u8, u8) = (255, 255, 255);
+
+  // Mapper 0 image whose program waits for the PPU to warm up, draws a white tile at column 6
+  // row 3 on a black background and spins
+  fn test_cartridge() -> Cartridge {
+    let mut program = vec![0x2C, 0x02, 0x20, 0x10, 0xFB, 0x2C, 0x02, 0x20, 0x10, 0xFB];
+    let writes = [
+      (0x2006, 0x3F), (0x2006, 0x00), (0x2007, 0x0F), (0x2007, 0x30),
+      (0x2006, 0x20), (0x2006, 0x66), (0x2007, 0x01),
+      (0x2005, 0x00), (0x2005, 0x00), (0x2000, 0x00), (0x2001, 0x0A)
+    ];
+    for (addr, value) in writes.iter() {
+      program.extend_from_slice(&[0xA9, *value as u8, 0x8D, *addr as u8, (*addr >> 8) as u8]);
+    }
+    let spin = 0x8000 + program.len() as u16;
+    program.extend_from_slice(&[0x4C, spin as u8, (spin >> 8) as u8]);
+
+    let mut bytes = vec![0x4E, 0x45, 0x53, 0x1A, 0x01, 0x01, 0x00, 0x00];
+    bytes.resize(16, 0x00);
+    let mut prg_rom = vec![0xEA; 0x4000];
+    prg_rom[..program.len()].copy_from_slice(&program);
+    prg_rom[0x3FFC] = 0x00;
+    prg_rom[0x3FFD] = 0x80;
+    bytes.extend_from_slice(&prg_rom);
+    // Tile 1 is solid color 1
+    let mut chr_rom = vec![0x00; 0x2000];
+    for byte in &mut chr_rom[0x10..0x18] {
+      *byte = 0xFF;
+    }
+    bytes.extend_from_slice(&chr_rom);
+    Cartridge::new(&bytes).unwrap()
+  }
+
+  // 8x8 white box with its top left corner at (x, y)
+  fn white_box(frame: &mut Frame, x: usize, y: usize) {
+    for row in y..(y + 8) {
+      for column in x..(x + 8) {
+        frame.set_pixel(column, row, WHITE);
+      }
+    }
+  }
+
+  fn light_sensed(zapper: &mut Zapper) -> bool {
+    zapper.read() & 0x08 == 0
+  }
+
+  #[test]
+  fn trigger_test() {
+    let mut zapper = Zapper::default();
+    assert_eq!(zapper.read(), 0x08);
+    zapper.trigger = true;
+    assert_eq!(zapper.read(), 0x18);
+  }
+
+  #[test]
+  fn light_timing_test() {
+    let mut frame = Frame::default();
+    white_box(&mut frame, 100, 100);
+    let mut zapper = Zapper::default();
+    zapper.cursor = Some((104, 104));
+
+    // Dark until the beam has drawn the aim point, then lit for a while after
+    zapper.scanline(&frame, 50);
+    assert!(!light_sensed(&mut zapper));
+    zapper.scanline(&frame, 104);
+    assert!(!light_sensed(&mut zapper));
+    zapper.scanline(&frame, 105);
+    assert!(light_sensed(&mut zapper));
+    zapper.scanline(&frame, 120);
+    assert!(light_sensed(&mut zapper));
+    zapper.scanline(&frame, 140);
+    assert!(!light_sensed(&mut zapper));
+
+    // Aimed at the dark part of the screen or away from it
+    zapper.cursor = Some((20, 104));
+    zapper.scanline(&frame, 110);
+    assert!(!light_sensed(&mut zapper));
+    zapper.cursor = None;
+    zapper.scanline(&frame, 110);
+    assert!(!light_sensed(&mut zapper));
+
+    // Dim pixels do not count
+    let mut frame = Frame::default();
+    frame.set_pixel(104, 104, (40, 40, 40));
+    zapper.cursor = Some((104, 104));
+    zapper.scanline(&frame, 110);
+    assert!(!light_sensed(&mut zapper));
+  }
+
+  #[test]
+  fn port_2_test() {
+    let mut nes = NES::new(test_cartridge());
+    nes.reset();
+    let mut zapper = Zapper::default();
+    zapper.cursor = Some((51, 27));
+    zapper.trigger = true;
+    nes.bus.ports[1] = Box::new(zapper);
+
+    // Light comes from the picture the PPU draws, run until the beam is a few lines past the
+    // aim point once the tile is up
+    nes.run(3, &[], None);
+    while nes.bus.ppu.scanline != 35 {
+      nes.step_instruction();
+    }
+    assert_eq!(nes.bus.read(0x4017), 0x40 | 0x10);
+
+    nes.bus.device::<Zapper>(1).unwrap().trigger = false;
+    while nes.bus.ppu.scanline != 100 {
+      nes.step_instruction();
+    }
+    assert_eq!(nes.bus.read(0x4017), 0x40 | 0x08);
+
+    // Port 1 still holds a controller
+    assert!(nes.bus.device::<Zapper>(0).is_none());
+    nes.bus.device::<Joypad>(0).unwrap().buttons = BUTTON_A;
+    nes.bus.write(0x4016, 1);
+    nes.bus.write(0x4016, 0);
+    assert_eq!(nes.bus.read(0x4016), 0x41);
+  }
+}